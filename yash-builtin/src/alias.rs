@@ -23,19 +23,29 @@ use std::rc::Rc;
 use yash_env::builtin::Result;
 use yash_env::exec::ExitStatus;
 use yash_env::expansion::Field;
+use yash_env::io::STDOUT;
 use yash_syntax::alias::{AliasSet, HashEntry};
 
 /// Part of the shell execution environment the alias built-in depends on.
 pub trait Env {
     /// Accesses the alias set in the environment.
     fn alias_set(&mut self) -> &mut Rc<AliasSet>;
-    // TODO stdout, stderr
+    /// Prints a text to the standard output.
+    fn print(&mut self, text: &str) -> nix::Result<()>;
+    /// Prints an error message to the standard error.
+    fn print_error(&mut self, message: &str);
 }
 
 impl Env for yash_env::Env {
     fn alias_set(&mut self) -> &mut Rc<AliasSet> {
         &mut self.aliases
     }
+    fn print(&mut self, text: &str) -> nix::Result<()> {
+        self.system.write_all(STDOUT, text.as_bytes())
+    }
+    fn print_error(&mut self, message: &str) {
+        yash_env::Env::print_error(self, message)
+    }
 }
 
 /// Implementation of the alias built-in.
@@ -47,11 +57,17 @@ pub fn builtin_main_sync<E: Env>(env: &mut E, args: Vec<Field>) -> Result {
     args.next(); // ignore the first argument, which is the command name
 
     if args.as_ref().is_empty() {
+        let mut output = String::new();
         for alias in env.alias_set().as_ref() {
-            // TODO should print via IoEnv rather than directly to stdout
-            println!("{}={}", &alias.0.name, &alias.0.replacement);
+            output.push_str(&format!("{}={}\n", &alias.0.name, &alias.0.replacement));
         }
-        return (ExitStatus::SUCCESS, None);
+        return match env.print(&output) {
+            Ok(()) => (ExitStatus::SUCCESS, None),
+            Err(error) => {
+                env.print_error(&format!("alias: cannot print aliases: {}", error));
+                (ExitStatus::FAILURE, None)
+            }
+        };
     }
 
     for Field { value, origin } in args {
@@ -60,7 +76,7 @@ pub fn builtin_main_sync<E: Env>(env: &mut E, args: Vec<Field>) -> Result {
             // TODO reject invalid name
             let replacement = value[eq_index + 1..].to_owned();
             let entry = HashEntry::new(name, replacement, false, origin);
-            Rc::make_mut(&mut env.alias_set()).insert(entry);
+            Rc::make_mut(env.alias_set()).insert(entry);
         } else {
             // TODO print alias definition
         }
@@ -89,12 +105,22 @@ mod tests {
     #[derive(Default)]
     struct DummyEnv {
         aliases: Rc<AliasSet>,
+        stdout: String,
+        stderr: String,
     }
 
     impl Env for DummyEnv {
         fn alias_set(&mut self) -> &mut Rc<AliasSet> {
             &mut self.aliases
         }
+        fn print(&mut self, text: &str) -> nix::Result<()> {
+            self.stdout.push_str(text);
+            Ok(())
+        }
+        fn print_error(&mut self, message: &str) {
+            self.stderr.push_str(message);
+            self.stderr.push('\n');
+        }
     }
 
     #[test]
@@ -182,4 +208,19 @@ mod tests {
         // TODO builtin should print to IoEnv rather than real standard output
     }
     // TODO test case with global aliases
+
+    #[test]
+    fn builtin_prints_all_aliases_without_operands() {
+        let mut env = DummyEnv::default();
+        let args = Field::dummies(["alias", "a=A", "b=B"]);
+        builtin_main_sync(&mut env, args);
+
+        let args = Field::dummies(["alias"]);
+        let result = builtin_main_sync(&mut env, args);
+        assert_eq!(result, (ExitStatus::SUCCESS, None));
+        let mut lines: Vec<&str> = env.stdout.lines().collect();
+        lines.sort_unstable();
+        assert_eq!(lines, ["a=A", "b=B"]);
+        assert_eq!(env.stderr, "");
+    }
 }
@@ -20,6 +20,7 @@
 
 pub mod alias;
 pub mod r#return;
+pub mod set;
 
 pub use yash_env::builtin::*;
 
@@ -41,4 +42,11 @@ pub const BUILTINS: &[(&str, Builtin)] = &[
             execute: r#return::builtin_main,
         },
     ),
+    (
+        "set",
+        Builtin {
+            r#type: Special,
+            execute: set::builtin_main,
+        },
+    ),
 ];
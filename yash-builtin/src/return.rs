@@ -68,34 +68,46 @@
 //!
 //! # Implementation notes
 //!
-//! TODO Describe in terms of Divert.
+//! Unless the `-n` option is given, the built-in returns
+//! [`Divert::Return`](yash_env::exec::Divert::Return), which is consumed at
+//! the boundary of the function or script that is being quit.
 
 use std::future::ready;
 use std::future::Future;
 use std::pin::Pin;
 use yash_env::builtin::Result;
+use yash_env::exec::Divert;
 use yash_env::exec::ExitStatus;
 use yash_env::expansion::Field;
 
 /// Part of the shell execution environment the return built-in depends on.
 pub trait Env {
-    // TODO Current value of $?
+    /// Returns the current value of `$?`.
+    fn exit_status(&self) -> ExitStatus;
     // TODO Current execution context (stack trace)
     // TODO stderr
 }
 
-impl Env for yash_env::Env {}
+impl Env for yash_env::Env {
+    fn exit_status(&self) -> ExitStatus {
+        self.exit_status
+    }
+}
 
 /// Implementation of the return built-in.
 ///
 /// See the [module-level documentation](self) for details.
-pub fn builtin_main_sync<E: Env>(_env: &mut E, args: Vec<Field>) -> Result {
+pub fn builtin_main_sync<E: Env>(env: &mut E, args: Vec<Field>) -> Result {
     // TODO Parse arguments correctly
-    let exit_status = match args.get(2) {
-        Some(field) => field.value.parse().unwrap_or(2),
-        None => 0,
+    let (operand, divert) = match args.get(1) {
+        Some(field) if field.value == "-n" || field.value == "--no-return" => (args.get(2), None),
+        operand => (operand, Some(Divert::Return)),
     };
-    (ExitStatus(exit_status), None)
+    let exit_status = match operand {
+        Some(field) => ExitStatus(field.value.parse().unwrap_or(2)),
+        None => env.exit_status(),
+    };
+    (exit_status, divert)
 }
 
 /// Implementation of the return built-in.
@@ -115,9 +127,15 @@ mod tests {
     use yash_env::exec::ExitStatus;
 
     #[derive(Default)]
-    struct DummyEnv;
+    struct DummyEnv {
+        exit_status: ExitStatus,
+    }
 
-    impl Env for DummyEnv {}
+    impl Env for DummyEnv {
+        fn exit_status(&self) -> ExitStatus {
+            self.exit_status
+        }
+    }
 
     #[test]
     fn returns_exit_status_12_with_n_option() {
@@ -134,4 +152,26 @@ mod tests {
         let result = builtin_main_sync(&mut env, args);
         assert_eq!(result, (ExitStatus(47), None));
     }
+
+    #[test]
+    fn returns_with_operand() {
+        let mut env = DummyEnv::default();
+        let args = Field::dummies(["return", "3"]);
+        let result = builtin_main_sync(&mut env, args);
+        assert_eq!(result, (ExitStatus(3), Some(Divert::Return)));
+    }
+
+    #[test]
+    fn returns_current_exit_status_without_operand() {
+        let mut env = DummyEnv {
+            exit_status: ExitStatus(19),
+        };
+        let args = Field::dummies(["return"]);
+        let result = builtin_main_sync(&mut env, args);
+        assert_eq!(result, (ExitStatus(19), Some(Divert::Return)));
+
+        let args = Field::dummies(["return", "-n"]);
+        let result = builtin_main_sync(&mut env, args);
+        assert_eq!(result, (ExitStatus(19), None));
+    }
 }
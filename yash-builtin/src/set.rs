@@ -0,0 +1,425 @@
+// This file is part of yash, an extended POSIX shell.
+// Copyright (C) 2021 WATANABE Yuki
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Set built-in.
+//!
+//! The set built-in modifies [shell options](yash_env::option) and positional
+//! parameters.
+//!
+//! # Syntax
+//!
+//! ```sh
+//! set [-abCefmnuvx] [-o option]... [--|-] [argument...]
+//! set -o
+//! set +o
+//! ```
+//!
+//! # Options
+//!
+//! Each single-letter option turns on the corresponding shell option. The
+//! option is turned off if it is preceded by `+` instead of `-`. The
+//! **`-o`** option takes the long name of a shell option as an argument.
+//!
+//! Without an option name, `set -o` prints the current state of all shell
+//! options in a human-readable form, and `set +o` prints commands that restore
+//! the current state. Options given before the `-o` or `+o` are applied
+//! before printing.
+//!
+//! # Operands
+//!
+//! The operands, if any, replace the positional parameters. If `--` is given,
+//! the positional parameters are replaced even if there are no operands. A
+//! single `-` has the same meaning as `--`.
+//!
+//! # Exit status
+//!
+//! The exit status is zero unless there is an error.
+//!
+//! # Errors
+//!
+//! It is an error to specify an unknown option or to change the `interactive`
+//! option. In that case, no options or positional parameters are modified and
+//! the exit status is 2. If the shell is not interactive, the built-in also
+//! causes the shell to exit.
+//!
+//! # Portability
+//!
+//! The `pipefail` and `posixlycorrect` options are non-standard extensions.
+//! Long option names are case-insensitive, and hyphens and underscores in them
+//! are ignored. This is also a non-standard extension.
+
+use std::future::ready;
+use std::future::Future;
+use std::pin::Pin;
+use yash_env::builtin::Result;
+use yash_env::exec::Divert;
+use yash_env::exec::ExitStatus;
+use yash_env::expansion::Field;
+use yash_env::io::STDOUT;
+use yash_env::option::OptionSet;
+use yash_env::option::ShellOption;
+
+/// Part of the shell execution environment the set built-in depends on.
+pub trait Env {
+    /// Accesses the shell options in the environment.
+    fn options_mut(&mut self) -> &mut OptionSet;
+    /// Accesses the current positional parameters.
+    fn positional_params_mut(&mut self) -> &mut Vec<String>;
+    /// Prints a text to the standard output.
+    fn print(&mut self, text: &str) -> nix::Result<()>;
+    /// Prints an error message to the standard error.
+    fn print_error(&mut self, message: &str);
+}
+
+impl Env for yash_env::Env {
+    fn options_mut(&mut self) -> &mut OptionSet {
+        &mut self.options
+    }
+    fn positional_params_mut(&mut self) -> &mut Vec<String> {
+        self.variables.positional_params_mut()
+    }
+    fn print(&mut self, text: &str) -> nix::Result<()> {
+        self.system.write_all(STDOUT, text.as_bytes())
+    }
+    fn print_error(&mut self, message: &str) {
+        yash_env::Env::print_error(self, message)
+    }
+}
+
+/// Formats the state of all the shell options.
+///
+/// If `as_commands` is true, the result is a list of `set` commands.
+fn format_options(options: &OptionSet, as_commands: bool) -> String {
+    let mut output = String::new();
+    for &option in &ShellOption::ALL {
+        let is_on = options.is_on(option);
+        let line = if as_commands {
+            format!("set {}o {}\n", if is_on { '-' } else { '+' }, option)
+        } else {
+            format!("{:15} {}\n", option, if is_on { "on" } else { "off" })
+        };
+        output.push_str(&line);
+    }
+    output
+}
+
+/// Prints the state of all the shell options.
+fn print_options<E: Env>(env: &mut E, as_commands: bool) -> Result {
+    let output = format_options(env.options_mut(), as_commands);
+    match env.print(&output) {
+        Ok(()) => (ExitStatus::SUCCESS, None),
+        Err(error) => {
+            env.print_error(&format!("set: cannot print options: {}", error));
+            (ExitStatus::FAILURE, None)
+        }
+    }
+}
+
+/// Applies option changes to the option set.
+fn apply_changes(options: &mut OptionSet, changes: Vec<(ShellOption, bool)>) {
+    for (option, is_on) in changes {
+        options.set(option, is_on);
+    }
+}
+
+/// Parses a shell option name for the set built-in.
+fn parse_option(name: &str) -> std::result::Result<ShellOption, String> {
+    match name.parse() {
+        Ok(ShellOption::Interactive) => Err("the interactive option cannot be changed".to_string()),
+        Ok(option) => Ok(option),
+        Err(error) => Err(format!("{}: {}", error, name)),
+    }
+}
+
+/// Implementation of the set built-in.
+///
+/// See the [module-level documentation](self) for details.
+pub fn builtin_main_sync<E: Env>(env: &mut E, args: Vec<Field>) -> Result {
+    let mut args = args.into_iter().skip(1).peekable();
+    let mut changes = Vec::new();
+    let mut replaces_params = false;
+
+    while let Some(arg) = args.next_if(|arg| {
+        arg.value.starts_with('-') || (arg.value.len() > 1 && arg.value.starts_with('+'))
+    }) {
+        if arg.value == "--" || arg.value == "-" {
+            replaces_params = true;
+            break;
+        }
+
+        let is_on = arg.value.starts_with('-');
+        let mut chars = arg.value[1..].chars();
+        while let Some(c) = chars.next() {
+            let option = if c == 'o' {
+                let rest = chars.as_str();
+                let name = if rest.is_empty() {
+                    match args.next() {
+                        Some(name) => name.value,
+                        None => {
+                            apply_changes(env.options_mut(), changes);
+                            return print_options(env, !is_on);
+                        }
+                    }
+                } else {
+                    rest.to_owned()
+                };
+                parse_option(&name)
+            } else {
+                match ShellOption::from_short_name(c) {
+                    Some(option) => parse_option(option.long_name()),
+                    None => Err(format!("no such option: {}{}", &arg.value[..1], c)),
+                }
+            };
+            match option {
+                Ok(option) => changes.push((option, is_on)),
+                Err(message) => {
+                    env.print_error(&format!("set: {}", message));
+                    let divert = if env.options_mut().is_on(ShellOption::Interactive) {
+                        None
+                    } else {
+                        Some(Divert::Exit(ExitStatus::ERROR))
+                    };
+                    return (ExitStatus::ERROR, divert);
+                }
+            }
+            if c == 'o' {
+                // The rest of the argument has been consumed as the name.
+                break;
+            }
+        }
+    }
+
+    apply_changes(env.options_mut(), changes);
+
+    let params: Vec<String> = args.map(|arg| arg.value).collect();
+    if replaces_params || !params.is_empty() {
+        *env.positional_params_mut() = params;
+    }
+
+    // TODO Print variables if there are no arguments
+    (ExitStatus::SUCCESS, None)
+}
+
+/// Implementation of the set built-in.
+///
+/// This function calls [`builtin_main_sync`] and wraps the result in a
+/// `Future`.
+pub fn builtin_main(
+    env: &mut yash_env::Env,
+    args: Vec<Field>,
+) -> Pin<Box<dyn Future<Output = Result>>> {
+    Box::pin(ready(builtin_main_sync(env, args)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct DummyEnv {
+        options: OptionSet,
+        params: Vec<String>,
+        stdout: String,
+        stderr: String,
+    }
+
+    impl Env for DummyEnv {
+        fn options_mut(&mut self) -> &mut OptionSet {
+            &mut self.options
+        }
+        fn positional_params_mut(&mut self) -> &mut Vec<String> {
+            &mut self.params
+        }
+        fn print(&mut self, text: &str) -> nix::Result<()> {
+            self.stdout.push_str(text);
+            Ok(())
+        }
+        fn print_error(&mut self, message: &str) {
+            self.stderr.push_str(message);
+            self.stderr.push('\n');
+        }
+    }
+
+    #[test]
+    fn setting_short_options() {
+        let mut env = DummyEnv::default();
+        let args = Field::dummies(["set", "-eu", "-C"]);
+        let result = builtin_main_sync(&mut env, args);
+        assert_eq!(result, (ExitStatus::SUCCESS, None));
+        assert_eq!(env.options.short_names(), "eCu");
+
+        let args = Field::dummies(["set", "+e"]);
+        let result = builtin_main_sync(&mut env, args);
+        assert_eq!(result, (ExitStatus::SUCCESS, None));
+        assert_eq!(env.options.short_names(), "Cu");
+    }
+
+    #[test]
+    fn setting_long_options() {
+        let mut env = DummyEnv::default();
+        let args = Field::dummies(["set", "-o", "pipefail", "-onoglob", "-xo", "nounset"]);
+        let result = builtin_main_sync(&mut env, args);
+        assert_eq!(result, (ExitStatus::SUCCESS, None));
+        assert!(env.options.is_on(ShellOption::PipeFail));
+        assert!(env.options.is_on(ShellOption::NoGlob));
+        assert!(env.options.is_on(ShellOption::XTrace));
+        assert!(env.options.is_on(ShellOption::NoUnset));
+
+        let args = Field::dummies(["set", "+o", "posixly-correct", "+o", "pipefail"]);
+        let result = builtin_main_sync(&mut env, args);
+        assert_eq!(result, (ExitStatus::SUCCESS, None));
+        assert!(!env.options.is_on(ShellOption::PipeFail));
+    }
+
+    fn exit_on_error() -> Option<Divert> {
+        Some(Divert::Exit(ExitStatus::ERROR))
+    }
+
+    #[test]
+    fn unknown_option_is_error() {
+        let mut env = DummyEnv::default();
+        let args = Field::dummies(["set", "-e", "-z", "a"]);
+        let result = builtin_main_sync(&mut env, args);
+        assert_eq!(result, (ExitStatus::ERROR, exit_on_error()));
+        assert_eq!(env.options, OptionSet::default());
+        assert_eq!(env.params, [] as [String; 0]);
+
+        let args = Field::dummies(["set", "-o", "no-such-option"]);
+        let result = builtin_main_sync(&mut env, args);
+        assert_eq!(result, (ExitStatus::ERROR, exit_on_error()));
+    }
+
+    #[test]
+    fn unknown_option_does_not_exit_interactive_shell() {
+        let mut env = DummyEnv::default();
+        env.options.set(ShellOption::Interactive, true);
+        let args = Field::dummies(["set", "-z"]);
+        let result = builtin_main_sync(&mut env, args);
+        assert_eq!(result, (ExitStatus::ERROR, None));
+    }
+
+    #[test]
+    fn interactive_option_cannot_be_changed() {
+        let mut env = DummyEnv::default();
+        let args = Field::dummies(["set", "-i"]);
+        let result = builtin_main_sync(&mut env, args);
+        assert_eq!(result, (ExitStatus::ERROR, exit_on_error()));
+        assert!(!env.options.is_on(ShellOption::Interactive));
+    }
+
+    #[test]
+    fn operands_replace_positional_params() {
+        let mut env = DummyEnv::default();
+        let args = Field::dummies(["set", "-f", "a", "-b"]);
+        let result = builtin_main_sync(&mut env, args);
+        assert_eq!(result, (ExitStatus::SUCCESS, None));
+        assert_eq!(env.params, ["a", "-b"]);
+        assert!(env.options.is_on(ShellOption::NoGlob));
+
+        let args = Field::dummies(["set", "+", "x"]);
+        builtin_main_sync(&mut env, args);
+        assert_eq!(env.params, ["+", "x"]);
+    }
+
+    #[test]
+    fn double_hyphen_clears_positional_params() {
+        let mut env = DummyEnv {
+            params: vec!["a".to_string()],
+            ..Default::default()
+        };
+        let args = Field::dummies(["set", "-e"]);
+        builtin_main_sync(&mut env, args);
+        assert_eq!(env.params, ["a"]);
+
+        let args = Field::dummies(["set", "--"]);
+        let result = builtin_main_sync(&mut env, args);
+        assert_eq!(result, (ExitStatus::SUCCESS, None));
+        assert_eq!(env.params, [] as [String; 0]);
+
+        let args = Field::dummies(["set", "--", "-x"]);
+        builtin_main_sync(&mut env, args);
+        assert_eq!(env.params, ["-x"]);
+        assert!(!env.options.is_on(ShellOption::XTrace));
+    }
+
+    #[test]
+    fn single_hyphen_acts_like_double_hyphen() {
+        let mut env = DummyEnv {
+            params: vec!["a".to_string()],
+            ..Default::default()
+        };
+        let args = Field::dummies(["set", "-"]);
+        let result = builtin_main_sync(&mut env, args);
+        assert_eq!(result, (ExitStatus::SUCCESS, None));
+        assert_eq!(env.params, [] as [String; 0]);
+
+        let args = Field::dummies(["set", "-e", "-", "-x", "y"]);
+        builtin_main_sync(&mut env, args);
+        assert_eq!(env.params, ["-x", "y"]);
+        assert!(env.options.is_on(ShellOption::ErrExit));
+        assert!(!env.options.is_on(ShellOption::XTrace));
+    }
+
+    #[test]
+    fn printing_options() {
+        let mut env = DummyEnv::default();
+        env.options.set(ShellOption::ErrExit, true);
+        let args = Field::dummies(["set", "-o"]);
+        let result = builtin_main_sync(&mut env, args);
+        assert_eq!(result, (ExitStatus::SUCCESS, None));
+        assert!(
+            env.stdout.contains("errexit         on\n"),
+            "{}",
+            env.stdout
+        );
+        assert!(
+            env.stdout.contains("noglob          off\n"),
+            "{}",
+            env.stdout
+        );
+
+        env.stdout.clear();
+        let args = Field::dummies(["set", "+o"]);
+        let result = builtin_main_sync(&mut env, args);
+        assert_eq!(result, (ExitStatus::SUCCESS, None));
+        assert!(env.stdout.contains("set -o errexit\n"), "{}", env.stdout);
+        assert!(env.stdout.contains("set +o noglob\n"), "{}", env.stdout);
+        assert_eq!(env.stdout.lines().count(), ShellOption::ALL.len());
+    }
+
+    #[test]
+    fn options_are_applied_before_printing() {
+        let mut env = DummyEnv::default();
+        let args = Field::dummies(["set", "-e", "-o"]);
+        let result = builtin_main_sync(&mut env, args);
+        assert_eq!(result, (ExitStatus::SUCCESS, None));
+        assert!(env.options.is_on(ShellOption::ErrExit));
+        assert!(
+            env.stdout.contains("errexit         on\n"),
+            "{}",
+            env.stdout
+        );
+    }
+
+    #[test]
+    fn error_message_is_printed_to_stderr() {
+        let mut env = DummyEnv::default();
+        let args = Field::dummies(["set", "-z"]);
+        builtin_main_sync(&mut env, args);
+        assert_eq!(env.stderr, "set: no such option: -z\n");
+        assert_eq!(env.stdout, "");
+    }
+}
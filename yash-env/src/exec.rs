@@ -87,6 +87,12 @@ pub enum Divert {
     Continue,
     /// Return from the current function or script.
     Return,
+    /// Abandon the current command line.
+    ///
+    /// This is the result of an error in an interactive shell. Unlike
+    /// `Continue`, it is not consumed by loops or functions but only by the
+    /// read-eval loop, which then goes on with the next command line.
+    Interrupt,
     /// Exit from the current shell execution environment.
     Exit(ExitStatus),
 }
@@ -18,14 +18,193 @@
 //!
 //! TODO Elaborate
 
+use nix::errno::Errno;
+use std::fmt;
 use yash_syntax::source::Location;
+use yash_syntax::syntax::SwitchCondition;
+
+/// Cause of an error in arithmetic expansion.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ArithError {
+    /// A value token contains an invalid character.
+    InvalidNumericConstant,
+    /// An expression contains a character that is not a whitespace, number,
+    /// operator, or variable name.
+    InvalidCharacter,
+    /// Expression with a missing value.
+    IncompleteExpression,
+    /// Operator missing between values.
+    MissingOperator,
+    /// `(` without `)`.
+    UnclosedParenthesis,
+    /// `?` without `:`.
+    QuestionWithoutColon,
+    /// `:` without `?`.
+    ColonWithoutQuestion,
+    /// Assignment or increment/decrement applied to a non-variable operand.
+    AssignmentToValue,
+    /// Variable value that cannot be parsed as a number.
+    InvalidVariableValue(String),
+    /// Result out of bounds.
+    Overflow,
+    /// Division by zero.
+    DivisionByZero,
+    /// Left shift with a negative or too large right-hand-side operand.
+    InvalidShift,
+}
+
+impl fmt::Display for ArithError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use ArithError::*;
+        match self {
+            InvalidNumericConstant => f.write_str("invalid numeric constant"),
+            InvalidCharacter => f.write_str("invalid character"),
+            IncompleteExpression => f.write_str("incomplete expression"),
+            MissingOperator => f.write_str("expected an operator"),
+            UnclosedParenthesis => f.write_str("unmatched parenthesis"),
+            QuestionWithoutColon => f.write_str("expected `:`"),
+            ColonWithoutQuestion => f.write_str("`:` without matching `?`"),
+            AssignmentToValue => f.write_str("assignment to a non-variable"),
+            InvalidVariableValue(value) => {
+                write!(f, "variable value {:?} cannot be parsed as a number", value)
+            }
+            Overflow => f.write_str("overflow"),
+            DivisionByZero => f.write_str("division by zero"),
+            InvalidShift => f.write_str("invalid shift width"),
+        }
+    }
+}
 
 /// Errors that may happen in word expansions.
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+///
+/// Every variant carries the location of the expansion that caused the error.
+/// The [`Display`](fmt::Display) implementation describes the error without
+/// the location; use [`location`](Self::location) to report where the error
+/// happened.
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub enum Error {
-    // TODO define error variants
+    /// Expansion of an unset parameter while the `nounset` option is on.
+    UnsetParam {
+        /// Name of the parameter.
+        name: String,
+        /// Location of the parameter expansion.
+        location: Location,
+    },
+    /// Expansion of an unset or empty parameter with the `?` modifier.
+    VacantParam {
+        /// Name of the parameter.
+        name: String,
+        /// Expanded word of the modifier, if not empty.
+        message: Option<String>,
+        /// Condition of the modifier.
+        condition: SwitchCondition,
+        /// Location of the parameter expansion.
+        location: Location,
+    },
+    /// Assignment to a read-only variable.
+    AssignReadOnly {
+        /// Name of the variable.
+        name: String,
+        /// Location where the variable was made read-only.
+        read_only_location: Location,
+        /// Location of the expansion that tried the assignment.
+        location: Location,
+    },
+    /// Assignment to a parameter that is not a variable with the `=` modifier.
+    NonassignableParam {
+        /// Name of the parameter.
+        name: String,
+        /// Location of the parameter expansion.
+        location: Location,
+    },
+    /// Parameter expansion with a name that is not a valid parameter.
+    InvalidParamName {
+        /// Name of the parameter.
+        name: String,
+        /// Location of the parameter expansion.
+        location: Location,
+    },
+    /// Error in arithmetic expansion.
+    Arith {
+        /// Cause of the error.
+        cause: ArithError,
+        /// Location of the arithmetic expansion.
+        location: Location,
+    },
+    /// System error that prevented a command substitution.
+    CommandSubst {
+        /// Error value returned from the system.
+        errno: Errno,
+        /// Location of the command substitution.
+        location: Location,
+    },
 }
 
+impl Error {
+    /// Returns the location of the expansion that caused this error.
+    pub fn location(&self) -> &Location {
+        use Error::*;
+        match self {
+            UnsetParam { location, .. }
+            | VacantParam { location, .. }
+            | AssignReadOnly { location, .. }
+            | NonassignableParam { location, .. }
+            | InvalidParamName { location, .. }
+            | Arith { location, .. }
+            | CommandSubst { location, .. } => location,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Error::*;
+        match self {
+            UnsetParam { name, .. } => write!(f, "parameter `{}` is not set", name),
+            VacantParam {
+                name,
+                message: Some(message),
+                ..
+            } => write!(f, "{}: {}", name, message),
+            VacantParam {
+                name,
+                message: None,
+                condition: SwitchCondition::Unset,
+                ..
+            } => write!(f, "parameter `{}` is not set", name),
+            VacantParam {
+                name,
+                message: None,
+                condition: SwitchCondition::UnsetOrEmpty,
+                ..
+            } => write!(f, "parameter `{}` is not set or empty", name),
+            AssignReadOnly {
+                name,
+                read_only_location,
+                ..
+            } => write!(
+                f,
+                "cannot assign to read-only variable `{}` (made read-only at line {})",
+                name, read_only_location.line.number
+            ),
+            NonassignableParam { name, .. } => {
+                write!(
+                    f,
+                    "cannot assign to parameter `{}`, which is not a variable",
+                    name
+                )
+            }
+            InvalidParamName { name, .. } => write!(f, "`{}` is not a valid parameter name", name),
+            Arith { cause, .. } => write!(f, "error in arithmetic expansion: {}", cause),
+            CommandSubst { errno, .. } => {
+                write!(f, "cannot perform command substitution: {}", errno.desc())
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
 /// Result type for word expansion.
 pub type Result<T = ()> = std::result::Result<T, Error>;
 
@@ -72,3 +251,69 @@ impl std::fmt::Display for Field {
         self.value.fmt(f)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_location() {
+        let location = Location::dummy("${x?}");
+        let error = Error::VacantParam {
+            name: "x".to_string(),
+            message: None,
+            condition: SwitchCondition::Unset,
+            location: location.clone(),
+        };
+        assert_eq!(error.location(), &location);
+
+        let error = Error::Arith {
+            cause: ArithError::DivisionByZero,
+            location: location.clone(),
+        };
+        assert_eq!(error.location(), &location);
+    }
+
+    #[test]
+    fn error_display() {
+        let location = Location::dummy("");
+        let error = Error::VacantParam {
+            name: "x".to_string(),
+            message: Some("x must be set".to_string()),
+            condition: SwitchCondition::Unset,
+            location: location.clone(),
+        };
+        assert_eq!(error.to_string(), "x: x must be set");
+
+        let error = Error::VacantParam {
+            name: "x".to_string(),
+            message: None,
+            condition: SwitchCondition::Unset,
+            location: location.clone(),
+        };
+        assert_eq!(error.to_string(), "parameter `x` is not set");
+
+        let error = Error::VacantParam {
+            name: "x".to_string(),
+            message: None,
+            condition: SwitchCondition::UnsetOrEmpty,
+            location: location.clone(),
+        };
+        assert_eq!(error.to_string(), "parameter `x` is not set or empty");
+
+        let error = Error::UnsetParam {
+            name: "y".to_string(),
+            location: location.clone(),
+        };
+        assert_eq!(error.to_string(), "parameter `y` is not set");
+
+        let error = Error::Arith {
+            cause: ArithError::DivisionByZero,
+            location,
+        };
+        assert_eq!(
+            error.to_string(),
+            "error in arithmetic expansion: division by zero"
+        );
+    }
+}
@@ -0,0 +1,79 @@
+// This file is part of yash, an extended POSIX shell.
+// Copyright (C) 2021 WATANABE Yuki
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Utilities for file descriptors.
+
+use crate::System;
+use std::os::unix::io::RawFd;
+
+/// File descriptor of the standard input.
+pub const STDIN: RawFd = 0;
+
+/// File descriptor of the standard output.
+pub const STDOUT: RawFd = 1;
+
+/// File descriptor of the standard error.
+pub const STDERR: RawFd = 2;
+
+/// Moves a file descriptor to another.
+///
+/// The file descriptor `from` is duplicated to `to` and then closed. Nothing
+/// happens if `from` and `to` are equal. Note that `from` is closed even if
+/// the duplication fails, in which case the error is returned.
+pub fn move_fd<S: System + ?Sized>(system: &mut S, from: RawFd, to: RawFd) -> nix::Result<()> {
+    if from == to {
+        return Ok(());
+    }
+    let result = system.dup2(from, to);
+    let _ = system.close(from);
+    result.map(drop)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::VirtualSystem;
+    use futures::executor::block_on;
+    use nix::errno::Errno;
+
+    #[test]
+    fn move_fd_moves_to_another_fd() {
+        let mut system = VirtualSystem::new();
+        let (reader, writer) = system.pipe().unwrap();
+        move_fd(&mut system, writer, 10).unwrap();
+        assert_eq!(system.write(writer, &[42]), Err(Errno::EBADF.into()));
+        assert_eq!(system.write(10, &[42]), Ok(1));
+        let mut buffer = [0; 2];
+        let result = block_on(system.read_async(reader, &mut buffer));
+        assert_eq!(result, Ok(1));
+        assert_eq!(buffer[0], 42);
+    }
+
+    #[test]
+    fn move_fd_to_same_fd_is_no_op() {
+        let mut system = VirtualSystem::new();
+        let (_reader, writer) = system.pipe().unwrap();
+        move_fd(&mut system, writer, writer).unwrap();
+        assert_eq!(system.write(writer, &[42]), Ok(1));
+    }
+
+    #[test]
+    fn move_fd_fails_with_closed_fd() {
+        let mut system = VirtualSystem::new();
+        assert_eq!(move_fd(&mut system, 7, 3), Err(Errno::EBADF.into()));
+        assert_eq!(system.write(3, &[42]), Err(Errno::EBADF.into()));
+    }
+}
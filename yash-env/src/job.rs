@@ -16,13 +16,17 @@
 
 //! Type definitions for job management.
 
+use crate::exec::ExitStatus;
 use nix::unistd::Pid;
 use std::collections::HashMap;
 
 /// Child process of the shell.
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
 pub struct ChildProcess {
-    // TODO state
+    /// Exit status of the process.
+    ///
+    /// This is `None` until the process is known to have finished.
+    pub exit_status: Option<ExitStatus>,
 }
 
 // TODO Job as a set of child processes
@@ -31,4 +35,71 @@ pub struct ChildProcess {
 #[derive(Clone, Debug, Default, Eq, PartialEq)]
 pub struct JobSet {
     pub child_processes: HashMap<Pid, ChildProcess>,
+
+    /// Process ID of the most recently started asynchronous command.
+    ///
+    /// This is the value of the `$!` special parameter.
+    pub last_async_pid: Option<Pid>,
+}
+
+impl JobSet {
+    /// Collects the exit status of a finished child process.
+    ///
+    /// If the child process has finished, it is removed from the set and its
+    /// exit status is returned. Otherwise, the result is `None` and the set is
+    /// not modified.
+    pub fn take_exit_status(&mut self, pid: Pid) -> Option<ExitStatus> {
+        let exit_status = self.child_processes.get(&pid)?.exit_status?;
+        self.child_processes.remove(&pid);
+        Some(exit_status)
+    }
+
+    /// Removes all the finished child processes from the set.
+    pub fn remove_finished(&mut self) {
+        self.child_processes
+            .retain(|_, process| process.exit_status.is_none());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job_set() -> JobSet {
+        let mut jobs = JobSet::default();
+        jobs.child_processes
+            .insert(Pid::from_raw(10), ChildProcess::default());
+        jobs.child_processes.insert(
+            Pid::from_raw(11),
+            ChildProcess {
+                exit_status: Some(ExitStatus(3)),
+            },
+        );
+        jobs
+    }
+
+    #[test]
+    fn take_exit_status_of_finished_process() {
+        let mut jobs = job_set();
+        let pid = Pid::from_raw(11);
+        assert_eq!(jobs.take_exit_status(pid), Some(ExitStatus(3)));
+        assert!(!jobs.child_processes.contains_key(&pid));
+        assert_eq!(jobs.take_exit_status(pid), None);
+    }
+
+    #[test]
+    fn take_exit_status_of_running_process() {
+        let mut jobs = job_set();
+        let pid = Pid::from_raw(10);
+        assert_eq!(jobs.take_exit_status(pid), None);
+        assert!(jobs.child_processes.contains_key(&pid));
+    }
+
+    #[test]
+    fn remove_finished_keeps_running_processes() {
+        let mut jobs = job_set();
+        jobs.remove_finished();
+        assert_eq!(jobs.child_processes.len(), 1);
+        assert!(jobs.child_processes.contains_key(&Pid::from_raw(10)));
+    }
 }
@@ -35,8 +35,11 @@ pub mod builtin;
 pub mod exec;
 pub mod expansion;
 pub mod function;
+pub mod io;
 pub mod job;
+pub mod option;
 mod real_system;
+pub mod signal;
 pub mod variable;
 pub mod virtual_system;
 
@@ -44,15 +47,29 @@ use self::builtin::Builtin;
 use self::exec::ExitStatus;
 use self::function::FunctionSet;
 use self::job::JobSet;
+use self::option::OptionSet;
+use self::signal::SignalHandling;
 use self::variable::VariableSet;
 use async_trait::async_trait;
+use nix::fcntl::FdFlag;
+use nix::fcntl::OFlag;
+use nix::sys::signal::SigSet;
+use nix::sys::signal::SigmaskHow;
+use nix::sys::signal::Signal;
+use nix::sys::stat::FileStat;
+use nix::sys::stat::Mode;
 use nix::unistd::Pid;
+use nix::unistd::Whence;
 use std::collections::HashMap;
 use std::convert::Infallible;
 use std::ffi::CStr;
 use std::ffi::CString;
+use std::ffi::OsString;
 use std::fmt::Debug;
 use std::future::Future;
+use std::os::unix::io::RawFd;
+use std::path::Path;
+use std::path::PathBuf;
 use std::pin::Pin;
 use std::rc::Rc;
 use yash_syntax::alias::AliasSet;
@@ -71,18 +88,47 @@ pub struct Env {
     /// while the parser is reading a command line.
     pub aliases: Rc<AliasSet>,
 
+    /// Name of the shell or the script being executed.
+    ///
+    /// This is the value of the `$0` special parameter.
+    pub arg0: String,
+
     /// Built-in utilities available in the environment.
     pub builtins: HashMap<&'static str, Builtin>,
 
+    /// Whether the errexit option is temporarily ignored.
+    ///
+    /// This flag is set while executing the condition of an `if`, `while`, or
+    /// `until` command, a pipeline other than the last in an and-or list, or a
+    /// negated pipeline. Functions and subshells started in such a context
+    /// inherit the flag.
+    pub errexit_is_suppressed: bool,
+
     /// Exit status of the last executed command.
     pub exit_status: ExitStatus,
 
+    /// Exit status of the last command substitution performed.
+    ///
+    /// A simple command resets this value to `None` before expanding its
+    /// words. If the simple command has no command name, its exit status is
+    /// the exit status of the last command substitution.
+    pub last_command_subst_exit_status: Option<ExitStatus>,
+
     /// Functions defined in the environment.
     pub functions: FunctionSet,
 
     /// Jobs managed in the environment.
     pub jobs: JobSet,
 
+    /// Process ID of the main shell process.
+    ///
+    /// This is the value of the `$$` special parameter. A subshell inherits
+    /// this value from the parent shell.
+    pub main_pid: Pid,
+
+    /// Shell options.
+    pub options: OptionSet,
+
     /// Variables and positional parameters defined in the environment.
     pub variables: VariableSet,
 
@@ -97,6 +143,162 @@ pub trait System: Debug {
     /// Whether there is an executable file at the specified path.
     fn is_executable_file(&self, path: &CStr) -> bool;
 
+    /// Returns the names of the entries in a directory.
+    ///
+    /// This is a wrapper around the `opendir`, `readdir`, and `closedir`
+    /// functions. The result does not include the `.` and `..` entries. The
+    /// order of the entries is unspecified.
+    fn read_dir(&self, path: &CStr) -> nix::Result<Vec<OsString>>;
+
+    /// Returns the home directory of a user.
+    ///
+    /// This is a wrapper around the `getpwnam_r` function. Returns `Ok(None)`
+    /// if the user is not found.
+    fn getpwnam_dir(&self, name: &str) -> nix::Result<Option<PathBuf>>;
+
+    /// Returns the process ID of the current process.
+    ///
+    /// This is a wrapper around the `getpid` system call.
+    fn getpid(&self) -> Pid;
+
+    /// Creates an unnamed pipe.
+    ///
+    /// This is a thin wrapper around the `pipe` system call.
+    /// If successful, returns the reading and writing ends of the pipe.
+    fn pipe(&mut self) -> nix::Result<(RawFd, RawFd)>;
+
+    /// Opens a file.
+    ///
+    /// This is a thin wrapper around the `open` system call. If successful,
+    /// returns the new file descriptor.
+    fn open(&mut self, path: &CStr, option: OFlag, mode: Mode) -> nix::Result<RawFd>;
+
+    /// Opens an unnamed temporary file.
+    ///
+    /// The file is created in `parent_dir` and removed from the directory
+    /// immediately so that it is deleted when closed. If successful, returns
+    /// a file descriptor open for reading and writing.
+    fn open_tmpfile(&mut self, parent_dir: &Path) -> nix::Result<RawFd>;
+
+    /// Returns the status of a file.
+    ///
+    /// This is a thin wrapper around the `stat` system call.
+    fn stat(&self, path: &CStr) -> nix::Result<FileStat>;
+
+    /// Returns the status of the file open for the file descriptor.
+    ///
+    /// This is a thin wrapper around the `fstat` system call.
+    fn fstat(&self, fd: RawFd) -> nix::Result<FileStat>;
+
+    /// Duplicates a file descriptor to a free one.
+    ///
+    /// This is a thin wrapper around the `fcntl` system call with
+    /// `F_DUPFD_CLOEXEC`. The new file descriptor is the minimum available one
+    /// not less than `to_min` and has the close-on-exec flag set.
+    fn fcntl_dupfd(&mut self, from: RawFd, to_min: RawFd) -> nix::Result<RawFd>;
+
+    /// Duplicates a file descriptor to the lowest free one.
+    ///
+    /// This is a thin wrapper around the `dup` system call. The new file
+    /// descriptor does not have the close-on-exec flag set.
+    fn dup(&mut self, from: RawFd) -> nix::Result<RawFd>;
+
+    /// Duplicates a file descriptor.
+    ///
+    /// This is a thin wrapper around the `dup2` system call. If successful,
+    /// returns `to`.
+    fn dup2(&mut self, from: RawFd, to: RawFd) -> nix::Result<RawFd>;
+
+    /// Returns the file descriptor flags.
+    ///
+    /// This is a thin wrapper around the `fcntl` system call with `F_GETFD`.
+    /// The only flag currently defined is `FD_CLOEXEC`.
+    fn fcntl_getfd(&self, fd: RawFd) -> nix::Result<FdFlag>;
+
+    /// Sets the file descriptor flags.
+    ///
+    /// This is a thin wrapper around the `fcntl` system call with `F_SETFD`.
+    fn fcntl_setfd(&mut self, fd: RawFd, flags: FdFlag) -> nix::Result<()>;
+
+    /// Returns the file status flags of the open file description.
+    ///
+    /// This is a thin wrapper around the `fcntl` system call with `F_GETFL`.
+    /// The result includes the access mode of the open file description.
+    fn fcntl_getfl(&self, fd: RawFd) -> nix::Result<OFlag>;
+
+    /// Sets the file status flags of the open file description.
+    ///
+    /// This is a thin wrapper around the `fcntl` system call with `F_SETFL`.
+    /// The access mode in `flags` is ignored.
+    fn fcntl_setfl(&mut self, fd: RawFd, flags: OFlag) -> nix::Result<()>;
+
+    /// Closes a file descriptor.
+    ///
+    /// This is a thin wrapper around the `close` system call.
+    fn close(&mut self, fd: RawFd) -> nix::Result<()>;
+
+    /// Reads from the file descriptor.
+    ///
+    /// This is a thin wrapper around the `read` system call. If successful,
+    /// returns the number of bytes read, which is zero at the end of file.
+    /// This function blocks if no data is available, unless the file
+    /// descriptor is non-blocking, in which case it fails with `EAGAIN`.
+    fn read(&mut self, fd: RawFd, buffer: &mut [u8]) -> nix::Result<usize>;
+
+    /// Reads from the file descriptor asynchronously.
+    ///
+    /// This is a thin wrapper around the `read` system call. If successful,
+    /// returns the number of bytes read, which is zero at the end of file.
+    ///
+    /// This function is a temporary API that performs asynchronous reading by
+    /// blocking in the function or by returning a future you need to await,
+    /// like [`wait_sync`](Self::wait_sync).
+    fn read_async<'a>(
+        &'a mut self,
+        fd: RawFd,
+        buffer: &'a mut [u8],
+    ) -> Pin<Box<dyn Future<Output = nix::Result<usize>> + 'a>>;
+
+    /// Writes to the file descriptor.
+    ///
+    /// This is a thin wrapper around the `write` system call. If successful,
+    /// returns the number of bytes written, which may be less than the length
+    /// of the buffer.
+    fn write(&mut self, fd: RawFd, buffer: &[u8]) -> nix::Result<usize>;
+
+    /// Writes to the file descriptor asynchronously.
+    ///
+    /// This is a thin wrapper around the `write` system call. If successful,
+    /// returns the number of bytes written, which may be less than the length
+    /// of the buffer.
+    ///
+    /// This function is a temporary API that performs asynchronous writing by
+    /// blocking in the function or by returning a future you need to await,
+    /// like [`read_async`](Self::read_async).
+    fn write_async<'a>(
+        &'a mut self,
+        fd: RawFd,
+        buffer: &'a [u8],
+    ) -> Pin<Box<dyn Future<Output = nix::Result<usize>> + 'a>>;
+
+    /// Writes the whole buffer to the file descriptor.
+    ///
+    /// This function calls [`write`](Self::write) repeatedly until all bytes
+    /// are written or an error occurs.
+    fn write_all(&mut self, fd: RawFd, mut buffer: &[u8]) -> nix::Result<()> {
+        while !buffer.is_empty() {
+            let count = self.write(fd, buffer)?;
+            buffer = &buffer[count..];
+        }
+        Ok(())
+    }
+
+    /// Moves the position of the file descriptor.
+    ///
+    /// This is a thin wrapper around the `lseek` system call. If successful,
+    /// returns the new position from the beginning of the file.
+    fn lseek(&mut self, fd: RawFd, offset: i64, whence: Whence) -> nix::Result<i64>;
+
     /// Creates a new child process.
     ///
     /// This is a thin wrapper around the `fork` system call. Users of `Env`
@@ -120,11 +322,11 @@ pub trait System: Debug {
     /// says:
     ///
     /// > If a multi-threaded process calls fork(), the new process shall
-    /// contain a replica of the calling thread and its entire address space,
-    /// possibly including the states of mutexes and other resources.
-    /// Consequently, to avoid errors, the child process may only execute
-    /// async-signal-safe operations until such time as one of the exec
-    /// functions is called.
+    /// > contain a replica of the calling thread and its entire address space,
+    /// > possibly including the states of mutexes and other resources.
+    /// > Consequently, to avoid errors, the child process may only execute
+    /// > async-signal-safe operations until such time as one of the exec
+    /// > functions is called.
     ///
     /// Since this function needs to allocate memory for the returned `Box`,
     /// which is not async-signal-safe, this function must be called in a
@@ -154,6 +356,64 @@ pub trait System: Debug {
         &mut self,
     ) -> Pin<Box<dyn Future<Output = nix::Result<nix::sys::wait::WaitStatus>> + '_>>;
 
+    /// Sets how a signal is handled.
+    ///
+    /// This is a wrapper around the `sigaction` system call. If successful,
+    /// returns the previous handling. The handling of `SIGKILL` and `SIGSTOP`
+    /// cannot be changed.
+    ///
+    /// If the handling is [`SignalHandling::Catch`], the signal is recorded
+    /// when delivered so that it can be obtained by
+    /// [`caught_signals`](Self::caught_signals).
+    fn set_signal_handling(
+        &mut self,
+        signal: Signal,
+        handling: SignalHandling,
+    ) -> nix::Result<SignalHandling>;
+
+    /// Blocks and/or unblocks signals.
+    ///
+    /// This is a thin wrapper around the `sigprocmask` system call. The new
+    /// signal mask is computed from the current mask and `signals` according
+    /// to `how`. If successful, returns the previous mask. `SIGKILL` and
+    /// `SIGSTOP` cannot be blocked.
+    fn sigmask(&mut self, how: SigmaskHow, signals: &SigSet) -> nix::Result<SigSet>;
+
+    /// Sends a signal.
+    ///
+    /// This is a thin wrapper around the `kill` system call. As in the system
+    /// call, a negative `target` designates a process group, and zero the
+    /// process group of the current process. If `signal` is `None`, this
+    /// function only checks if the target exists.
+    fn kill(&mut self, target: Pid, signal: Option<Signal>) -> nix::Result<()>;
+
+    /// Sends a signal to a process group.
+    ///
+    /// This function is equivalent to [`kill`](Self::kill) with the negated
+    /// process group ID.
+    fn killpg(&mut self, pgid: Pid, signal: Option<Signal>) -> nix::Result<()> {
+        self.kill(Pid::from_raw(-pgid.as_raw()), signal)
+    }
+
+    /// Returns signals that have been caught.
+    ///
+    /// This function returns the signals that have been delivered to the
+    /// current process with [`SignalHandling::Catch`] since the last call to
+    /// this function or [`wait_for_signals`](Self::wait_for_signals). If no
+    /// signals have been caught, the result is empty.
+    fn caught_signals(&mut self) -> Vec<Signal>;
+
+    /// Waits for signals to be caught.
+    ///
+    /// The returned future resolves to the same result as
+    /// [`caught_signals`](Self::caught_signals) once at least one signal has
+    /// been caught.
+    ///
+    /// This function is a temporary API that performs asynchronous waiting by
+    /// blocking in the function or by returning a future you need to await,
+    /// like [`read_async`](Self::read_async).
+    fn wait_for_signals(&mut self) -> Pin<Box<dyn Future<Output = Vec<Signal>> + '_>>;
+
     // TODO Consider passing raw pointers for optimization
     /// Replaces the current process with an external utility.
     ///
@@ -195,10 +455,15 @@ impl Env {
     pub fn with_system(system: Box<dyn System>) -> Env {
         Env {
             aliases: Default::default(),
+            arg0: Default::default(),
             builtins: Default::default(),
+            errexit_is_suppressed: false,
             exit_status: Default::default(),
+            last_command_subst_exit_status: None,
             functions: Default::default(),
             jobs: Default::default(),
+            main_pid: system.getpid(),
+            options: Default::default(),
             variables: Default::default(),
             system,
         }
@@ -217,32 +482,74 @@ impl Env {
     pub fn clone_with_system(&self, system: Box<dyn System>) -> Env {
         Env {
             aliases: self.aliases.clone(),
+            arg0: self.arg0.clone(),
             builtins: self.builtins.clone(),
+            errexit_is_suppressed: self.errexit_is_suppressed,
             exit_status: self.exit_status,
+            last_command_subst_exit_status: self.last_command_subst_exit_status,
             functions: self.functions.clone(),
             jobs: self.jobs.clone(),
+            main_pid: self.main_pid,
+            options: self.options.clone(),
             variables: self.variables.clone(),
             system,
         }
     }
 
+    /// Prints a message to the standard error.
+    ///
+    /// A newline is appended to the message. Errors writing the message are
+    /// ignored.
+    pub fn print_error(&mut self, message: &str) {
+        let line = format!("{}\n", message);
+        let _ = self.system.write_all(io::STDERR, line.as_bytes());
+    }
+
+    /// Tests whether the errexit option is in effect.
+    ///
+    /// The result is true if the [`ErrExit`](option::ShellOption::ErrExit)
+    /// option is on and not [suppressed](Self::errexit_is_suppressed).
+    #[must_use]
+    pub fn errexit_is_applicable(&self) -> bool {
+        self.options.is_on(option::ShellOption::ErrExit) && !self.errexit_is_suppressed
+    }
+
+    /// Applies the errexit option to the current exit status.
+    ///
+    /// If the errexit option [is applicable](Self::errexit_is_applicable) and
+    /// the current exit status is not successful, the result is
+    /// [`Divert::Exit`](exec::Divert::Exit) that should make the shell exit
+    /// with the exit status. Otherwise, the result is `Ok(())`.
+    pub fn apply_errexit(&self) -> exec::Result {
+        if self.errexit_is_applicable() && !self.exit_status.is_successful() {
+            Err(exec::Divert::Exit(self.exit_status))
+        } else {
+            Ok(())
+        }
+    }
+
     /// Starts a subshell.
     ///
     /// This function creates a new child process in which the argument function
     /// is run. If the child was started successfully, this function returns the
     /// child's process ID. Otherwise, it returns an error.
     ///
+    /// The argument function returns a future that performs the task of the
+    /// subshell. The subshell exits with the exit status in the environment
+    /// after the future completes.
+    ///
     /// Although this function is `async`, it does not wait for the child to
     /// finish, which means the parent and child processes will run
-    /// concurrently.
+    /// concurrently. Use [`wait_for_subshell`](Self::wait_for_subshell) to
+    /// wait for the child.
     pub async fn start_subshell<F>(&mut self, f: F) -> nix::Result<Pid>
     where
-        F: FnOnce(&mut Env) + 'static,
+        F: for<'a> FnOnce(&'a mut Env) -> Pin<Box<dyn Future<Output = ()> + 'a>> + 'static,
     {
         let mut f = Some(f);
         let task: ChildProcessTask = Box::new(move |env| {
             if let Some(f) = f.take() {
-                Box::pin(async move { f(env) })
+                f(env)
             } else {
                 Box::pin(async {})
             }
@@ -253,6 +560,52 @@ impl Env {
         Ok(child_pid)
     }
 
+    /// Waits for subshells to finish.
+    ///
+    /// This function waits for the child processes started by
+    /// [`start_subshell`](Self::start_subshell) and returns their exit
+    /// statuses in the order of `child_pids`. The children may finish in any
+    /// order. The exit status of a child killed by a signal is 128 plus the
+    /// signal number.
+    ///
+    /// The waited-for subshells are removed from the [job set](Self::jobs) if
+    /// they are in it. If another child process in the job set finishes while
+    /// waiting, its exit status is recorded in the job set so that it can be
+    /// collected later by [`JobSet::take_exit_status`](job::JobSet::take_exit_status).
+    ///
+    /// This function does not support job control. If a subshell suspends,
+    /// the current shell continues waiting for the subshell to finish, so it
+    /// must be resumed by some other means.
+    pub async fn wait_for_subshells(&mut self, child_pids: &[Pid]) -> nix::Result<Vec<ExitStatus>> {
+        use nix::sys::wait::WaitStatus::*;
+        let mut exit_statuses = vec![None; child_pids.len()];
+        while exit_statuses.iter().any(std::option::Option::is_none) {
+            #[allow(deprecated)]
+            let (pid, exit_status) = match self.system.wait_sync().await? {
+                Exited(pid, exit_status) => (pid, ExitStatus(exit_status)),
+                Signaled(pid, signal, _core_dumped) => (pid, ExitStatus(128 + signal as i32)),
+                _ => continue,
+            };
+            if let Some(index) = child_pids.iter().position(|&child| child == pid) {
+                exit_statuses[index] = Some(exit_status);
+                self.jobs.child_processes.remove(&pid);
+            } else if let Some(process) = self.jobs.child_processes.get_mut(&pid) {
+                process.exit_status = Some(exit_status);
+            }
+        }
+        Ok(exit_statuses.into_iter().flatten().collect())
+    }
+
+    /// Waits for a subshell to finish.
+    ///
+    /// This function waits for the child process started by
+    /// [`start_subshell`](Self::start_subshell) and returns its exit status.
+    /// See [`wait_for_subshells`](Self::wait_for_subshells) for details.
+    pub async fn wait_for_subshell(&mut self, child_pid: Pid) -> nix::Result<ExitStatus> {
+        let exit_statuses = self.wait_for_subshells(&[child_pid]).await?;
+        Ok(exit_statuses[0])
+    }
+
     /// Runs the argument function in a subshell.
     ///
     /// This function creates a new (real or virtual) subshell in which the
@@ -282,29 +635,11 @@ impl Env {
     /// [`new_child_process`](System::new_child_process), the error is returned.
     pub async fn run_in_subshell<F>(&mut self, f: F) -> nix::Result<ExitStatus>
     where
-        F: FnOnce(&mut Env) + 'static,
+        F: for<'a> FnOnce(&'a mut Env) -> Pin<Box<dyn Future<Output = ()> + 'a>> + 'static,
     {
         // TODO Use a virtual subshell when possible
         let child_pid = self.start_subshell(f).await?;
-
-        use nix::sys::wait::WaitStatus::*;
-        #[allow(deprecated)]
-        match self.system.wait_sync().await? {
-            Exited(pid, exit_status) => {
-                // TODO This assertion is not correct. We need to handle
-                // other possibly existing child processes.
-                assert_eq!(pid, child_pid);
-                Ok(ExitStatus(exit_status))
-            }
-            Signaled(pid, _signal, _core_dumped) => {
-                // TODO This assertion is not correct. We need to handle
-                // other possibly existing child processes.
-                assert_eq!(pid, child_pid);
-                // TODO Convert signal to exit status
-                Ok(ExitStatus(128))
-            }
-            _ => todo!(),
-        }
+        self.wait_for_subshell(child_pid).await
     }
 }
 
@@ -323,11 +658,97 @@ mod tests {
 
         let status = ExitStatus(97);
         let mut env = Env::with_system(Box::new(system));
-        let result = executor.run_until(env.run_in_subshell(move |env| env.exit_status = status));
+        let result = executor.run_until(env.run_in_subshell(move |env| {
+            Box::pin(async move {
+                env.exit_status = status;
+            })
+        }));
         assert_eq!(result, Ok(status));
     }
 
-    // TODO Test case for parent with signaled child
+    #[test]
+    fn apply_errexit_with_failure() {
+        let mut env = Env::new_virtual();
+        env.exit_status = ExitStatus(3);
+        assert_eq!(env.apply_errexit(), Ok(()));
+
+        env.options.set(option::ShellOption::ErrExit, true);
+        assert_eq!(env.apply_errexit(), Err(exec::Divert::Exit(ExitStatus(3))));
+
+        env.errexit_is_suppressed = true;
+        assert_eq!(env.apply_errexit(), Ok(()));
+    }
+
+    #[test]
+    fn apply_errexit_with_success() {
+        let mut env = Env::new_virtual();
+        env.options.set(option::ShellOption::ErrExit, true);
+        assert_eq!(env.apply_errexit(), Ok(()));
+    }
+
+    fn virtual_env_with_executor() -> (Env, LocalPool) {
+        let system = VirtualSystem::new();
+        let executor = LocalPool::new();
+        system.state.borrow_mut().executor = Some(Rc::new(executor.spawner()));
+        (Env::with_system(Box::new(system)), executor)
+    }
+
+    #[test]
+    fn wait_for_subshell_with_signaled_child() {
+        let (mut env, mut executor) = virtual_env_with_executor();
+        let result = executor.run_until(env.run_in_subshell(|env| {
+            Box::pin(async move {
+                let pid = env.system.getpid();
+                env.system.kill(pid, Some(Signal::SIGTERM)).unwrap();
+            })
+        }));
+        assert_eq!(result, Ok(ExitStatus(128 + Signal::SIGTERM as i32)));
+    }
+
+    #[test]
+    fn wait_for_subshells_records_other_children_in_job_set() {
+        let (mut env, mut executor) = virtual_env_with_executor();
+        let job_pid = executor
+            .run_until(env.start_subshell(|env| {
+                Box::pin(async move {
+                    env.exit_status = ExitStatus(5);
+                })
+            }))
+            .unwrap();
+        env.jobs
+            .child_processes
+            .insert(job_pid, job::ChildProcess::default());
+        executor.run_until_stalled();
+
+        let result = executor.run_until(env.run_in_subshell(|env| {
+            Box::pin(async move {
+                env.exit_status = ExitStatus(7);
+            })
+        }));
+        assert_eq!(result, Ok(ExitStatus(7)));
+        assert_eq!(
+            env.jobs.child_processes[&job_pid].exit_status,
+            Some(ExitStatus(5))
+        );
+    }
+
+    #[test]
+    fn wait_for_subshell_removes_child_from_job_set() {
+        let (mut env, mut executor) = virtual_env_with_executor();
+        let pid = executor
+            .run_until(env.start_subshell(|env| {
+                Box::pin(async move {
+                    env.exit_status = ExitStatus(3);
+                })
+            }))
+            .unwrap();
+        env.jobs
+            .child_processes
+            .insert(pid, job::ChildProcess::default());
+        let result = executor.run_until(env.wait_for_subshell(pid));
+        assert_eq!(result, Ok(ExitStatus(3)));
+        assert!(!env.jobs.child_processes.contains_key(&pid));
+    }
 
     // TODO Test case where fork fails
 }
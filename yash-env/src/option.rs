@@ -0,0 +1,274 @@
+// This file is part of yash, an extended POSIX shell.
+// Copyright (C) 2021 WATANABE Yuki
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Type definitions for shell options.
+//!
+//! A shell option is a boolean flag that modifies the behavior of the shell.
+//! [`OptionSet`] holds the states of all the shell options in the
+//! environment.
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::str::FromStr;
+
+/// Shell option.
+///
+/// Each option has a [long name](Self::long_name) and may have a
+/// [single-letter name](Self::short_name).
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum ShellOption {
+    /// Exports all variables when they are assigned.
+    AllExport,
+    /// Makes the shell exit when a command fails.
+    ErrExit,
+    /// Prevents an interactive shell from exiting on end-of-file.
+    IgnoreEof,
+    /// Makes the shell interactive.
+    Interactive,
+    /// Enables job control.
+    Monitor,
+    /// Prevents the `>` redirection from overwriting an existing file.
+    NoClobber,
+    /// Makes the shell read commands without executing them.
+    NoExec,
+    /// Disables pathname expansion.
+    NoGlob,
+    /// Makes the expansion of an unset parameter an error.
+    NoUnset,
+    /// Reports the completion of background jobs asynchronously.
+    Notify,
+    /// Makes the exit status of a pipeline non-zero if any of its commands
+    /// fails.
+    PipeFail,
+    /// Disables non-POSIX extensions.
+    PosixlyCorrect,
+    /// Echoes the input to the standard error as it is read.
+    Verbose,
+    /// Traces expanded commands to the standard error before executing them.
+    XTrace,
+}
+
+impl ShellOption {
+    /// Array of all the shell options.
+    pub const ALL: [ShellOption; 14] = [
+        ShellOption::AllExport,
+        ShellOption::ErrExit,
+        ShellOption::IgnoreEof,
+        ShellOption::Interactive,
+        ShellOption::Monitor,
+        ShellOption::NoClobber,
+        ShellOption::NoExec,
+        ShellOption::NoGlob,
+        ShellOption::NoUnset,
+        ShellOption::Notify,
+        ShellOption::PipeFail,
+        ShellOption::PosixlyCorrect,
+        ShellOption::Verbose,
+        ShellOption::XTrace,
+    ];
+
+    /// Returns the single-letter name of the option, if any.
+    #[must_use]
+    pub fn short_name(self) -> Option<char> {
+        use ShellOption::*;
+        match self {
+            AllExport => Some('a'),
+            ErrExit => Some('e'),
+            IgnoreEof => None,
+            Interactive => Some('i'),
+            Monitor => Some('m'),
+            NoClobber => Some('C'),
+            NoExec => Some('n'),
+            NoGlob => Some('f'),
+            NoUnset => Some('u'),
+            Notify => Some('b'),
+            PipeFail => None,
+            PosixlyCorrect => None,
+            Verbose => Some('v'),
+            XTrace => Some('x'),
+        }
+    }
+
+    /// Returns the option that has the single-letter name, if any.
+    #[must_use]
+    pub fn from_short_name(name: char) -> Option<ShellOption> {
+        ShellOption::ALL
+            .iter()
+            .copied()
+            .find(|option| option.short_name() == Some(name))
+    }
+
+    /// Returns the long name of the option.
+    ///
+    /// The long name is in lowercase and does not contain hyphens.
+    #[must_use]
+    pub fn long_name(self) -> &'static str {
+        use ShellOption::*;
+        match self {
+            AllExport => "allexport",
+            ErrExit => "errexit",
+            IgnoreEof => "ignoreeof",
+            Interactive => "interactive",
+            Monitor => "monitor",
+            NoClobber => "noclobber",
+            NoExec => "noexec",
+            NoGlob => "noglob",
+            NoUnset => "nounset",
+            Notify => "notify",
+            PipeFail => "pipefail",
+            PosixlyCorrect => "posixlycorrect",
+            Verbose => "verbose",
+            XTrace => "xtrace",
+        }
+    }
+}
+
+impl fmt::Display for ShellOption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.long_name())
+    }
+}
+
+/// Error in parsing a shell option name.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct NoSuchOption;
+
+impl fmt::Display for NoSuchOption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("no such option")
+    }
+}
+
+impl std::error::Error for NoSuchOption {}
+
+impl FromStr for ShellOption {
+    type Err = NoSuchOption;
+
+    /// Parses a long option name.
+    ///
+    /// The name is case-insensitive, and hyphens and underscores in it are
+    /// ignored, so `posixly-correct` and `PosixlyCorrect` both name
+    /// [`ShellOption::PosixlyCorrect`].
+    fn from_str(name: &str) -> Result<ShellOption, NoSuchOption> {
+        let name: String = name
+            .chars()
+            .filter(|&c| c != '-' && c != '_')
+            .map(|c| c.to_ascii_lowercase())
+            .collect();
+        ShellOption::ALL
+            .iter()
+            .copied()
+            .find(|option| option.long_name() == name)
+            .ok_or(NoSuchOption)
+    }
+}
+
+/// Set of shell options that are on.
+///
+/// All options are off by default.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct OptionSet {
+    enabled: BTreeSet<ShellOption>,
+}
+
+impl OptionSet {
+    /// Returns whether the option is on.
+    #[must_use]
+    pub fn is_on(&self, option: ShellOption) -> bool {
+        self.enabled.contains(&option)
+    }
+
+    /// Returns the single-letter names of the options that are on.
+    ///
+    /// The result is the value of the `$-` special parameter.
+    #[must_use]
+    pub fn short_names(&self) -> String {
+        self.enabled
+            .iter()
+            .filter_map(|option| option.short_name())
+            .collect()
+    }
+
+    /// Turns the option on or off.
+    pub fn set(&mut self, option: ShellOption, on: bool) {
+        if on {
+            self.enabled.insert(option);
+        } else {
+            self.enabled.remove(&option);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn options_are_off_by_default() {
+        let options = OptionSet::default();
+        for &option in &ShellOption::ALL {
+            assert!(!options.is_on(option), "{}", option);
+        }
+    }
+
+    #[test]
+    fn setting_options() {
+        let mut options = OptionSet::default();
+        options.set(ShellOption::PipeFail, true);
+        assert!(options.is_on(ShellOption::PipeFail));
+        options.set(ShellOption::PipeFail, false);
+        assert!(!options.is_on(ShellOption::PipeFail));
+    }
+
+    #[test]
+    fn short_names_of_enabled_options() {
+        let mut options = OptionSet::default();
+        assert_eq!(options.short_names(), "");
+        options.set(ShellOption::PipeFail, true);
+        assert_eq!(options.short_names(), "");
+        options.set(ShellOption::Monitor, true);
+        assert_eq!(options.short_names(), "m");
+        options.set(ShellOption::NoUnset, true);
+        options.set(ShellOption::ErrExit, true);
+        assert_eq!(options.short_names(), "emu");
+    }
+
+    #[test]
+    fn short_name_round_trip() {
+        for &option in &ShellOption::ALL {
+            if let Some(name) = option.short_name() {
+                assert_eq!(ShellOption::from_short_name(name), Some(option));
+            }
+        }
+        assert_eq!(
+            ShellOption::from_short_name('C'),
+            Some(ShellOption::NoClobber)
+        );
+        assert_eq!(ShellOption::from_short_name('c'), None);
+    }
+
+    #[test]
+    fn parsing_long_names() {
+        for &option in &ShellOption::ALL {
+            assert_eq!(option.long_name().parse(), Ok(option));
+        }
+        assert_eq!("NoUnset".parse(), Ok(ShellOption::NoUnset));
+        assert_eq!("posixly-correct".parse(), Ok(ShellOption::PosixlyCorrect));
+        assert_eq!("no_glob".parse(), Ok(ShellOption::NoGlob));
+        assert_eq!("unset".parse::<ShellOption>(), Err(NoSuchOption));
+        assert_eq!("".parse::<ShellOption>(), Err(NoSuchOption));
+    }
+}
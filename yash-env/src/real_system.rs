@@ -16,20 +16,46 @@
 
 //! Implementation of `System` that actually interacts with the system.
 
+use super::exec::ExitStatus;
+use super::signal::SignalHandling;
 use super::ChildProcess;
 use super::Env;
 use super::System;
 use async_trait::async_trait;
-use nix::libc::{S_IFMT, S_IFREG};
+use nix::errno::Errno;
+use nix::fcntl::FcntlArg;
+use nix::fcntl::FdFlag;
+use nix::fcntl::OFlag;
+use nix::libc::{c_int, S_IFMT, S_IFREG};
+use nix::poll::PollFd;
+use nix::poll::PollFlags;
+use nix::sys::signal::SaFlags;
+use nix::sys::signal::SigAction;
+use nix::sys::signal::SigHandler;
+use nix::sys::signal::SigSet;
+use nix::sys::signal::SigmaskHow;
+use nix::sys::signal::Signal;
 use nix::sys::stat::stat;
+use nix::sys::stat::FileStat;
+use nix::sys::stat::Mode;
 use nix::unistd::access;
 use nix::unistd::AccessFlags;
 use nix::unistd::Pid;
+use nix::unistd::Whence;
 use std::convert::Infallible;
 use std::ffi::CStr;
 use std::ffi::CString;
+use std::ffi::OsStr;
+use std::ffi::OsString;
 use std::future::Future;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::RawFd;
+use std::path::Path;
+use std::path::PathBuf;
 use std::pin::Pin;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicI32;
+use std::sync::atomic::Ordering::SeqCst;
 
 fn is_executable(path: &CStr) -> bool {
     let flags = AccessFlags::X_OK;
@@ -44,8 +70,98 @@ fn is_regular_file(path: &CStr) -> bool {
     }
 }
 
+/// Minimum file descriptor used for the self-pipe.
+///
+/// The self-pipe is moved to a file descriptor not less than this value so
+/// that it does not conflict with file descriptors the user may redirect.
+const MIN_INTERNAL_FD: RawFd = 10;
+
+/// Flags indicating which signals have been caught, indexed by signal number.
+static CAUGHT_SIGNALS: [AtomicBool; 64] = [const { AtomicBool::new(false) }; 64];
+
+/// Reading end of the self-pipe, or -1 if the pipe has not been created.
+static SIGNAL_PIPE_READER: AtomicI32 = AtomicI32::new(-1);
+
+/// Writing end of the self-pipe, or -1 if the pipe has not been created.
+static SIGNAL_PIPE_WRITER: AtomicI32 = AtomicI32::new(-1);
+
+/// Whether a byte has been written to the self-pipe and not yet consumed.
+///
+/// The signal handler writes at most one byte to the pipe until the pipe is
+/// drained, so the pipe never gets full and the write never fails.
+static WAKE_PENDING: AtomicBool = AtomicBool::new(false);
+
+/// Signal handler that records the signal and wakes the self-pipe.
+///
+/// This function only performs async-signal-safe operations.
+extern "C" fn catch_signal(signal: c_int) {
+    if let Some(flag) = CAUGHT_SIGNALS.get(signal as usize) {
+        flag.store(true, SeqCst);
+    }
+    let writer = SIGNAL_PIPE_WRITER.load(SeqCst);
+    if writer >= 0 && !WAKE_PENDING.swap(true, SeqCst) {
+        let byte = [0u8];
+        // SAFETY: `write` is async-signal-safe and the buffer is valid.
+        unsafe { nix::libc::write(writer, byte.as_ptr().cast(), 1) };
+    }
+}
+
+/// Moves a file descriptor of the self-pipe to an internal one.
+///
+/// The new file descriptor has the close-on-exec and non-blocking flags set.
+/// The original file descriptor is closed.
+fn to_internal_fd(fd: RawFd) -> nix::Result<RawFd> {
+    let result = nix::fcntl::fcntl(fd, FcntlArg::F_DUPFD_CLOEXEC(MIN_INTERNAL_FD));
+    let _ = nix::unistd::close(fd);
+    let new_fd = result?;
+    match nix::fcntl::fcntl(new_fd, FcntlArg::F_SETFL(OFlag::O_NONBLOCK)) {
+        Ok(_) => Ok(new_fd),
+        Err(error) => {
+            let _ = nix::unistd::close(new_fd);
+            Err(error)
+        }
+    }
+}
+
+/// Creates a new self-pipe, replacing the existing one if any.
+fn open_signal_pipe() -> nix::Result<RawFd> {
+    let (reader, writer) = nix::unistd::pipe()?;
+    let reader = to_internal_fd(reader);
+    let writer = to_internal_fd(writer);
+    let (reader, writer) = match (reader, writer) {
+        (Ok(reader), Ok(writer)) => (reader, writer),
+        (reader, writer) => {
+            for fd in reader.iter().chain(writer.iter()) {
+                let _ = nix::unistd::close(*fd);
+            }
+            return Err(reader.and(writer).unwrap_err());
+        }
+    };
+    WAKE_PENDING.store(false, SeqCst);
+    let old_writer = SIGNAL_PIPE_WRITER.swap(writer, SeqCst);
+    let old_reader = SIGNAL_PIPE_READER.swap(reader, SeqCst);
+    for fd in [old_reader, old_writer] {
+        if fd >= 0 {
+            let _ = nix::unistd::close(fd);
+        }
+    }
+    Ok(reader)
+}
+
+/// Returns the reading end of the self-pipe, creating the pipe if necessary.
+fn signal_pipe_reader() -> nix::Result<RawFd> {
+    match SIGNAL_PIPE_READER.load(SeqCst) {
+        reader if reader >= 0 => Ok(reader),
+        _ => open_signal_pipe(),
+    }
+}
+
 /// Implementation of `System` that actually interacts with the system.
 ///
+/// Caught signals are recorded by a signal handler that also writes to a
+/// self-pipe so that [`wait_for_signals`](System::wait_for_signals) can wait
+/// for the pipe to become readable.
+///
 /// `RealSystem` has no state at the Rust level because the relevant state of
 /// the environment is managed by the underlying operating system.
 #[derive(Debug)]
@@ -56,6 +172,146 @@ impl System for RealSystem {
         is_regular_file(path) && is_executable(path)
     }
 
+    fn read_dir(&self, path: &CStr) -> nix::Result<Vec<OsString>> {
+        let path = Path::new(OsStr::from_bytes(path.to_bytes()));
+        let to_nix_error = |e: std::io::Error| {
+            nix::Error::Sys(nix::errno::Errno::from_i32(e.raw_os_error().unwrap_or(0)))
+        };
+        std::fs::read_dir(path)
+            .map_err(to_nix_error)?
+            .map(|entry| entry.map(|entry| entry.file_name()).map_err(to_nix_error))
+            .collect()
+    }
+
+    fn getpwnam_dir(&self, name: &str) -> nix::Result<Option<PathBuf>> {
+        if name.contains('\0') {
+            return Ok(None);
+        }
+        let user = nix::unistd::User::from_name(name)?;
+        Ok(user.map(|user| user.dir))
+    }
+
+    fn getpid(&self) -> Pid {
+        nix::unistd::getpid()
+    }
+
+    fn pipe(&mut self) -> nix::Result<(RawFd, RawFd)> {
+        nix::unistd::pipe()
+    }
+
+    fn open(&mut self, path: &CStr, option: OFlag, mode: Mode) -> nix::Result<RawFd> {
+        loop {
+            let result = nix::fcntl::open(path, option, mode);
+            if result != Err(nix::Error::Sys(nix::errno::Errno::EINTR)) {
+                return result;
+            }
+        }
+    }
+
+    fn open_tmpfile(&mut self, parent_dir: &Path) -> nix::Result<RawFd> {
+        let (fd, path) = nix::unistd::mkstemp(&parent_dir.join("yash-XXXXXX"))?;
+        let _ = nix::unistd::unlink(&path);
+        Ok(fd)
+    }
+
+    fn stat(&self, path: &CStr) -> nix::Result<FileStat> {
+        nix::sys::stat::stat(path)
+    }
+
+    fn fstat(&self, fd: RawFd) -> nix::Result<FileStat> {
+        nix::sys::stat::fstat(fd)
+    }
+
+    fn fcntl_dupfd(&mut self, from: RawFd, to_min: RawFd) -> nix::Result<RawFd> {
+        nix::fcntl::fcntl(from, nix::fcntl::FcntlArg::F_DUPFD_CLOEXEC(to_min))
+    }
+
+    fn dup(&mut self, from: RawFd) -> nix::Result<RawFd> {
+        nix::unistd::dup(from)
+    }
+
+    fn dup2(&mut self, from: RawFd, to: RawFd) -> nix::Result<RawFd> {
+        loop {
+            let result = nix::unistd::dup2(from, to);
+            if result != Err(nix::Error::Sys(nix::errno::Errno::EINTR)) {
+                return result;
+            }
+        }
+    }
+
+    fn fcntl_getfd(&self, fd: RawFd) -> nix::Result<FdFlag> {
+        let flags = nix::fcntl::fcntl(fd, nix::fcntl::FcntlArg::F_GETFD)?;
+        Ok(FdFlag::from_bits_truncate(flags))
+    }
+
+    fn fcntl_setfd(&mut self, fd: RawFd, flags: FdFlag) -> nix::Result<()> {
+        nix::fcntl::fcntl(fd, nix::fcntl::FcntlArg::F_SETFD(flags)).map(drop)
+    }
+
+    fn fcntl_getfl(&self, fd: RawFd) -> nix::Result<OFlag> {
+        let flags = nix::fcntl::fcntl(fd, nix::fcntl::FcntlArg::F_GETFL)?;
+        Ok(OFlag::from_bits_truncate(flags))
+    }
+
+    fn fcntl_setfl(&mut self, fd: RawFd, flags: OFlag) -> nix::Result<()> {
+        nix::fcntl::fcntl(fd, nix::fcntl::FcntlArg::F_SETFL(flags)).map(drop)
+    }
+
+    fn close(&mut self, fd: RawFd) -> nix::Result<()> {
+        match nix::unistd::close(fd) {
+            Err(nix::Error::Sys(nix::errno::Errno::EBADF)) => Ok(()),
+            result => result,
+        }
+    }
+
+    fn read(&mut self, fd: RawFd, buffer: &mut [u8]) -> nix::Result<usize> {
+        loop {
+            let result = nix::unistd::read(fd, buffer);
+            if result != Err(nix::Error::Sys(nix::errno::Errno::EINTR)) {
+                return result;
+            }
+        }
+    }
+
+    /// Reads from the file descriptor.
+    ///
+    /// This implementation blocks inside the function and returns a future that
+    /// will immediately return a `Ready`.
+    fn read_async<'a>(
+        &'a mut self,
+        fd: RawFd,
+        buffer: &'a mut [u8],
+    ) -> Pin<Box<dyn Future<Output = nix::Result<usize>> + 'a>> {
+        let result = self.read(fd, buffer);
+        Box::pin(std::future::ready(result))
+    }
+
+    fn write(&mut self, fd: RawFd, buffer: &[u8]) -> nix::Result<usize> {
+        loop {
+            let result = nix::unistd::write(fd, buffer);
+            if result != Err(nix::Error::Sys(nix::errno::Errno::EINTR)) {
+                return result;
+            }
+        }
+    }
+
+    /// Writes to the file descriptor.
+    ///
+    /// This implementation blocks inside the function and returns a future that
+    /// will immediately return a `Ready`.
+    fn write_async<'a>(
+        &'a mut self,
+        fd: RawFd,
+        buffer: &'a [u8],
+    ) -> Pin<Box<dyn Future<Output = nix::Result<usize>> + 'a>> {
+        let result = self.write(fd, buffer);
+        Box::pin(std::future::ready(result))
+    }
+
+    fn lseek(&mut self, fd: RawFd, offset: i64, whence: Whence) -> nix::Result<i64> {
+        nix::unistd::lseek(fd, offset, whence)
+    }
+
     /// Creates a new child process.
     ///
     /// This implementation calls the `fork` system call and returns both in the
@@ -63,13 +319,26 @@ impl System for RealSystem {
     /// returned `ChildProcess` ignores arguments and returns the child process
     /// ID. In the child, the `run` function runs the task and exits the
     /// process.
+    ///
+    /// If the self-pipe for caught signals exists, the child replaces it with
+    /// a new pipe so that the parent and child do not consume each other's
+    /// wake-ups. This function never fails in the child: if the new pipe
+    /// cannot be created, the `run` function prints the error and exits the
+    /// child process without running the task.
     unsafe fn new_child_process(&mut self) -> nix::Result<Box<dyn ChildProcess>> {
         use nix::unistd::ForkResult::*;
         match nix::unistd::fork()? {
             Parent { child } => Ok(Box::new(DummyChildProcess {
                 child_process_id: child,
             })),
-            Child => Ok(Box::new(RealChildProcess)),
+            Child => {
+                let signal_pipe_error = if SIGNAL_PIPE_READER.load(SeqCst) >= 0 {
+                    open_signal_pipe().err()
+                } else {
+                    None
+                };
+                Ok(Box::new(RealChildProcess { signal_pipe_error }))
+            }
         }
     }
 
@@ -93,6 +362,86 @@ impl System for RealSystem {
         Box::pin(std::future::ready(result))
     }
 
+    /// Sets how a signal is handled.
+    ///
+    /// The self-pipe is created when a signal is caught for the first time.
+    /// Signal handlers are installed with `SA_RESTART`.
+    fn set_signal_handling(
+        &mut self,
+        signal: Signal,
+        handling: SignalHandling,
+    ) -> nix::Result<SignalHandling> {
+        let handler = match handling {
+            SignalHandling::Default => SigHandler::SigDfl,
+            SignalHandling::Ignore => SigHandler::SigIgn,
+            SignalHandling::Catch => {
+                signal_pipe_reader()?;
+                SigHandler::Handler(catch_signal)
+            }
+        };
+        let action = SigAction::new(handler, SaFlags::SA_RESTART, SigSet::empty());
+        // SAFETY: The handler only performs async-signal-safe operations.
+        let old_action = unsafe { nix::sys::signal::sigaction(signal, &action) }?;
+        Ok(match old_action.handler() {
+            SigHandler::SigDfl => SignalHandling::Default,
+            SigHandler::SigIgn => SignalHandling::Ignore,
+            _ => SignalHandling::Catch,
+        })
+    }
+
+    fn sigmask(&mut self, how: SigmaskHow, signals: &SigSet) -> nix::Result<SigSet> {
+        let mut old_mask = SigSet::empty();
+        nix::sys::signal::sigprocmask(how, Some(signals), Some(&mut old_mask))?;
+        Ok(old_mask)
+    }
+
+    fn kill(&mut self, target: Pid, signal: Option<Signal>) -> nix::Result<()> {
+        nix::sys::signal::kill(target, signal)
+    }
+
+    fn caught_signals(&mut self) -> Vec<Signal> {
+        let reader = SIGNAL_PIPE_READER.load(SeqCst);
+        if reader >= 0 {
+            // Drain the pipe before clearing the flag so that no wake-up is lost
+            let mut buffer = [0; 16];
+            loop {
+                match nix::unistd::read(reader, &mut buffer) {
+                    Ok(0) => break,
+                    Ok(_) | Err(nix::Error::Sys(Errno::EINTR)) => continue,
+                    Err(_) => break,
+                }
+            }
+            WAKE_PENDING.store(false, SeqCst);
+        }
+        Signal::iterator()
+            .filter(|&signal| {
+                CAUGHT_SIGNALS
+                    .get(signal as usize)
+                    .is_some_and(|flag| flag.swap(false, SeqCst))
+            })
+            .collect()
+    }
+
+    /// Waits for signals to be caught.
+    ///
+    /// This implementation blocks inside the function and returns a future that
+    /// will immediately return a `Ready`. If no signal has ever been set to be
+    /// caught, the returned future never resolves.
+    fn wait_for_signals(&mut self) -> Pin<Box<dyn Future<Output = Vec<Signal>> + '_>> {
+        loop {
+            let signals = self.caught_signals();
+            if !signals.is_empty() {
+                return Box::pin(std::future::ready(signals));
+            }
+            let reader = SIGNAL_PIPE_READER.load(SeqCst);
+            if reader < 0 {
+                return Box::pin(std::future::pending());
+            }
+            let mut fds = [PollFd::new(reader, PollFlags::POLLIN)];
+            let _ = nix::poll::poll(&mut fds, -1);
+        }
+    }
+
     fn execve(
         &mut self,
         path: &CStr,
@@ -127,7 +476,10 @@ impl ChildProcess for DummyChildProcess {
 /// Implementor of [`ChildProcess`] that is returned from
 /// [`RealSystem::new_child_process`] in the child process.
 #[derive(Debug)]
-struct RealChildProcess;
+struct RealChildProcess {
+    /// Error that occurred while preparing the child process.
+    signal_pipe_error: Option<nix::Error>,
+}
 
 #[async_trait(?Send)]
 impl ChildProcess for RealChildProcess {
@@ -136,6 +488,10 @@ impl ChildProcess for RealChildProcess {
         env: &mut Env,
         mut task: Box<dyn for<'a> FnMut(&'a mut Env) -> Pin<Box<dyn Future<Output = ()> + 'a>>>,
     ) -> Pid {
+        if let Some(error) = self.signal_pipe_error {
+            env.print_error(&format!("cannot create a signal pipe: {}", error));
+            std::process::exit(ExitStatus::ERROR.0)
+        }
         task(env).await;
         std::process::exit(env.exit_status.0)
     }
@@ -0,0 +1,39 @@
+// This file is part of yash, an extended POSIX shell.
+// Copyright (C) 2021 WATANABE Yuki
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Type definitions for signal handling.
+//!
+//! The [`System`](crate::System) interface allows the shell to choose how each
+//! signal is handled. A signal that is caught is recorded by the system so
+//! that the shell can examine it later with
+//! [`caught_signals`](crate::System::caught_signals) rather than running a
+//! handler function asynchronously.
+
+/// How a signal is handled when delivered to a process.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum SignalHandling {
+    /// Performs the default action for the signal.
+    ///
+    /// Depending on the signal, the default action terminates, stops, or
+    /// continues the process, or does nothing.
+    #[default]
+    Default,
+    /// Discards the signal.
+    Ignore,
+    /// Records the signal so that it can be obtained by
+    /// [`caught_signals`](crate::System::caught_signals).
+    Catch,
+}
@@ -98,10 +98,92 @@ impl Variable {
     }
 }
 
+/// Type of a context.
+///
+/// See [`VariableSet::push_context`] for details.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ContextType {
+    /// Context for a function call.
+    ///
+    /// A regular context has its own positional parameters and can contain
+    /// local variables.
+    Regular,
+    /// Context for temporary assignments.
+    ///
+    /// A volatile context contains variables that are assigned for a single
+    /// built-in or function call.
+    Volatile,
+}
+
+/// Variable context.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Context {
+    /// Regular context with the positional parameters.
+    Regular { positional_params: Vec<String> },
+    /// Volatile context.
+    Volatile,
+}
+
+/// Scope in which a variable is assigned.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Scope {
+    /// The variable is assigned in the innermost context that defines the
+    /// variable. If there is no such context, a new variable is created in the
+    /// base context.
+    Global,
+    /// The variable is assigned in the innermost regular context. If the
+    /// variable is defined in an outer context, it is hidden by the new
+    /// variable until the context is popped.
+    Local,
+    /// The variable is assigned in the innermost context, which must be a
+    /// volatile context.
+    Volatile,
+}
+
+/// Variable with the index of the context that contains it.
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct VariableInContext {
+    variable: Variable,
+    context_index: usize,
+}
+
 /// Collection of variables.
-#[derive(Clone, Debug, Default, Eq, PartialEq)]
-pub struct VariableSet(HashMap<String, Variable>);
-// TODO Support local scopes
+///
+/// A variable set is a stack of contexts. The base context is always present
+/// at the bottom of the stack. A new context is pushed when a function is
+/// called or temporary assignments are performed, and popped when the
+/// function returns or the command finishes, removing the variables defined
+/// in the context.
+///
+/// A variable in an inner context hides the variables with the same name in
+/// the outer contexts. [`get`](Self::get) returns the variable in the
+/// innermost context that defines it.
+///
+/// A regular context also contains positional parameters, so that a function
+/// call can temporarily replace them.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct VariableSet {
+    /// Named variables.
+    ///
+    /// Each vector is a stack of variables with the same name, sorted by the
+    /// context index. The vectors are never empty.
+    all_variables: HashMap<String, Vec<VariableInContext>>,
+    /// Stack of contexts.
+    ///
+    /// The first item is the base context, which is always a regular context.
+    contexts: Vec<Context>,
+}
+
+impl Default for VariableSet {
+    fn default() -> Self {
+        VariableSet {
+            all_variables: HashMap::new(),
+            contexts: vec![Context::Regular {
+                positional_params: Vec::new(),
+            }],
+        }
+    }
+}
 
 impl VariableSet {
     /// Creates an empty variable set.
@@ -111,31 +193,159 @@ impl VariableSet {
     }
 
     /// Get a reference to the variable with the specified name.
+    ///
+    /// The result is the variable in the innermost context that defines it.
     #[must_use]
-    pub fn get<N: ?Sized>(&self, name: &N) -> Option<&Variable>
+    pub fn get<N>(&self, name: &N) -> Option<&Variable>
     where
         String: Borrow<N>,
-        N: Hash + Eq,
+        N: Hash + Eq + ?Sized,
     {
-        self.0.get(name)
+        Some(&self.all_variables.get(name)?.last()?.variable)
+    }
+
+    /// Returns the index of the innermost regular context.
+    fn regular_context_index(&self) -> usize {
+        self.contexts
+            .iter()
+            .rposition(|context| matches!(context, Context::Regular { .. }))
+            .unwrap()
     }
 
-    // TODO Reject if the existing variable is read-only
-    // TODO Export if the existing variable has been exported
-    // TODO Specifying a scope to assign in
     /// Assigns a variable.
     ///
-    /// Returns the old value, if any.
-    pub fn assign(&mut self, name: String, value: Variable) -> Option<Variable> {
-        self.0.insert(name, value)
+    /// The `scope` specifies the context in which the variable is assigned.
+    /// Returns the old variable that has been replaced in the context, if any.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if the `scope` is [`Scope::Volatile`] and the
+    /// innermost context is not volatile.
+    pub fn assign(&mut self, scope: Scope, name: String, value: Variable) -> Option<Variable> {
+        let context_index = match scope {
+            Scope::Global => self
+                .all_variables
+                .get(&name)
+                .and_then(|stack| stack.last())
+                .map_or(0, |var| var.context_index),
+            Scope::Local => self.regular_context_index(),
+            Scope::Volatile => {
+                assert_eq!(
+                    self.contexts.last(),
+                    Some(&Context::Volatile),
+                    "no volatile context to assign to"
+                );
+                self.contexts.len() - 1
+            }
+        };
+
+        let stack = self.all_variables.entry(name).or_default();
+        let position = stack.partition_point(|var| var.context_index < context_index);
+        match stack.get_mut(position) {
+            Some(var) if var.context_index == context_index => {
+                Some(std::mem::replace(&mut var.variable, value))
+            }
+            _ => {
+                let variable = VariableInContext {
+                    variable: value,
+                    context_index,
+                };
+                stack.insert(position, variable);
+                None
+            }
+        }
+    }
+
+    /// Removes a variable.
+    ///
+    /// The variable in the innermost context that defines it is removed, which
+    /// may reveal a variable with the same name in an outer context.
+    /// Returns the removed variable, if any.
+    pub fn unset<N>(&mut self, name: &N) -> Option<Variable>
+    where
+        String: Borrow<N>,
+        N: Hash + Eq + ?Sized,
+    {
+        let stack = self.all_variables.get_mut(name)?;
+        let removed = stack.pop()?.variable;
+        if stack.is_empty() {
+            self.all_variables.remove(name);
+        }
+        Some(removed)
+    }
+
+    /// Pushes a new empty context.
+    ///
+    /// A [regular](ContextType::Regular) context is pushed when a function is
+    /// called. It starts with no positional parameters and no local
+    /// variables. A [volatile](ContextType::Volatile) context is pushed before
+    /// performing temporary assignments for a built-in or function call.
+    ///
+    /// The context must be removed by [`pop_context`](Self::pop_context).
+    pub fn push_context(&mut self, context_type: ContextType) {
+        let context = match context_type {
+            ContextType::Regular => Context::Regular {
+                positional_params: Vec::new(),
+            },
+            ContextType::Volatile => Context::Volatile,
+        };
+        self.contexts.push(context);
+    }
+
+    /// Pops the innermost context pushed by
+    /// [`push_context`](Self::push_context).
+    ///
+    /// The variables defined in the context are removed.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if there is no pushed context, that is, the base
+    /// context cannot be popped.
+    pub fn pop_context(&mut self) {
+        assert!(self.contexts.len() > 1, "cannot pop the base context");
+        let index = self.contexts.len() - 1;
+        self.contexts.pop();
+        self.all_variables.retain(|_, stack| {
+            if stack.last().map(|var| var.context_index) == Some(index) {
+                stack.pop();
+            }
+            !stack.is_empty()
+        });
+    }
+
+    /// Returns the current positional parameters.
+    ///
+    /// The positional parameters belong to the innermost regular context.
+    #[must_use]
+    pub fn positional_params(&self) -> &[String] {
+        match &self.contexts[self.regular_context_index()] {
+            Context::Regular { positional_params } => positional_params,
+            Context::Volatile => unreachable!(),
+        }
+    }
+
+    /// Returns a mutable reference to the current positional parameters.
+    ///
+    /// This function is used to replace the positional parameters, as by
+    /// `set --` or a function call.
+    #[must_use]
+    pub fn positional_params_mut(&mut self) -> &mut Vec<String> {
+        let index = self.regular_context_index();
+        match &mut self.contexts[index] {
+            Context::Regular { positional_params } => positional_params,
+            Context::Volatile => unreachable!(),
+        }
     }
 
     /// Returns environment variables in a new vector of C string.
+    ///
+    /// Only the innermost variable is considered for each name.
     #[must_use]
     pub fn env_c_strings(&self) -> Vec<CString> {
-        self.0
+        self.all_variables
             .iter()
-            .filter_map(|(name, var)| {
+            .filter_map(|(name, stack)| {
+                let var = &stack.last()?.variable;
                 if var.is_exported {
                     let mut s = name.clone();
                     s.push('=');
@@ -166,16 +376,159 @@ mod tests {
             is_exported: false,
             read_only_location: Some(Location::dummy("dummy")),
         };
-        variables.assign("foo".to_string(), variable.clone());
+        variables.assign(Scope::Global, "foo".to_string(), variable.clone());
         assert_eq!(variables.get("foo"), Some(&variable));
     }
 
+    #[test]
+    fn unset_variable() {
+        let mut variables = VariableSet::new();
+        let variable = Variable {
+            value: Value::Scalar("value".to_string()),
+            last_assigned_location: None,
+            is_exported: false,
+            read_only_location: None,
+        };
+        variables.assign(Scope::Global, "foo".to_string(), variable.clone());
+        assert_eq!(variables.unset("foo"), Some(variable));
+        assert_eq!(variables.get("foo"), None);
+        assert_eq!(variables.unset("foo"), None);
+    }
+
+    fn scalar(value: &str) -> Variable {
+        Variable {
+            value: Value::Scalar(value.to_string()),
+            last_assigned_location: None,
+            is_exported: false,
+            read_only_location: None,
+        }
+    }
+
+    #[test]
+    fn variables_in_outer_context_are_visible() {
+        let mut variables = VariableSet::new();
+        variables.assign(Scope::Global, "foo".to_string(), scalar("1"));
+        variables.push_context(ContextType::Regular);
+        variables.push_context(ContextType::Volatile);
+        assert_eq!(variables.get("foo"), Some(&scalar("1")));
+    }
+
+    #[test]
+    fn global_assignment_targets_innermost_defining_context() {
+        let mut variables = VariableSet::new();
+        variables.assign(Scope::Global, "foo".to_string(), scalar("1"));
+        variables.push_context(ContextType::Regular);
+        variables.assign(Scope::Local, "foo".to_string(), scalar("2"));
+        variables.push_context(ContextType::Regular);
+
+        let old = variables.assign(Scope::Global, "foo".to_string(), scalar("3"));
+        assert_eq!(old, Some(scalar("2")));
+        let old = variables.assign(Scope::Global, "bar".to_string(), scalar("4"));
+        assert_eq!(old, None);
+
+        variables.pop_context();
+        assert_eq!(variables.get("foo"), Some(&scalar("3")));
+        variables.pop_context();
+        assert_eq!(variables.get("foo"), Some(&scalar("1")));
+        assert_eq!(variables.get("bar"), Some(&scalar("4")));
+    }
+
+    #[test]
+    fn local_assignment_hides_outer_variable() {
+        let mut variables = VariableSet::new();
+        variables.assign(Scope::Global, "foo".to_string(), scalar("1"));
+        variables.push_context(ContextType::Regular);
+
+        let old = variables.assign(Scope::Local, "foo".to_string(), scalar("2"));
+        assert_eq!(old, None);
+        assert_eq!(variables.get("foo"), Some(&scalar("2")));
+        let old = variables.assign(Scope::Local, "foo".to_string(), scalar("3"));
+        assert_eq!(old, Some(scalar("2")));
+
+        variables.pop_context();
+        assert_eq!(variables.get("foo"), Some(&scalar("1")));
+    }
+
+    #[test]
+    fn local_assignment_skips_volatile_context() {
+        let mut variables = VariableSet::new();
+        variables.push_context(ContextType::Regular);
+        variables.push_context(ContextType::Volatile);
+        variables.assign(Scope::Volatile, "foo".to_string(), scalar("v"));
+        variables.assign(Scope::Local, "foo".to_string(), scalar("l"));
+        assert_eq!(variables.get("foo"), Some(&scalar("v")));
+
+        variables.pop_context();
+        assert_eq!(variables.get("foo"), Some(&scalar("l")));
+        variables.pop_context();
+        assert_eq!(variables.get("foo"), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "no volatile context to assign to")]
+    fn volatile_assignment_without_volatile_context() {
+        let mut variables = VariableSet::new();
+        variables.push_context(ContextType::Regular);
+        variables.assign(Scope::Volatile, "foo".to_string(), scalar(""));
+    }
+
+    #[test]
+    fn unset_reveals_outer_variable() {
+        let mut variables = VariableSet::new();
+        variables.assign(Scope::Global, "foo".to_string(), scalar("1"));
+        variables.push_context(ContextType::Regular);
+        variables.assign(Scope::Local, "foo".to_string(), scalar("2"));
+        assert_eq!(variables.unset("foo"), Some(scalar("2")));
+        assert_eq!(variables.get("foo"), Some(&scalar("1")));
+    }
+
+    #[test]
+    fn positional_params_in_contexts() {
+        let mut variables = VariableSet::new();
+        assert_eq!(variables.positional_params(), [] as [String; 0]);
+        *variables.positional_params_mut() = vec!["a".to_string(), "b".to_string()];
+
+        variables.push_context(ContextType::Regular);
+        assert_eq!(variables.positional_params(), [] as [String; 0]);
+        *variables.positional_params_mut() = vec!["c".to_string()];
+        variables.push_context(ContextType::Volatile);
+        assert_eq!(variables.positional_params(), ["c"]);
+
+        variables.pop_context();
+        assert_eq!(variables.positional_params(), ["c"]);
+        variables.pop_context();
+        assert_eq!(variables.positional_params(), ["a", "b"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot pop the base context")]
+    fn popping_base_context() {
+        VariableSet::new().pop_context();
+    }
+
+    #[test]
+    fn env_c_strings_uses_innermost_variable() {
+        let mut variables = VariableSet::new();
+        let mut exported = scalar("outer");
+        exported.is_exported = true;
+        variables.assign(Scope::Global, "foo".to_string(), exported);
+        variables.push_context(ContextType::Volatile);
+        let mut exported = scalar("inner");
+        exported.is_exported = true;
+        variables.assign(Scope::Volatile, "foo".to_string(), exported);
+        assert_eq!(
+            variables.env_c_strings(),
+            [CString::new("foo=inner").unwrap()]
+        );
+    }
+
     #[test]
     fn env_c_strings() {
         let mut variables = VariableSet::new();
-        assert_eq!(&variables.env_c_strings(), &[]);
+        assert_eq!(variables.env_c_strings(), [] as [CString; 0]);
 
         variables.assign(
+            Scope::Global,
             "foo".to_string(),
             Variable {
                 value: Value::Scalar("FOO".to_string()),
@@ -185,6 +538,7 @@ mod tests {
             },
         );
         variables.assign(
+            Scope::Global,
             "bar".to_string(),
             Variable {
                 value: Value::Array(vec!["BAR".to_string()]),
@@ -194,6 +548,7 @@ mod tests {
             },
         );
         variables.assign(
+            Scope::Global,
             "baz".to_string(),
             Variable {
                 value: Value::Array(vec!["1".to_string(), "two".to_string(), "3".to_string()]),
@@ -203,6 +558,7 @@ mod tests {
             },
         );
         variables.assign(
+            Scope::Global,
             "null".to_string(),
             Variable {
                 value: Value::Scalar("not exported".to_string()),
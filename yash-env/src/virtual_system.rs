@@ -23,15 +23,32 @@
 //!
 //! This module also defines elements that compose a virtual system.
 
+pub mod file_system;
+pub mod io;
+pub mod pipe;
+
+pub use self::file_system::FileBody;
+pub use self::file_system::FileSystem;
+pub use self::file_system::INode;
+
+use self::io::FdBody;
+use self::io::OpenFileDescription;
 use crate::exec::ExitStatus;
+use crate::signal::SignalHandling;
 use crate::ChildProcess;
 use crate::Env;
 use crate::System;
 use async_trait::async_trait;
 use nix::errno::Errno;
+use nix::fcntl::FdFlag;
+use nix::fcntl::OFlag;
+use nix::sys::signal::SigSet;
+use nix::sys::signal::SigmaskHow;
 use nix::sys::signal::Signal;
+use nix::sys::stat::FileStat;
 use nix::sys::wait::WaitStatus;
 use nix::unistd::Pid;
+use nix::unistd::Whence;
 use std::cell::Ref;
 use std::cell::RefCell;
 use std::cell::RefMut;
@@ -41,9 +58,11 @@ use std::convert::Infallible;
 use std::ffi::CStr;
 use std::ffi::CString;
 use std::ffi::OsStr;
+use std::ffi::OsString;
 use std::fmt::Debug;
 use std::future::Future;
 use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::RawFd;
 use std::path::Path;
 use std::path::PathBuf;
 use std::pin::Pin;
@@ -87,7 +106,7 @@ impl VirtualSystem {
     pub fn new() -> VirtualSystem {
         let mut state = SystemState::default();
         let process_id = Pid::from_raw(2);
-        let process = Process::with_parent(Pid::from_raw(1));
+        let process = Process::with_parent_and_group(Pid::from_raw(1), process_id);
         state.processes.insert(process_id, process);
 
         let state = Rc::new(RefCell::new(state));
@@ -117,6 +136,24 @@ impl VirtualSystem {
             state.processes.get_mut(&self.process_id).unwrap()
         })
     }
+
+    /// Finds the open file description for a file descriptor of the current
+    /// process.
+    fn open_file_description(&self, fd: RawFd) -> nix::Result<Rc<RefCell<OpenFileDescription>>> {
+        self.current_process()
+            .get_fd(fd)
+            .map(|body| Rc::clone(&body.open_file_description))
+            .ok_or_else(|| Errno::EBADF.into())
+    }
+
+    /// Sends `SIGPIPE` to the current process.
+    ///
+    /// The signal is handled as described in [`Process::raise_signal`]. By
+    /// default, the signal terminates the process.
+    pub fn raise_sigpipe(&mut self) {
+        let wakers = self.current_process_mut().raise_signal(Signal::SIGPIPE);
+        wakers.into_iter().for_each(Waker::wake);
+    }
 }
 
 impl Default for VirtualSystem {
@@ -131,21 +168,310 @@ impl System for VirtualSystem {
     /// The current implementation only checks if the file has any executable
     /// bit in the permissions. The file owner and group are not considered.
     fn is_executable_file(&self, path: &CStr) -> bool {
-        let path = match path.to_str() {
-            Ok(path) => PathBuf::from(path),
-            Err(_) => return false,
+        let path = Path::new(OsStr::from_bytes(path.to_bytes()));
+        match self.state.borrow().file_system.get(path) {
+            Ok(inode) => {
+                let inode = inode.borrow();
+                matches!(inode.body, FileBody::Regular { .. }) && inode.permissions.0 & 0o111 != 0
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Returns the names of the entries in a directory.
+    ///
+    /// A relative path is resolved from the root directory since the virtual
+    /// system does not yet have a working directory.
+    fn read_dir(&self, path: &CStr) -> nix::Result<Vec<OsString>> {
+        let path = Path::new(OsStr::from_bytes(path.to_bytes()));
+        self.state.borrow().file_system.entries(path)
+    }
+
+    /// Returns the home directory of a user.
+    ///
+    /// The result is looked up in [`SystemState::home_dirs`].
+    fn getpwnam_dir(&self, name: &str) -> nix::Result<Option<PathBuf>> {
+        Ok(self.state.borrow().home_dirs.get(name).cloned())
+    }
+
+    /// Returns `self.process_id`.
+    fn getpid(&self) -> Pid {
+        self.process_id
+    }
+
+    /// Creates a new pipe.
+    ///
+    /// The pipe content is kept in memory. Returned file descriptors are the
+    /// lowest ones that are not in use.
+    fn pipe(&mut self) -> nix::Result<(RawFd, RawFd)> {
+        let (reader, writer) = OpenFileDescription::new_pipe();
+        let mut process = self.current_process_mut();
+        let reader = process.open_fd(0, reader, FdFlag::empty());
+        let writer = process.open_fd(0, writer, FdFlag::empty());
+        Ok((reader, writer))
+    }
+
+    /// Opens a file.
+    ///
+    /// A relative path is resolved from the root directory. The access mode,
+    /// `O_APPEND`, `O_CLOEXEC`, `O_CREAT`, `O_EXCL`, and `O_TRUNC` are
+    /// supported. Other flags are ignored. A new file is created with the permissions in
+    /// `mode`. File permissions are not checked.
+    fn open(
+        &mut self,
+        path: &CStr,
+        option: OFlag,
+        mode: nix::sys::stat::Mode,
+    ) -> nix::Result<RawFd> {
+        let path = Path::new(OsStr::from_bytes(path.to_bytes()));
+        let (is_readable, is_writable) = match option & OFlag::O_ACCMODE {
+            OFlag::O_RDONLY => (true, false),
+            OFlag::O_WRONLY => (false, true),
+            OFlag::O_RDWR => (true, true),
+            _ => return Err(Errno::EINVAL.into()),
+        };
+        let is_creating = option.contains(OFlag::O_CREAT);
+        let is_exclusive = is_creating && option.contains(OFlag::O_EXCL);
+
+        let mut state = self.state.borrow_mut();
+        let file = match state.file_system.lookup(path, !is_exclusive) {
+            Ok(_) if is_exclusive => return Err(Errno::EEXIST.into()),
+            Ok(file) => file,
+            Err(nix::Error::Sys(Errno::ENOENT)) if is_creating => {
+                let mut inode = INode::new();
+                inode.permissions = Mode(mode.bits() & 0o7777);
+                state.file_system.create(path, inode)?
+            }
+            Err(error) => return Err(error),
+        };
+        drop(state);
+
+        let is_truncating = is_writable && option.contains(OFlag::O_TRUNC);
+        match &mut file.borrow_mut().body {
+            FileBody::Regular { content, .. } if is_truncating => content.clear(),
+            FileBody::Directory { .. } if is_writable => return Err(Errno::EISDIR.into()),
+            _ => (),
+        }
+
+        let is_appending = option.contains(OFlag::O_APPEND);
+        let ofd = OpenFileDescription::new(file, is_readable, is_writable, is_appending);
+        let flags = if option.contains(OFlag::O_CLOEXEC) {
+            FdFlag::FD_CLOEXEC
+        } else {
+            FdFlag::empty()
+        };
+        Ok(self.current_process_mut().open_fd(0, ofd, flags))
+    }
+
+    /// Opens an unnamed temporary file.
+    ///
+    /// The file is not linked to any directory, so `parent_dir` is only checked
+    /// to be an existing directory.
+    fn open_tmpfile(&mut self, parent_dir: &Path) -> nix::Result<RawFd> {
+        let parent = self.state.borrow().file_system.get(parent_dir)?;
+        if !matches!(parent.borrow().body, FileBody::Directory { .. }) {
+            return Err(Errno::ENOTDIR.into());
+        }
+        let mut inode = INode::new();
+        inode.permissions = Mode(0o600);
+        let file = Rc::new(RefCell::new(inode));
+        let ofd = OpenFileDescription::new(file, true, true, false);
+        Ok(self.current_process_mut().open_fd(0, ofd, FdFlag::empty()))
+    }
+
+    /// Returns the status of a file.
+    ///
+    /// A relative path is resolved from the root directory.
+    fn stat(&self, path: &CStr) -> nix::Result<FileStat> {
+        let path = Path::new(OsStr::from_bytes(path.to_bytes()));
+        let file = self.state.borrow().file_system.get(path)?;
+        let stat = file.borrow().stat();
+        Ok(stat)
+    }
+
+    /// Returns the status of the file open for the file descriptor.
+    fn fstat(&self, fd: RawFd) -> nix::Result<FileStat> {
+        let ofd = self.open_file_description(fd)?;
+        let ofd = ofd.borrow();
+        let stat = ofd.file().borrow().stat();
+        Ok(stat)
+    }
+
+    /// Duplicates a file descriptor.
+    ///
+    /// The new file descriptor is the lowest one that is not in use and not
+    /// less than `to_min`. It has the `FD_CLOEXEC` flag set.
+    fn fcntl_dupfd(&mut self, from: RawFd, to_min: RawFd) -> nix::Result<RawFd> {
+        let mut process = self.current_process_mut();
+        let mut body = process.get_fd(from).ok_or(Errno::EBADF)?.clone();
+        body.flags = FdFlag::FD_CLOEXEC;
+        Ok(process.set_fd_at_min(to_min, body))
+    }
+
+    /// Duplicates a file descriptor.
+    ///
+    /// The new file descriptor is the lowest one that is not in use. It does
+    /// not have the `FD_CLOEXEC` flag set.
+    fn dup(&mut self, from: RawFd) -> nix::Result<RawFd> {
+        let mut process = self.current_process_mut();
+        let mut body = process.get_fd(from).ok_or(Errno::EBADF)?.clone();
+        body.flags = FdFlag::empty();
+        Ok(process.set_fd_at_min(0, body))
+    }
+
+    /// Duplicates a file descriptor.
+    ///
+    /// If `to` is already open, it is closed first. The new file descriptor
+    /// does not have the `FD_CLOEXEC` flag set.
+    fn dup2(&mut self, from: RawFd, to: RawFd) -> nix::Result<RawFd> {
+        let mut process = self.current_process_mut();
+        let mut body = process.get_fd(from).ok_or(Errno::EBADF)?.clone();
+        body.flags = FdFlag::empty();
+        process.set_fd(to, body);
+        Ok(to)
+    }
+
+    fn fcntl_getfd(&self, fd: RawFd) -> nix::Result<FdFlag> {
+        let process = self.current_process();
+        let body = process.get_fd(fd).ok_or(Errno::EBADF)?;
+        Ok(body.flags)
+    }
+
+    fn fcntl_setfd(&mut self, fd: RawFd, flags: FdFlag) -> nix::Result<()> {
+        let mut process = self.current_process_mut();
+        let body = process.fds.get_mut(&fd).ok_or(Errno::EBADF)?;
+        body.flags = flags;
+        Ok(())
+    }
+
+    fn fcntl_getfl(&self, fd: RawFd) -> nix::Result<OFlag> {
+        let ofd = self.open_file_description(fd)?;
+        let ofd = ofd.borrow();
+        let mut flags = match (ofd.is_readable(), ofd.is_writable()) {
+            (true, true) => OFlag::O_RDWR,
+            (false, true) => OFlag::O_WRONLY,
+            _ => OFlag::O_RDONLY,
+        };
+        flags.set(OFlag::O_APPEND, ofd.is_appending());
+        Ok(flags)
+    }
+
+    /// Sets the file status flags of the open file description.
+    ///
+    /// Only the `O_APPEND` flag is supported. Other flags are ignored.
+    fn fcntl_setfl(&mut self, fd: RawFd, flags: OFlag) -> nix::Result<()> {
+        let ofd = self.open_file_description(fd)?;
+        ofd.borrow_mut()
+            .set_appending(flags.contains(OFlag::O_APPEND));
+        Ok(())
+    }
+
+    /// Closes a file descriptor.
+    ///
+    /// This function succeeds even if the file descriptor is not open.
+    fn close(&mut self, fd: RawFd) -> nix::Result<()> {
+        self.current_process_mut().close_fd(fd);
+        Ok(())
+    }
+
+    /// Reads from a file descriptor.
+    ///
+    /// This function does not block. If the file descriptor is an empty pipe
+    /// with a writer, it fails with `EAGAIN`.
+    fn read(&mut self, fd: RawFd, buffer: &mut [u8]) -> nix::Result<usize> {
+        self.open_file_description(fd)?.borrow_mut().read(buffer)
+    }
+
+    /// Reads from a file descriptor asynchronously.
+    ///
+    /// If the file descriptor is an empty pipe with a writer, the returned
+    /// future remains pending until some data is written or the writer is
+    /// closed. The waiting task is woken by the writer, so the future can be
+    /// awaited in a task running concurrently with the writer (see
+    /// [`new_child_process`](Self::new_child_process)).
+    fn read_async<'a>(
+        &'a mut self,
+        fd: RawFd,
+        buffer: &'a mut [u8],
+    ) -> Pin<Box<dyn Future<Output = nix::Result<usize>> + 'a>> {
+        let ofd = match self.open_file_description(fd) {
+            Ok(ofd) => ofd,
+            Err(errno) => return Box::pin(std::future::ready(Err(errno))),
         };
-        match self.state.borrow().file_system.get(&path) {
-            None => false,
-            Some(inode) => inode.permissions.0 & 0o111 != 0,
+        Box::pin(futures::future::poll_fn(move |context| {
+            let result = ofd.borrow_mut().read(buffer);
+            match result {
+                Err(nix::Error::Sys(Errno::EAGAIN)) => {
+                    ofd.borrow().add_awaiter(context.waker().clone());
+                    Poll::Pending
+                }
+                result => Poll::Ready(result),
+            }
+        }))
+    }
+
+    /// Writes to a file descriptor.
+    ///
+    /// This function does not block. If the file descriptor is a full pipe,
+    /// it fails with `EAGAIN`. If the file descriptor is a pipe without a
+    /// reader, it fails with `EPIPE` after sending `SIGPIPE` to the current
+    /// process (see [`raise_sigpipe`](Self::raise_sigpipe)).
+    fn write(&mut self, fd: RawFd, buffer: &[u8]) -> nix::Result<usize> {
+        let result = self.open_file_description(fd)?.borrow_mut().write(buffer);
+        if result == Err(Errno::EPIPE.into()) {
+            self.raise_sigpipe();
         }
+        result
+    }
+
+    /// Writes to a file descriptor asynchronously.
+    ///
+    /// If the file descriptor is a full pipe, the returned future remains
+    /// pending until the reader reads some data or is closed. Otherwise, this
+    /// function behaves like [`write`](Self::write).
+    fn write_async<'a>(
+        &'a mut self,
+        fd: RawFd,
+        buffer: &'a [u8],
+    ) -> Pin<Box<dyn Future<Output = nix::Result<usize>> + 'a>> {
+        let ofd = match self.open_file_description(fd) {
+            Ok(ofd) => ofd,
+            Err(errno) => return Box::pin(std::future::ready(Err(errno))),
+        };
+        Box::pin(futures::future::poll_fn(move |context| {
+            let result = ofd.borrow_mut().write(buffer);
+            match result {
+                Err(nix::Error::Sys(Errno::EAGAIN)) => {
+                    ofd.borrow().add_awaiter(context.waker().clone());
+                    Poll::Pending
+                }
+                Err(nix::Error::Sys(Errno::EPIPE)) => {
+                    self.raise_sigpipe();
+                    Poll::Ready(result)
+                }
+                result => Poll::Ready(result),
+            }
+        }))
+    }
+
+    /// Moves the position of a file descriptor.
+    ///
+    /// This function fails with `ESPIPE` for a pipe.
+    fn lseek(&mut self, fd: RawFd, offset: i64, whence: Whence) -> nix::Result<i64> {
+        self.open_file_description(fd)?
+            .borrow_mut()
+            .seek(offset, whence)
     }
 
     /// Creates a new child process.
     ///
     /// This implementation does not create any real child process. Instead,
     /// it returns an implementor of [`ChildProcess`] that `run`s its task
-    /// concurrently in the same process.
+    /// concurrently in the same process. The child process inherits the file
+    /// descriptors of the parent, and they are closed when the task finishes.
+    /// The child also inherits the signal handlings and the signal mask of the
+    /// parent. The task is not polled while the child process is stopped by a
+    /// signal, and it is abandoned when the child is terminated by a signal.
     ///
     /// To run the concurrent task, this function needs an executor that has
     /// been set in the system state. If the system state does not have an
@@ -164,7 +490,11 @@ impl System for VirtualSystem {
             .keys()
             .max()
             .map_or(Pid::from_raw(2), |pid| Pid::from_raw(pid.as_raw() + 1));
-        let child_process = Process::with_parent(self.process_id);
+        let parent = &state.processes[&self.process_id];
+        let mut child_process = Process::with_parent_and_group(self.process_id, parent.pgid);
+        child_process.fds = parent.fds.clone();
+        child_process.signal_handlings = parent.signal_handlings.clone();
+        child_process.blocked_signals = parent.blocked_signals;
         state.processes.insert(process_id, child_process);
         drop(state);
 
@@ -220,37 +550,140 @@ impl System for VirtualSystem {
         }))
     }
 
+    /// Sets how a signal is handled.
+    ///
+    /// This function fails with `EINVAL` if `signal` is `SIGKILL` or
+    /// `SIGSTOP`. If the new handling is [`SignalHandling::Ignore`], a pending
+    /// instance of the signal is discarded.
+    fn set_signal_handling(
+        &mut self,
+        signal: Signal,
+        handling: SignalHandling,
+    ) -> nix::Result<SignalHandling> {
+        if signal == Signal::SIGKILL || signal == Signal::SIGSTOP {
+            return Err(Errno::EINVAL.into());
+        }
+        Ok(self
+            .current_process_mut()
+            .set_signal_handling(signal, handling))
+    }
+
+    /// Blocks and/or unblocks signals.
+    ///
+    /// Pending signals that are unblocked are delivered before this function
+    /// returns.
+    fn sigmask(&mut self, how: SigmaskHow, signals: &SigSet) -> nix::Result<SigSet> {
+        let mut process = self.current_process_mut();
+        let old_mask = process.blocked_signals;
+        let wakers = process.block_signals(how, signals);
+        drop(process);
+        wakers.into_iter().for_each(Waker::wake);
+        Ok(old_mask)
+    }
+
+    /// Sends a signal.
+    ///
+    /// The signal is delivered to the target processes before this function
+    /// returns, unless blocked. If `target` is -1, the signal is sent to all
+    /// processes except process 1 and the current process. This function fails
+    /// with `ESRCH` if there is no target process.
+    fn kill(&mut self, target: Pid, signal: Option<Signal>) -> nix::Result<()> {
+        let mut state = self.state.borrow_mut();
+        let self_pid = self.process_id;
+        let self_pgid = state.processes[&self_pid].pgid;
+        let is_target = |pid: Pid, process: &Process| match target.as_raw() {
+            0 => process.pgid == self_pgid,
+            -1 => pid != self_pid && pid.as_raw() != 1,
+            raw if raw < 0 => process.pgid.as_raw() == -raw,
+            _ => pid == target,
+        };
+
+        let mut found = false;
+        let mut wakers = Vec::new();
+        for (&pid, process) in &mut state.processes {
+            if is_target(pid, process) {
+                found = true;
+                if let Some(signal) = signal {
+                    wakers.extend(process.raise_signal(signal));
+                }
+            }
+        }
+        drop(state);
+        wakers.into_iter().for_each(Waker::wake);
+
+        if found {
+            Ok(())
+        } else {
+            Err(Errno::ESRCH.into())
+        }
+    }
+
+    fn caught_signals(&mut self) -> Vec<Signal> {
+        std::mem::take(&mut self.current_process_mut().caught_signals)
+    }
+
+    /// Waits for signals to be caught.
+    ///
+    /// The returned future remains pending until a signal is caught by the
+    /// current process.
+    fn wait_for_signals(&mut self) -> Pin<Box<dyn Future<Output = Vec<Signal>> + '_>> {
+        Box::pin(futures::future::poll_fn(move |context| {
+            let mut process = self.current_process_mut();
+            if process.caught_signals.is_empty() {
+                process.add_signal_awaiter(context.waker());
+                Poll::Pending
+            } else {
+                Poll::Ready(std::mem::take(&mut process.caught_signals))
+            }
+        }))
+    }
+
     /// Stub for the `execve` system call.
     ///
     /// The `execve` system call cannot be simulated in the userland. This
     /// function returns `ENOSYS` if the file at `path` is a native executable,
-    /// `ENOEXEC` if a non-executable file, and `ENOENT` otherwise.
+    /// `ENOEXEC` if a non-executable regular file, and `EACCES` if not a
+    /// regular file. If the file cannot be found, the error from the path
+    /// resolution is returned.
+    ///
+    /// For a native executable, the arguments are saved in the process (see
+    /// [`Process::last_exec`]) and the file descriptors with the `FD_CLOEXEC`
+    /// flag are closed as if the new program had started. Signals that were
+    /// caught are reset to the default handling.
     fn execve(
         &mut self,
         path: &CStr,
         args: &[CString],
         envs: &[CString],
     ) -> nix::Result<Infallible> {
-        let os_path = OsStr::from_bytes(path.to_bytes()).as_ref();
+        let os_path = Path::new(OsStr::from_bytes(path.to_bytes()));
         let mut state = self.state.borrow_mut();
-        let fs = &state.file_system;
-        if let Some(file) = fs.get(os_path) {
-            // TODO Check file permissions
-            if file.is_native_executable {
-                // Save arguments in the Process
-                let process = state.processes.get_mut(&self.process_id).unwrap();
-                let path = path.to_owned();
-                let args = args.to_owned();
-                let envs = envs.to_owned();
-                process.last_exec = Some((path, args, envs));
-
-                Err(Errno::ENOSYS.into())
-            } else {
-                Err(Errno::ENOEXEC.into())
-            }
+        let file = state.file_system.get(os_path)?;
+        // TODO Check file permissions
+        let is_native_executable = match &file.borrow().body {
+            FileBody::Regular {
+                is_native_executable,
+                ..
+            } => *is_native_executable,
+            _ => return Err(Errno::EACCES.into()),
+        };
+        if is_native_executable {
+            // Save arguments in the Process
+            let process = state.processes.get_mut(&self.process_id).unwrap();
+            let path = path.to_owned();
+            let args = args.to_owned();
+            let envs = envs.to_owned();
+            process.last_exec = Some((path, args, envs));
+            process.close_fds_on_exec();
+            // Caught signals are reset to the default in the new program
+            process
+                .signal_handlings
+                .retain(|_, handling| *handling != SignalHandling::Catch);
+            process.caught_signals.clear();
+
+            Err(Errno::ENOSYS.into())
         } else {
-            // TODO Maybe ENOTDIR
-            Err(Errno::ENOENT.into())
+            Err(Errno::ENOEXEC.into())
         }
     }
 }
@@ -277,15 +710,32 @@ impl ChildProcess for DummyChildProcess {
 
         let state = self.state.clone();
         let run_task_and_set_exit_status = Box::pin(async move {
-            task(&mut child_env).await;
+            let mut future = task(&mut child_env);
+            futures::future::poll_fn(|context| {
+                if let Some(poll) = poll_process_state(&state, process_id, context) {
+                    return poll;
+                }
+                if future.as_mut().poll(context).is_ready() {
+                    return Poll::Ready(());
+                }
+                poll_process_state(&state, process_id, context).unwrap_or(Poll::Pending)
+            })
+            .await;
+            drop(future);
 
             let mut state = state.borrow_mut();
             let process = state
                 .processes
                 .get_mut(&process_id)
                 .expect("the child process is missing");
-            let wakers = process.set_state(ProcessState::Exited(child_env.exit_status));
+            let fds = std::mem::take(&mut process.fds);
+            let wakers = if process.state == ProcessState::Running {
+                process.set_state(ProcessState::Exited(child_env.exit_status))
+            } else {
+                Vec::new()
+            };
             drop(state);
+            drop(fds);
             wakers.into_iter().for_each(Waker::wake);
         });
 
@@ -297,6 +747,34 @@ impl ChildProcess for DummyChildProcess {
     }
 }
 
+/// Checks if the task of a virtual process should be polled.
+///
+/// Returns `None` if the process is running. Otherwise, returns `Pending` if
+/// the process is stopped and `Ready` if terminated. The waker is registered in
+/// the process so that the task is woken when a signal changes the state.
+fn poll_process_state(
+    state: &RefCell<SystemState>,
+    process_id: Pid,
+    context: &mut std::task::Context<'_>,
+) -> Option<Poll<()>> {
+    let mut state = state.borrow_mut();
+    let process = state
+        .processes
+        .get_mut(&process_id)
+        .expect("the child process is missing");
+    match process.state {
+        ProcessState::Running => {
+            process.add_signal_awaiter(context.waker());
+            None
+        }
+        ProcessState::Stopped(_) => {
+            process.add_signal_awaiter(context.waker());
+            Some(Poll::Pending)
+        }
+        ProcessState::Exited(_) | ProcessState::Signaled(_) => Some(Poll::Ready(())),
+    }
+}
+
 /// State of the virtual system.
 #[derive(Clone, Debug, Default)]
 pub struct SystemState {
@@ -311,46 +789,12 @@ pub struct SystemState {
 
     /// Collection of files existing in the virtual system.
     pub file_system: FileSystem,
-}
 
-/// Collection of files.
-#[derive(Clone, Debug, Default, Eq, PartialEq)]
-pub struct FileSystem(HashMap<PathBuf, INode>);
-// TODO should be a link to the root i-node
-// In the current implementation, this hash map stores all files in a flat
-// namespace, without any recursive directory structure.
-
-impl FileSystem {
-    /// Saves a file.
+    /// Home directories of users, keyed by user name.
     ///
-    /// If there is an existing file at the specified path, it is replaced with
-    /// the new file and returned.
-    pub fn save(&mut self, path: PathBuf, content: INode) -> Option<INode> {
-        self.0.insert(path, content)
-    }
-
-    /// Returns a reference to the existing file at the specified path.
-    pub fn get(&self, path: &Path) -> Option<&INode> {
-        // TODO Return ENOTDIR or ENOENT if not found
-        self.0.get(path)
-    }
-}
-
-/// File on the file system.
-#[derive(Clone, Debug, Default, Eq, PartialEq)]
-pub struct INode {
-    /// Access permissions.
-    pub permissions: Mode,
-    /// Whether this file is a native binary that can be exec'ed.
-    pub is_native_executable: bool,
-    // TODO File content, owner user and group, etc.
-}
-
-impl INode {
-    /// Create an empty regular file.
-    pub fn new() -> INode {
-        INode::default()
-    }
+    /// This is the user database that [`VirtualSystem::getpwnam_dir`]
+    /// consults.
+    pub home_dirs: HashMap<String, PathBuf>,
 }
 
 /// File permission bits.
@@ -401,11 +845,26 @@ impl Executor for futures::executor::LocalSpawner {
 }
 
 /// Process in a virtual system.
+///
+/// Each process has its own file descriptor table, whose entries refer to
+/// [open file descriptions](OpenFileDescription). An open file description
+/// can be shared by file descriptors in one or more processes, in which case
+/// they share the file offset. A child process created by
+/// [`VirtualSystem::new_child_process`] inherits a copy of the table of the
+/// parent.
+///
+/// A process also has its own signal handlings, a mask of blocked signals, and
+/// a set of pending signals. A signal sent to the process by
+/// [`VirtualSystem::kill`] is delivered immediately unless blocked, and may
+/// change the process state according to its handling.
 #[derive(Clone, Debug)]
 pub struct Process {
     /// Process ID of the parent process.
     ppid: Pid,
 
+    /// Process group ID of the process.
+    pgid: Pid,
+
     /// State of the process.
     state: ProcessState,
 
@@ -420,16 +879,52 @@ pub struct Process {
 
     /// Copy of arguments passed to [`execve`](VirtualSystem::execve).
     last_exec: Option<(CString, Vec<CString>, Vec<CString>)>,
+
+    /// File descriptors opened in the process.
+    fds: BTreeMap<RawFd, FdBody>,
+
+    /// How signals are handled.
+    ///
+    /// Signals not contained in the map have the default handling.
+    signal_handlings: HashMap<Signal, SignalHandling>,
+
+    /// Signals that are blocked.
+    blocked_signals: SigSet,
+
+    /// Signals that have been sent but not yet delivered.
+    pending_signals: SigSet,
+
+    /// Signals that have been caught but not yet reported by
+    /// [`caught_signals`](VirtualSystem::caught_signals).
+    caught_signals: Vec<Signal>,
+
+    /// References to tasks that are waiting for a signal to be delivered to
+    /// this process.
+    signal_awaiters: Vec<Waker>,
 }
 
 impl Process {
     /// Creates a new running process.
+    ///
+    /// The new process belongs to the process group whose ID is `ppid`.
     pub fn with_parent(ppid: Pid) -> Process {
+        Process::with_parent_and_group(ppid, ppid)
+    }
+
+    /// Creates a new running process in the process group.
+    pub fn with_parent_and_group(ppid: Pid, pgid: Pid) -> Process {
         Process {
             ppid,
+            pgid,
             state: ProcessState::Running,
             state_awaiters: Some(Vec::new()),
             last_exec: None,
+            fds: BTreeMap::new(),
+            signal_handlings: HashMap::new(),
+            blocked_signals: SigSet::empty(),
+            pending_signals: SigSet::empty(),
+            caught_signals: Vec::new(),
+            signal_awaiters: Vec::new(),
         }
     }
 
@@ -438,6 +933,11 @@ impl Process {
         self.ppid
     }
 
+    /// Returns the process group ID of the process.
+    pub fn pgid(&self) -> Pid {
+        self.pgid
+    }
+
     /// Returns the process state.
     pub fn state(&self) -> ProcessState {
         self.state
@@ -456,7 +956,237 @@ impl Process {
         if old_state == state {
             Vec::new()
         } else {
-            self.state_awaiters.take().unwrap_or_else(Vec::new)
+            self.state_awaiters.take().unwrap_or_default()
+        }
+    }
+
+    /// Returns the file descriptor body for the file descriptor.
+    #[must_use]
+    pub fn get_fd(&self, fd: RawFd) -> Option<&FdBody> {
+        self.fds.get(&fd)
+    }
+
+    /// Assigns a file descriptor body to the file descriptor.
+    ///
+    /// Returns the previous body, if any.
+    pub fn set_fd(&mut self, fd: RawFd, body: FdBody) -> Option<FdBody> {
+        self.fds.insert(fd, body)
+    }
+
+    /// Assigns a file descriptor body to the lowest unused file descriptor
+    /// that is not less than `min_fd`.
+    ///
+    /// Returns the file descriptor assigned.
+    pub fn set_fd_at_min(&mut self, min_fd: RawFd, body: FdBody) -> RawFd {
+        let fd = (min_fd..)
+            .find(|fd| !self.fds.contains_key(fd))
+            .expect("no file descriptor available");
+        self.fds.insert(fd, body);
+        fd
+    }
+
+    /// Opens a new file descriptor for the open file description.
+    ///
+    /// The new file descriptor is the lowest unused one that is not less than
+    /// `min_fd`, and has the given file descriptor flags.
+    pub fn open_fd(&mut self, min_fd: RawFd, ofd: OpenFileDescription, flags: FdFlag) -> RawFd {
+        let open_file_description = Rc::new(RefCell::new(ofd));
+        self.set_fd_at_min(
+            min_fd,
+            FdBody {
+                open_file_description,
+                flags,
+            },
+        )
+    }
+
+    /// Returns the file descriptor table of the process.
+    ///
+    /// The table maps each open file descriptor to its body, which refers to
+    /// an open file description that may be shared with other file
+    /// descriptors.
+    #[must_use]
+    pub fn fds(&self) -> &BTreeMap<RawFd, FdBody> {
+        &self.fds
+    }
+
+    /// Closes the file descriptors that have the `FD_CLOEXEC` flag.
+    ///
+    /// This function is called when the process executes a new program.
+    pub fn close_fds_on_exec(&mut self) {
+        self.fds
+            .retain(|_, body| !body.flags.contains(FdFlag::FD_CLOEXEC));
+    }
+
+    /// Closes the file descriptor.
+    ///
+    /// Returns the body of the closed file descriptor, if any.
+    pub fn close_fd(&mut self, fd: RawFd) -> Option<FdBody> {
+        self.fds.remove(&fd)
+    }
+
+    /// Returns how the signal is handled.
+    #[must_use]
+    pub fn signal_handling(&self, signal: Signal) -> SignalHandling {
+        self.signal_handlings
+            .get(&signal)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Sets how the signal is handled.
+    ///
+    /// Returns the previous handling. If the new handling is
+    /// [`SignalHandling::Ignore`], the pending signal is discarded. This
+    /// function does not reject `SIGKILL` or `SIGSTOP`, but their handlings
+    /// are ignored when they are delivered.
+    pub fn set_signal_handling(
+        &mut self,
+        signal: Signal,
+        handling: SignalHandling,
+    ) -> SignalHandling {
+        if handling == SignalHandling::Ignore {
+            self.pending_signals.remove(signal);
+        }
+        self.signal_handlings
+            .insert(signal, handling)
+            .unwrap_or_default()
+    }
+
+    /// Returns the set of blocked signals.
+    #[must_use]
+    pub fn blocked_signals(&self) -> &SigSet {
+        &self.blocked_signals
+    }
+
+    /// Returns the set of signals that have been sent but not delivered.
+    #[must_use]
+    pub fn pending_signals(&self) -> &SigSet {
+        &self.pending_signals
+    }
+
+    /// Changes the set of blocked signals.
+    ///
+    /// The new mask is computed from the current mask and `signals` according
+    /// to `how`. `SIGKILL` and `SIGSTOP` are never blocked. Pending signals
+    /// that are no longer blocked are delivered.
+    ///
+    /// This function returns wakers that must be woken in the same manner as
+    /// [`set_state`](Self::set_state).
+    #[must_use]
+    pub fn block_signals(&mut self, how: SigmaskHow, signals: &SigSet) -> Vec<Waker> {
+        match how {
+            SigmaskHow::SIG_BLOCK => self.blocked_signals.extend(signals),
+            SigmaskHow::SIG_UNBLOCK => {
+                for signal in Signal::iterator().filter(|&signal| signals.contains(signal)) {
+                    self.blocked_signals.remove(signal);
+                }
+            }
+            SigmaskHow::SIG_SETMASK => self.blocked_signals = *signals,
+        }
+        self.blocked_signals.remove(Signal::SIGKILL);
+        self.blocked_signals.remove(Signal::SIGSTOP);
+        self.deliver_pending_signals()
+    }
+
+    /// Sends a signal to this process.
+    ///
+    /// `SIGCONT` resumes the process if stopped, regardless of the handling
+    /// of the signal. If the signal is blocked or the process is stopped, the
+    /// signal is left pending, except that `SIGKILL` is always delivered.
+    /// Otherwise, the signal is delivered: an ignored signal is discarded, a
+    /// caught signal is recorded so that it is reported by
+    /// [`caught_signals`](VirtualSystem::caught_signals), and a signal with
+    /// the default handling terminates or stops the process, or does nothing,
+    /// depending on the signal. When the process is terminated, its file
+    /// descriptors are closed. A signal sent to a terminated process is
+    /// ignored.
+    ///
+    /// This function returns wakers that must be woken in the same manner as
+    /// [`set_state`](Self::set_state).
+    #[must_use]
+    pub fn raise_signal(&mut self, signal: Signal) -> Vec<Waker> {
+        if let ProcessState::Exited(_) | ProcessState::Signaled(_) = self.state {
+            return Vec::new();
+        }
+
+        let mut wakers = Vec::new();
+        let action = DefaultAction::of(signal);
+        if action == DefaultAction::Continue {
+            for stop in Signal::iterator().filter(|&s| DefaultAction::of(s) == DefaultAction::Stop)
+            {
+                self.pending_signals.remove(stop);
+            }
+            if let ProcessState::Stopped(_) = self.state {
+                wakers.extend(self.set_state(ProcessState::Running));
+                wakers.append(&mut self.signal_awaiters);
+            }
+        } else if action == DefaultAction::Stop {
+            self.pending_signals.remove(Signal::SIGCONT);
+        }
+
+        let is_stopped = matches!(self.state, ProcessState::Stopped(_));
+        if self.blocked_signals.contains(signal) || (is_stopped && signal != Signal::SIGKILL) {
+            self.pending_signals.add(signal);
+        } else {
+            wakers.extend(self.deliver_signal(signal));
+        }
+
+        if action == DefaultAction::Continue {
+            wakers.extend(self.deliver_pending_signals());
+        }
+        wakers
+    }
+
+    /// Delivers a signal to this process, ignoring the signal mask.
+    fn deliver_signal(&mut self, signal: Signal) -> Vec<Waker> {
+        let handling = if signal == Signal::SIGKILL || signal == Signal::SIGSTOP {
+            SignalHandling::Default
+        } else {
+            self.signal_handling(signal)
+        };
+        match handling {
+            SignalHandling::Ignore => Vec::new(),
+            SignalHandling::Catch => {
+                if !self.caught_signals.contains(&signal) {
+                    self.caught_signals.push(signal);
+                }
+                std::mem::take(&mut self.signal_awaiters)
+            }
+            SignalHandling::Default => match DefaultAction::of(signal) {
+                DefaultAction::Ignore | DefaultAction::Continue => Vec::new(),
+                DefaultAction::Terminate => {
+                    self.fds.clear();
+                    let mut wakers = self.set_state(ProcessState::Signaled(signal));
+                    wakers.append(&mut self.signal_awaiters);
+                    wakers
+                }
+                DefaultAction::Stop => self.set_state(ProcessState::Stopped(signal)),
+            },
+        }
+    }
+
+    /// Delivers pending signals that are not blocked.
+    ///
+    /// Signals remain pending while the process is stopped.
+    fn deliver_pending_signals(&mut self) -> Vec<Waker> {
+        let mut wakers = Vec::new();
+        for signal in Signal::iterator() {
+            if self.state != ProcessState::Running {
+                break;
+            }
+            if self.pending_signals.contains(signal) && !self.blocked_signals.contains(signal) {
+                self.pending_signals.remove(signal);
+                wakers.extend(self.deliver_signal(signal));
+            }
+        }
+        wakers
+    }
+
+    /// Registers a task to be woken when a signal is delivered.
+    fn add_signal_awaiter(&mut self, waker: &Waker) {
+        if !self.signal_awaiters.iter().any(|w| w.will_wake(waker)) {
+            self.signal_awaiters.push(waker.clone());
         }
     }
 
@@ -468,6 +1198,28 @@ impl Process {
     }
 }
 
+/// Default action of a signal.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum DefaultAction {
+    Terminate,
+    Ignore,
+    Stop,
+    Continue,
+}
+
+impl DefaultAction {
+    /// Returns the default action of the signal.
+    fn of(signal: Signal) -> DefaultAction {
+        use Signal::*;
+        match signal {
+            SIGCHLD | SIGURG | SIGWINCH => DefaultAction::Ignore,
+            SIGSTOP | SIGTSTP | SIGTTIN | SIGTTOU => DefaultAction::Stop,
+            SIGCONT => DefaultAction::Continue,
+            _ => DefaultAction::Terminate,
+        }
+    }
+}
+
 /// State of a process.
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum ProcessState {
@@ -495,6 +1247,9 @@ mod tests {
     use super::*;
     use futures::executor::block_on;
     use futures::executor::LocalPool;
+    use futures::task::LocalSpawnExt;
+    use nix::libc::{S_IFDIR, S_IFIFO, S_IFMT, S_IFREG};
+    use pipe::PIPE_SIZE;
     use std::ffi::CString;
 
     #[test]
@@ -508,7 +1263,12 @@ mod tests {
         let system = VirtualSystem::new();
         let path = PathBuf::from("/some/file");
         let content = INode::default();
-        system.state.borrow_mut().file_system.save(path, content);
+        system
+            .state
+            .borrow_mut()
+            .file_system
+            .save(path, content)
+            .unwrap();
         assert!(!system.is_executable_file(&CString::new("/some/file").unwrap()));
     }
 
@@ -518,14 +1278,504 @@ mod tests {
         let path = PathBuf::from("/some/file");
         let mut content = INode::default();
         content.permissions.0 |= 0o100;
-        system.state.borrow_mut().file_system.save(path, content);
+        system
+            .state
+            .borrow_mut()
+            .file_system
+            .save(path, content)
+            .unwrap();
         assert!(system.is_executable_file(&CString::new("/some/file").unwrap()));
     }
 
     #[test]
-    fn new_child_process_without_executor() {
-        let mut system = VirtualSystem::new();
-        let result = unsafe { system.new_child_process() };
+    fn read_dir_lists_direct_children() {
+        let system = VirtualSystem::new();
+        let mut state = system.state.borrow_mut();
+        state.file_system.save("/a/b", INode::default()).unwrap();
+        state.file_system.save("/a/c/d", INode::default()).unwrap();
+        state.file_system.save("/e", INode::default()).unwrap();
+        drop(state);
+
+        let mut entries = system.read_dir(&CString::new("/a").unwrap()).unwrap();
+        entries.sort();
+        assert_eq!(entries, ["b", "c"]);
+        let mut entries = system.read_dir(&CString::new("/").unwrap()).unwrap();
+        entries.sort();
+        assert_eq!(entries, ["a", "e"]);
+    }
+
+    #[test]
+    fn read_dir_non_existing_directory() {
+        let system = VirtualSystem::new();
+        let result = system.read_dir(&CString::new("/no/such/dir").unwrap());
+        assert_eq!(result, Err(Errno::ENOENT.into()));
+    }
+
+    #[test]
+    fn getpwnam_dir_looks_up_home_dirs() {
+        let system = VirtualSystem::new();
+        system
+            .state
+            .borrow_mut()
+            .home_dirs
+            .insert("foo".to_string(), PathBuf::from("/home/foo"));
+        let result = system.getpwnam_dir("foo");
+        assert_eq!(result, Ok(Some(PathBuf::from("/home/foo"))));
+        let result = system.getpwnam_dir("bar");
+        assert_eq!(result, Ok(None));
+    }
+
+    #[test]
+    fn pipe_read_write() {
+        let mut system = VirtualSystem::new();
+        let (reader, writer) = system.pipe().unwrap();
+        assert_eq!((reader, writer), (0, 1));
+        let result = system.write(writer, &[5, 42, 29]);
+        assert_eq!(result, Ok(3));
+
+        let mut buffer = [1; 4];
+        let result = block_on(system.read_async(reader, &mut buffer));
+        assert_eq!(result, Ok(3));
+        assert_eq!(buffer, [5, 42, 29, 1]);
+
+        system.close(writer).unwrap();
+        let result = block_on(system.read_async(reader, &mut buffer));
+        assert_eq!(result, Ok(0));
+    }
+
+    #[test]
+    fn reader_is_woken_when_data_is_written() {
+        let mut system = VirtualSystem::new();
+        let mut executor = LocalPool::new();
+        let (reader, writer) = system.pipe().unwrap();
+        let result = Rc::new(RefCell::new(None));
+        let mut reader_system = system.clone();
+        let reader_result = Rc::clone(&result);
+        executor
+            .spawner()
+            .spawn_local(async move {
+                let mut buffer = [0; 4];
+                let count = reader_system.read_async(reader, &mut buffer).await;
+                *reader_result.borrow_mut() = Some(count.map(|count| buffer[..count].to_vec()));
+            })
+            .unwrap();
+        executor.run_until_stalled();
+        assert_eq!(*result.borrow(), None);
+
+        system.write(writer, b"xyz").unwrap();
+        executor.run_until_stalled();
+        assert_eq!(*result.borrow(), Some(Ok(b"xyz".to_vec())));
+    }
+
+    #[test]
+    fn reader_is_woken_when_writer_is_closed() {
+        let mut system = VirtualSystem::new();
+        let mut executor = LocalPool::new();
+        let (reader, writer) = system.pipe().unwrap();
+        let result = Rc::new(RefCell::new(None));
+        let mut reader_system = system.clone();
+        let reader_result = Rc::clone(&result);
+        executor
+            .spawner()
+            .spawn_local(async move {
+                let count = reader_system.read_async(reader, &mut [0; 4]).await;
+                *reader_result.borrow_mut() = Some(count);
+            })
+            .unwrap();
+        executor.run_until_stalled();
+        assert_eq!(*result.borrow(), None);
+
+        system.close(writer).unwrap();
+        executor.run_until_stalled();
+        assert_eq!(*result.borrow(), Some(Ok(0)));
+    }
+
+    #[test]
+    fn writer_waits_for_full_pipe_to_be_read() {
+        let mut system = VirtualSystem::new();
+        let mut executor = LocalPool::new();
+        let (reader, writer) = system.pipe().unwrap();
+        let content = vec![7; PIPE_SIZE + 10];
+        assert_eq!(system.write(writer, &content), Ok(PIPE_SIZE));
+        assert_eq!(system.write(writer, &content), Err(Errno::EAGAIN.into()));
+
+        let result = Rc::new(RefCell::new(None));
+        let mut writer_system = system.clone();
+        let writer_result = Rc::clone(&result);
+        executor
+            .spawner()
+            .spawn_local(async move {
+                let count = writer_system.write_async(writer, &[1, 2, 3]).await;
+                *writer_result.borrow_mut() = Some(count);
+            })
+            .unwrap();
+        executor.run_until_stalled();
+        assert_eq!(*result.borrow(), None);
+
+        let mut buffer = vec![0; PIPE_SIZE];
+        assert_eq!(system.read(reader, &mut buffer), Ok(PIPE_SIZE));
+        executor.run_until_stalled();
+        assert_eq!(*result.borrow(), Some(Ok(3)));
+        assert_eq!(system.read(reader, &mut buffer), Ok(3));
+        assert_eq!(&buffer[..3], [1, 2, 3]);
+    }
+
+    #[test]
+    fn writing_to_pipe_without_reader_raises_sigpipe() {
+        let mut system = VirtualSystem::new();
+        let (reader, writer) = system.pipe().unwrap();
+        system.close(reader).unwrap();
+        assert_eq!(system.write(writer, b"x"), Err(Errno::EPIPE.into()));
+        let process = system.current_process();
+        assert_eq!(process.state(), ProcessState::Signaled(Signal::SIGPIPE));
+        assert!(process.fds().is_empty());
+    }
+
+    #[test]
+    fn child_process_killed_by_sigpipe() {
+        let mut system = VirtualSystem::new();
+        let mut executor = LocalPool::new();
+        system.state.borrow_mut().executor = Some(Rc::new(executor.spawner()));
+        let (reader, writer) = system.pipe().unwrap();
+        let mut child_process = unsafe { system.new_child_process() }.unwrap();
+        system.close(reader).unwrap();
+
+        let mut env = Env::with_system(Box::new(system));
+        let future = child_process.run(
+            &mut env,
+            Box::new(move |env| {
+                Box::pin(async move {
+                    let _ = env.system.close(reader);
+                    let _ = env.system.write_async(writer, b"x").await;
+                    // The task is not resumed after being killed.
+                    futures::future::pending::<()>().await;
+                    env.exit_status = ExitStatus(5);
+                })
+            }),
+        );
+        let pid = executor.run_until(future);
+        executor.run_until_stalled();
+
+        #[allow(deprecated)]
+        let future = env.system.wait_sync();
+        let result = executor.run_until(future);
+        assert_eq!(
+            result,
+            Ok(WaitStatus::Signaled(pid, Signal::SIGPIPE, false))
+        );
+    }
+
+    #[test]
+    fn pipe_between_concurrent_processes() {
+        let mut system = VirtualSystem::new();
+        let mut executor = LocalPool::new();
+        system.state.borrow_mut().executor = Some(Rc::new(executor.spawner()));
+        let (reader, writer) = system.pipe().unwrap();
+        let mut child_process = unsafe { system.new_child_process() }.unwrap();
+        system.close(writer).unwrap();
+
+        let mut env = Env::with_system(Box::new(system));
+        let content = vec![3; PIPE_SIZE * 3];
+        let expected = content.clone();
+        let future = child_process.run(
+            &mut env,
+            Box::new(move |env| {
+                let content = content.clone();
+                Box::pin(async move {
+                    let _ = env.system.close(reader);
+                    let mut buffer = &content[..];
+                    while !buffer.is_empty() {
+                        let count = env.system.write_async(writer, buffer).await.unwrap();
+                        buffer = &buffer[count..];
+                    }
+                })
+            }),
+        );
+        executor.run_until(future);
+
+        let mut received = Vec::<u8>::new();
+        let mut buffer = [0; 1000];
+        loop {
+            let future = env.system.read_async(reader, &mut buffer);
+            match executor.run_until(future).unwrap() {
+                0 => break,
+                count => received.extend(&buffer[..count]),
+            }
+        }
+        assert_eq!(received, expected);
+    }
+
+    #[test]
+    fn dup2_and_close() {
+        let mut system = VirtualSystem::new();
+        let (reader, writer) = system.pipe().unwrap();
+        assert_eq!(system.dup2(writer, 5), Ok(5));
+        system.close(writer).unwrap();
+        assert_eq!(system.write(writer, &[1]), Err(Errno::EBADF.into()));
+        assert_eq!(system.write(5, &[1]), Ok(1));
+
+        let mut buffer = [0; 2];
+        let result = block_on(system.read_async(reader, &mut buffer));
+        assert_eq!(result, Ok(1));
+        assert_eq!(system.close(writer), Ok(()));
+    }
+
+    #[test]
+    fn dup_chooses_lowest_unused_fd() {
+        let mut system = VirtualSystem::new();
+        let (reader, writer) = system.pipe().unwrap();
+        assert_eq!((reader, writer), (0, 1));
+        assert_eq!(system.dup2(reader, 3), Ok(3));
+        assert_eq!(system.dup(writer), Ok(2));
+        assert_eq!(system.dup(writer), Ok(4));
+        assert_eq!(system.fcntl_getfd(2), Ok(FdFlag::empty()));
+        assert_eq!(system.dup(7), Err(Errno::EBADF.into()));
+
+        assert_eq!(system.write(2, &[42]), Ok(1));
+        let mut buffer = [0; 2];
+        assert_eq!(system.read(reader, &mut buffer), Ok(1));
+        assert_eq!(buffer[0], 42);
+    }
+
+    #[test]
+    fn fcntl_dupfd_chooses_lowest_unused_fd() {
+        let mut system = VirtualSystem::new();
+        let (reader, writer) = system.pipe().unwrap();
+        assert_eq!(system.dup2(reader, 11), Ok(11));
+        assert_eq!(system.fcntl_dupfd(writer, 10), Ok(10));
+        assert_eq!(system.fcntl_dupfd(writer, 10), Ok(12));
+        assert_eq!(system.fcntl_dupfd(7, 10), Err(Errno::EBADF.into()));
+    }
+
+    #[test]
+    fn fd_flags_of_duplicated_fds() {
+        let mut system = VirtualSystem::new();
+        let (reader, _writer) = system.pipe().unwrap();
+        assert_eq!(system.fcntl_getfd(reader), Ok(FdFlag::empty()));
+        let dup = system.fcntl_dupfd(reader, 10).unwrap();
+        assert_eq!(system.fcntl_getfd(dup), Ok(FdFlag::FD_CLOEXEC));
+        system.dup2(dup, 5).unwrap();
+        assert_eq!(system.fcntl_getfd(5), Ok(FdFlag::empty()));
+
+        system.fcntl_setfd(5, FdFlag::FD_CLOEXEC).unwrap();
+        assert_eq!(system.fcntl_getfd(5), Ok(FdFlag::FD_CLOEXEC));
+        assert_eq!(system.fcntl_getfd(reader), Ok(FdFlag::empty()));
+        let result = system.fcntl_setfd(7, FdFlag::empty());
+        assert_eq!(result, Err(Errno::EBADF.into()));
+    }
+
+    #[test]
+    fn fcntl_getfl_returns_access_mode() {
+        let mut system = VirtualSystem::new();
+        let (reader, writer) = system.pipe().unwrap();
+        assert_eq!(system.fcntl_getfl(reader), Ok(OFlag::O_RDONLY));
+        assert_eq!(system.fcntl_getfl(writer), Ok(OFlag::O_WRONLY));
+        assert_eq!(system.fcntl_getfl(7), Err(Errno::EBADF.into()));
+    }
+
+    #[test]
+    fn fcntl_setfl_changes_append_flag() {
+        let mut system = VirtualSystem::new();
+        let (_reader, writer) = system.pipe().unwrap();
+        let dup = system.dup(writer).unwrap();
+        assert_eq!(system.fcntl_setfl(writer, OFlag::O_APPEND), Ok(()));
+        assert_eq!(
+            system.fcntl_getfl(writer),
+            Ok(OFlag::O_WRONLY | OFlag::O_APPEND)
+        );
+        assert_eq!(
+            system.fcntl_getfl(dup),
+            Ok(OFlag::O_WRONLY | OFlag::O_APPEND)
+        );
+
+        assert_eq!(system.fcntl_setfl(dup, OFlag::O_RDWR), Ok(()));
+        assert_eq!(system.fcntl_getfl(writer), Ok(OFlag::O_WRONLY));
+        let result = system.fcntl_setfl(7, OFlag::empty());
+        assert_eq!(result, Err(Errno::EBADF.into()));
+    }
+
+    #[test]
+    fn read_does_not_block() {
+        let mut system = VirtualSystem::new();
+        let (reader, writer) = system.pipe().unwrap();
+        let mut buffer = [0; 4];
+        assert_eq!(system.read(reader, &mut buffer), Err(Errno::EAGAIN.into()));
+        system.write_all(writer, &[1, 2]).unwrap();
+        assert_eq!(system.read(reader, &mut buffer), Ok(2));
+        assert_eq!(buffer[..2], [1, 2]);
+        system.close(writer).unwrap();
+        assert_eq!(system.read(reader, &mut buffer), Ok(0));
+    }
+
+    #[test]
+    fn stat_and_fstat() {
+        let mut system = VirtualSystem::new();
+        let mut content = INode::default();
+        content.permissions.0 = 0o755;
+        system
+            .state
+            .borrow_mut()
+            .file_system
+            .save("/a/b", content)
+            .unwrap();
+
+        let stat = system.stat(&CString::new("/a/b").unwrap()).unwrap();
+        assert_eq!(stat.st_mode, S_IFREG | 0o755);
+        let stat = system.stat(&CString::new("/a").unwrap()).unwrap();
+        assert_eq!(stat.st_mode & S_IFMT, S_IFDIR);
+        let result = system.stat(&CString::new("/c").unwrap());
+        assert_eq!(result.map(|stat| stat.st_mode), Err(Errno::ENOENT.into()));
+
+        let (reader, _writer) = system.pipe().unwrap();
+        let stat = system.fstat(reader).unwrap();
+        assert_eq!(stat.st_mode & S_IFMT, S_IFIFO);
+        let result = system.fstat(7);
+        assert_eq!(result.map(|stat| stat.st_mode), Err(Errno::EBADF.into()));
+    }
+
+    #[test]
+    fn lseek_on_pipe() {
+        let mut system = VirtualSystem::new();
+        let (reader, _writer) = system.pipe().unwrap();
+        let result = system.lseek(reader, 0, Whence::SeekSet);
+        assert_eq!(result, Err(Errno::ESPIPE.into()));
+        let result = system.lseek(7, 0, Whence::SeekSet);
+        assert_eq!(result, Err(Errno::EBADF.into()));
+    }
+
+    #[test]
+    fn open_creates_and_writes_file() {
+        let mut system = VirtualSystem::new();
+        let path = CString::new("/file").unwrap();
+        let flags = OFlag::O_WRONLY | OFlag::O_CREAT;
+        let mode = nix::sys::stat::Mode::from_bits_truncate(0o640);
+        let fd = system.open(&path, flags, mode).unwrap();
+        assert_eq!(system.write(fd, b"data"), Ok(4));
+
+        let file = system.state.borrow().file_system.get("/file").unwrap();
+        assert_eq!(file.borrow().permissions, Mode(0o640));
+        let stat = system.fstat(fd).unwrap();
+        assert_eq!(stat.st_mode, S_IFREG | 0o640);
+        assert_eq!(stat.st_size, 4);
+
+        let fd = system.open(&path, OFlag::O_RDONLY, mode).unwrap();
+        let mut buffer = [0; 8];
+        assert_eq!(system.read(fd, &mut buffer), Ok(4));
+        assert_eq!(&buffer[..4], b"data");
+    }
+
+    #[test]
+    fn open_errors() {
+        let mut system = VirtualSystem::new();
+        let mut state = system.state.borrow_mut();
+        state.file_system.save("/dir/file", INode::new()).unwrap();
+        drop(state);
+        let mode = nix::sys::stat::Mode::empty();
+
+        let path = CString::new("/none").unwrap();
+        let result = system.open(&path, OFlag::O_RDONLY, mode);
+        assert_eq!(result, Err(Errno::ENOENT.into()));
+        let path = CString::new("/dir/file/x").unwrap();
+        let result = system.open(&path, OFlag::O_RDONLY | OFlag::O_CREAT, mode);
+        assert_eq!(result, Err(Errno::ENOTDIR.into()));
+        let path = CString::new("/dir/file").unwrap();
+        let flags = OFlag::O_WRONLY | OFlag::O_CREAT | OFlag::O_EXCL;
+        assert_eq!(system.open(&path, flags, mode), Err(Errno::EEXIST.into()));
+        let path = CString::new("/dir").unwrap();
+        let result = system.open(&path, OFlag::O_WRONLY, mode);
+        assert_eq!(result, Err(Errno::EISDIR.into()));
+    }
+
+    #[test]
+    fn open_tmpfile_and_lseek() {
+        let mut system = VirtualSystem::new();
+        let result = system.open_tmpfile(Path::new("/tmp"));
+        assert_eq!(result, Err(Errno::ENOENT.into()));
+
+        let tmp = INode::new_directory();
+        system
+            .state
+            .borrow_mut()
+            .file_system
+            .save("/tmp", tmp)
+            .unwrap();
+        let fd = system.open_tmpfile(Path::new("/tmp")).unwrap();
+        system.write_all(fd, b"temporary").unwrap();
+        assert_eq!(system.lseek(fd, 3, Whence::SeekSet), Ok(3));
+        let mut buffer = [0; 10];
+        assert_eq!(system.read(fd, &mut buffer), Ok(6));
+        assert_eq!(&buffer[..6], b"porary");
+        assert_eq!(system.read_dir(&CString::new("/tmp").unwrap()), Ok(vec![]));
+    }
+
+    #[test]
+    fn open_with_cloexec_and_append() {
+        let mut system = VirtualSystem::new();
+        let path = CString::new("/file").unwrap();
+        let mode = nix::sys::stat::Mode::empty();
+        let flags = OFlag::O_WRONLY | OFlag::O_CREAT | OFlag::O_CLOEXEC;
+        let fd = system.open(&path, flags, mode).unwrap();
+        assert_eq!(system.fcntl_getfd(fd), Ok(FdFlag::FD_CLOEXEC));
+        assert_eq!(system.fcntl_getfl(fd), Ok(OFlag::O_WRONLY));
+
+        let fd = system
+            .open(&path, OFlag::O_RDWR | OFlag::O_APPEND, mode)
+            .unwrap();
+        assert_eq!(system.fcntl_getfd(fd), Ok(FdFlag::empty()));
+        assert_eq!(system.fcntl_getfl(fd), Ok(OFlag::O_RDWR | OFlag::O_APPEND));
+    }
+
+    #[test]
+    fn duplicated_fds_share_offset() {
+        let mut system = VirtualSystem::new();
+        let file = INode::with_content("abcdef");
+        system
+            .state
+            .borrow_mut()
+            .file_system
+            .save("/file", file)
+            .unwrap();
+        let path = CString::new("/file").unwrap();
+        let mode = nix::sys::stat::Mode::empty();
+        let fd1 = system.open(&path, OFlag::O_RDONLY, mode).unwrap();
+        let fd2 = system.fcntl_dupfd(fd1, 10).unwrap();
+        let fd3 = system.open(&path, OFlag::O_RDONLY, mode).unwrap();
+
+        let mut buffer = [0; 2];
+        system.read(fd1, &mut buffer).unwrap();
+        system.read(fd2, &mut buffer).unwrap();
+        assert_eq!(&buffer, b"cd");
+        system.read(fd3, &mut buffer).unwrap();
+        assert_eq!(&buffer, b"ab");
+    }
+
+    #[test]
+    fn child_process_inherits_fd_table() {
+        let mut system = VirtualSystem::new();
+        let executor = LocalPool::new();
+        system.state.borrow_mut().executor = Some(Rc::new(executor.spawner()));
+        let (reader, writer) = system.pipe().unwrap();
+        system.fcntl_setfd(writer, FdFlag::FD_CLOEXEC).unwrap();
+
+        let _child = unsafe { system.new_child_process() }.unwrap();
+        let state = system.state.borrow();
+        let parent = &state.processes[&system.process_id];
+        let child = &state.processes[&Pid::from_raw(3)];
+        assert_eq!(child.fds().keys().collect::<Vec<_>>(), [&reader, &writer]);
+        for (fd, body) in child.fds() {
+            let parent_body = parent.get_fd(*fd).unwrap();
+            assert!(Rc::ptr_eq(
+                &body.open_file_description,
+                &parent_body.open_file_description
+            ));
+            assert_eq!(body.flags, parent_body.flags);
+        }
+    }
+
+    #[test]
+    fn new_child_process_without_executor() {
+        let mut system = VirtualSystem::new();
+        let result = unsafe { system.new_child_process() };
         assert_eq!(result.unwrap_err(), Errno::ENOSYS.into());
     }
 
@@ -586,18 +1836,411 @@ mod tests {
         assert_eq!(result, Err(Errno::ECHILD.into()));
     }
 
+    /// Returns the signals in the set.
+    ///
+    /// `SigSet` cannot be compared directly because it may contain
+    /// uninitialized bytes.
+    fn members(set: &SigSet) -> Vec<Signal> {
+        Signal::iterator()
+            .filter(|&signal| set.contains(signal))
+            .collect()
+    }
+
+    #[test]
+    fn setting_signal_handling() {
+        let mut system = VirtualSystem::new();
+        let result = system.set_signal_handling(Signal::SIGINT, SignalHandling::Catch);
+        assert_eq!(result, Ok(SignalHandling::Default));
+        let result = system.set_signal_handling(Signal::SIGINT, SignalHandling::Ignore);
+        assert_eq!(result, Ok(SignalHandling::Catch));
+        let handling = system.current_process().signal_handling(Signal::SIGINT);
+        assert_eq!(handling, SignalHandling::Ignore);
+
+        let result = system.set_signal_handling(Signal::SIGKILL, SignalHandling::Ignore);
+        assert_eq!(result, Err(Errno::EINVAL.into()));
+        let result = system.set_signal_handling(Signal::SIGSTOP, SignalHandling::Catch);
+        assert_eq!(result, Err(Errno::EINVAL.into()));
+    }
+
+    #[test]
+    fn caught_signal_is_reported() {
+        let mut system = VirtualSystem::new();
+        system
+            .set_signal_handling(Signal::SIGUSR1, SignalHandling::Catch)
+            .unwrap();
+        system
+            .set_signal_handling(Signal::SIGINT, SignalHandling::Catch)
+            .unwrap();
+        assert_eq!(system.caught_signals(), []);
+
+        system
+            .kill(system.process_id, Some(Signal::SIGUSR1))
+            .unwrap();
+        system
+            .kill(system.process_id, Some(Signal::SIGINT))
+            .unwrap();
+        system
+            .kill(system.process_id, Some(Signal::SIGUSR1))
+            .unwrap();
+        assert_eq!(system.caught_signals(), [Signal::SIGUSR1, Signal::SIGINT]);
+        assert_eq!(system.caught_signals(), []);
+        assert_eq!(system.current_process().state(), ProcessState::Running);
+    }
+
+    #[test]
+    fn ignored_signal_is_discarded() {
+        let mut system = VirtualSystem::new();
+        system
+            .set_signal_handling(Signal::SIGTERM, SignalHandling::Ignore)
+            .unwrap();
+        system
+            .kill(system.process_id, Some(Signal::SIGTERM))
+            .unwrap();
+        assert_eq!(system.caught_signals(), []);
+        assert_eq!(system.current_process().state(), ProcessState::Running);
+    }
+
+    #[test]
+    fn signal_with_default_handling_terminates_process() {
+        let mut system = VirtualSystem::new();
+        system.pipe().unwrap();
+        system
+            .kill(system.process_id, Some(Signal::SIGTERM))
+            .unwrap();
+        let process = system.current_process();
+        assert_eq!(process.state(), ProcessState::Signaled(Signal::SIGTERM));
+        assert!(process.fds().is_empty());
+    }
+
+    #[test]
+    fn signal_with_default_ignore_action() {
+        let mut system = VirtualSystem::new();
+        system
+            .kill(system.process_id, Some(Signal::SIGCHLD))
+            .unwrap();
+        assert_eq!(system.current_process().state(), ProcessState::Running);
+    }
+
+    #[test]
+    fn blocked_signal_is_delivered_when_unblocked() {
+        let mut system = VirtualSystem::new();
+        system
+            .set_signal_handling(Signal::SIGUSR1, SignalHandling::Catch)
+            .unwrap();
+        let mut mask = SigSet::empty();
+        mask.add(Signal::SIGUSR1);
+        let old_mask = system.sigmask(SigmaskHow::SIG_BLOCK, &mask).unwrap();
+        assert_eq!(members(&old_mask), []);
+
+        system
+            .kill(system.process_id, Some(Signal::SIGUSR1))
+            .unwrap();
+        assert_eq!(system.caught_signals(), []);
+        assert!(system
+            .current_process()
+            .pending_signals()
+            .contains(Signal::SIGUSR1));
+
+        let old_mask = system.sigmask(SigmaskHow::SIG_UNBLOCK, &mask).unwrap();
+        assert_eq!(members(&old_mask), [Signal::SIGUSR1]);
+        assert_eq!(system.caught_signals(), [Signal::SIGUSR1]);
+        assert_eq!(members(system.current_process().pending_signals()), []);
+    }
+
+    #[test]
+    fn sigkill_and_sigstop_cannot_be_blocked() {
+        let mut system = VirtualSystem::new();
+        system
+            .sigmask(SigmaskHow::SIG_SETMASK, &SigSet::all())
+            .unwrap();
+        let mask = *system.current_process().blocked_signals();
+        assert!(mask.contains(Signal::SIGTERM));
+        assert!(!mask.contains(Signal::SIGKILL));
+        assert!(!mask.contains(Signal::SIGSTOP));
+
+        system
+            .kill(system.process_id, Some(Signal::SIGKILL))
+            .unwrap();
+        let state = system.current_process().state();
+        assert_eq!(state, ProcessState::Signaled(Signal::SIGKILL));
+    }
+
+    #[test]
+    fn stopped_process_is_continued() {
+        let mut process = Process::with_parent(Pid::from_raw(1));
+        let _ = process.raise_signal(Signal::SIGTSTP);
+        assert_eq!(process.state(), ProcessState::Stopped(Signal::SIGTSTP));
+
+        // A signal is pending while the process is stopped
+        let _ = process.raise_signal(Signal::SIGTERM);
+        assert_eq!(process.state(), ProcessState::Stopped(Signal::SIGTSTP));
+        assert!(process.pending_signals().contains(Signal::SIGTERM));
+
+        let _ = process.raise_signal(Signal::SIGCONT);
+        assert_eq!(process.state(), ProcessState::Signaled(Signal::SIGTERM));
+    }
+
+    #[test]
+    fn ignored_sigcont_still_continues_process() {
+        let mut process = Process::with_parent(Pid::from_raw(1));
+        process.set_signal_handling(Signal::SIGCONT, SignalHandling::Ignore);
+        let _ = process.raise_signal(Signal::SIGSTOP);
+        let _ = process.raise_signal(Signal::SIGCONT);
+        assert_eq!(process.state(), ProcessState::Running);
+    }
+
+    #[test]
+    fn sigcont_discards_pending_stop_signals() {
+        let mut process = Process::with_parent(Pid::from_raw(1));
+        let mut mask = SigSet::empty();
+        mask.add(Signal::SIGTTIN);
+        let _ = process.block_signals(SigmaskHow::SIG_BLOCK, &mask);
+        let _ = process.raise_signal(Signal::SIGTTIN);
+        let _ = process.raise_signal(Signal::SIGCONT);
+        let _ = process.block_signals(SigmaskHow::SIG_UNBLOCK, &mask);
+        assert_eq!(process.state(), ProcessState::Running);
+    }
+
+    #[test]
+    fn kill_process_group() {
+        let mut system = VirtualSystem::new();
+        let mut state = system.state.borrow_mut();
+        let group = Pid::from_raw(10);
+        for pid in 10..13 {
+            let process = Process::with_parent_and_group(Pid::from_raw(2), group);
+            state.processes.insert(Pid::from_raw(pid), process);
+        }
+        let process = Process::with_parent_and_group(Pid::from_raw(2), Pid::from_raw(13));
+        state.processes.insert(Pid::from_raw(13), process);
+        drop(state);
+
+        system.killpg(group, Some(Signal::SIGHUP)).unwrap();
+        let state = system.state.borrow();
+        for pid in 10..13 {
+            let state = state.processes[&Pid::from_raw(pid)].state();
+            assert_eq!(state, ProcessState::Signaled(Signal::SIGHUP));
+        }
+        assert_eq!(
+            state.processes[&Pid::from_raw(13)].state(),
+            ProcessState::Running
+        );
+        assert_eq!(
+            state.processes[&system.process_id].state(),
+            ProcessState::Running
+        );
+    }
+
+    #[test]
+    fn kill_own_process_group() {
+        let mut system = VirtualSystem::new();
+        let mut state = system.state.borrow_mut();
+        let process = Process::with_parent_and_group(Pid::from_raw(2), Pid::from_raw(2));
+        state.processes.insert(Pid::from_raw(3), process);
+        let process = Process::with_parent_and_group(Pid::from_raw(2), Pid::from_raw(4));
+        state.processes.insert(Pid::from_raw(4), process);
+        drop(state);
+
+        system.kill(Pid::from_raw(0), Some(Signal::SIGINT)).unwrap();
+        let state = system.state.borrow();
+        let signaled = ProcessState::Signaled(Signal::SIGINT);
+        assert_eq!(state.processes[&Pid::from_raw(2)].state(), signaled);
+        assert_eq!(state.processes[&Pid::from_raw(3)].state(), signaled);
+        assert_eq!(
+            state.processes[&Pid::from_raw(4)].state(),
+            ProcessState::Running
+        );
+    }
+
+    #[test]
+    fn kill_without_signal_checks_existence() {
+        let mut system = VirtualSystem::new();
+        assert_eq!(system.kill(system.process_id, None), Ok(()));
+        assert_eq!(
+            system.kill(Pid::from_raw(100), None),
+            Err(Errno::ESRCH.into())
+        );
+        assert_eq!(
+            system.killpg(Pid::from_raw(100), Some(Signal::SIGINT)),
+            Err(Errno::ESRCH.into())
+        );
+        assert_eq!(system.current_process().state(), ProcessState::Running);
+    }
+
+    #[test]
+    fn wait_for_signals_is_woken_by_kill() {
+        let mut system = VirtualSystem::new();
+        let mut executor = LocalPool::new();
+        system
+            .set_signal_handling(Signal::SIGINT, SignalHandling::Catch)
+            .unwrap();
+        let result = Rc::new(RefCell::new(None));
+        let mut waiting_system = system.clone();
+        let waiting_result = Rc::clone(&result);
+        executor
+            .spawner()
+            .spawn_local(async move {
+                let signals = waiting_system.wait_for_signals().await;
+                *waiting_result.borrow_mut() = Some(signals);
+            })
+            .unwrap();
+        executor.run_until_stalled();
+        assert_eq!(*result.borrow(), None);
+
+        system
+            .kill(system.process_id, Some(Signal::SIGINT))
+            .unwrap();
+        executor.run_until_stalled();
+        assert_eq!(*result.borrow(), Some(vec![Signal::SIGINT]));
+    }
+
+    #[test]
+    fn child_process_is_stopped_and_continued_by_signals() {
+        let mut system = VirtualSystem::new();
+        let mut executor = LocalPool::new();
+        system.state.borrow_mut().executor = Some(Rc::new(executor.spawner()));
+        let (reader, writer) = system.pipe().unwrap();
+        let mut child_process = unsafe { system.new_child_process() }.unwrap();
+
+        let mut env = Env::with_system(Box::new(system.clone()));
+        let future = child_process.run(
+            &mut env,
+            Box::new(move |env| {
+                Box::pin(async move {
+                    let mut buffer = [0; 1];
+                    let _ = env.system.read_async(reader, &mut buffer).await;
+                    env.exit_status = ExitStatus(buffer[0].into());
+                })
+            }),
+        );
+        let pid = executor.run_until(future);
+        executor.run_until_stalled();
+
+        system.kill(pid, Some(Signal::SIGSTOP)).unwrap();
+        system.write(writer, &[42]).unwrap();
+        executor.run_until_stalled();
+        let state = system.state.borrow().processes[&pid].state();
+        assert_eq!(state, ProcessState::Stopped(Signal::SIGSTOP));
+
+        system.kill(pid, Some(Signal::SIGCONT)).unwrap();
+        executor.run_until_stalled();
+        let state = system.state.borrow().processes[&pid].state();
+        assert_eq!(state, ProcessState::Exited(ExitStatus(42)));
+    }
+
+    #[test]
+    fn child_process_killed_while_waiting() {
+        let mut system = VirtualSystem::new();
+        let mut executor = LocalPool::new();
+        system.state.borrow_mut().executor = Some(Rc::new(executor.spawner()));
+        let (reader, _writer) = system.pipe().unwrap();
+        let mut child_process = unsafe { system.new_child_process() }.unwrap();
+
+        let mut env = Env::with_system(Box::new(system.clone()));
+        let future = child_process.run(
+            &mut env,
+            Box::new(move |env| {
+                Box::pin(async move {
+                    let _ = env.system.read_async(reader, &mut [0; 1]).await;
+                    env.exit_status = ExitStatus(1);
+                })
+            }),
+        );
+        let pid = executor.run_until(future);
+        executor.run_until_stalled();
+
+        system.kill(pid, Some(Signal::SIGTERM)).unwrap();
+        #[allow(deprecated)]
+        let future = env.system.wait_sync();
+        let result = executor.run_until(future);
+        assert_eq!(
+            result,
+            Ok(WaitStatus::Signaled(pid, Signal::SIGTERM, false))
+        );
+        assert!(system.state.borrow().processes[&pid].fds().is_empty());
+    }
+
+    #[test]
+    fn child_process_inherits_signal_handlings_and_mask() {
+        let mut system = VirtualSystem::new();
+        let executor = LocalPool::new();
+        system.state.borrow_mut().executor = Some(Rc::new(executor.spawner()));
+        system
+            .set_signal_handling(Signal::SIGQUIT, SignalHandling::Ignore)
+            .unwrap();
+        let mut mask = SigSet::empty();
+        mask.add(Signal::SIGINT);
+        system.sigmask(SigmaskHow::SIG_BLOCK, &mask).unwrap();
+
+        let _child = unsafe { system.new_child_process() }.unwrap();
+        let state = system.state.borrow();
+        let child = &state.processes[&Pid::from_raw(3)];
+        assert_eq!(child.pgid(), system.current_process().pgid());
+        assert_eq!(
+            child.signal_handling(Signal::SIGQUIT),
+            SignalHandling::Ignore
+        );
+        assert_eq!(members(child.blocked_signals()), [Signal::SIGINT]);
+    }
+
+    #[test]
+    fn ignored_sigpipe_does_not_terminate_writer() {
+        let mut system = VirtualSystem::new();
+        system
+            .set_signal_handling(Signal::SIGPIPE, SignalHandling::Ignore)
+            .unwrap();
+        let (reader, writer) = system.pipe().unwrap();
+        system.close(reader).unwrap();
+        assert_eq!(system.write(writer, b"x"), Err(Errno::EPIPE.into()));
+        assert_eq!(system.current_process().state(), ProcessState::Running);
+    }
+
+    #[test]
+    fn execve_resets_caught_signals() {
+        let mut system = VirtualSystem::new();
+        let mut content = INode::default();
+        content.body = FileBody::Regular {
+            content: Vec::new(),
+            is_native_executable: true,
+        };
+        let mut state = system.state.borrow_mut();
+        state.file_system.save("/some/file", content).unwrap();
+        drop(state);
+        system
+            .set_signal_handling(Signal::SIGINT, SignalHandling::Catch)
+            .unwrap();
+        system
+            .set_signal_handling(Signal::SIGQUIT, SignalHandling::Ignore)
+            .unwrap();
+
+        let path = CString::new("/some/file").unwrap();
+        let _ = system.execve(&path, &[], &[]);
+        let process = system.current_process();
+        assert_eq!(
+            process.signal_handling(Signal::SIGINT),
+            SignalHandling::Default
+        );
+        assert_eq!(
+            process.signal_handling(Signal::SIGQUIT),
+            SignalHandling::Ignore
+        );
+    }
+
     #[test]
     fn execve_returns_enosys_for_executable_file() {
         let mut system = VirtualSystem::new();
         let path = PathBuf::from("/some/file");
         let mut content = INode::default();
         content.permissions.0 |= 0o100;
-        content.is_native_executable = true;
+        content.body = FileBody::Regular {
+            content: Vec::new(),
+            is_native_executable: true,
+        };
         system
             .state
             .borrow_mut()
             .file_system
-            .save(path.clone(), content);
+            .save(&path, content)
+            .unwrap();
         let path = CString::new(path.as_os_str().as_bytes()).unwrap();
         let result = system.execve(&path, &[], &[]);
         assert_eq!(result, Err(Errno::ENOSYS.into()));
@@ -609,12 +2252,16 @@ mod tests {
         let path = PathBuf::from("/some/file");
         let mut content = INode::default();
         content.permissions.0 |= 0o100;
-        content.is_native_executable = true;
+        content.body = FileBody::Regular {
+            content: Vec::new(),
+            is_native_executable: true,
+        };
         system
             .state
             .borrow_mut()
             .file_system
-            .save(path.clone(), content);
+            .save(&path, content)
+            .unwrap();
         let path = CString::new(path.as_os_str().as_bytes()).unwrap();
         let args = [CString::new("file").unwrap(), CString::new("bar").unwrap()];
         let envs = [
@@ -630,6 +2277,37 @@ mod tests {
         assert_eq!(arguments.2, envs);
     }
 
+    #[test]
+    fn execve_closes_cloexec_fds() {
+        let mut system = VirtualSystem::new();
+        let mut content = INode::default();
+        content.body = FileBody::Regular {
+            content: Vec::new(),
+            is_native_executable: true,
+        };
+        let mut state = system.state.borrow_mut();
+        state.file_system.save("/some/file", content).unwrap();
+        drop(state);
+        let (reader, writer) = system.pipe().unwrap();
+        let cloexec = system.fcntl_dupfd(writer, 10).unwrap();
+
+        let path = CString::new("/some/file").unwrap();
+        let _ = system.execve(&path, &[], &[]);
+        let process = system.current_process();
+        assert_eq!(process.fds().keys().collect::<Vec<_>>(), [&reader, &writer]);
+        assert_eq!(process.get_fd(cloexec).map(|_| ()), None);
+    }
+
+    #[test]
+    fn execve_keeps_fds_on_failure() {
+        let mut system = VirtualSystem::new();
+        let (reader, _writer) = system.pipe().unwrap();
+        let cloexec = system.fcntl_dupfd(reader, 10).unwrap();
+        let path = CString::new("/no/such/file").unwrap();
+        let _ = system.execve(&path, &[], &[]);
+        assert!(system.current_process().get_fd(cloexec).is_some());
+    }
+
     #[test]
     fn execve_returns_enoexec_for_non_executable_file() {
         let mut system = VirtualSystem::new();
@@ -640,12 +2318,43 @@ mod tests {
             .state
             .borrow_mut()
             .file_system
-            .save(path.clone(), content);
+            .save(&path, content)
+            .unwrap();
         let path = CString::new(path.as_os_str().as_bytes()).unwrap();
         let result = system.execve(&path, &[], &[]);
         assert_eq!(result, Err(Errno::ENOEXEC.into()));
     }
 
+    #[test]
+    fn execve_returns_eacces_for_directory() {
+        let mut system = VirtualSystem::new();
+        let dir = INode::new_directory();
+        system
+            .state
+            .borrow_mut()
+            .file_system
+            .save("/dir", dir)
+            .unwrap();
+        let path = CString::new("/dir").unwrap();
+        let result = system.execve(&path, &[], &[]);
+        assert_eq!(result, Err(Errno::EACCES.into()));
+    }
+
+    #[test]
+    fn execve_returns_enotdir_for_file_in_non_directory() {
+        let mut system = VirtualSystem::new();
+        let file = INode::new();
+        system
+            .state
+            .borrow_mut()
+            .file_system
+            .save("/file", file)
+            .unwrap();
+        let path = CString::new("/file/x").unwrap();
+        let result = system.execve(&path, &[], &[]);
+        assert_eq!(result, Err(Errno::ENOTDIR.into()));
+    }
+
     #[test]
     fn execve_returns_enoent_on_file_not_found() {
         let mut system = VirtualSystem::new();
@@ -0,0 +1,564 @@
+// This file is part of yash, an extended POSIX shell.
+// Copyright (C) 2021 WATANABE Yuki
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! File system in a virtual system.
+//!
+//! A [`FileSystem`] is a tree of [`INode`]s rooted at the root directory. Each
+//! i-node has a [`FileBody`] that determines the type of the file: a regular
+//! file with its content, a directory containing other files, a FIFO, or a
+//! symbolic link.
+//!
+//! Since the virtual system does not have a working directory, a relative path
+//! is resolved from the root directory. Permissions are recorded but not
+//! checked when a file is accessed.
+
+use super::pipe::Pipe;
+use super::Mode;
+use nix::errno::Errno;
+use nix::libc::{mode_t, S_IFDIR, S_IFIFO, S_IFLNK, S_IFREG};
+use nix::sys::stat::FileStat;
+use nix::unistd::{Gid, Uid};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+/// Maximum number of symbolic links followed in resolving a path.
+///
+/// Path resolution fails with `ELOOP` if more symbolic links are encountered.
+pub const SYMLOOP_MAX: usize = 40;
+
+/// Type and content of a file.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum FileBody {
+    /// Regular file.
+    Regular {
+        /// File content.
+        content: Vec<u8>,
+        /// Whether this file is a native binary that can be exec'ed.
+        is_native_executable: bool,
+    },
+    /// Directory.
+    Directory {
+        /// Files contained in this directory, keyed by their names.
+        ///
+        /// The `.` and `..` entries are not included.
+        files: HashMap<OsString, Rc<RefCell<INode>>>,
+    },
+    /// Named pipe.
+    ///
+    /// An unnamed pipe created by [`pipe`](crate::System::pipe) is also
+    /// represented as a FIFO that is not contained in any directory.
+    Fifo(Pipe),
+    /// Symbolic link.
+    Symlink {
+        /// Path to the file referenced by the link.
+        target: PathBuf,
+    },
+}
+
+impl Default for FileBody {
+    fn default() -> Self {
+        FileBody::Regular {
+            content: Vec::new(),
+            is_native_executable: false,
+        }
+    }
+}
+
+impl FileBody {
+    /// Returns the file type bits for `st_mode`.
+    #[must_use]
+    pub fn type_bits(&self) -> mode_t {
+        match self {
+            FileBody::Regular { .. } => S_IFREG,
+            FileBody::Directory { .. } => S_IFDIR,
+            FileBody::Fifo(_) => S_IFIFO,
+            FileBody::Symlink { .. } => S_IFLNK,
+        }
+    }
+
+    /// Returns the size of the file.
+    ///
+    /// The size of a directory is the number of entries, that of a FIFO is the
+    /// number of unread bytes, and that of a symbolic link is the length of
+    /// the target path.
+    #[must_use]
+    pub fn size(&self) -> usize {
+        match self {
+            FileBody::Regular { content, .. } => content.len(),
+            FileBody::Directory { files } => files.len(),
+            FileBody::Fifo(pipe) => pipe.content().len(),
+            FileBody::Symlink { target } => target.as_os_str().len(),
+        }
+    }
+}
+
+/// File on the file system.
+///
+/// The timestamps are not updated automatically when the file is accessed
+/// because the virtual system has no clock.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct INode {
+    /// Type and content of the file.
+    pub body: FileBody,
+    /// Access permissions.
+    pub permissions: Mode,
+    /// User ID of the owner.
+    pub owner: Uid,
+    /// Group ID of the owner.
+    pub group: Gid,
+    /// Time of last access.
+    pub last_accessed: SystemTime,
+    /// Time of last modification of the content.
+    pub last_modified: SystemTime,
+    /// Time of last change of the i-node.
+    pub last_changed: SystemTime,
+}
+
+impl Default for INode {
+    fn default() -> Self {
+        INode {
+            body: FileBody::default(),
+            permissions: Mode::default(),
+            owner: Uid::from_raw(0),
+            group: Gid::from_raw(0),
+            last_accessed: UNIX_EPOCH,
+            last_modified: UNIX_EPOCH,
+            last_changed: UNIX_EPOCH,
+        }
+    }
+}
+
+impl INode {
+    /// Create an empty regular file.
+    #[must_use]
+    pub fn new() -> INode {
+        INode::default()
+    }
+
+    /// Creates a regular file with the given content.
+    #[must_use]
+    pub fn with_content<C: Into<Vec<u8>>>(content: C) -> INode {
+        let body = FileBody::Regular {
+            content: content.into(),
+            is_native_executable: false,
+        };
+        INode {
+            body,
+            ..INode::default()
+        }
+    }
+
+    /// Creates an empty directory.
+    ///
+    /// The permissions of the directory are `0o755`.
+    #[must_use]
+    pub fn new_directory() -> INode {
+        let files = HashMap::new();
+        INode {
+            body: FileBody::Directory { files },
+            permissions: Mode(0o755),
+            ..INode::default()
+        }
+    }
+
+    /// Creates an empty FIFO with no readers or writers.
+    #[must_use]
+    pub fn new_fifo() -> INode {
+        let body = FileBody::Fifo(Pipe::new());
+        INode {
+            body,
+            ..INode::default()
+        }
+    }
+
+    /// Creates a symbolic link to the target.
+    ///
+    /// The permissions of the link are `0o777`.
+    #[must_use]
+    pub fn new_symlink<P: Into<PathBuf>>(target: P) -> INode {
+        let target = target.into();
+        INode {
+            body: FileBody::Symlink { target },
+            permissions: Mode(0o777),
+            ..INode::default()
+        }
+    }
+
+    /// Returns the status of this file.
+    ///
+    /// The device and i-node numbers are zero.
+    #[must_use]
+    pub fn stat(&self) -> FileStat {
+        fn seconds(time: SystemTime) -> (i64, i64) {
+            match time.duration_since(UNIX_EPOCH) {
+                Ok(duration) => (duration.as_secs() as i64, duration.subsec_nanos() as i64),
+                Err(_) => (0, 0),
+            }
+        }
+
+        // SAFETY: `FileStat` is a plain C struct, for which all-zero is valid.
+        let mut stat: FileStat = unsafe { std::mem::zeroed() };
+        stat.st_mode = self.body.type_bits() | self.permissions.0 as mode_t;
+        stat.st_nlink = 1;
+        stat.st_uid = self.owner.as_raw();
+        stat.st_gid = self.group.as_raw();
+        stat.st_size = self.body.size() as _;
+        let (sec, nsec) = seconds(self.last_accessed);
+        stat.st_atime = sec as _;
+        stat.st_atime_nsec = nsec as _;
+        let (sec, nsec) = seconds(self.last_modified);
+        stat.st_mtime = sec as _;
+        stat.st_mtime_nsec = nsec as _;
+        let (sec, nsec) = seconds(self.last_changed);
+        stat.st_ctime = sec as _;
+        stat.st_ctime_nsec = nsec as _;
+        stat
+    }
+}
+
+/// Step in path resolution.
+enum Step {
+    Root,
+    Parent,
+    Name(OsString),
+}
+
+/// Pushes the steps to resolve the path onto the stack in reverse order.
+fn push_steps(steps: &mut Vec<Step>, path: &Path) {
+    let start = steps.len();
+    for component in path.components() {
+        match component {
+            Component::Prefix(_) | Component::CurDir => (),
+            Component::RootDir => steps.push(Step::Root),
+            Component::ParentDir => steps.push(Step::Parent),
+            Component::Normal(name) => steps.push(Step::Name(name.to_owned())),
+        }
+    }
+    steps[start..].reverse();
+}
+
+/// Tree of files.
+///
+/// Cloning a `FileSystem` produces another reference to the same tree.
+#[derive(Clone, Debug)]
+pub struct FileSystem {
+    /// Root directory.
+    root: Rc<RefCell<INode>>,
+}
+
+impl Default for FileSystem {
+    /// Creates a file system that only has an empty root directory.
+    fn default() -> Self {
+        let root = Rc::new(RefCell::new(INode::new_directory()));
+        FileSystem { root }
+    }
+}
+
+impl FileSystem {
+    /// Returns the root directory.
+    #[must_use]
+    pub fn root(&self) -> &Rc<RefCell<INode>> {
+        &self.root
+    }
+
+    /// Finds the file at the specified path.
+    ///
+    /// Symbolic links in the path are followed. If `follow_last` is false and
+    /// the last component of the path is a symbolic link, the link itself is
+    /// returned.
+    ///
+    /// This function fails with `ENOENT` if a file in the path does not exist,
+    /// `ENOTDIR` if a non-final component of the path is not a directory, and
+    /// `ELOOP` if more than [`SYMLOOP_MAX`] symbolic links are encountered.
+    pub fn lookup(&self, path: &Path, follow_last: bool) -> nix::Result<Rc<RefCell<INode>>> {
+        let mut dirs = vec![Rc::clone(&self.root)];
+        let mut steps = Vec::new();
+        push_steps(&mut steps, path);
+        let mut link_count = 0;
+
+        while let Some(step) = steps.pop() {
+            let current = Rc::clone(dirs.last().unwrap());
+            let current = current.borrow();
+            let files = match &current.body {
+                FileBody::Directory { files } => files,
+                _ => return Err(Errno::ENOTDIR.into()),
+            };
+            match step {
+                Step::Root => dirs.truncate(1),
+                Step::Parent => {
+                    if dirs.len() > 1 {
+                        dirs.pop();
+                    }
+                }
+                Step::Name(name) => {
+                    let file = files.get(&name).ok_or(Errno::ENOENT)?;
+                    let target = match &file.borrow().body {
+                        FileBody::Symlink { target } if follow_last || !steps.is_empty() => {
+                            Some(target.clone())
+                        }
+                        _ => None,
+                    };
+                    match target {
+                        Some(target) => {
+                            link_count += 1;
+                            if link_count > SYMLOOP_MAX {
+                                return Err(Errno::ELOOP.into());
+                            }
+                            push_steps(&mut steps, &target);
+                        }
+                        None => dirs.push(Rc::clone(file)),
+                    }
+                }
+            }
+        }
+
+        Ok(dirs.pop().unwrap())
+    }
+
+    /// Finds the file at the specified path, following symbolic links.
+    ///
+    /// See [`lookup`](Self::lookup) for the errors.
+    pub fn get<P: AsRef<Path>>(&self, path: P) -> nix::Result<Rc<RefCell<INode>>> {
+        self.lookup(path.as_ref(), true)
+    }
+
+    /// Creates a new file in an existing directory.
+    ///
+    /// The parent directory is resolved by [`lookup`](Self::lookup). This
+    /// function fails with `EEXIST` if there is already a file at the path,
+    /// including a dangling symbolic link. Returns a reference to the created
+    /// file.
+    pub fn create<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        content: INode,
+    ) -> nix::Result<Rc<RefCell<INode>>> {
+        let path = path.as_ref();
+        let name = path.file_name().ok_or(Errno::EEXIST)?;
+        let parent = path.parent().unwrap_or_else(|| Path::new("/"));
+        let parent = self.lookup(parent, true)?;
+        let mut parent = parent.borrow_mut();
+        let files = match &mut parent.body {
+            FileBody::Directory { files } => files,
+            _ => return Err(Errno::ENOTDIR.into()),
+        };
+        if files.contains_key(name) {
+            return Err(Errno::EEXIST.into());
+        }
+        let file = Rc::new(RefCell::new(content));
+        files.insert(name.to_owned(), Rc::clone(&file));
+        Ok(file)
+    }
+
+    /// Saves a file.
+    ///
+    /// Missing parent directories are created. Symbolic links in the path are
+    /// not followed, so this function fails with `ENOTDIR` if a parent is not
+    /// a directory. If there is an existing file at the specified path, it is
+    /// replaced with the new file and returned.
+    pub fn save<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        content: INode,
+    ) -> nix::Result<Option<Rc<RefCell<INode>>>> {
+        let path = path.as_ref();
+        let name = path.file_name().ok_or(Errno::EINVAL)?;
+        let mut dir = Rc::clone(&self.root);
+        for component in path.parent().into_iter().flat_map(Path::components) {
+            let name = match component {
+                Component::Normal(name) => name,
+                Component::ParentDir => return Err(Errno::EINVAL.into()),
+                _ => continue,
+            };
+            let next = match &mut dir.borrow_mut().body {
+                FileBody::Directory { files } => Rc::clone(
+                    files
+                        .entry(name.to_owned())
+                        .or_insert_with(|| Rc::new(RefCell::new(INode::new_directory()))),
+                ),
+                _ => return Err(Errno::ENOTDIR.into()),
+            };
+            dir = next;
+        }
+
+        let mut dir = dir.borrow_mut();
+        match &mut dir.body {
+            FileBody::Directory { files } => {
+                let file = Rc::new(RefCell::new(content));
+                Ok(files.insert(name.to_owned(), file))
+            }
+            _ => Err(Errno::ENOTDIR.into()),
+        }
+    }
+
+    /// Returns the names of the entries in the directory.
+    ///
+    /// The result does not include `.` and `..` and is sorted by name. This
+    /// function fails with `ENOTDIR` if the file is not a directory.
+    pub fn entries<P: AsRef<Path>>(&self, dir: P) -> nix::Result<Vec<OsString>> {
+        let dir = self.get(dir)?;
+        let dir = dir.borrow();
+        match &dir.body {
+            FileBody::Directory { files } => {
+                let mut names: Vec<OsString> = files.keys().cloned().collect();
+                names.sort();
+                Ok(names)
+            }
+            _ => Err(Errno::ENOTDIR.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn save_creates_parent_directories() {
+        let mut fs = FileSystem::default();
+        let old = fs.save("/a/b/c", INode::with_content("C")).unwrap();
+        assert_eq!(old, None);
+
+        let dir = fs.get("/a/b").unwrap();
+        assert_eq!(dir.borrow().body.type_bits(), S_IFDIR);
+        let file = fs.get("/a/b/c").unwrap();
+        assert_eq!(*file.borrow(), INode::with_content("C"));
+        assert_eq!(fs.entries("/").unwrap(), ["a"]);
+    }
+
+    #[test]
+    fn save_replaces_existing_file() {
+        let mut fs = FileSystem::default();
+        fs.save("/a", INode::with_content("1")).unwrap();
+        let old = fs.save("/a", INode::with_content("2")).unwrap();
+        assert_eq!(*old.unwrap().borrow(), INode::with_content("1"));
+        assert_eq!(*fs.get("/a").unwrap().borrow(), INode::with_content("2"));
+    }
+
+    #[test]
+    fn save_under_non_directory() {
+        let mut fs = FileSystem::default();
+        fs.save("/a", INode::new()).unwrap();
+        let result = fs.save("/a/b", INode::new());
+        assert_eq!(result, Err(Errno::ENOTDIR.into()));
+    }
+
+    #[test]
+    fn lookup_errors() {
+        let mut fs = FileSystem::default();
+        fs.save("/file", INode::new()).unwrap();
+        assert_eq!(fs.get("/no/such").unwrap_err(), Errno::ENOENT.into());
+        assert_eq!(fs.get("/file/x").unwrap_err(), Errno::ENOTDIR.into());
+        assert_eq!(fs.get("/file/..").unwrap_err(), Errno::ENOTDIR.into());
+    }
+
+    #[test]
+    fn lookup_with_dot_and_dot_dot() {
+        let mut fs = FileSystem::default();
+        fs.save("/a/b/c", INode::with_content("C")).unwrap();
+        let file = fs.get("/a/./b/../b/c").unwrap();
+        assert_eq!(*file.borrow(), INode::with_content("C"));
+        let file = fs.get("/../a/b/c").unwrap();
+        assert_eq!(*file.borrow(), INode::with_content("C"));
+        let file = fs.get("a/b/c").unwrap();
+        assert_eq!(*file.borrow(), INode::with_content("C"));
+        assert!(Rc::ptr_eq(&fs.get("/").unwrap(), fs.root()));
+    }
+
+    #[test]
+    fn lookup_follows_symlinks() {
+        let mut fs = FileSystem::default();
+        fs.save("/a/b/c", INode::with_content("C")).unwrap();
+        fs.save("/a/link", INode::new_symlink("b")).unwrap();
+        fs.save("/abs", INode::new_symlink("/a/link/c")).unwrap();
+
+        let file = fs.get("/a/link/c").unwrap();
+        assert_eq!(*file.borrow(), INode::with_content("C"));
+        let file = fs.get("/abs").unwrap();
+        assert_eq!(*file.borrow(), INode::with_content("C"));
+
+        let link = fs.lookup(Path::new("/abs"), false).unwrap();
+        assert_eq!(link.borrow().body.type_bits(), S_IFLNK);
+        let file = fs.lookup(Path::new("/a/link/c"), false).unwrap();
+        assert_eq!(*file.borrow(), INode::with_content("C"));
+    }
+
+    #[test]
+    fn lookup_with_dangling_symlink() {
+        let mut fs = FileSystem::default();
+        fs.save("/link", INode::new_symlink("/none")).unwrap();
+        assert_eq!(fs.get("/link").unwrap_err(), Errno::ENOENT.into());
+        assert!(fs.lookup(Path::new("/link"), false).is_ok());
+    }
+
+    #[test]
+    fn lookup_with_symlink_loop() {
+        let mut fs = FileSystem::default();
+        fs.save("/a", INode::new_symlink("b")).unwrap();
+        fs.save("/b", INode::new_symlink("a")).unwrap();
+        assert_eq!(fs.get("/a").unwrap_err(), Errno::ELOOP.into());
+        assert_eq!(fs.get("/a/x").unwrap_err(), Errno::ELOOP.into());
+    }
+
+    #[test]
+    fn create_file() {
+        let mut fs = FileSystem::default();
+        fs.save("/dir/file", INode::new()).unwrap();
+        fs.save("/link", INode::new_symlink("/none")).unwrap();
+
+        let file = fs.create("/dir/new", INode::with_content("x")).unwrap();
+        assert!(Rc::ptr_eq(&file, &fs.get("/dir/new").unwrap()));
+        let result = fs.create("/dir/file", INode::new());
+        assert_eq!(result.unwrap_err(), Errno::EEXIST.into());
+        let result = fs.create("/link", INode::new());
+        assert_eq!(result.unwrap_err(), Errno::EEXIST.into());
+        let result = fs.create("/none/new", INode::new());
+        assert_eq!(result.unwrap_err(), Errno::ENOENT.into());
+        let result = fs.create("/dir/file/new", INode::new());
+        assert_eq!(result.unwrap_err(), Errno::ENOTDIR.into());
+    }
+
+    #[test]
+    fn entries_of_directory() {
+        let mut fs = FileSystem::default();
+        fs.save("/a/b", INode::new()).unwrap();
+        fs.save("/a/c/d", INode::new()).unwrap();
+        assert_eq!(fs.entries("/a").unwrap(), ["b", "c"]);
+        assert_eq!(fs.entries("/a/b").unwrap_err(), Errno::ENOTDIR.into());
+        assert_eq!(fs.entries("/x").unwrap_err(), Errno::ENOENT.into());
+    }
+
+    #[test]
+    fn stat_of_inode() {
+        let mut inode = INode::with_content("hello");
+        inode.permissions = Mode(0o600);
+        inode.owner = Uid::from_raw(10);
+        inode.last_modified = UNIX_EPOCH + std::time::Duration::new(100, 5);
+        let stat = inode.stat();
+        assert_eq!(stat.st_mode, S_IFREG | 0o600);
+        assert_eq!(stat.st_size, 5);
+        assert_eq!(stat.st_uid, 10);
+        assert_eq!(stat.st_mtime, 100);
+        assert_eq!(stat.st_mtime_nsec, 5);
+    }
+}
@@ -0,0 +1,353 @@
+// This file is part of yash, an extended POSIX shell.
+// Copyright (C) 2021 WATANABE Yuki
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! I/O within a virtual system.
+//!
+//! A virtual [`Process`](super::Process) has a table of file descriptors,
+//! each of which refers to an [`OpenFileDescription`]. An open file
+//! description may be shared among file descriptors of one or more
+//! processes. An open file description refers to a file in the
+//! [file system](super::file_system) or an unnamed pipe.
+
+use super::file_system::FileBody;
+use super::file_system::INode;
+use nix::errno::Errno;
+use nix::fcntl::FdFlag;
+use nix::unistd::Whence;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::task::Waker;
+
+/// State of a file opened for reading and/or writing.
+///
+/// Opening a FIFO increments its reader and/or writer count, and dropping the
+/// open file description decrements them.
+#[derive(Debug)]
+pub struct OpenFileDescription {
+    /// File this open file description refers to.
+    file: Rc<RefCell<INode>>,
+    /// Position in the file at which the next read or write starts.
+    offset: usize,
+    /// Whether the file is open for reading.
+    is_readable: bool,
+    /// Whether the file is open for writing.
+    is_writable: bool,
+    /// Whether each write appends to the end of the file.
+    is_appending: bool,
+}
+
+impl OpenFileDescription {
+    /// Opens a file.
+    ///
+    /// The offset is initially zero. If the file is a FIFO, its reader and/or
+    /// writer count is incremented.
+    #[must_use]
+    pub fn new(
+        file: Rc<RefCell<INode>>,
+        is_readable: bool,
+        is_writable: bool,
+        is_appending: bool,
+    ) -> OpenFileDescription {
+        if let FileBody::Fifo(pipe) = &mut file.borrow_mut().body {
+            pipe.open(is_readable, is_writable);
+        }
+        OpenFileDescription {
+            file,
+            offset: 0,
+            is_readable,
+            is_writable,
+            is_appending,
+        }
+    }
+
+    /// Creates a new pipe and returns open file descriptions for the reading
+    /// and writing ends.
+    #[must_use]
+    pub fn new_pipe() -> (OpenFileDescription, OpenFileDescription) {
+        let file = Rc::new(RefCell::new(INode::new_fifo()));
+        let reader = OpenFileDescription::new(Rc::clone(&file), true, false, false);
+        let writer = OpenFileDescription::new(file, false, true, false);
+        (reader, writer)
+    }
+
+    /// Returns the file this open file description refers to.
+    #[must_use]
+    pub fn file(&self) -> &Rc<RefCell<INode>> {
+        &self.file
+    }
+
+    /// Returns true if you can read from this open file description.
+    #[must_use]
+    pub fn is_readable(&self) -> bool {
+        self.is_readable
+    }
+
+    /// Returns true if you can write to this open file description.
+    #[must_use]
+    pub fn is_writable(&self) -> bool {
+        self.is_writable
+    }
+
+    /// Returns true if writes to this open file description append to the
+    /// end of the file.
+    #[must_use]
+    pub fn is_appending(&self) -> bool {
+        self.is_appending
+    }
+
+    /// Sets whether writes to this open file description append to the end of
+    /// the file.
+    pub fn set_appending(&mut self, is_appending: bool) {
+        self.is_appending = is_appending;
+    }
+
+    /// Reads from the file.
+    ///
+    /// Returns the number of bytes read, which is zero at the end of file.
+    /// If the file is an empty pipe and there is a writer, this function fails
+    /// with `EAGAIN`. Reading from a pipe wakes the tasks waiting for the pipe.
+    pub fn read(&mut self, buffer: &mut [u8]) -> nix::Result<usize> {
+        if !self.is_readable {
+            return Err(Errno::EBADF.into());
+        }
+        let mut wakers = Vec::new();
+        let result = self.read_impl(buffer, &mut wakers);
+        wakers.into_iter().for_each(Waker::wake);
+        result
+    }
+
+    fn read_impl(&mut self, buffer: &mut [u8], wakers: &mut Vec<Waker>) -> nix::Result<usize> {
+        match &mut self.file.borrow_mut().body {
+            FileBody::Regular { content, .. } => {
+                let rest = content.get(self.offset..).unwrap_or(&[]);
+                let count = buffer.len().min(rest.len());
+                buffer[..count].copy_from_slice(&rest[..count]);
+                self.offset += count;
+                Ok(count)
+            }
+            FileBody::Fifo(pipe) => pipe.read(buffer, wakers),
+            FileBody::Directory { .. } => Err(Errno::EISDIR.into()),
+            FileBody::Symlink { .. } => Err(Errno::EBADF.into()),
+        }
+    }
+
+    /// Writes to the file.
+    ///
+    /// Returns the number of bytes written. If the file is a pipe that has no
+    /// reader, this function fails with `EPIPE`. If the pipe is full, this
+    /// function fails with `EAGAIN` or writes only part of the buffer (see
+    /// [`Pipe::write`](super::pipe::Pipe::write)). Writing to a pipe wakes the tasks waiting for the
+    /// pipe. Writing to a regular file past its end fills the gap with null
+    /// bytes.
+    pub fn write(&mut self, buffer: &[u8]) -> nix::Result<usize> {
+        if !self.is_writable {
+            return Err(Errno::EBADF.into());
+        }
+        let mut wakers = Vec::new();
+        let result = self.write_impl(buffer, &mut wakers);
+        wakers.into_iter().for_each(Waker::wake);
+        result
+    }
+
+    fn write_impl(&mut self, buffer: &[u8], wakers: &mut Vec<Waker>) -> nix::Result<usize> {
+        match &mut self.file.borrow_mut().body {
+            FileBody::Regular { content, .. } => {
+                if self.is_appending {
+                    self.offset = content.len();
+                }
+                let end = self.offset + buffer.len();
+                if content.len() < end {
+                    content.resize(end, 0);
+                }
+                content[self.offset..end].copy_from_slice(buffer);
+                self.offset = end;
+                Ok(buffer.len())
+            }
+            FileBody::Fifo(pipe) => pipe.write(buffer, wakers),
+            FileBody::Directory { .. } => Err(Errno::EISDIR.into()),
+            FileBody::Symlink { .. } => Err(Errno::EBADF.into()),
+        }
+    }
+
+    /// Moves the offset of the open file description.
+    ///
+    /// Returns the new offset. This function fails with `ESPIPE` if the file
+    /// is a pipe and `EINVAL` if the new offset would be negative.
+    pub fn seek(&mut self, offset: i64, whence: Whence) -> nix::Result<i64> {
+        let base = match &self.file.borrow().body {
+            FileBody::Regular { content, .. } => match whence {
+                Whence::SeekSet => 0,
+                Whence::SeekCur => self.offset,
+                Whence::SeekEnd => content.len(),
+                _ => return Err(Errno::EINVAL.into()),
+            },
+            FileBody::Directory { .. } => 0,
+            FileBody::Fifo(_) => return Err(Errno::ESPIPE.into()),
+            FileBody::Symlink { .. } => return Err(Errno::EBADF.into()),
+        };
+        let new_offset = (base as i64)
+            .checked_add(offset)
+            .filter(|offset| *offset >= 0)
+            .ok_or(Errno::EINVAL)?;
+        self.offset = new_offset as usize;
+        Ok(new_offset)
+    }
+
+    /// Registers a task that is waiting for the file to become ready.
+    ///
+    /// If the file is a pipe, the waker is woken when the state of the pipe
+    /// changes. Otherwise, the waker is woken immediately since a regular file
+    /// is always ready.
+    pub fn add_awaiter(&self, waker: Waker) {
+        match &mut self.file.borrow_mut().body {
+            FileBody::Fifo(pipe) => pipe.add_awaiter(waker),
+            _ => waker.wake(),
+        }
+    }
+}
+
+impl Drop for OpenFileDescription {
+    fn drop(&mut self) {
+        let wakers = match &mut self.file.borrow_mut().body {
+            FileBody::Fifo(pipe) => pipe.close(self.is_readable, self.is_writable),
+            _ => return,
+        };
+        wakers.into_iter().for_each(Waker::wake);
+    }
+}
+
+/// State of a file descriptor.
+#[derive(Clone, Debug)]
+pub struct FdBody {
+    /// Underlying open file description.
+    pub open_file_description: Rc<RefCell<OpenFileDescription>>,
+    /// File descriptor flags.
+    pub flags: FdFlag,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pipe_read_write() {
+        let (mut reader, mut writer) = OpenFileDescription::new_pipe();
+        assert_eq!(writer.write(&[1, 2, 3]), Ok(3));
+        let mut buffer = [0; 2];
+        assert_eq!(reader.read(&mut buffer), Ok(2));
+        assert_eq!(buffer, [1, 2]);
+        assert_eq!(reader.read(&mut buffer), Ok(1));
+        assert_eq!(buffer[0], 3);
+        assert_eq!(reader.read(&mut buffer), Err(Errno::EAGAIN.into()));
+    }
+
+    #[test]
+    fn pipe_end_of_file() {
+        let (mut reader, mut writer) = OpenFileDescription::new_pipe();
+        assert_eq!(writer.write(&[1]), Ok(1));
+        drop(writer);
+        let mut buffer = [0; 2];
+        assert_eq!(reader.read(&mut buffer), Ok(1));
+        assert_eq!(reader.read(&mut buffer), Ok(0));
+    }
+
+    #[test]
+    fn pipe_without_reader() {
+        let (reader, mut writer) = OpenFileDescription::new_pipe();
+        drop(reader);
+        assert_eq!(writer.write(&[1]), Err(Errno::EPIPE.into()));
+    }
+
+    #[test]
+    fn wrong_direction() {
+        let (mut reader, mut writer) = OpenFileDescription::new_pipe();
+        assert_eq!(reader.write(&[1]), Err(Errno::EBADF.into()));
+        assert_eq!(writer.read(&mut [0]), Err(Errno::EBADF.into()));
+    }
+
+    fn regular_file(content: &str) -> Rc<RefCell<INode>> {
+        Rc::new(RefCell::new(INode::with_content(content)))
+    }
+
+    fn content(file: &Rc<RefCell<INode>>) -> Vec<u8> {
+        match &file.borrow().body {
+            FileBody::Regular { content, .. } => content.clone(),
+            body => panic!("not a regular file: {:?}", body),
+        }
+    }
+
+    #[test]
+    fn regular_file_read_advances_offset() {
+        let file = regular_file("abcde");
+        let mut ofd = OpenFileDescription::new(file, true, false, false);
+        let mut buffer = [0; 3];
+        assert_eq!(ofd.read(&mut buffer), Ok(3));
+        assert_eq!(&buffer, b"abc");
+        assert_eq!(ofd.read(&mut buffer), Ok(2));
+        assert_eq!(&buffer[..2], b"de");
+        assert_eq!(ofd.read(&mut buffer), Ok(0));
+    }
+
+    #[test]
+    fn regular_file_write_overwrites_and_extends() {
+        let file = regular_file("abcde");
+        let mut ofd = OpenFileDescription::new(Rc::clone(&file), false, true, false);
+        assert_eq!(ofd.seek(3, Whence::SeekSet), Ok(3));
+        assert_eq!(ofd.write(b"XYZ"), Ok(3));
+        assert_eq!(content(&file), b"abcXYZ");
+        assert_eq!(ofd.seek(2, Whence::SeekEnd), Ok(8));
+        assert_eq!(ofd.write(b"!"), Ok(1));
+        assert_eq!(content(&file), b"abcXYZ\0\0!");
+    }
+
+    #[test]
+    fn regular_file_append() {
+        let file = regular_file("abc");
+        let mut ofd = OpenFileDescription::new(Rc::clone(&file), true, true, true);
+        assert_eq!(ofd.write(b"d"), Ok(1));
+        ofd.seek(0, Whence::SeekSet).unwrap();
+        assert_eq!(ofd.write(b"e"), Ok(1));
+        assert_eq!(content(&file), b"abcde");
+    }
+
+    #[test]
+    fn seek_errors() {
+        let file = regular_file("abc");
+        let mut ofd = OpenFileDescription::new(file, true, false, false);
+        assert_eq!(ofd.seek(1, Whence::SeekCur), Ok(1));
+        assert_eq!(ofd.seek(-2, Whence::SeekCur), Err(Errno::EINVAL.into()));
+        assert_eq!(ofd.seek(-1, Whence::SeekEnd), Ok(2));
+
+        let (mut reader, _writer) = OpenFileDescription::new_pipe();
+        assert_eq!(reader.seek(0, Whence::SeekSet), Err(Errno::ESPIPE.into()));
+    }
+
+    #[test]
+    fn opening_fifo_counts_readers_and_writers() {
+        let fifo = Rc::new(RefCell::new(INode::new_fifo()));
+        let reader = OpenFileDescription::new(Rc::clone(&fifo), true, false, false);
+        let both = OpenFileDescription::new(Rc::clone(&fifo), true, true, false);
+        let counts = |fifo: &Rc<RefCell<INode>>| match &fifo.borrow().body {
+            FileBody::Fifo(pipe) => (pipe.readers(), pipe.writers()),
+            body => panic!("not a FIFO: {:?}", body),
+        };
+        assert_eq!(counts(&fifo), (2, 1));
+        drop(both);
+        assert_eq!(counts(&fifo), (1, 0));
+        drop(reader);
+        assert_eq!(counts(&fifo), (0, 0));
+    }
+}
@@ -0,0 +1,262 @@
+// This file is part of yash, an extended POSIX shell.
+// Copyright (C) 2021 WATANABE Yuki
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Pipes within a virtual system.
+//!
+//! A [`Pipe`] is the content of a FIFO, either named or unnamed. It has a
+//! bounded buffer of [`PIPE_SIZE`] bytes. A reader trying to read from an
+//! empty pipe or a writer trying to write to a full pipe cannot proceed until
+//! the other end makes progress, so the pipe keeps wakers of such tasks and
+//! wakes them when the state of the pipe changes.
+
+use nix::errno::Errno;
+use std::collections::VecDeque;
+use std::task::Waker;
+
+/// Maximum number of bytes a pipe can hold.
+pub const PIPE_SIZE: usize = 65536;
+
+/// Maximum number of bytes that are written to a pipe atomically.
+///
+/// A write of at most this size either writes the whole buffer or nothing.
+pub const PIPE_BUF: usize = nix::libc::PIPE_BUF;
+
+/// Content and state of a FIFO.
+///
+/// The `PartialEq` implementation compares the content and the numbers of
+/// readers and writers, ignoring the wakers.
+#[derive(Clone, Debug, Default)]
+pub struct Pipe {
+    /// Bytes written to the pipe that have not yet been read.
+    content: VecDeque<u8>,
+    /// Number of open file descriptions reading from the pipe.
+    readers: usize,
+    /// Number of open file descriptions writing to the pipe.
+    writers: usize,
+    /// Wakers of tasks waiting for the state of the pipe to change.
+    awaiters: Vec<Waker>,
+}
+
+impl PartialEq for Pipe {
+    fn eq(&self, other: &Pipe) -> bool {
+        self.content == other.content
+            && self.readers == other.readers
+            && self.writers == other.writers
+    }
+}
+
+impl Eq for Pipe {}
+
+impl Pipe {
+    /// Creates an empty pipe with no readers or writers.
+    #[must_use]
+    pub fn new() -> Pipe {
+        Pipe::default()
+    }
+
+    /// Returns the bytes that have been written but not yet read.
+    #[must_use]
+    pub fn content(&self) -> &VecDeque<u8> {
+        &self.content
+    }
+
+    /// Returns the number of open file descriptions reading from the pipe.
+    #[must_use]
+    pub fn readers(&self) -> usize {
+        self.readers
+    }
+
+    /// Returns the number of open file descriptions writing to the pipe.
+    #[must_use]
+    pub fn writers(&self) -> usize {
+        self.writers
+    }
+
+    /// Increments the numbers of readers and/or writers.
+    pub fn open(&mut self, is_readable: bool, is_writable: bool) {
+        if is_readable {
+            self.readers += 1;
+        }
+        if is_writable {
+            self.writers += 1;
+        }
+    }
+
+    /// Decrements the numbers of readers and/or writers.
+    ///
+    /// This function returns wakers that must be woken after the pipe is
+    /// released, since the other end may now see the end of file or `EPIPE`.
+    #[must_use]
+    pub fn close(&mut self, is_readable: bool, is_writable: bool) -> Vec<Waker> {
+        if is_readable {
+            self.readers -= 1;
+        }
+        if is_writable {
+            self.writers -= 1;
+        }
+        self.take_awaiters()
+    }
+
+    /// Reads from the pipe.
+    ///
+    /// Returns the number of bytes read, which is zero if the pipe is empty
+    /// and has no writers. If the pipe is empty but has a writer, this
+    /// function fails with `EAGAIN`.
+    ///
+    /// If any bytes are read, the awaiters are moved to `wakers`.
+    pub fn read(&mut self, buffer: &mut [u8], wakers: &mut Vec<Waker>) -> nix::Result<usize> {
+        if self.content.is_empty() {
+            return if self.writers == 0 || buffer.is_empty() {
+                Ok(0)
+            } else {
+                Err(Errno::EAGAIN.into())
+            };
+        }
+        let count = buffer.len().min(self.content.len());
+        for (to, from) in buffer.iter_mut().zip(self.content.drain(..count)) {
+            *to = from;
+        }
+        wakers.append(&mut self.awaiters);
+        Ok(count)
+    }
+
+    /// Writes to the pipe.
+    ///
+    /// Returns the number of bytes written, which may be less than the length
+    /// of the buffer if the pipe does not have enough room. A buffer not
+    /// longer than [`PIPE_BUF`] is written atomically. This function fails
+    /// with `EPIPE` if the pipe has no readers and `EAGAIN` if the pipe is too
+    /// full to write anything.
+    ///
+    /// If any bytes are written, the awaiters are moved to `wakers`.
+    pub fn write(&mut self, buffer: &[u8], wakers: &mut Vec<Waker>) -> nix::Result<usize> {
+        if buffer.is_empty() {
+            return Ok(0);
+        }
+        if self.readers == 0 {
+            return Err(Errno::EPIPE.into());
+        }
+        let room = PIPE_SIZE - self.content.len();
+        if room == 0 || (buffer.len() <= PIPE_BUF && room < buffer.len()) {
+            return Err(Errno::EAGAIN.into());
+        }
+        let count = buffer.len().min(room);
+        self.content.extend(&buffer[..count]);
+        wakers.append(&mut self.awaiters);
+        Ok(count)
+    }
+
+    /// Registers a task waiting for the state of the pipe to change.
+    ///
+    /// The waker is woken when some bytes are read or written, or when a
+    /// reader or writer is closed.
+    pub fn add_awaiter(&mut self, waker: Waker) {
+        self.awaiters.push(waker);
+    }
+
+    /// Removes and returns all the wakers registered.
+    #[must_use]
+    pub fn take_awaiters(&mut self) -> Vec<Waker> {
+        std::mem::take(&mut self.awaiters)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::task::noop_waker;
+
+    #[test]
+    fn read_and_write() {
+        let mut pipe = Pipe::new();
+        pipe.open(true, true);
+        let mut wakers = Vec::new();
+        assert_eq!(pipe.write(b"abc", &mut wakers), Ok(3));
+        let mut buffer = [0; 2];
+        assert_eq!(pipe.read(&mut buffer, &mut wakers), Ok(2));
+        assert_eq!(&buffer, b"ab");
+        assert_eq!(pipe.read(&mut buffer, &mut wakers), Ok(1));
+        assert_eq!(buffer[0], b'c');
+        assert_eq!(
+            pipe.read(&mut buffer, &mut wakers),
+            Err(Errno::EAGAIN.into())
+        );
+    }
+
+    #[test]
+    fn read_without_writer() {
+        let mut pipe = Pipe::new();
+        pipe.open(true, true);
+        let mut wakers = Vec::new();
+        pipe.write(b"x", &mut wakers).unwrap();
+        let _ = pipe.close(false, true);
+        let mut buffer = [0; 2];
+        assert_eq!(pipe.read(&mut buffer, &mut wakers), Ok(1));
+        assert_eq!(pipe.read(&mut buffer, &mut wakers), Ok(0));
+    }
+
+    #[test]
+    fn write_without_reader() {
+        let mut pipe = Pipe::new();
+        pipe.open(false, true);
+        let mut wakers = Vec::new();
+        assert_eq!(pipe.write(b"x", &mut wakers), Err(Errno::EPIPE.into()));
+        assert_eq!(pipe.write(b"", &mut wakers), Ok(0));
+    }
+
+    #[test]
+    fn write_to_full_pipe() {
+        let mut pipe = Pipe::new();
+        pipe.open(true, true);
+        let mut wakers = Vec::new();
+        let data = vec![0; PIPE_SIZE + 10];
+        assert_eq!(pipe.write(&data, &mut wakers), Ok(PIPE_SIZE));
+        assert_eq!(pipe.write(&data, &mut wakers), Err(Errno::EAGAIN.into()));
+        assert_eq!(pipe.content().len(), PIPE_SIZE);
+    }
+
+    #[test]
+    fn small_write_is_atomic() {
+        let mut pipe = Pipe::new();
+        pipe.open(true, true);
+        let mut wakers = Vec::new();
+        let data = vec![0; PIPE_SIZE - 1];
+        assert_eq!(pipe.write(&data, &mut wakers), Ok(PIPE_SIZE - 1));
+        assert_eq!(pipe.write(&[1, 2], &mut wakers), Err(Errno::EAGAIN.into()));
+
+        let mut buffer = vec![0; PIPE_BUF];
+        assert_eq!(pipe.read(&mut buffer, &mut wakers), Ok(PIPE_BUF));
+        let data = vec![0; PIPE_BUF + 3];
+        assert_eq!(pipe.write(&data, &mut wakers), Ok(PIPE_BUF + 1));
+    }
+
+    #[test]
+    fn awaiters_are_taken_on_progress() {
+        let mut pipe = Pipe::new();
+        pipe.open(true, true);
+        let mut wakers = Vec::new();
+        pipe.add_awaiter(noop_waker());
+        assert_eq!(pipe.read(&mut [0], &mut wakers), Err(Errno::EAGAIN.into()));
+        assert!(wakers.is_empty());
+        pipe.write(b"x", &mut wakers).unwrap();
+        assert_eq!(wakers.len(), 1);
+
+        pipe.add_awaiter(noop_waker());
+        pipe.add_awaiter(noop_waker());
+        assert_eq!(pipe.close(true, false).len(), 2);
+        assert!(pipe.take_awaiters().is_empty());
+    }
+}
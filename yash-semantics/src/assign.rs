@@ -0,0 +1,284 @@
+// This file is part of yash, an extended POSIX shell.
+// Copyright (C) 2021 WATANABE Yuki
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Assignment.
+//!
+//! This module provides functions to perform the assignments of a simple
+//! command. The value of an assignment is expanded by [`expand_value`] and
+//! assigned by [`perform_assignment`] in the specified [`Scope`]. Other
+//! constructs that assign to variables use [`assign_variable`]. Temporary
+//! assignments are made in a volatile context of the variable set (see
+//! [`VariableSet::push_context`](yash_env::variable::VariableSet::push_context))
+//! so that they are removed when the context is popped.
+
+use crate::expansion::expand_word;
+use crate::expansion::expand_words;
+use crate::xtrace::XTrace;
+use yash_env::expansion::Error;
+use yash_env::expansion::Result;
+use yash_env::option::ShellOption;
+use yash_env::variable::Scope;
+use yash_env::variable::Value;
+use yash_env::variable::Variable;
+use yash_env::Env;
+use yash_syntax::source::Location;
+use yash_syntax::syntax;
+
+/// Expands the value of an assignment.
+///
+/// A scalar value is expanded without field splitting or pathname expansion.
+/// The words of an array value are fully expanded.
+pub async fn expand_value(env: &mut Env, value: &syntax::Value) -> Result<Value> {
+    match value {
+        syntax::Scalar(word) => Ok(Value::Scalar(expand_word(env, word).await?.value)),
+        syntax::Array(words) => {
+            let fields = expand_words(env, words).await?;
+            Ok(Value::Array(fields.into_iter().map(|f| f.value).collect()))
+        }
+    }
+}
+
+/// Assigns a value to a variable.
+///
+/// The variable is assigned in the given scope with the `location` recorded as
+/// the location of the assignment. The variable is exported if `export` is
+/// true, the [`AllExport`](ShellOption::AllExport) option is on, or the
+/// existing variable is exported. Returns the variable replaced by the
+/// assignment, if any.
+///
+/// It is an error to assign to a read-only variable, even if the assignment
+/// would create a new variable in an inner context.
+pub fn assign_variable(
+    env: &mut Env,
+    scope: Scope,
+    name: String,
+    value: Value,
+    location: Location,
+    export: bool,
+) -> Result<Option<Variable>> {
+    let old = env.variables.get(name.as_str());
+    if let Some(read_only_location) = old.and_then(|v| v.read_only_location.clone()) {
+        return Err(Error::AssignReadOnly {
+            name,
+            read_only_location,
+            location,
+        });
+    }
+    let is_exported =
+        export || env.options.is_on(ShellOption::AllExport) || old.is_some_and(|v| v.is_exported);
+
+    Ok(env.variables.assign(
+        scope,
+        name,
+        Variable {
+            value,
+            last_assigned_location: Some(location),
+            is_exported,
+            read_only_location: None,
+        },
+    ))
+}
+
+/// Performs an assignment.
+///
+/// The value is expanded and assigned to the variable by
+/// [`assign_variable`].
+///
+/// If `xtrace` is given, the expanded assignment is added to it.
+pub async fn perform_assignment(
+    env: &mut Env,
+    assign: &syntax::Assign,
+    scope: Scope,
+    export: bool,
+    xtrace: Option<&mut XTrace>,
+) -> Result<Option<Variable>> {
+    let value = expand_value(env, &assign.value).await?;
+    if let Some(xtrace) = xtrace {
+        xtrace.push_assign(&assign.name, &value);
+    }
+
+    let name = assign.name.clone();
+    let location = assign.location.clone();
+    assign_variable(env, scope, name, value, location, export)
+}
+
+/// Performs assignments.
+///
+/// The assignments are performed in order by [`perform_assignment`]. If an
+/// assignment fails, the remaining ones are not performed, but the variables
+/// already assigned remain.
+pub async fn perform_assignments(
+    env: &mut Env,
+    assigns: &[syntax::Assign],
+    scope: Scope,
+    export: bool,
+    mut xtrace: Option<&mut XTrace>,
+) -> Result<()> {
+    for assign in assigns {
+        perform_assignment(env, assign, scope, export, xtrace.as_deref_mut()).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expansion::tests::assign_scalar;
+    use futures::executor::block_on;
+    use yash_env::variable::ContextType;
+    use yash_syntax::source::Location;
+
+    fn assign_in(
+        env: &mut Env,
+        assign: &syntax::Assign,
+        scope: Scope,
+        export: bool,
+    ) -> Result<Option<Variable>> {
+        block_on(perform_assignment(env, assign, scope, export, None))
+    }
+
+    fn assign_global(env: &mut Env, assign: &syntax::Assign) -> Result<Option<Variable>> {
+        assign_in(env, assign, Scope::Global, false)
+    }
+
+    #[test]
+    fn perform_scalar_assignment() {
+        let mut env = Env::new_virtual();
+        assign_scalar(&mut env, "x", "X");
+        let assign: syntax::Assign = "foo=a$x".parse().unwrap();
+        let old = assign_global(&mut env, &assign).unwrap();
+        assert_eq!(old, None);
+        let foo = env.variables.get("foo").unwrap();
+        assert_eq!(foo.value, Value::Scalar("aX".to_string()));
+        assert_eq!(foo.last_assigned_location, Some(assign.location));
+        assert!(!foo.is_exported);
+    }
+
+    #[test]
+    fn perform_array_assignment() {
+        let mut env = Env::new_virtual();
+        assign_scalar(&mut env, "x", "b c");
+        let assign: syntax::Assign = "foo=(a $x)".parse().unwrap();
+        assign_global(&mut env, &assign).unwrap();
+        let foo = env.variables.get("foo").unwrap();
+        let values = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        assert_eq!(foo.value, Value::Array(values));
+    }
+
+    #[test]
+    fn assignment_keeps_or_sets_export_attribute() {
+        let mut env = Env::new_virtual();
+        env.variables.assign(
+            Scope::Global,
+            "foo".to_string(),
+            Variable {
+                value: Value::Scalar("".to_string()),
+                last_assigned_location: None,
+                is_exported: true,
+                read_only_location: None,
+            },
+        );
+        let assign: syntax::Assign = "foo=1".parse().unwrap();
+        assign_global(&mut env, &assign).unwrap();
+        assert!(env.variables.get("foo").unwrap().is_exported);
+
+        let assign: syntax::Assign = "bar=1".parse().unwrap();
+        assign_in(&mut env, &assign, Scope::Global, true).unwrap();
+        assert!(env.variables.get("bar").unwrap().is_exported);
+    }
+
+    #[test]
+    fn allexport_option_exports_assigned_variable() {
+        let mut env = Env::new_virtual();
+        env.options.set(ShellOption::AllExport, true);
+        let assign: syntax::Assign = "foo=1".parse().unwrap();
+        assign_global(&mut env, &assign).unwrap();
+        assert!(env.variables.get("foo").unwrap().is_exported);
+    }
+
+    #[test]
+    fn assignment_to_read_only_variable() {
+        let mut env = Env::new_virtual();
+        let read_only_location = Location::dummy("readonly foo");
+        let variable = Variable {
+            value: Value::Scalar("old".to_string()),
+            last_assigned_location: None,
+            is_exported: false,
+            read_only_location: Some(read_only_location.clone()),
+        };
+        env.variables
+            .assign(Scope::Global, "foo".to_string(), variable.clone());
+        let assign: syntax::Assign = "foo=new".parse().unwrap();
+        let result = assign_global(&mut env, &assign);
+        assert_eq!(
+            result,
+            Err(Error::AssignReadOnly {
+                name: "foo".to_string(),
+                read_only_location,
+                location: assign.location,
+            })
+        );
+        assert_eq!(env.variables.get("foo"), Some(&variable));
+    }
+
+    #[test]
+    fn volatile_assignments_are_removed_with_context() {
+        let mut env = Env::new_virtual();
+        assign_scalar(&mut env, "a", "A");
+        let original = env.variables.clone();
+        let assigns: Vec<syntax::Assign> = vec![
+            "a=1".parse().unwrap(),
+            "b=2".parse().unwrap(),
+            "a=3".parse().unwrap(),
+        ];
+        env.variables.push_context(ContextType::Volatile);
+        block_on(perform_assignments(
+            &mut env,
+            &assigns,
+            Scope::Volatile,
+            true,
+            None,
+        ))
+        .unwrap();
+        let a = env.variables.get("a").unwrap();
+        assert_eq!(a.value, Value::Scalar("3".to_string()));
+        assert!(a.is_exported);
+        env.variables.pop_context();
+        assert_eq!(env.variables, original);
+    }
+
+    #[test]
+    fn volatile_assignment_to_read_only_variable() {
+        let mut env = Env::new_virtual();
+        let read_only_location = Location::dummy("readonly foo");
+        let variable = Variable {
+            value: Value::Scalar("old".to_string()),
+            last_assigned_location: None,
+            is_exported: false,
+            read_only_location: Some(read_only_location),
+        };
+        env.variables
+            .assign(Scope::Global, "foo".to_string(), variable);
+        env.variables.push_context(ContextType::Volatile);
+        let assign: syntax::Assign = "foo=new".parse().unwrap();
+        let result = assign_in(&mut env, &assign, Scope::Volatile, false);
+        assert!(
+            matches!(result, Err(Error::AssignReadOnly { .. })),
+            "{:?}",
+            result
+        );
+    }
+}
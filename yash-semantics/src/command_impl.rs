@@ -17,10 +17,21 @@
 //! Implementations for Command.
 
 use super::Command;
+use crate::execute_suppressing_errexit;
 use async_trait::async_trait;
+use nix::fcntl::OFlag;
+use nix::sys::stat::Mode;
+use std::ffi::CStr;
+use yash_env::exec::Divert;
+use yash_env::exec::ExitStatus;
 use yash_env::exec::Result;
+use yash_env::io::move_fd;
+use yash_env::io::STDIN;
+use yash_env::job::ChildProcess;
+use yash_env::option::ShellOption::Monitor;
 use yash_env::Env;
 use yash_syntax::syntax;
+use yash_syntax::syntax::AndOr;
 
 #[async_trait(?Send)]
 impl Command for syntax::Command {
@@ -28,29 +39,108 @@ impl Command for syntax::Command {
         use syntax::Command::*;
         match self {
             Simple(command) => command.execute(env).await,
-            #[allow(clippy::unit_arg)]
-            Compound(_) | Function(_) => Ok(println!("{}", self)),
-            // TODO execute compound command / function definition
+            Compound(command) => command.execute(env).await,
+            Function(definition) => definition.execute(env).await,
         }
     }
 }
 
+/// Executes the and-or list.
+///
+/// The first pipeline is always executed. Each of the rest is executed if the
+/// exit status of the previously executed pipeline is zero (for `&&`) or
+/// non-zero (for `||`). If a pipeline returns a `Divert`, the rest is not
+/// executed.
+///
+/// The errexit option is suppressed while executing the pipelines other than
+/// the last.
 #[async_trait(?Send)]
 impl Command for syntax::AndOrList {
     async fn execute(&self, env: &mut Env) -> Result {
-        self.first.execute(env).await
-        // TODO rest
+        if self.rest.is_empty() {
+            return self.first.execute(env).await;
+        }
+
+        execute_suppressing_errexit(&self.first, env).await?;
+        let last_index = self.rest.len() - 1;
+        for (index, (and_or, pipeline)) in self.rest.iter().enumerate() {
+            let success = env.exit_status.is_successful();
+            let should_execute = match and_or {
+                AndOr::AndThen => success,
+                AndOr::OrElse => !success,
+            };
+            if !should_execute {
+                continue;
+            }
+            if index < last_index {
+                execute_suppressing_errexit(pipeline, env).await?;
+            } else {
+                pipeline.execute(env).await?;
+            }
+        }
+        Ok(())
     }
 }
 
+/// Executes the item.
+///
+/// An asynchronous item is executed in a subshell that the shell does not wait
+/// for. The subshell's process ID is saved in [`JobSet::last_async_pid`] and
+/// the subshell is registered in the job set. Unless job control is enabled,
+/// the standard input of the subshell is redirected from `/dev/null`. The exit
+/// status of an asynchronous item is zero.
+///
+/// [`JobSet::last_async_pid`]: yash_env::job::JobSet::last_async_pid
 #[async_trait(?Send)]
 impl Command for syntax::Item {
     async fn execute(&self, env: &mut Env) -> Result {
-        self.and_or.execute(env).await
-        // TODO async
+        if !self.is_async {
+            return self.and_or.execute(env).await;
+        }
+
+        let and_or = self.and_or.clone();
+        let result = env
+            .start_subshell(move |env| {
+                Box::pin(async move {
+                    if !env.options.is_on(Monitor) {
+                        if let Err(e) = nullify_stdin(env) {
+                            let message = format!("cannot redirect the standard input: {}", e);
+                            env.print_error(&message);
+                            env.exit_status = ExitStatus::ERROR;
+                            return;
+                        }
+                    }
+                    if let Err(Divert::Exit(exit_status)) = and_or.execute(env).await {
+                        env.exit_status = exit_status;
+                    }
+                })
+            })
+            .await;
+
+        match result {
+            Ok(pid) => {
+                env.jobs
+                    .child_processes
+                    .insert(pid, ChildProcess::default());
+                env.jobs.last_async_pid = Some(pid);
+                env.exit_status = ExitStatus::SUCCESS;
+            }
+            Err(e) => {
+                env.print_error(&format!("cannot start asynchronous command: {}", e));
+                env.exit_status = ExitStatus::NOEXEC;
+            }
+        }
+        Ok(())
     }
 }
 
+/// Redirects the standard input from `/dev/null`.
+fn nullify_stdin(env: &mut Env) -> nix::Result<()> {
+    let path = CStr::from_bytes_with_nul(b"/dev/null\0").unwrap();
+    let fd = env.system.open(path, OFlag::O_RDONLY, Mode::empty())?;
+    move_fd(&mut *env.system, fd, STDIN)
+}
+
 #[async_trait(?Send)]
 impl Command for syntax::List {
     async fn execute(&self, env: &mut Env) -> Result {
@@ -60,3 +150,228 @@ impl Command for syntax::List {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::return_builtin;
+    use futures::executor::block_on;
+    use futures::executor::LocalPool;
+    use std::rc::Rc;
+    use yash_env::option::ShellOption::ErrExit;
+    use yash_env::variable::Value;
+    use yash_env::virtual_system::INode;
+    use yash_env::virtual_system::ProcessState;
+    use yash_env::virtual_system::VirtualSystem;
+
+    fn execute(env: &mut Env, command: &str) -> Result {
+        let list: syntax::List = command.parse().unwrap();
+        block_on(list.execute(env))
+    }
+
+    #[test]
+    fn and_then_executes_second_on_success() {
+        let mut env = Env::new_virtual();
+        env.builtins.insert("return", return_builtin());
+        let result = execute(&mut env, "return -n 0 && return -n 5");
+        assert_eq!(result, Ok(()));
+        assert_eq!(env.exit_status, ExitStatus(5));
+    }
+
+    #[test]
+    fn and_then_skips_second_on_failure() {
+        let mut env = Env::new_virtual();
+        env.builtins.insert("return", return_builtin());
+        let result = execute(&mut env, "return -n 3 && return -n 5");
+        assert_eq!(result, Ok(()));
+        assert_eq!(env.exit_status, ExitStatus(3));
+    }
+
+    #[test]
+    fn or_else_executes_second_on_failure() {
+        let mut env = Env::new_virtual();
+        env.builtins.insert("return", return_builtin());
+        let result = execute(&mut env, "return -n 3 || return -n 5");
+        assert_eq!(result, Ok(()));
+        assert_eq!(env.exit_status, ExitStatus(5));
+    }
+
+    fn env_with_errexit() -> Env {
+        let mut env = Env::new_virtual();
+        env.builtins.insert("return", return_builtin());
+        env.options.set(ErrExit, true);
+        env
+    }
+
+    fn scalar(env: &Env, name: &str) -> Option<String> {
+        match &env.variables.get(name)?.value {
+            Value::Scalar(value) => Some(value.clone()),
+            Value::Array(_) => None,
+        }
+    }
+
+    #[test]
+    fn errexit_ignores_failure_of_non_last_pipeline() {
+        let mut env = env_with_errexit();
+        let result = execute(&mut env, "return -n 3 && a=1; b=2");
+        assert_eq!(result, Ok(()));
+        assert_eq!(scalar(&env, "a"), None);
+        assert_eq!(scalar(&env, "b"), Some("2".to_string()));
+
+        let result = execute(&mut env, "return -n 1 || return -n 0 && c=3");
+        assert_eq!(result, Ok(()));
+        assert_eq!(scalar(&env, "c"), Some("3".to_string()));
+        assert!(!env.errexit_is_suppressed);
+    }
+
+    #[test]
+    fn errexit_applies_to_last_pipeline() {
+        let mut env = env_with_errexit();
+        let result = execute(&mut env, "return -n 0 && return -n 4; a=1");
+        assert_eq!(result, Err(Divert::Exit(ExitStatus(4))));
+        assert_eq!(env.exit_status, ExitStatus(4));
+        assert_eq!(scalar(&env, "a"), None);
+
+        let result = execute(&mut env, "return -n 1 || return -n 5");
+        assert_eq!(result, Err(Divert::Exit(ExitStatus(5))));
+    }
+
+    #[test]
+    fn errexit_ignores_and_or_list_ending_with_skipped_pipeline() {
+        let mut env = env_with_errexit();
+        let result = execute(&mut env, "return -n 0 && return -n 1 && a=1; b=2");
+        assert_eq!(result, Ok(()));
+        assert_eq!(env.exit_status, ExitStatus::SUCCESS);
+        assert_eq!(scalar(&env, "a"), None);
+        assert_eq!(scalar(&env, "b"), Some("2".to_string()));
+    }
+
+    #[test]
+    fn errexit_suppression_carries_into_function_call() {
+        let mut env = env_with_errexit();
+        let result = execute(&mut env, "f() { return -n 1; a=1; }; f && b=2");
+        assert_eq!(result, Ok(()));
+        assert_eq!(scalar(&env, "a"), Some("1".to_string()));
+        assert_eq!(scalar(&env, "b"), Some("2".to_string()));
+
+        let result = execute(&mut env, "f() { return -n 1; c=3; }; f");
+        assert_eq!(result, Err(Divert::Exit(ExitStatus(1))));
+        assert_eq!(scalar(&env, "c"), None);
+    }
+
+    #[test]
+    fn or_else_skips_second_on_success() {
+        let mut env = Env::new_virtual();
+        env.builtins.insert("return", return_builtin());
+        let result = execute(&mut env, "return -n 0 || return -n 5");
+        assert_eq!(result, Ok(()));
+        assert_eq!(env.exit_status, ExitStatus(0));
+    }
+
+    #[test]
+    fn mixed_and_or_list() {
+        let mut env = Env::new_virtual();
+        env.builtins.insert("return", return_builtin());
+        let result = execute(&mut env, "return -n 1 && return -n 2 || return -n 3");
+        assert_eq!(result, Ok(()));
+        assert_eq!(env.exit_status, ExitStatus(3));
+
+        let result = execute(&mut env, "return -n 1 || return -n 0 && return -n 4");
+        assert_eq!(result, Ok(()));
+        assert_eq!(env.exit_status, ExitStatus(4));
+    }
+
+    #[test]
+    fn divert_in_and_or_list_stops_execution() {
+        let mut env = Env::new_virtual();
+        env.builtins.insert("return", return_builtin());
+        let result = execute(&mut env, "return -n 0 && return 7 && return -n 5");
+        assert_eq!(result, Err(Divert::Return));
+        assert_eq!(env.exit_status, ExitStatus(7));
+    }
+
+    #[test]
+    fn async_item_runs_in_subshell_without_waiting() {
+        let system = VirtualSystem::new();
+        let state = Rc::clone(&system.state);
+        let mut executor = LocalPool::new();
+        state.borrow_mut().executor = Some(Rc::new(executor.spawner()));
+        let mut env = Env::with_system(Box::new(system));
+        env.builtins.insert("return", return_builtin());
+        env.options.set(Monitor, true);
+        env.exit_status = ExitStatus(42);
+
+        let list: syntax::List = "return -n 13&".parse().unwrap();
+        let result = executor.run_until(list.execute(&mut env));
+        assert_eq!(result, Ok(()));
+        assert_eq!(env.exit_status, ExitStatus::SUCCESS);
+        let pid = env.jobs.last_async_pid.unwrap();
+        assert!(env.jobs.child_processes.contains_key(&pid));
+
+        executor.run_until_stalled();
+        let state = state.borrow();
+        assert_eq!(
+            state.processes[&pid].state(),
+            ProcessState::Exited(ExitStatus(13))
+        );
+    }
+
+    #[test]
+    fn async_item_redirects_stdin_without_job_control() {
+        let system = VirtualSystem::new();
+        let state = Rc::clone(&system.state);
+        let mut executor = LocalPool::new();
+        state.borrow_mut().executor = Some(Rc::new(executor.spawner()));
+        state
+            .borrow_mut()
+            .file_system
+            .save("/dev/null", INode::new())
+            .unwrap();
+        let mut env = Env::with_system(Box::new(system));
+        env.builtins.insert("return", return_builtin());
+
+        let list: syntax::List = "return -n 13&".parse().unwrap();
+        let result = executor.run_until(list.execute(&mut env));
+        assert_eq!(result, Ok(()));
+        let pid = env.jobs.last_async_pid.unwrap();
+
+        executor.run_until_stalled();
+        let state = state.borrow();
+        assert_eq!(
+            state.processes[&pid].state(),
+            ProcessState::Exited(ExitStatus(13))
+        );
+    }
+
+    #[test]
+    fn async_item_fails_without_dev_null() {
+        let system = VirtualSystem::new();
+        let state = Rc::clone(&system.state);
+        let mut executor = LocalPool::new();
+        state.borrow_mut().executor = Some(Rc::new(executor.spawner()));
+        let mut env = Env::with_system(Box::new(system));
+        env.builtins.insert("return", return_builtin());
+
+        let list: syntax::List = "return -n 13&".parse().unwrap();
+        let result = executor.run_until(list.execute(&mut env));
+        assert_eq!(result, Ok(()));
+        let pid = env.jobs.last_async_pid.unwrap();
+
+        executor.run_until_stalled();
+        let state = state.borrow();
+        assert_eq!(
+            state.processes[&pid].state(),
+            ProcessState::Exited(ExitStatus::ERROR)
+        );
+    }
+
+    #[test]
+    fn async_item_fails_to_start_subshell() {
+        let mut env = Env::new_virtual();
+        env.builtins.insert("return", return_builtin());
+        let result = execute(&mut env, "return -n 0&");
+        assert_eq!(result, Ok(()));
+        assert_eq!(env.exit_status, ExitStatus::NOEXEC);
+        assert_eq!(env.jobs.last_async_pid, None);
+    }
+}
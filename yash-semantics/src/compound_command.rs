@@ -0,0 +1,190 @@
+// This file is part of yash, an extended POSIX shell.
+// Copyright (C) 2021 WATANABE Yuki
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Implementation of compound command semantics.
+//!
+//! The redirections of a [`FullCompoundCommand`](syntax::FullCompoundCommand)
+//! are performed before the compound command is executed and undone after
+//! the command finishes. A redirection error aborts the shell.
+
+mod case;
+mod for_loop;
+mod if_command;
+mod subshell;
+mod while_loop;
+
+use super::Command;
+use crate::redir::perform_redirs;
+use crate::redir::undo_redirs;
+use async_trait::async_trait;
+use yash_env::exec::Divert;
+use yash_env::exec::Result;
+use yash_env::Env;
+use yash_syntax::syntax;
+
+#[async_trait(?Send)]
+impl Command for syntax::FullCompoundCommand {
+    async fn execute(&self, env: &mut Env) -> Result {
+        let saved_fds = match perform_redirs(env, &self.redirs, None).await {
+            Ok(saved_fds) => saved_fds,
+            Err(error) => return crate::redir::handle_error(env, error, true),
+        };
+        let result = self.command.execute(env).await;
+        undo_redirs(env, saved_fds);
+        result
+    }
+}
+
+#[async_trait(?Send)]
+impl Command for syntax::CompoundCommand {
+    async fn execute(&self, env: &mut Env) -> Result {
+        use syntax::CompoundCommand::*;
+        match self {
+            Grouping(list) => list.execute(env).await,
+            Subshell(list) => subshell::execute(env, list).await,
+            For { name, values, body } => for_loop::execute(env, name, values, body).await,
+            While { condition, body } => while_loop::execute(env, condition, body, true).await,
+            Until { condition, body } => while_loop::execute(env, condition, body, false).await,
+            If {
+                condition,
+                body,
+                elifs,
+                r#else,
+            } => if_command::execute(env, condition, body, elifs, r#else).await,
+            Case { subject, items } => case::execute(env, subject, items).await,
+        }
+    }
+}
+
+/// What a loop should do after executing its condition or body.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum LoopControl {
+    /// Go on with the current iteration.
+    Proceed,
+    /// Start the next iteration.
+    Continue,
+    /// Quit the loop.
+    Break,
+}
+
+/// Interprets the result of executing part of a loop.
+///
+/// A `Divert::Break` or `Divert::Continue` targeting this loop is consumed and
+/// converted to a [`LoopControl`]. A `Divert::Break` targeting an outer loop
+/// is returned with the count decremented. Other diverts are returned intact.
+fn loop_control(result: Result) -> Result<LoopControl> {
+    match result {
+        Ok(()) => Ok(LoopControl::Proceed),
+        Err(Divert::Continue) => Ok(LoopControl::Continue),
+        Err(Divert::Break { count: 0 }) => Ok(LoopControl::Break),
+        Err(Divert::Break { count }) => Err(Divert::Break { count: count - 1 }),
+        Err(divert) => Err(divert),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::return_builtin;
+    use futures::executor::block_on;
+    use yash_env::exec::ExitStatus;
+    use yash_env::option::ShellOption::ErrExit;
+
+    #[test]
+    fn loop_control_conversion() {
+        assert_eq!(loop_control(Ok(())), Ok(LoopControl::Proceed));
+        assert_eq!(
+            loop_control(Err(Divert::Continue)),
+            Ok(LoopControl::Continue)
+        );
+        assert_eq!(
+            loop_control(Err(Divert::Break { count: 0 })),
+            Ok(LoopControl::Break)
+        );
+        assert_eq!(
+            loop_control(Err(Divert::Break { count: 2 })),
+            Err(Divert::Break { count: 1 })
+        );
+        assert_eq!(loop_control(Err(Divert::Return)), Err(Divert::Return));
+        assert_eq!(loop_control(Err(Divert::Interrupt)), Err(Divert::Interrupt));
+    }
+
+    #[test]
+    fn grouping_executes_list_in_current_environment() {
+        let mut env = Env::new_virtual();
+        env.builtins.insert("return", return_builtin());
+        let command: syntax::CompoundCommand = "{ x=1; return -n 5; }".parse().unwrap();
+        let result = block_on(command.execute(&mut env));
+        assert_eq!(result, Ok(()));
+        assert_eq!(env.exit_status, ExitStatus(5));
+        assert!(env.variables.get("x").is_some());
+    }
+
+    #[test]
+    fn grouping_propagates_divert() {
+        let mut env = Env::new_virtual();
+        env.builtins.insert("return", return_builtin());
+        let command: syntax::CompoundCommand = "{ return 3; return -n 5; }".parse().unwrap();
+        let result = block_on(command.execute(&mut env));
+        assert_eq!(result, Err(Divert::Return));
+        assert_eq!(env.exit_status, ExitStatus(3));
+    }
+
+    #[test]
+    fn redirection_error_aborts_compound_command() {
+        let mut env = Env::new_virtual();
+        env.builtins.insert("return", return_builtin());
+        let command: syntax::FullCompoundCommand = "{ return -n 5; } <nonexistent".parse().unwrap();
+        let result = block_on(command.execute(&mut env));
+        assert_eq!(result, Err(Divert::Exit(ExitStatus::ERROR)));
+        assert_eq!(env.exit_status, ExitStatus::ERROR);
+    }
+
+    #[test]
+    fn redirections_are_undone_after_compound_command() {
+        let mut env = Env::new_virtual();
+        env.builtins.insert("return", return_builtin());
+        let (_reader, writer) = env.system.pipe().unwrap();
+        let command = format!("{{ return -n 0; }} 5>&{}", writer);
+        let command: syntax::FullCompoundCommand = command.parse().unwrap();
+        let result = block_on(command.execute(&mut env));
+        assert_eq!(result, Ok(()));
+        let result = env.system.write(5, b"x");
+        assert_eq!(result, Err(nix::errno::Errno::EBADF.into()));
+    }
+
+    #[test]
+    fn errexit_in_grouping() {
+        let mut env = Env::new_virtual();
+        env.builtins.insert("return", return_builtin());
+        env.options.set(ErrExit, true);
+        let command: syntax::CompoundCommand = "{ return -n 5; x=1; }".parse().unwrap();
+        let result = block_on(command.execute(&mut env));
+        assert_eq!(result, Err(Divert::Exit(ExitStatus(5))));
+        assert_eq!(env.variables.get("x"), None);
+    }
+
+    #[test]
+    fn errexit_ignores_grouping_failed_while_suppressed() {
+        let mut env = Env::new_virtual();
+        env.builtins.insert("return", return_builtin());
+        env.options.set(ErrExit, true);
+        let command: syntax::CompoundCommand = "{ return -n 5 && x=1; }".parse().unwrap();
+        let result = block_on(command.execute(&mut env));
+        assert_eq!(result, Ok(()));
+        assert_eq!(env.exit_status, ExitStatus(5));
+    }
+}
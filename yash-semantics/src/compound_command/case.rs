@@ -0,0 +1,166 @@
+// This file is part of yash, an extended POSIX shell.
+// Copyright (C) 2021 WATANABE Yuki
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Execution of the case command.
+
+use crate::expansion::expand_word;
+use crate::expansion::expand_word_attr;
+use crate::expansion::AttrChar;
+use crate::pattern::Pattern;
+use crate::xtrace;
+use crate::xtrace::quote;
+use crate::Command;
+use yash_env::exec::ExitStatus;
+use yash_env::exec::Result;
+use yash_env::option::ShellOption;
+use yash_env::Env;
+use yash_syntax::syntax::CaseItem;
+use yash_syntax::syntax::Word;
+
+/// Executes the case command.
+///
+/// The subject is expanded and matched against the patterns of each item in
+/// order. The patterns are expanded lazily, so patterns after the first match
+/// are not expanded. Quoted characters in a pattern are matched literally. When
+/// a pattern matches, the body of the item is executed and the rest of the
+/// items are ignored. The exit status is that of the executed body, or zero if
+/// no pattern matches.
+///
+/// If the xtrace option is on, the expanded subject is traced before the
+/// patterns are expanded.
+pub async fn execute(env: &mut Env, subject: &Word, items: &[CaseItem]) -> Result {
+    let subject = match expand_word(env, subject).await {
+        Ok(field) => field.value,
+        Err(error) => return crate::expansion::handle_error(env, error),
+    };
+    if env.options.is_on(ShellOption::XTrace) {
+        let line = format!("case {} in", quote(&subject));
+        xtrace::print(env, &line).await;
+    }
+
+    for item in items {
+        for pattern in &item.patterns {
+            let pattern = match expand_word_attr(env, pattern).await {
+                Ok(field) => field,
+                Err(error) => return crate::expansion::handle_error(env, error),
+            };
+            let chars = pattern.chars.into_iter();
+            let pattern = Pattern::new(chars.filter_map(AttrChar::to_pattern_char));
+            if pattern.is_match(&subject) {
+                env.exit_status = ExitStatus::SUCCESS;
+                return item.body.execute(env).await;
+            }
+        }
+    }
+
+    env.exit_status = ExitStatus::SUCCESS;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::return_builtin;
+    use crate::xtrace::tests::env_with_stderr_pipe;
+    use crate::xtrace::tests::read_all;
+    use crate::Command;
+    use futures::executor::block_on;
+    use yash_env::exec::Divert;
+    use yash_env::exec::ExitStatus;
+    use yash_env::Env;
+    use yash_syntax::syntax::List;
+
+    fn execute(env: &mut Env, command: &str) -> yash_env::exec::Result {
+        let list: List = command.parse().unwrap();
+        block_on(list.execute(env))
+    }
+
+    fn env() -> Env {
+        let mut env = Env::new_virtual();
+        env.builtins.insert("return", return_builtin());
+        env
+    }
+
+    #[test]
+    fn first_matching_item_is_executed() {
+        let mut env = env();
+        let result = execute(
+            &mut env,
+            "case foo in (bar) return -n 1;; (f*|x) return -n 2;; (foo) return -n 3;; esac",
+        );
+        assert_eq!(result, Ok(()));
+        assert_eq!(env.exit_status, ExitStatus(2));
+    }
+
+    #[test]
+    fn no_matching_item() {
+        let mut env = env();
+        env.exit_status = ExitStatus(5);
+        let result = execute(&mut env, "case foo in (bar) return -n 1;; esac");
+        assert_eq!(result, Ok(()));
+        assert_eq!(env.exit_status, ExitStatus::SUCCESS);
+    }
+
+    #[test]
+    fn empty_body_results_in_zero_exit_status() {
+        let mut env = env();
+        let result = execute(&mut env, "return -n 4; case foo in (foo) ;; esac");
+        assert_eq!(result, Ok(()));
+        assert_eq!(env.exit_status, ExitStatus::SUCCESS);
+    }
+
+    #[test]
+    fn subject_and_patterns_are_expanded() {
+        let mut env = env();
+        let result = execute(&mut env, "x=abc p='a*'; case $x in ($p) return -n 6;; esac");
+        assert_eq!(result, Ok(()));
+        assert_eq!(env.exit_status, ExitStatus(6));
+    }
+
+    #[test]
+    fn quoted_pattern_characters_match_literally() {
+        let mut env = env();
+        let result = execute(
+            &mut env,
+            "p='a*'; case abc in (\"$p\"|'a?c') return -n 1;; (a\\*) return -n 2;; \
+             (\"a\"*) return -n 3;; esac",
+        );
+        assert_eq!(result, Ok(()));
+        assert_eq!(env.exit_status, ExitStatus(3));
+    }
+
+    #[test]
+    fn divert_in_body() {
+        let mut env = env();
+        let result = execute(&mut env, "case a in (a) return 8;; esac");
+        assert_eq!(result, Err(Divert::Return));
+        assert_eq!(env.exit_status, ExitStatus(8));
+    }
+
+    #[test]
+    fn expansion_error_in_subject() {
+        let mut env = env();
+        let result = execute(&mut env, "case ${x?} in (a) return -n 1;; esac");
+        assert_eq!(result, Err(Divert::Exit(ExitStatus::ERROR)));
+    }
+
+    #[test]
+    fn xtrace_prints_expanded_subject() {
+        let (mut env, reader) = env_with_stderr_pipe();
+        let result = execute(&mut env, "case 'a b' in (x) esac");
+        assert_eq!(result, Ok(()));
+        assert_eq!(read_all(&mut env, reader), "+ case 'a b' in\n");
+    }
+}
@@ -0,0 +1,214 @@
+// This file is part of yash, an extended POSIX shell.
+// Copyright (C) 2021 WATANABE Yuki
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Execution of the for loop.
+
+use super::loop_control;
+use super::LoopControl;
+use crate::assign::assign_variable;
+use crate::expansion::expand_words;
+use crate::xtrace;
+use crate::xtrace::quote;
+use crate::Command;
+use yash_env::exec::ExitStatus;
+use yash_env::exec::Result;
+use yash_env::option::ShellOption;
+use yash_env::variable::Scope;
+use yash_env::variable::Value;
+use yash_env::Env;
+use yash_syntax::syntax::List;
+use yash_syntax::syntax::Word;
+
+/// Executes the for loop.
+///
+/// The `values` are expanded to fields, and the body is executed for each
+/// field with the field assigned to the variable `name`. If `values` is
+/// `None`, the positional parameters are used as if `"$@"` were given. The
+/// exit status of the loop is that of the last executed body, or zero if the
+/// body is never executed.
+///
+/// If the xtrace option is on, the header of the loop is traced with the
+/// expanded values after the expansion.
+pub async fn execute(
+    env: &mut Env,
+    name: &Word,
+    values: &Option<Vec<Word>>,
+    body: &List,
+) -> Result {
+    let fields = match values {
+        Some(words) => expand_words(env, words).await,
+        None => {
+            let mut word: Word = "\"$@\"".parse().unwrap();
+            word.location = name.location.clone();
+            expand_words(env, std::slice::from_ref(&word)).await
+        }
+    };
+    let fields = match fields {
+        Ok(fields) => fields,
+        Err(error) => return crate::expansion::handle_error(env, error),
+    };
+
+    let name = name.to_string();
+    if env.options.is_on(ShellOption::XTrace) {
+        let mut line = format!("for {} in", name);
+        for field in &fields {
+            line.push(' ');
+            line.push_str(&quote(&field.value));
+        }
+        xtrace::print(env, &line).await;
+    }
+
+    let mut exit_status = ExitStatus::SUCCESS;
+    for field in fields {
+        let value = Value::Scalar(field.value);
+        let result = assign_variable(env, Scope::Global, name.clone(), value, field.origin, false);
+        if let Err(error) = result {
+            return crate::expansion::handle_error(env, error);
+        }
+
+        let control = loop_control(body.execute(env).await)?;
+        exit_status = env.exit_status;
+        if control == LoopControl::Break {
+            return Ok(());
+        }
+    }
+    env.exit_status = exit_status;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::break_builtin;
+    use crate::tests::continue_builtin;
+    use crate::tests::return_builtin;
+    use crate::xtrace::tests::env_with_stderr_pipe;
+    use crate::xtrace::tests::read_all;
+    use crate::Command;
+    use futures::executor::block_on;
+    use yash_env::exec::Divert;
+    use yash_env::exec::ExitStatus;
+    use yash_env::variable::Scope;
+    use yash_env::variable::Value;
+    use yash_env::variable::Variable;
+    use yash_env::Env;
+    use yash_syntax::source::Location;
+    use yash_syntax::syntax::List;
+
+    fn env() -> Env {
+        let mut env = Env::new_virtual();
+        env.builtins.insert("break", break_builtin());
+        env.builtins.insert("continue", continue_builtin());
+        env.builtins.insert("return", return_builtin());
+        env
+    }
+
+    fn execute(env: &mut Env, command: &str) -> yash_env::exec::Result {
+        let list: List = command.parse().unwrap();
+        block_on(list.execute(env))
+    }
+
+    fn value(env: &Env, name: &str) -> Value {
+        env.variables.get(name).unwrap().value.clone()
+    }
+
+    #[test]
+    fn for_loop_iterates_over_expanded_values() {
+        let mut env = env();
+        let result = execute(
+            &mut env,
+            "s=; y='b c'; for i in a $y \"d e\"; do s=$s[$i]; return -n 7; done",
+        );
+        assert_eq!(result, Ok(()));
+        assert_eq!(
+            value(&env, "s"),
+            Value::Scalar("[a][b][c][d e]".to_string())
+        );
+        assert_eq!(value(&env, "i"), Value::Scalar("d e".to_string()));
+        assert_eq!(env.exit_status, ExitStatus(7));
+    }
+
+    #[test]
+    fn for_loop_without_values_to_iterate() {
+        let mut env = env();
+        env.exit_status = ExitStatus(3);
+        let result = execute(&mut env, "for i in; do return -n 7; done");
+        assert_eq!(result, Ok(()));
+        assert_eq!(env.exit_status, ExitStatus::SUCCESS);
+        assert_eq!(env.variables.get("i"), None);
+    }
+
+    #[test]
+    fn for_loop_iterates_over_positional_params_by_default() {
+        let mut env = env();
+        let params = vec!["1".to_string(), "".to_string(), "a b".to_string()];
+        *env.variables.positional_params_mut() = params;
+        let result = execute(&mut env, "s=; for i do s=$s[$i]; done");
+        assert_eq!(result, Ok(()));
+        assert_eq!(value(&env, "s"), Value::Scalar("[1][][a b]".to_string()));
+    }
+
+    #[test]
+    fn break_and_continue_in_for_loop() {
+        let mut env = env();
+        let result = execute(
+            &mut env,
+            "s=; for i in 1 2 3 4; do \
+             return -n $((i==2)) || continue; return -n $((i==4)) || break; s=$s$i; done",
+        );
+        assert_eq!(result, Ok(()));
+        assert_eq!(value(&env, "s"), Value::Scalar("13".to_string()));
+        assert_eq!(value(&env, "i"), Value::Scalar("4".to_string()));
+    }
+
+    #[test]
+    fn return_in_for_loop() {
+        let mut env = env();
+        let result = execute(&mut env, "for i in 1 2; do return 5; done");
+        assert_eq!(result, Err(Divert::Return));
+        assert_eq!(value(&env, "i"), Value::Scalar("1".to_string()));
+        assert_eq!(env.exit_status, ExitStatus(5));
+    }
+
+    #[test]
+    fn assigning_read_only_variable() {
+        let mut env = env();
+        env.variables.assign(
+            Scope::Global,
+            "i".to_string(),
+            Variable {
+                value: Value::Scalar("ro".to_string()),
+                last_assigned_location: None,
+                is_exported: false,
+                read_only_location: Some(Location::dummy("")),
+            },
+        );
+        let result = execute(&mut env, "for i in 1; do return -n 5; done");
+        assert_eq!(result, Err(Divert::Exit(ExitStatus::ERROR)));
+        assert_eq!(value(&env, "i"), Value::Scalar("ro".to_string()));
+    }
+
+    #[test]
+    fn xtrace_prints_header_with_expanded_values() {
+        let (mut env, reader) = env_with_stderr_pipe();
+        env.builtins.insert("return", return_builtin());
+        let result = execute(&mut env, "for i in 1 '2 3'; do return -n 0; done");
+        assert_eq!(result, Ok(()));
+        assert_eq!(
+            read_all(&mut env, reader),
+            "+ for i in 1 '2 3'\n+ return -n 0\n+ return -n 0\n"
+        );
+    }
+}
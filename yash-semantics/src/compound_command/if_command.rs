@@ -0,0 +1,169 @@
+// This file is part of yash, an extended POSIX shell.
+// Copyright (C) 2021 WATANABE Yuki
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Execution of the if command.
+
+use crate::execute_suppressing_errexit;
+use crate::Command;
+use yash_env::exec::ExitStatus;
+use yash_env::exec::Result;
+use yash_env::Env;
+use yash_syntax::syntax::ElifThen;
+use yash_syntax::syntax::List;
+
+/// Executes the if command.
+///
+/// The conditions are executed in order until one of them succeeds, and then
+/// the corresponding body is executed. If no condition succeeds, the `else`
+/// clause is executed if any. The exit status is that of the executed body, or
+/// zero if no body is executed. The errexit option is suppressed while
+/// executing the conditions.
+pub async fn execute(
+    env: &mut Env,
+    condition: &List,
+    body: &List,
+    elifs: &[ElifThen],
+    r#else: &Option<List>,
+) -> Result {
+    let branches = std::iter::once((condition, body))
+        .chain(elifs.iter().map(|elif| (&elif.condition, &elif.body)));
+    for (condition, body) in branches {
+        execute_suppressing_errexit(condition, env).await?;
+        if env.exit_status.is_successful() {
+            return body.execute(env).await;
+        }
+    }
+
+    match r#else {
+        Some(r#else) => r#else.execute(env).await,
+        None => {
+            env.exit_status = ExitStatus::SUCCESS;
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::return_builtin;
+    use crate::Command;
+    use futures::executor::block_on;
+    use yash_env::exec::Divert;
+    use yash_env::exec::ExitStatus;
+    use yash_env::option::ShellOption::ErrExit;
+    use yash_env::Env;
+    use yash_syntax::syntax::CompoundCommand;
+    use yash_syntax::syntax::List;
+
+    fn execute(env: &mut Env, command: &str) -> yash_env::exec::Result {
+        let command: CompoundCommand = command.parse().unwrap();
+        block_on(command.execute(env))
+    }
+
+    #[test]
+    fn true_condition_executes_body() {
+        let mut env = Env::new_virtual();
+        env.builtins.insert("return", return_builtin());
+        let result = execute(&mut env, "if return -n 0; then return -n 3; fi");
+        assert_eq!(result, Ok(()));
+        assert_eq!(env.exit_status, ExitStatus(3));
+    }
+
+    #[test]
+    fn false_condition_without_else() {
+        let mut env = Env::new_virtual();
+        env.builtins.insert("return", return_builtin());
+        let result = execute(&mut env, "if return -n 1; then return -n 3; fi");
+        assert_eq!(result, Ok(()));
+        assert_eq!(env.exit_status, ExitStatus::SUCCESS);
+    }
+
+    #[test]
+    fn false_condition_with_else() {
+        let mut env = Env::new_virtual();
+        env.builtins.insert("return", return_builtin());
+        let result = execute(
+            &mut env,
+            "if return -n 1; then return -n 3; else return -n 4; fi",
+        );
+        assert_eq!(result, Ok(()));
+        assert_eq!(env.exit_status, ExitStatus(4));
+    }
+
+    #[test]
+    fn elif_conditions_are_tried_in_order() {
+        let mut env = Env::new_virtual();
+        env.builtins.insert("return", return_builtin());
+        let result = execute(
+            &mut env,
+            "if return -n 1; then return -n 3; \
+             elif return -n 2; then return -n 4; \
+             elif return -n 0; then return -n 5; \
+             elif return -n 0; then return -n 6; \
+             else return -n 7; fi",
+        );
+        assert_eq!(result, Ok(()));
+        assert_eq!(env.exit_status, ExitStatus(5));
+    }
+
+    #[test]
+    fn divert_in_condition() {
+        let mut env = Env::new_virtual();
+        env.builtins.insert("return", return_builtin());
+        let result = execute(&mut env, "if return 9; then return -n 3; fi");
+        assert_eq!(result, Err(Divert::Return));
+        assert_eq!(env.exit_status, ExitStatus(9));
+    }
+
+    #[test]
+    fn errexit_is_suppressed_in_conditions() {
+        let mut env = Env::new_virtual();
+        env.builtins.insert("return", return_builtin());
+        env.options.set(ErrExit, true);
+        let result = execute(
+            &mut env,
+            "if return -n 1; then return -n 2; elif return -n 3; then return -n 4; fi",
+        );
+        assert_eq!(result, Ok(()));
+        assert_eq!(env.exit_status, ExitStatus::SUCCESS);
+        assert!(!env.errexit_is_suppressed);
+    }
+
+    #[test]
+    fn errexit_applies_in_bodies() {
+        let mut env = Env::new_virtual();
+        env.builtins.insert("return", return_builtin());
+        env.options.set(ErrExit, true);
+        let result = execute(&mut env, "if return -n 0; then return -n 2; fi");
+        assert_eq!(result, Err(Divert::Exit(ExitStatus(2))));
+
+        let result = execute(&mut env, "if return -n 1; then :; else return -n 3; fi");
+        assert_eq!(result, Err(Divert::Exit(ExitStatus(3))));
+    }
+
+    #[test]
+    fn errexit_suppression_carries_into_function_in_condition() {
+        let mut env = Env::new_virtual();
+        env.builtins.insert("return", return_builtin());
+        env.options.set(ErrExit, true);
+        let list: List = "f() { return -n 1; x=1; }; if f; then return -n 6; fi"
+            .parse()
+            .unwrap();
+        let result = block_on(list.execute(&mut env));
+        assert_eq!(result, Err(Divert::Exit(ExitStatus(6))));
+        assert!(env.variables.get("x").is_some());
+    }
+}
@@ -0,0 +1,118 @@
+// This file is part of yash, an extended POSIX shell.
+// Copyright (C) 2021 WATANABE Yuki
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Execution of the subshell compound command.
+
+use crate::Command;
+use yash_env::exec::Divert;
+use yash_env::exec::ExitStatus;
+use yash_env::exec::Result;
+use yash_env::Env;
+use yash_syntax::syntax::List;
+
+/// Executes the list in a subshell.
+///
+/// The exit status of the subshell becomes that of the compound command.
+/// A `Divert` in the subshell does not affect the current shell, but the
+/// errexit option is applied to the exit status of the subshell.
+pub async fn execute(env: &mut Env, list: &List) -> Result {
+    let list = list.clone();
+    let result = env
+        .run_in_subshell(move |env| {
+            Box::pin(async move {
+                if let Err(Divert::Exit(exit_status)) = list.execute(env).await {
+                    env.exit_status = exit_status;
+                }
+            })
+        })
+        .await;
+
+    match result {
+        Ok(exit_status) => env.exit_status = exit_status,
+        Err(e) => {
+            env.print_error(&format!("cannot start subshell: {}", e));
+            env.exit_status = ExitStatus::NOEXEC;
+        }
+    }
+    env.apply_errexit()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::return_builtin;
+    use futures::executor::block_on;
+    use futures::executor::LocalPool;
+    use std::rc::Rc;
+    use yash_env::option::ShellOption::ErrExit;
+    use yash_env::virtual_system::VirtualSystem;
+
+    #[test]
+    fn subshell_preserves_current_environment() {
+        let system = VirtualSystem::new();
+        let mut executor = LocalPool::new();
+        system.state.borrow_mut().executor = Some(Rc::new(executor.spawner()));
+        let mut env = Env::with_system(Box::new(system));
+        env.builtins.insert("return", return_builtin());
+        let list: List = "x=1; return 23".parse().unwrap();
+
+        let result = executor.run_until(execute(&mut env, &list));
+        assert_eq!(result, Ok(()));
+        assert_eq!(env.exit_status, ExitStatus(23));
+        assert_eq!(env.variables.get("x"), None);
+    }
+
+    #[test]
+    fn error_starting_subshell() {
+        let mut env = Env::new_virtual();
+        let list: List = "x=1".parse().unwrap();
+        let result = block_on(execute(&mut env, &list));
+        assert_eq!(result, Ok(()));
+        assert_eq!(env.exit_status, ExitStatus::NOEXEC);
+    }
+
+    #[test]
+    fn errexit_applies_to_subshell() {
+        let system = VirtualSystem::new();
+        let mut executor = LocalPool::new();
+        system.state.borrow_mut().executor = Some(Rc::new(executor.spawner()));
+        let mut env = Env::with_system(Box::new(system));
+        env.builtins.insert("return", return_builtin());
+        env.options.set(ErrExit, true);
+
+        // The failure in the subshell is exempt, but the subshell is not.
+        let list: List = "return -n 7 && x=1".parse().unwrap();
+        let result = executor.run_until(execute(&mut env, &list));
+        assert_eq!(result, Err(Divert::Exit(ExitStatus(7))));
+        assert_eq!(env.exit_status, ExitStatus(7));
+    }
+
+    #[test]
+    fn subshell_inherits_errexit_suppression() {
+        let system = VirtualSystem::new();
+        let mut executor = LocalPool::new();
+        system.state.borrow_mut().executor = Some(Rc::new(executor.spawner()));
+        let mut env = Env::with_system(Box::new(system));
+        env.builtins.insert("return", return_builtin());
+        env.options.set(ErrExit, true);
+        env.errexit_is_suppressed = true;
+
+        let list: List = "return -n 3; x=1".parse().unwrap();
+        let result = executor.run_until(execute(&mut env, &list));
+        assert_eq!(result, Ok(()));
+        assert_eq!(env.exit_status, ExitStatus::SUCCESS);
+    }
+}
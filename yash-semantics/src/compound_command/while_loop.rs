@@ -0,0 +1,195 @@
+// This file is part of yash, an extended POSIX shell.
+// Copyright (C) 2021 WATANABE Yuki
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Execution of the while and until loops.
+
+use super::loop_control;
+use super::LoopControl;
+use crate::execute_suppressing_errexit;
+use crate::Command;
+use yash_env::exec::ExitStatus;
+use yash_env::exec::Result;
+use yash_env::Env;
+use yash_syntax::syntax::List;
+
+/// Executes the while or until loop.
+///
+/// The body is executed repeatedly while the exit status of the condition is
+/// zero (if `is_while` is true) or non-zero (if `is_while` is false). The exit
+/// status of the loop is that of the last executed body, or zero if the body
+/// is never executed. The errexit option is suppressed while executing the
+/// condition.
+pub async fn execute(env: &mut Env, condition: &List, body: &List, is_while: bool) -> Result {
+    let mut exit_status = ExitStatus::SUCCESS;
+    loop {
+        match loop_control(execute_suppressing_errexit(condition, env).await)? {
+            LoopControl::Proceed => (),
+            LoopControl::Continue => continue,
+            LoopControl::Break => return Ok(()),
+        }
+        if env.exit_status.is_successful() != is_while {
+            break;
+        }
+
+        let control = loop_control(body.execute(env).await)?;
+        exit_status = env.exit_status;
+        if control == LoopControl::Break {
+            return Ok(());
+        }
+    }
+    env.exit_status = exit_status;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::break_builtin;
+    use crate::tests::continue_builtin;
+    use crate::tests::return_builtin;
+    use crate::Command;
+    use futures::executor::block_on;
+    use yash_env::exec::Divert;
+    use yash_env::exec::ExitStatus;
+    use yash_env::option::ShellOption::ErrExit;
+    use yash_env::variable::Value;
+    use yash_env::Env;
+    use yash_syntax::syntax::List;
+
+    fn env() -> Env {
+        let mut env = Env::new_virtual();
+        env.builtins.insert("break", break_builtin());
+        env.builtins.insert("continue", continue_builtin());
+        env.builtins.insert("return", return_builtin());
+        env
+    }
+
+    fn execute(env: &mut Env, command: &str) -> yash_env::exec::Result {
+        let list: List = command.parse().unwrap();
+        block_on(list.execute(env))
+    }
+
+    fn value(env: &Env, name: &str) -> Value {
+        env.variables.get(name).unwrap().value.clone()
+    }
+
+    #[test]
+    fn while_loop_repeats_while_condition_succeeds() {
+        let mut env = env();
+        let result = execute(
+            &mut env,
+            "i=0 s=; while return -n $((i>=3)); do i=$((i+1)) s=$s$i; return -n $i; done",
+        );
+        assert_eq!(result, Ok(()));
+        assert_eq!(value(&env, "s"), Value::Scalar("123".to_string()));
+        assert_eq!(env.exit_status, ExitStatus(3));
+    }
+
+    #[test]
+    fn while_loop_without_iteration() {
+        let mut env = env();
+        let result = execute(&mut env, "while return -n 1; do return -n 2; done");
+        assert_eq!(result, Ok(()));
+        assert_eq!(env.exit_status, ExitStatus::SUCCESS);
+    }
+
+    #[test]
+    fn until_loop_repeats_until_condition_succeeds() {
+        let mut env = env();
+        let result = execute(
+            &mut env,
+            "i=0 s=; until return -n $((i<2)); do i=$((i+1)) s=$s$i; done",
+        );
+        assert_eq!(result, Ok(()));
+        assert_eq!(value(&env, "s"), Value::Scalar("12".to_string()));
+        assert_eq!(env.exit_status, ExitStatus::SUCCESS);
+    }
+
+    #[test]
+    fn break_in_body() {
+        let mut env = env();
+        let result = execute(
+            &mut env,
+            "i=0; while return -n 0; do i=$((i+1)); return -n $((i>=3)) || break; done",
+        );
+        assert_eq!(result, Ok(()));
+        assert_eq!(value(&env, "i"), Value::Scalar("3".to_string()));
+        assert_eq!(env.exit_status, ExitStatus::SUCCESS);
+    }
+
+    #[test]
+    fn continue_in_body() {
+        let mut env = env();
+        let result = execute(
+            &mut env,
+            "i=0 s=; while return -n $((i>=3)); do i=$((i+1)); continue; s=x; done",
+        );
+        assert_eq!(result, Ok(()));
+        assert_eq!(value(&env, "i"), Value::Scalar("3".to_string()));
+        assert_eq!(value(&env, "s"), Value::Scalar("".to_string()));
+    }
+
+    #[test]
+    fn break_in_condition() {
+        let mut env = env();
+        let result = execute(&mut env, "while break; do return -n 2; done");
+        assert_eq!(result, Ok(()));
+        assert_eq!(env.exit_status, ExitStatus::SUCCESS);
+    }
+
+    #[test]
+    fn break_outer_loop() {
+        let mut env = env();
+        let result = execute(
+            &mut env,
+            "i=0; while return -n 0; do while return -n 0; do i=$((i+1)); break 2; done; i=x; done",
+        );
+        assert_eq!(result, Ok(()));
+        assert_eq!(value(&env, "i"), Value::Scalar("1".to_string()));
+    }
+
+    #[test]
+    fn return_in_body() {
+        let mut env = env();
+        let result = execute(&mut env, "while return -n 0; do return 4; done");
+        assert_eq!(result, Err(Divert::Return));
+        assert_eq!(env.exit_status, ExitStatus(4));
+    }
+
+    #[test]
+    fn errexit_is_suppressed_in_condition() {
+        let mut env = env();
+        env.options.set(ErrExit, true);
+        let result = execute(&mut env, "while return -n 1; do :; done; x=1");
+        assert_eq!(result, Ok(()));
+        assert_eq!(value(&env, "x"), Value::Scalar("1".to_string()));
+
+        let result = execute(
+            &mut env,
+            "i=0; until return -n $((i>=2)); do i=$((i+1)); done; y=1",
+        );
+        assert_eq!(result, Ok(()));
+        assert_eq!(value(&env, "y"), Value::Scalar("1".to_string()));
+        assert!(!env.errexit_is_suppressed);
+    }
+
+    #[test]
+    fn errexit_applies_in_body() {
+        let mut env = env();
+        env.options.set(ErrExit, true);
+        let result = execute(&mut env, "while return -n 0; do return -n 4; done");
+        assert_eq!(result, Err(Divert::Exit(ExitStatus(4))));
+    }
+}
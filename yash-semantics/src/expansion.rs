@@ -0,0 +1,589 @@
+// This file is part of yash, an extended POSIX shell.
+// Copyright (C) 2021 WATANABE Yuki
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Word expansion.
+//!
+//! The word expansion involves many kinds of operations described below.
+//! The [`expand_words`] function performs all of them and produces any number
+//! of fields depending on the expanded words. The [`expand_word`] function
+//! performs all but field splitting and pathname expansion and produces
+//! exactly one field, which is suitable for expanding assignment values and
+//! redirection operands.
+//!
+//! # Initial expansion
+//!
+//! The [initial expansion](Expand) converts a word fragment to attributed
+//! characters ([`AttrChar`]). It may involve the tilde expansion, parameter
+//! expansion, command substitution, and arithmetic expansion performed by the
+//! implementations of [`Expand`]. The result is a [`Phrase`], a list of zero or
+//! more fields.
+//!
+//! # Field splitting
+//!
+//! The field splitting divides the fields into smaller parts delimited by a
+//! field separator character. Only characters that resulted from a parameter
+//! expansion, command substitution, or arithmetic expansion and are not quoted
+//! are candidates for a delimiter.
+//!
+//! # Pathname expansion
+//!
+//! The pathname expansion performs pattern matching on the fields and replaces
+//! each field matching a pattern with pathnames of existing files.
+//!
+//! # Quote removal
+//!
+//! The quote removal drops characters quoting other characters, and converts
+//! [`AttrChar`]s into a normal string.
+
+mod arith;
+mod command_subst;
+mod glob;
+mod param;
+mod split;
+mod text;
+mod word;
+
+use crate::pattern::PatternChar;
+use async_trait::async_trait;
+use yash_env::exec::ExitStatus;
+use yash_env::expansion::Error;
+use yash_env::expansion::Field;
+use yash_env::expansion::Result;
+use yash_env::Env;
+use yash_syntax::source::Location;
+
+/// Category of syntactic elements from which expansion originates.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Origin {
+    /// The character appeared literally in the original word.
+    Literal,
+
+    /// The character originates from a tilde expansion.
+    ///
+    /// This kind of character is treated literally in the pathname expansion
+    /// and is not subject to field splitting.
+    HardExpansion,
+
+    /// The character originates from a parameter expansion, command
+    /// substitution, or arithmetic expansion.
+    ///
+    /// This kind of character is subject to field splitting where applicable.
+    SoftExpansion,
+}
+
+/// Character with attributes describing its origin.
+///
+/// Attributes provide additional information about the character that is
+/// needed in the later steps of the word expansion.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct AttrChar {
+    /// Character value.
+    pub value: char,
+    /// Character origin.
+    pub origin: Origin,
+    /// Whether this character is quoted by another character.
+    pub is_quoted: bool,
+    /// Whether this is a quotation character that quotes another character.
+    ///
+    /// Note that a character can be both quoting and quoted. For example, the
+    /// backslash in `"\$"` quotes the dollar and is quoted by the
+    /// double-quotes.
+    pub is_quoting: bool,
+}
+
+impl AttrChar {
+    /// Converts this character to a pattern character.
+    ///
+    /// Returns `None` for a quoting character, which does not take part in
+    /// pattern matching. Quoted characters and results of tilde expansion are
+    /// matched literally.
+    #[must_use]
+    pub fn to_pattern_char(self) -> Option<PatternChar> {
+        if self.is_quoting {
+            None
+        } else if self.is_quoted || self.origin == Origin::HardExpansion {
+            Some(PatternChar::Literal(self.value))
+        } else {
+            Some(PatternChar::Normal(self.value))
+        }
+    }
+}
+
+/// Result of the initial expansion.
+///
+/// A phrase is a list of zero or more fields. Each field is a vector of
+/// [`AttrChar`]s. Most expansions produce exactly one field, but the
+/// expansion of an array or the special parameter `$@` may produce any number
+/// of fields.
+///
+/// When phrases are [appended](Self::append), the last field of the first
+/// phrase is joined with the first field of the second. A phrase of zero
+/// fields is the identity of this operation.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Phrase(pub Vec<Vec<AttrChar>>);
+
+impl Phrase {
+    /// Creates a phrase that contains no fields.
+    #[must_use]
+    pub fn zero_fields() -> Phrase {
+        Phrase(Vec::new())
+    }
+
+    /// Creates a phrase that contains one field.
+    #[must_use]
+    pub fn one_field(chars: Vec<AttrChar>) -> Phrase {
+        Phrase(vec![chars])
+    }
+
+    /// Creates a phrase that contains one empty field.
+    #[must_use]
+    pub fn one_empty_field() -> Phrase {
+        Phrase::one_field(Vec::new())
+    }
+
+    /// Creates a phrase of one field that contains the given string.
+    ///
+    /// The characters will have the given origin and be neither quoted nor
+    /// quoting.
+    #[must_use]
+    pub fn with_str(value: &str, origin: Origin) -> Phrase {
+        let chars = value
+            .chars()
+            .map(|value| AttrChar {
+                value,
+                origin,
+                is_quoted: false,
+                is_quoting: false,
+            })
+            .collect();
+        Phrase::one_field(chars)
+    }
+
+    /// Appends another phrase to this phrase.
+    ///
+    /// The first field of `other` is joined to the last field of `self`. The
+    /// other fields of `other` follow.
+    pub fn append(&mut self, other: Phrase) {
+        let mut others = other.0.into_iter();
+        match (self.0.last_mut(), others.next()) {
+            (Some(last), Some(first)) => last.extend(first),
+            (None, Some(first)) => self.0.push(first),
+            (_, None) => return,
+        }
+        self.0.extend(others);
+    }
+
+    /// Marks all the characters in this phrase as quoted.
+    pub fn quote(&mut self) {
+        self.0.iter_mut().flatten().for_each(|c| c.is_quoted = true);
+    }
+
+    /// Joins the fields into one string, separated by the given character.
+    ///
+    /// If the phrase contains no fields, the result is an empty vector.
+    #[must_use]
+    pub fn join(self, separator: Option<AttrChar>) -> Vec<AttrChar> {
+        let mut fields = self.0.into_iter();
+        let mut result = fields.next().unwrap_or_default();
+        for field in fields {
+            result.extend(separator);
+            result.extend(field);
+        }
+        result
+    }
+}
+
+/// Syntactic construct that can be subjected to the initial expansion.
+///
+/// The initial expansion is the first step of the word expansion that produces
+/// a [`Phrase`] from a syntactic element. Implementors of this trait perform
+/// the tilde expansion, parameter expansion, command substitution, and
+/// arithmetic expansion contained in the element.
+#[async_trait(?Send)]
+pub trait Expand {
+    /// Performs the initial expansion.
+    async fn expand(&self, env: &mut Env) -> Result<Phrase>;
+}
+
+/// Expands a sequence of items and concatenates the results.
+///
+/// If the slice is empty, the result is one empty field.
+#[async_trait(?Send)]
+impl<T: Expand> Expand for [T] {
+    async fn expand(&self, env: &mut Env) -> Result<Phrase> {
+        let mut phrase = None;
+        for item in self {
+            let item_phrase = item.expand(env).await?;
+            match &mut phrase {
+                None => phrase = Some(item_phrase),
+                Some(phrase) => phrase.append(item_phrase),
+            }
+        }
+        Ok(phrase.unwrap_or_else(Phrase::one_empty_field))
+    }
+}
+
+/// Result of the initial expansion with the location of the original word.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AttrField {
+    /// Value of the field.
+    pub chars: Vec<AttrChar>,
+    /// Location of the word this field resulted from.
+    pub origin: Location,
+}
+
+impl AttrField {
+    /// Performs quote removal on this field.
+    ///
+    /// Characters that quote other characters are removed. The other
+    /// characters are converted to a string.
+    #[must_use]
+    pub fn remove_quotes(self) -> Field {
+        let value = self
+            .chars
+            .iter()
+            .filter(|c| !c.is_quoting)
+            .map(|c| c.value)
+            .collect();
+        let origin = self.origin;
+        Field { value, origin }
+    }
+}
+
+/// Expands words to fields.
+///
+/// This function performs all of the initial expansion, field splitting,
+/// pathname expansion, and quote removal. Each word may result in any number
+/// of fields. The origin of each field is the location of the word from which
+/// the field resulted.
+pub async fn expand_words<'a, I>(env: &mut Env, words: I) -> Result<Vec<Field>>
+where
+    I: IntoIterator<Item = &'a yash_syntax::syntax::Word>,
+{
+    let mut fields = Vec::new();
+    for word in words {
+        let phrase = word.expand(env).await?;
+        fields.extend(phrase.0.into_iter().map(|chars| AttrField {
+            chars,
+            origin: word.location.clone(),
+        }));
+    }
+
+    let ifs = split::Ifs::from_env(env);
+    let fields = split::split_fields(fields, &ifs);
+
+    Ok(fields
+        .into_iter()
+        .flat_map(|field| glob::glob(env, field))
+        .collect())
+}
+
+/// Expands a word to a field.
+///
+/// This function performs the initial expansion and quote removal. Field
+/// splitting and pathname expansion are not performed. If the initial
+/// expansion produces more than one field, they are joined with a space
+/// between them.
+pub async fn expand_word(env: &mut Env, word: &yash_syntax::syntax::Word) -> Result<Field> {
+    Ok(expand_word_attr(env, word).await?.remove_quotes())
+}
+
+/// Expands a word to a field without quote removal.
+///
+/// This function is the same as [`expand_word`] except that quote removal is
+/// not performed. The result can be converted to a
+/// [`Pattern`](crate::pattern::Pattern) in which quoted characters are
+/// matched literally.
+pub async fn expand_word_attr(
+    env: &mut Env,
+    word: &yash_syntax::syntax::Word,
+) -> Result<AttrField> {
+    let phrase = word.expand(env).await?;
+    let separator = AttrChar {
+        value: ' ',
+        origin: Origin::SoftExpansion,
+        is_quoted: false,
+        is_quoting: false,
+    };
+    let chars = phrase.join(Some(separator));
+    let origin = word.location.clone();
+    Ok(AttrField { chars, origin })
+}
+
+/// Reports an expansion error.
+///
+/// This function prints an error message with the location of the error and
+/// sets the exit status to [`ExitStatus::ERROR`]. An expansion error aborts a
+/// non-interactive shell, so the result is
+/// [`Divert::Exit`](yash_env::exec::Divert::Exit) which the caller should
+/// propagate. In an interactive shell, the result is
+/// [`Divert::Interrupt`](yash_env::exec::Divert::Interrupt) instead, which
+/// only abandons the current command line.
+pub fn handle_error(env: &mut Env, error: Error) -> yash_env::exec::Result {
+    crate::print_error(env, error.location(), &error);
+    env.exit_status = ExitStatus::ERROR;
+    Err(crate::error_divert(env))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use yash_env::exec::Divert;
+    use yash_env::option::ShellOption;
+    use yash_env::variable::Scope;
+    use yash_env::variable::Value;
+    use yash_env::variable::Variable;
+    use yash_syntax::syntax::Word;
+
+    pub fn assign_scalar(env: &mut Env, name: &str, value: &str) {
+        env.variables.assign(
+            Scope::Global,
+            name.to_string(),
+            Variable {
+                value: Value::Scalar(value.to_string()),
+                last_assigned_location: None,
+                is_exported: false,
+                read_only_location: None,
+            },
+        );
+    }
+
+    fn dummy_char(value: char) -> AttrChar {
+        AttrChar {
+            value,
+            origin: Origin::Literal,
+            is_quoted: false,
+            is_quoting: false,
+        }
+    }
+
+    #[test]
+    fn phrase_append() {
+        let a = dummy_char('a');
+        let b = dummy_char('b');
+        let c = dummy_char('c');
+
+        let mut phrase = Phrase::zero_fields();
+        phrase.append(Phrase::zero_fields());
+        assert_eq!(phrase, Phrase::zero_fields());
+
+        phrase.append(Phrase::one_field(vec![a]));
+        assert_eq!(phrase, Phrase::one_field(vec![a]));
+
+        phrase.append(Phrase::zero_fields());
+        assert_eq!(phrase, Phrase::one_field(vec![a]));
+
+        phrase.append(Phrase(vec![vec![b], vec![c]]));
+        assert_eq!(phrase, Phrase(vec![vec![a, b], vec![c]]));
+    }
+
+    #[test]
+    fn phrase_join() {
+        let a = dummy_char('a');
+        let b = dummy_char('b');
+        let s = dummy_char('-');
+        assert_eq!(Phrase::zero_fields().join(Some(s)), []);
+        assert_eq!(Phrase(vec![vec![a], vec![b]]).join(Some(s)), [a, s, b]);
+        assert_eq!(Phrase(vec![vec![a], vec![b]]).join(None), [a, b]);
+    }
+
+    #[test]
+    fn remove_quotes_drops_quoting_characters() {
+        let quote = AttrChar {
+            value: '"',
+            is_quoting: true,
+            ..dummy_char('"')
+        };
+        let field = AttrField {
+            chars: vec![quote, dummy_char('x'), quote],
+            origin: Location::dummy("x"),
+        };
+        let field = field.remove_quotes();
+        assert_eq!(field.value, "x");
+        assert_eq!(field.origin, Location::dummy("x"));
+    }
+
+    #[test]
+    fn expand_words_performs_quote_removal() {
+        let mut env = Env::new_virtual();
+        let words: Vec<Word> = vec![
+            "a".parse().unwrap(),
+            r#"'b'"c"\d"#.parse().unwrap(),
+            r#""\$e\f""#.parse().unwrap(),
+        ];
+        let fields = block_on(expand_words(&mut env, &words)).unwrap();
+        let values: Vec<_> = fields.iter().map(|f| f.value.as_str()).collect();
+        assert_eq!(values, ["a", "bcd", r"$e\f"]);
+        assert_eq!(fields[1].origin, words[1].location);
+    }
+
+    #[test]
+    fn expand_words_expands_parameters() {
+        let mut env = Env::new_virtual();
+        assign_scalar(&mut env, "foo", "FOO");
+        let words: Vec<Word> = vec!["$foo".parse().unwrap(), "${foo}bar".parse().unwrap()];
+        let fields = block_on(expand_words(&mut env, &words)).unwrap();
+        let values: Vec<_> = fields.iter().map(|f| f.value.as_str()).collect();
+        assert_eq!(values, ["FOO", "FOObar"]);
+    }
+
+    #[test]
+    fn expand_words_performs_pathname_expansion() {
+        let system = yash_env::VirtualSystem::new();
+        let mut state = system.state.borrow_mut();
+        for path in ["/tmp/a.c", "/tmp/b.c", "/tmp/c.h"] {
+            let inode = yash_env::virtual_system::INode::default();
+            state.file_system.save(path, inode).unwrap();
+        }
+        drop(state);
+        let mut env = Env::with_system(Box::new(system));
+        let words: Vec<Word> = vec!["/tmp/*.c".parse().unwrap(), "/tmp/'*'.h".parse().unwrap()];
+        let fields = block_on(expand_words(&mut env, &words)).unwrap();
+        let values: Vec<_> = fields.iter().map(|f| f.value.as_str()).collect();
+        assert_eq!(values, ["/tmp/a.c", "/tmp/b.c", "/tmp/*.h"]);
+    }
+
+    #[test]
+    fn expand_words_removes_empty_fields() {
+        let mut env = Env::new_virtual();
+        let words: Vec<Word> = vec![
+            "$unset".parse().unwrap(),
+            "\"$unset\"".parse().unwrap(),
+            "''".parse().unwrap(),
+        ];
+        let fields = block_on(expand_words(&mut env, &words)).unwrap();
+        let values: Vec<_> = fields.iter().map(|f| f.value.as_str()).collect();
+        assert_eq!(values, ["", ""]);
+        assert_eq!(fields[0].origin, words[1].location);
+        assert_eq!(fields[1].origin, words[2].location);
+    }
+
+    #[test]
+    fn expand_words_splits_fields() {
+        let mut env = Env::new_virtual();
+        assign_scalar(&mut env, "list", " a  b\tc ");
+        let words: Vec<Word> = vec![
+            "$list".parse().unwrap(),
+            "\"$list\"".parse().unwrap(),
+            "x${list}y".parse().unwrap(),
+        ];
+        let fields = block_on(expand_words(&mut env, &words)).unwrap();
+        let values: Vec<_> = fields.iter().map(|f| f.value.as_str()).collect();
+        assert_eq!(
+            values,
+            ["a", "b", "c", " a  b\tc ", "x", "a", "b", "c", "y"]
+        );
+        assert_eq!(fields[2].origin, words[0].location);
+        assert_eq!(fields[3].origin, words[1].location);
+        assert_eq!(fields[8].origin, words[2].location);
+    }
+
+    #[test]
+    fn expand_words_with_custom_ifs() {
+        let mut env = Env::new_virtual();
+        assign_scalar(&mut env, "IFS", ":");
+        assign_scalar(&mut env, "path", "/bin::/usr/bin:");
+        let words: Vec<Word> = vec!["$path".parse().unwrap(), "a:b".parse().unwrap()];
+        let fields = block_on(expand_words(&mut env, &words)).unwrap();
+        let values: Vec<_> = fields.iter().map(|f| f.value.as_str()).collect();
+        assert_eq!(values, ["/bin", "", "/usr/bin", "a:b"]);
+    }
+
+    fn set_positional_params(env: &mut Env, params: &[&str]) {
+        let params = params.iter().map(|&param| param.to_owned()).collect();
+        *env.variables.positional_params_mut() = params;
+    }
+
+    #[test]
+    fn expand_words_expands_quoted_at_to_separate_fields() {
+        let mut env = Env::new_virtual();
+        set_positional_params(&mut env, &["a b", "", "c"]);
+        let words: Vec<Word> = vec!["\"$@\"".parse().unwrap(), "x\"$@\"y".parse().unwrap()];
+        let fields = block_on(expand_words(&mut env, &words)).unwrap();
+        let values: Vec<_> = fields.iter().map(|f| f.value.as_str()).collect();
+        assert_eq!(values, ["a b", "", "c", "xa b", "", "cy"]);
+    }
+
+    #[test]
+    fn expand_words_expands_quoted_at_without_params_to_no_fields() {
+        let mut env = Env::new_virtual();
+        let words: Vec<Word> = vec!["\"$@\"".parse().unwrap(), "\"${@}\"".parse().unwrap()];
+        let fields = block_on(expand_words(&mut env, &words)).unwrap();
+        assert_eq!(fields, []);
+    }
+
+    #[test]
+    fn expand_words_expands_unquoted_at_and_asterisk() {
+        let mut env = Env::new_virtual();
+        set_positional_params(&mut env, &["a b", "", "c"]);
+        let words: Vec<Word> = vec!["$@".parse().unwrap(), "$*".parse().unwrap()];
+        let fields = block_on(expand_words(&mut env, &words)).unwrap();
+        let values: Vec<_> = fields.iter().map(|f| f.value.as_str()).collect();
+        assert_eq!(values, ["a", "b", "c", "a", "b", "c"]);
+    }
+
+    #[test]
+    fn expand_words_joins_quoted_asterisk_with_ifs() {
+        let mut env = Env::new_virtual();
+        set_positional_params(&mut env, &["a", "b", "c"]);
+        let words: Vec<Word> = vec!["\"$*\"".parse().unwrap()];
+        let fields = block_on(expand_words(&mut env, &words)).unwrap();
+        assert_eq!(fields[0].value, "a b c");
+
+        assign_scalar(&mut env, "IFS", ":-");
+        let fields = block_on(expand_words(&mut env, &words)).unwrap();
+        assert_eq!(fields[0].value, "a:b:c");
+
+        assign_scalar(&mut env, "IFS", "");
+        let fields = block_on(expand_words(&mut env, &words)).unwrap();
+        assert_eq!(fields[0].value, "abc");
+    }
+
+    #[test]
+    fn expand_word_keeps_empty_field() {
+        let mut env = Env::new_virtual();
+        let word: Word = "$unset".parse().unwrap();
+        let field = block_on(expand_word(&mut env, &word)).unwrap();
+        assert_eq!(field.value, "");
+        assert_eq!(field.origin, word.location);
+    }
+
+    #[test]
+    fn handle_error_aborts_non_interactive_shell() {
+        let mut env = Env::new_virtual();
+        let error = Error::UnsetParam {
+            name: "x".to_string(),
+            location: Location::dummy("$x"),
+        };
+        let result = handle_error(&mut env, error);
+        assert_eq!(result, Err(Divert::Exit(ExitStatus::ERROR)));
+        assert_eq!(env.exit_status, ExitStatus::ERROR);
+    }
+
+    #[test]
+    fn handle_error_continues_interactive_shell() {
+        let mut env = Env::new_virtual();
+        env.options.set(ShellOption::Interactive, true);
+        let error = Error::UnsetParam {
+            name: "x".to_string(),
+            location: Location::dummy("$x"),
+        };
+        let result = handle_error(&mut env, error);
+        assert_eq!(result, Err(Divert::Interrupt));
+        assert_eq!(env.exit_status, ExitStatus::ERROR);
+    }
+}
@@ -0,0 +1,455 @@
+// This file is part of yash, an extended POSIX shell.
+// Copyright (C) 2021 WATANABE Yuki
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Arithmetic expansion.
+//!
+//! The arithmetic expansion first expands the content of `$((...))` and then
+//! evaluates the result as an arithmetic expression. The expression consists
+//! of integer constants, variables, and operators that have the same
+//! precedence and associativity as in C. All values are signed 64-bit
+//! integers.
+//!
+//! An unset variable evaluates to zero. A variable whose value is not an
+//! integer constant is an error. An assignment operator or increment/decrement
+//! operator updates the variable in the [`VariableSet`].
+//!
+//! [`VariableSet`]: yash_env::variable::VariableSet
+
+mod ast;
+mod token;
+
+use self::ast::Ast;
+use self::ast::BinaryOp;
+use self::ast::PrefixOp;
+use super::Expand;
+use super::Origin;
+use super::Phrase;
+use crate::assign::assign_variable;
+use std::convert::TryFrom;
+use yash_env::expansion::ArithError;
+use yash_env::expansion::Error;
+use yash_env::expansion::Result;
+use yash_env::option::ShellOption;
+use yash_env::variable::Scope;
+use yash_env::variable::Value;
+use yash_env::Env;
+use yash_syntax::source::Location;
+use yash_syntax::syntax::Text;
+
+/// Applies a binary operator to values.
+fn apply_binary(operator: BinaryOp, lhs: i64, rhs: i64) -> std::result::Result<i64, ArithError> {
+    use BinaryOp::*;
+    let result = match operator {
+        BitOr => Some(lhs | rhs),
+        BitXor => Some(lhs ^ rhs),
+        BitAnd => Some(lhs & rhs),
+        Equal => Some((lhs == rhs) as i64),
+        NotEqual => Some((lhs != rhs) as i64),
+        Less => Some((lhs < rhs) as i64),
+        LessEqual => Some((lhs <= rhs) as i64),
+        Greater => Some((lhs > rhs) as i64),
+        GreaterEqual => Some((lhs >= rhs) as i64),
+        ShiftLeft | ShiftRight => {
+            let shift = u32::try_from(rhs)
+                .ok()
+                .filter(|&shift| shift < i64::BITS)
+                .ok_or(ArithError::InvalidShift)?;
+            if operator == ShiftLeft {
+                // checked_shl only checks the shift width, so make sure no
+                // significant bits are lost
+                lhs.checked_shl(shift)
+                    .filter(|&result| result >> shift == lhs)
+            } else {
+                lhs.checked_shr(shift)
+            }
+        }
+        Add => lhs.checked_add(rhs),
+        Subtract => lhs.checked_sub(rhs),
+        Multiply => lhs.checked_mul(rhs),
+        Divide | Remainder if rhs == 0 => return Err(ArithError::DivisionByZero),
+        Divide => lhs.checked_div(rhs),
+        Remainder => lhs.checked_rem(rhs),
+    };
+    result.ok_or(ArithError::Overflow)
+}
+
+/// Evaluator of syntax trees.
+struct Evaluator<'a> {
+    env: &'a mut Env,
+    /// Location of the arithmetic expansion.
+    location: &'a Location,
+}
+
+impl Evaluator<'_> {
+    /// Creates an error with the location of the arithmetic expansion.
+    fn error(&self, cause: ArithError) -> Error {
+        Error::Arith {
+            cause,
+            location: self.location.clone(),
+        }
+    }
+
+    /// Returns the value of a variable.
+    ///
+    /// An unset variable is zero unless the
+    /// [`NoUnset`](ShellOption::NoUnset) option is on.
+    fn get(&self, name: &str) -> Result<i64> {
+        let value = match self.env.variables.get(name).map(|v| &v.value) {
+            None if self.env.options.is_on(ShellOption::NoUnset) => {
+                return Err(Error::UnsetParam {
+                    name: name.to_owned(),
+                    location: self.location.clone(),
+                })
+            }
+            None => return Ok(0),
+            Some(Value::Scalar(value)) => value.trim().to_owned(),
+            Some(Value::Array(values)) => values.join(" "),
+        };
+        if value.is_empty() {
+            return Ok(0);
+        }
+        token::parse_number(&value).map_err(|_| self.error(ArithError::InvalidVariableValue(value)))
+    }
+
+    /// Assigns a value to a variable.
+    fn set(&mut self, name: &str, value: i64) -> Result<()> {
+        assign_variable(
+            self.env,
+            Scope::Global,
+            name.to_owned(),
+            Value::Scalar(value.to_string()),
+            self.location.clone(),
+            false,
+        )?;
+        Ok(())
+    }
+
+    /// Evaluates a syntax tree.
+    ///
+    /// The operands of `&&`, `||`, and `?:` are evaluated only if needed, so
+    /// the side effects of unevaluated operands do not happen.
+    fn eval(&mut self, ast: &Ast) -> Result<i64> {
+        match ast {
+            Ast::Number(number) => Ok(*number),
+            Ast::Variable(name) => self.get(name),
+            Ast::Prefix { operator, operand } => {
+                let operand = self.eval(operand)?;
+                match operator {
+                    PrefixOp::Plus => Ok(operand),
+                    PrefixOp::Minus => operand
+                        .checked_neg()
+                        .ok_or_else(|| self.error(ArithError::Overflow)),
+                    PrefixOp::BitNot => Ok(!operand),
+                    PrefixOp::LogicalNot => Ok((operand == 0) as i64),
+                }
+            }
+            Ast::Increment {
+                name,
+                delta,
+                is_postfix,
+            } => {
+                let old = self.get(name)?;
+                let new = old
+                    .checked_add(*delta)
+                    .ok_or_else(|| self.error(ArithError::Overflow))?;
+                self.set(name, new)?;
+                Ok(if *is_postfix { old } else { new })
+            }
+            Ast::Binary { lhs, operator, rhs } => {
+                let lhs = self.eval(lhs)?;
+                let rhs = self.eval(rhs)?;
+                apply_binary(*operator, lhs, rhs).map_err(|cause| self.error(cause))
+            }
+            Ast::LogicalAnd(lhs, rhs) => Ok((self.eval(lhs)? != 0 && self.eval(rhs)? != 0) as i64),
+            Ast::LogicalOr(lhs, rhs) => Ok((self.eval(lhs)? != 0 || self.eval(rhs)? != 0) as i64),
+            Ast::Conditional {
+                condition,
+                then,
+                r#else,
+            } => {
+                if self.eval(condition)? != 0 {
+                    self.eval(then)
+                } else {
+                    self.eval(r#else)
+                }
+            }
+            Ast::Assign {
+                name,
+                operator,
+                value,
+            } => {
+                let mut value = self.eval(value)?;
+                if let Some(operator) = operator {
+                    let old = self.get(name)?;
+                    value = apply_binary(*operator, old, value).map_err(|c| self.error(c))?;
+                }
+                self.set(name, value)?;
+                Ok(value)
+            }
+            Ast::Comma(lhs, rhs) => {
+                self.eval(lhs)?;
+                self.eval(rhs)
+            }
+        }
+    }
+}
+
+/// Evaluates an arithmetic expression.
+///
+/// An empty expression evaluates to zero.
+pub fn eval(env: &mut Env, expression: &str, location: &Location) -> Result<i64> {
+    let to_error = |cause| Error::Arith {
+        cause,
+        location: location.clone(),
+    };
+    let tokens = token::tokenize(expression).map_err(to_error)?;
+    if tokens.is_empty() {
+        return Ok(0);
+    }
+    let ast = ast::parse(tokens).map_err(to_error)?;
+    Evaluator { env, location }.eval(&ast)
+}
+
+/// Performs arithmetic expansion.
+///
+/// The content is expanded and the quotes are removed before the evaluation.
+pub async fn expand(env: &mut Env, content: &Text, location: &Location) -> Result<Phrase> {
+    let chars = content.expand(env).await?.join(None);
+    let expression: String = chars
+        .iter()
+        .filter(|c| !c.is_quoting)
+        .map(|c| c.value)
+        .collect();
+    let value = eval(env, &expression, location)?;
+    Ok(Phrase::with_str(&value.to_string(), Origin::SoftExpansion))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expansion::tests::assign_scalar;
+    use futures::executor::block_on;
+    use yash_env::variable::Variable;
+
+    fn eval_str(env: &mut Env, expression: &str) -> Result<i64> {
+        eval(env, expression, &Location::dummy(expression))
+    }
+
+    fn cause(result: Result<i64>) -> ArithError {
+        match result {
+            Err(Error::Arith { cause, .. }) => cause,
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+
+    fn scalar(env: &Env, name: &str) -> Option<String> {
+        match &env.variables.get(name)?.value {
+            Value::Scalar(value) => Some(value.clone()),
+            Value::Array(_) => None,
+        }
+    }
+
+    #[test]
+    fn constants() {
+        let mut env = Env::new_virtual();
+        assert_eq!(eval_str(&mut env, ""), Ok(0));
+        assert_eq!(eval_str(&mut env, " 42 "), Ok(42));
+        assert_eq!(eval_str(&mut env, "010"), Ok(8));
+        assert_eq!(eval_str(&mut env, "0x10"), Ok(16));
+    }
+
+    #[test]
+    fn operators() {
+        let mut env = Env::new_virtual();
+        assert_eq!(eval_str(&mut env, "1 + 2 * 3 - 4 / 2"), Ok(5));
+        assert_eq!(eval_str(&mut env, "-7 % 3"), Ok(-1));
+        assert_eq!(eval_str(&mut env, "1 << 4 | 3 & 6 ^ 1"), Ok(19));
+        assert_eq!(eval_str(&mut env, "-16 >> 2"), Ok(-4));
+        assert_eq!(eval_str(&mut env, "-1 << 63"), Ok(i64::MIN));
+        assert_eq!(eval_str(&mut env, "~0 + !0 + !5"), Ok(0));
+        assert_eq!(
+            eval_str(&mut env, "(1 < 2) + (2 <= 2) + (1 > 2) + (3 >= 4)"),
+            Ok(2)
+        );
+        assert_eq!(eval_str(&mut env, "(1 == 1) + (1 != 1)"), Ok(1));
+        assert_eq!(eval_str(&mut env, "2 && 3 || 0"), Ok(1));
+        assert_eq!(eval_str(&mut env, "0 ? 1 : 2 ? 3 : 4"), Ok(3));
+        assert_eq!(eval_str(&mut env, "1, 2, 3"), Ok(3));
+    }
+
+    #[test]
+    fn variables() {
+        let mut env = Env::new_virtual();
+        assign_scalar(&mut env, "x", "12");
+        assign_scalar(&mut env, "hex", "0x1f");
+        assign_scalar(&mut env, "empty", "");
+        assert_eq!(eval_str(&mut env, "x * 2"), Ok(24));
+        assert_eq!(eval_str(&mut env, "hex"), Ok(31));
+        assert_eq!(eval_str(&mut env, "empty + unset"), Ok(0));
+
+        assign_scalar(&mut env, "bad", "1 + 1");
+        assert_eq!(
+            cause(eval_str(&mut env, "bad")),
+            ArithError::InvalidVariableValue("1 + 1".to_string())
+        );
+    }
+
+    #[test]
+    fn unset_variable_with_nounset_is_error() {
+        let mut env = Env::new_virtual();
+        env.options.set(ShellOption::NoUnset, true);
+        assign_scalar(&mut env, "empty", "");
+        assert_eq!(eval_str(&mut env, "empty"), Ok(0));
+        assert_eq!(
+            eval_str(&mut env, "unset"),
+            Err(Error::UnsetParam {
+                name: "unset".to_string(),
+                location: Location::dummy("unset"),
+            })
+        );
+    }
+
+    #[test]
+    fn assignments() {
+        let mut env = Env::new_virtual();
+        assert_eq!(eval_str(&mut env, "a = 3"), Ok(3));
+        assert_eq!(scalar(&env, "a").unwrap(), "3");
+        assert_eq!(eval_str(&mut env, "a += 4"), Ok(7));
+        assert_eq!(eval_str(&mut env, "a <<= 2"), Ok(28));
+        assert_eq!(eval_str(&mut env, "a %= 5"), Ok(3));
+        assert_eq!(eval_str(&mut env, "b = a *= 2"), Ok(6));
+        assert_eq!(scalar(&env, "a").unwrap(), "6");
+        assert_eq!(scalar(&env, "b").unwrap(), "6");
+    }
+
+    #[test]
+    fn increments_and_decrements() {
+        let mut env = Env::new_virtual();
+        assert_eq!(eval_str(&mut env, "i++"), Ok(0));
+        assert_eq!(scalar(&env, "i").unwrap(), "1");
+        assert_eq!(eval_str(&mut env, "++i"), Ok(2));
+        assert_eq!(eval_str(&mut env, "i--"), Ok(2));
+        assert_eq!(eval_str(&mut env, "--i"), Ok(0));
+        assert_eq!(scalar(&env, "i").unwrap(), "0");
+    }
+
+    #[test]
+    fn short_circuit_evaluation() {
+        let mut env = Env::new_virtual();
+        assert_eq!(eval_str(&mut env, "0 && (a = 1)"), Ok(0));
+        assert_eq!(eval_str(&mut env, "1 || (b = 1)"), Ok(1));
+        assert_eq!(eval_str(&mut env, "1 ? 2 : (c = 1)"), Ok(2));
+        assert_eq!(eval_str(&mut env, "0 ? 1 / 0 : 5"), Ok(5));
+        assert_eq!(env.variables.get("a"), None);
+        assert_eq!(env.variables.get("b"), None);
+        assert_eq!(env.variables.get("c"), None);
+    }
+
+    #[test]
+    fn arithmetic_errors() {
+        let mut env = Env::new_virtual();
+        assert_eq!(
+            cause(eval_str(&mut env, "1 / 0")),
+            ArithError::DivisionByZero
+        );
+        assert_eq!(
+            cause(eval_str(&mut env, "1 % 0")),
+            ArithError::DivisionByZero
+        );
+        assert_eq!(
+            cause(eval_str(&mut env, "9223372036854775807 + 1")),
+            ArithError::Overflow
+        );
+        assert_eq!(
+            cause(eval_str(&mut env, "(-9223372036854775807 - 1) / -1")),
+            ArithError::Overflow
+        );
+        assert_eq!(
+            cause(eval_str(&mut env, "1 << 64")),
+            ArithError::InvalidShift
+        );
+        assert_eq!(
+            cause(eval_str(&mut env, "1 << 62 << 2")),
+            ArithError::Overflow
+        );
+        assert_eq!(cause(eval_str(&mut env, "1 << 63")), ArithError::Overflow);
+        assert_eq!(eval_str(&mut env, "a = 3"), Ok(3));
+        assert_eq!(cause(eval_str(&mut env, "a <<= 62")), ArithError::Overflow);
+        assert_eq!(scalar(&env, "a").unwrap(), "3");
+        assert_eq!(
+            cause(eval_str(&mut env, "1 >> -1")),
+            ArithError::InvalidShift
+        );
+        assert_eq!(
+            cause(eval_str(&mut env, "1 +")),
+            ArithError::IncompleteExpression
+        );
+        assert_eq!(
+            cause(eval_str(&mut env, "1 # 1")),
+            ArithError::InvalidCharacter
+        );
+    }
+
+    #[test]
+    fn error_location() {
+        let mut env = Env::new_virtual();
+        let location = Location::dummy("$((1/0))");
+        let result = eval(&mut env, "1/0", &location);
+        assert_eq!(
+            result,
+            Err(Error::Arith {
+                cause: ArithError::DivisionByZero,
+                location,
+            })
+        );
+    }
+
+    #[test]
+    fn assignment_to_read_only_variable() {
+        let mut env = Env::new_virtual();
+        let read_only_location = Location::dummy("readonly");
+        env.variables.assign(
+            Scope::Global,
+            "r".to_string(),
+            Variable {
+                value: Value::Scalar("1".to_string()),
+                last_assigned_location: None,
+                is_exported: false,
+                read_only_location: Some(read_only_location.clone()),
+            },
+        );
+        let location = Location::dummy("r=2");
+        let result = eval(&mut env, "r=2", &location);
+        assert_eq!(
+            result,
+            Err(Error::AssignReadOnly {
+                name: "r".to_string(),
+                read_only_location,
+                location,
+            })
+        );
+    }
+
+    #[test]
+    fn expansion_of_content() {
+        let mut env = Env::new_virtual();
+        assign_scalar(&mut env, "n", "3");
+        let text: Text = "$n * 2 + 1".parse().unwrap();
+        let location = Location::dummy("");
+        let phrase = block_on(expand(&mut env, &text, &location)).unwrap();
+        assert_eq!(phrase, Phrase::with_str("7", Origin::SoftExpansion));
+    }
+}
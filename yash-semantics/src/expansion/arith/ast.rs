@@ -0,0 +1,519 @@
+// This file is part of yash, an extended POSIX shell.
+// Copyright (C) 2021 WATANABE Yuki
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Syntax tree of arithmetic expressions.
+
+use super::token::Operator;
+use super::token::Token;
+use std::iter::Peekable;
+use yash_env::expansion::ArithError;
+
+/// Prefix operator that is not an increment or decrement.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum PrefixOp {
+    /// `+`
+    Plus,
+    /// `-`
+    Minus,
+    /// `~`
+    BitNot,
+    /// `!`
+    LogicalNot,
+}
+
+/// Binary operator that is not a logical operator.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum BinaryOp {
+    /// `|`
+    BitOr,
+    /// `^`
+    BitXor,
+    /// `&`
+    BitAnd,
+    /// `==`
+    Equal,
+    /// `!=`
+    NotEqual,
+    /// `<`
+    Less,
+    /// `<=`
+    LessEqual,
+    /// `>`
+    Greater,
+    /// `>=`
+    GreaterEqual,
+    /// `<<`
+    ShiftLeft,
+    /// `>>`
+    ShiftRight,
+    /// `+`
+    Add,
+    /// `-`
+    Subtract,
+    /// `*`
+    Multiply,
+    /// `/`
+    Divide,
+    /// `%`
+    Remainder,
+}
+
+/// Node of the syntax tree.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Ast {
+    /// Integer constant.
+    Number(i64),
+    /// Variable.
+    Variable(String),
+    /// Prefix operator applied to an operand.
+    Prefix {
+        operator: PrefixOp,
+        operand: Box<Ast>,
+    },
+    /// Increment or decrement of a variable: `++x`, `--x`, `x++`, or `x--`.
+    Increment {
+        /// Name of the variable.
+        name: String,
+        /// Value added to the variable: `1` or `-1`.
+        delta: i64,
+        /// Whether the result is the value before the increment.
+        is_postfix: bool,
+    },
+    /// Binary operator applied to operands.
+    Binary {
+        lhs: Box<Ast>,
+        operator: BinaryOp,
+        rhs: Box<Ast>,
+    },
+    /// `&&`
+    LogicalAnd(Box<Ast>, Box<Ast>),
+    /// `||`
+    LogicalOr(Box<Ast>, Box<Ast>),
+    /// `?:`
+    Conditional {
+        condition: Box<Ast>,
+        then: Box<Ast>,
+        r#else: Box<Ast>,
+    },
+    /// Assignment to a variable.
+    Assign {
+        /// Name of the variable.
+        name: String,
+        /// Operator of a compound assignment like `+=`. `None` for `=`.
+        operator: Option<BinaryOp>,
+        /// Right-hand-side operand.
+        value: Box<Ast>,
+    },
+    /// `,`
+    Comma(Box<Ast>, Box<Ast>),
+}
+
+/// Binary operator appearing in the operator-precedence part of the grammar.
+enum Binary {
+    Normal(BinaryOp),
+    LogicalAnd,
+    LogicalOr,
+}
+
+/// Returns the binary operator and its precedence.
+///
+/// A larger number means a higher precedence.
+fn binary_operator(operator: Operator) -> Option<(Binary, u8)> {
+    use Operator::*;
+    let result = match operator {
+        BarBar => (Binary::LogicalOr, 1),
+        AndAnd => (Binary::LogicalAnd, 2),
+        Bar => (Binary::Normal(BinaryOp::BitOr), 3),
+        Caret => (Binary::Normal(BinaryOp::BitXor), 4),
+        And => (Binary::Normal(BinaryOp::BitAnd), 5),
+        EqualEqual => (Binary::Normal(BinaryOp::Equal), 6),
+        BangEqual => (Binary::Normal(BinaryOp::NotEqual), 6),
+        Less => (Binary::Normal(BinaryOp::Less), 7),
+        LessEqual => (Binary::Normal(BinaryOp::LessEqual), 7),
+        Greater => (Binary::Normal(BinaryOp::Greater), 7),
+        GreaterEqual => (Binary::Normal(BinaryOp::GreaterEqual), 7),
+        LessLess => (Binary::Normal(BinaryOp::ShiftLeft), 8),
+        GreaterGreater => (Binary::Normal(BinaryOp::ShiftRight), 8),
+        Plus => (Binary::Normal(BinaryOp::Add), 9),
+        Minus => (Binary::Normal(BinaryOp::Subtract), 9),
+        Asterisk => (Binary::Normal(BinaryOp::Multiply), 10),
+        Slash => (Binary::Normal(BinaryOp::Divide), 10),
+        Percent => (Binary::Normal(BinaryOp::Remainder), 10),
+        _ => return None,
+    };
+    Some(result)
+}
+
+/// Returns the operator of an assignment.
+///
+/// The outer `None` means the operator is not an assignment. The inner `None`
+/// means a simple assignment (`=`).
+fn assignment_operator(operator: Operator) -> Option<Option<BinaryOp>> {
+    use Operator::*;
+    let result = match operator {
+        Equal => None,
+        BarEqual => Some(BinaryOp::BitOr),
+        CaretEqual => Some(BinaryOp::BitXor),
+        AndEqual => Some(BinaryOp::BitAnd),
+        LessLessEqual => Some(BinaryOp::ShiftLeft),
+        GreaterGreaterEqual => Some(BinaryOp::ShiftRight),
+        PlusEqual => Some(BinaryOp::Add),
+        MinusEqual => Some(BinaryOp::Subtract),
+        AsteriskEqual => Some(BinaryOp::Multiply),
+        SlashEqual => Some(BinaryOp::Divide),
+        PercentEqual => Some(BinaryOp::Remainder),
+        _ => return None,
+    };
+    Some(result)
+}
+
+/// Returns the name of the variable if the node is a variable.
+fn into_variable_name(ast: Ast) -> Result<String, ArithError> {
+    match ast {
+        Ast::Variable(name) => Ok(name),
+        _ => Err(ArithError::AssignmentToValue),
+    }
+}
+
+/// Recursive descent parser for arithmetic expressions.
+struct Parser<I: Iterator<Item = Token>> {
+    tokens: Peekable<I>,
+}
+
+impl<I: Iterator<Item = Token>> Parser<I> {
+    /// Consumes the next token if it is the given operator.
+    fn skip_operator(&mut self, operator: Operator) -> bool {
+        self.tokens.next_if_eq(&Token::Operator(operator)).is_some()
+    }
+
+    /// Returns the next token if it is an operator.
+    fn peek_operator(&mut self) -> Option<Operator> {
+        match self.tokens.peek() {
+            Some(Token::Operator(operator)) => Some(*operator),
+            _ => None,
+        }
+    }
+
+    /// Parses a comma-separated expression.
+    fn expression(&mut self) -> Result<Ast, ArithError> {
+        let mut lhs = self.assignment()?;
+        while self.skip_operator(Operator::Comma) {
+            let rhs = self.assignment()?;
+            lhs = Ast::Comma(Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    /// Parses an assignment expression.
+    ///
+    /// Assignment operators are right-associative.
+    fn assignment(&mut self) -> Result<Ast, ArithError> {
+        let lhs = self.conditional()?;
+        let operator = match self.peek_operator().and_then(assignment_operator) {
+            Some(operator) => operator,
+            None => return Ok(lhs),
+        };
+        self.tokens.next();
+        let name = into_variable_name(lhs)?;
+        let value = Box::new(self.assignment()?);
+        Ok(Ast::Assign {
+            name,
+            operator,
+            value,
+        })
+    }
+
+    /// Parses a conditional expression.
+    fn conditional(&mut self) -> Result<Ast, ArithError> {
+        let condition = self.binary(1)?;
+        if !self.skip_operator(Operator::Question) {
+            return Ok(condition);
+        }
+        let then = self.expression()?;
+        if !self.skip_operator(Operator::Colon) {
+            return Err(ArithError::QuestionWithoutColon);
+        }
+        let r#else = self.conditional()?;
+        Ok(Ast::Conditional {
+            condition: Box::new(condition),
+            then: Box::new(then),
+            r#else: Box::new(r#else),
+        })
+    }
+
+    /// Parses binary operators having at least the given precedence.
+    ///
+    /// All binary operators are left-associative.
+    fn binary(&mut self, min_precedence: u8) -> Result<Ast, ArithError> {
+        let mut lhs = self.unary()?;
+        while let Some((operator, precedence)) = self.peek_operator().and_then(binary_operator) {
+            if precedence < min_precedence {
+                break;
+            }
+            self.tokens.next();
+            let rhs = Box::new(self.binary(precedence + 1)?);
+            let lhs_box = Box::new(lhs);
+            lhs = match operator {
+                Binary::Normal(operator) => Ast::Binary {
+                    lhs: lhs_box,
+                    operator,
+                    rhs,
+                },
+                Binary::LogicalAnd => Ast::LogicalAnd(lhs_box, rhs),
+                Binary::LogicalOr => Ast::LogicalOr(lhs_box, rhs),
+            };
+        }
+        Ok(lhs)
+    }
+
+    /// Parses a prefix operator and its operand.
+    fn unary(&mut self) -> Result<Ast, ArithError> {
+        let operator = match self.peek_operator() {
+            Some(Operator::Plus) => PrefixOp::Plus,
+            Some(Operator::Minus) => PrefixOp::Minus,
+            Some(Operator::Tilde) => PrefixOp::BitNot,
+            Some(Operator::Bang) => PrefixOp::LogicalNot,
+            Some(Operator::PlusPlus) | Some(Operator::MinusMinus) => {
+                let delta = if self.skip_operator(Operator::PlusPlus) {
+                    1
+                } else {
+                    self.tokens.next();
+                    -1
+                };
+                let name = into_variable_name(self.unary()?)?;
+                return Ok(Ast::Increment {
+                    name,
+                    delta,
+                    is_postfix: false,
+                });
+            }
+            _ => return self.postfix(),
+        };
+        self.tokens.next();
+        let operand = Box::new(self.unary()?);
+        Ok(Ast::Prefix { operator, operand })
+    }
+
+    /// Parses a primary expression optionally followed by postfix operators.
+    fn postfix(&mut self) -> Result<Ast, ArithError> {
+        let mut ast = self.primary()?;
+        loop {
+            let delta = if self.skip_operator(Operator::PlusPlus) {
+                1
+            } else if self.skip_operator(Operator::MinusMinus) {
+                -1
+            } else {
+                return Ok(ast);
+            };
+            let name = into_variable_name(ast)?;
+            ast = Ast::Increment {
+                name,
+                delta,
+                is_postfix: true,
+            };
+        }
+    }
+
+    /// Parses a number, variable, or parenthesized expression.
+    fn primary(&mut self) -> Result<Ast, ArithError> {
+        match self.tokens.next() {
+            Some(Token::Number(number)) => Ok(Ast::Number(number)),
+            Some(Token::Variable(name)) => Ok(Ast::Variable(name)),
+            Some(Token::Operator(Operator::OpenParen)) => {
+                let ast = self.expression()?;
+                if self.skip_operator(Operator::CloseParen) {
+                    Ok(ast)
+                } else {
+                    Err(ArithError::UnclosedParenthesis)
+                }
+            }
+            Some(Token::Operator(Operator::Colon)) => Err(ArithError::ColonWithoutQuestion),
+            Some(Token::Operator(_)) | None => Err(ArithError::IncompleteExpression),
+        }
+    }
+}
+
+/// Parses tokens into a syntax tree.
+///
+/// All the tokens must be consumed to make a single expression.
+pub fn parse<I: IntoIterator<Item = Token>>(tokens: I) -> Result<Ast, ArithError> {
+    let mut parser = Parser {
+        tokens: tokens.into_iter().peekable(),
+    };
+    let ast = parser.expression()?;
+    match parser.tokens.next() {
+        None => Ok(ast),
+        Some(Token::Operator(Operator::Colon)) => Err(ArithError::ColonWithoutQuestion),
+        Some(_) => Err(ArithError::MissingOperator),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::token::tokenize;
+    use super::*;
+
+    fn parse_str(s: &str) -> Result<Ast, ArithError> {
+        parse(tokenize(s).unwrap())
+    }
+
+    fn num(n: i64) -> Box<Ast> {
+        Box::new(Ast::Number(n))
+    }
+
+    fn var(name: &str) -> Box<Ast> {
+        Box::new(Ast::Variable(name.to_string()))
+    }
+
+    #[test]
+    fn precedence_of_binary_operators() {
+        assert_eq!(
+            parse_str("1 + 2 * 3"),
+            Ok(Ast::Binary {
+                lhs: num(1),
+                operator: BinaryOp::Add,
+                rhs: Box::new(Ast::Binary {
+                    lhs: num(2),
+                    operator: BinaryOp::Multiply,
+                    rhs: num(3),
+                }),
+            })
+        );
+        assert_eq!(
+            parse_str("1 || 2 && 3"),
+            Ok(Ast::LogicalOr(
+                num(1),
+                Box::new(Ast::LogicalAnd(num(2), num(3)))
+            ))
+        );
+    }
+
+    #[test]
+    fn left_associativity_of_binary_operators() {
+        assert_eq!(
+            parse_str("1 - 2 - 3"),
+            Ok(Ast::Binary {
+                lhs: Box::new(Ast::Binary {
+                    lhs: num(1),
+                    operator: BinaryOp::Subtract,
+                    rhs: num(2),
+                }),
+                operator: BinaryOp::Subtract,
+                rhs: num(3),
+            })
+        );
+    }
+
+    #[test]
+    fn right_associativity_of_assignments() {
+        assert_eq!(
+            parse_str("a = b += 1"),
+            Ok(Ast::Assign {
+                name: "a".to_string(),
+                operator: None,
+                value: Box::new(Ast::Assign {
+                    name: "b".to_string(),
+                    operator: Some(BinaryOp::Add),
+                    value: num(1),
+                }),
+            })
+        );
+    }
+
+    #[test]
+    fn conditional_and_comma() {
+        assert_eq!(
+            parse_str("a ? b : c ? 1 : 2, 3"),
+            Ok(Ast::Comma(
+                Box::new(Ast::Conditional {
+                    condition: var("a"),
+                    then: var("b"),
+                    r#else: Box::new(Ast::Conditional {
+                        condition: var("c"),
+                        then: num(1),
+                        r#else: num(2),
+                    }),
+                }),
+                num(3)
+            ))
+        );
+    }
+
+    #[test]
+    fn unary_operators() {
+        assert_eq!(
+            parse_str("-~!x++"),
+            Ok(Ast::Prefix {
+                operator: PrefixOp::Minus,
+                operand: Box::new(Ast::Prefix {
+                    operator: PrefixOp::BitNot,
+                    operand: Box::new(Ast::Prefix {
+                        operator: PrefixOp::LogicalNot,
+                        operand: Box::new(Ast::Increment {
+                            name: "x".to_string(),
+                            delta: 1,
+                            is_postfix: true,
+                        }),
+                    }),
+                }),
+            })
+        );
+        assert_eq!(
+            parse_str("--x"),
+            Ok(Ast::Increment {
+                name: "x".to_string(),
+                delta: -1,
+                is_postfix: false,
+            })
+        );
+    }
+
+    #[test]
+    fn parentheses() {
+        assert_eq!(
+            parse_str("(1 + 2) * 3"),
+            Ok(Ast::Binary {
+                lhs: Box::new(Ast::Binary {
+                    lhs: num(1),
+                    operator: BinaryOp::Add,
+                    rhs: num(2),
+                }),
+                operator: BinaryOp::Multiply,
+                rhs: num(3),
+            })
+        );
+    }
+
+    #[test]
+    fn syntax_errors() {
+        assert_eq!(parse_str("1 +"), Err(ArithError::IncompleteExpression));
+        assert_eq!(parse_str("1 2"), Err(ArithError::MissingOperator));
+        assert_eq!(parse_str("(1"), Err(ArithError::UnclosedParenthesis));
+        assert_eq!(parse_str("1)"), Err(ArithError::MissingOperator));
+        assert_eq!(parse_str("1 ? 2"), Err(ArithError::QuestionWithoutColon));
+        assert_eq!(parse_str("1 : 2"), Err(ArithError::ColonWithoutQuestion));
+        assert_eq!(parse_str("1 = 2"), Err(ArithError::AssignmentToValue));
+        assert_eq!(parse_str("1++"), Err(ArithError::AssignmentToValue));
+        assert_eq!(
+            parse_str("++(x)"),
+            Ok(Ast::Increment {
+                name: "x".to_string(),
+                delta: 1,
+                is_postfix: false,
+            })
+        );
+    }
+}
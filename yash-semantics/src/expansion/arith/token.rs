@@ -0,0 +1,256 @@
+// This file is part of yash, an extended POSIX shell.
+// Copyright (C) 2021 WATANABE Yuki
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Tokenization of arithmetic expressions.
+
+use yash_env::expansion::ArithError;
+
+/// Operator token.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Operator {
+    /// `(`
+    OpenParen,
+    /// `)`
+    CloseParen,
+    /// `?`
+    Question,
+    /// `:`
+    Colon,
+    /// `,`
+    Comma,
+    /// `=`
+    Equal,
+    /// `|=`
+    BarEqual,
+    /// `^=`
+    CaretEqual,
+    /// `&=`
+    AndEqual,
+    /// `<<=`
+    LessLessEqual,
+    /// `>>=`
+    GreaterGreaterEqual,
+    /// `+=`
+    PlusEqual,
+    /// `-=`
+    MinusEqual,
+    /// `*=`
+    AsteriskEqual,
+    /// `/=`
+    SlashEqual,
+    /// `%=`
+    PercentEqual,
+    /// `||`
+    BarBar,
+    /// `&&`
+    AndAnd,
+    /// `|`
+    Bar,
+    /// `^`
+    Caret,
+    /// `&`
+    And,
+    /// `==`
+    EqualEqual,
+    /// `!=`
+    BangEqual,
+    /// `<`
+    Less,
+    /// `<=`
+    LessEqual,
+    /// `>`
+    Greater,
+    /// `>=`
+    GreaterEqual,
+    /// `<<`
+    LessLess,
+    /// `>>`
+    GreaterGreater,
+    /// `+`
+    Plus,
+    /// `-`
+    Minus,
+    /// `*`
+    Asterisk,
+    /// `/`
+    Slash,
+    /// `%`
+    Percent,
+    /// `~`
+    Tilde,
+    /// `!`
+    Bang,
+    /// `++`
+    PlusPlus,
+    /// `--`
+    MinusMinus,
+}
+
+/// Operators sorted so that a longer operator precedes its prefixes.
+const OPERATORS: &[(&str, Operator)] = {
+    use Operator::*;
+    &[
+        ("<<=", LessLessEqual),
+        (">>=", GreaterGreaterEqual),
+        ("|=", BarEqual),
+        ("^=", CaretEqual),
+        ("&=", AndEqual),
+        ("+=", PlusEqual),
+        ("-=", MinusEqual),
+        ("*=", AsteriskEqual),
+        ("/=", SlashEqual),
+        ("%=", PercentEqual),
+        ("||", BarBar),
+        ("&&", AndAnd),
+        ("==", EqualEqual),
+        ("!=", BangEqual),
+        ("<=", LessEqual),
+        (">=", GreaterEqual),
+        ("<<", LessLess),
+        (">>", GreaterGreater),
+        ("++", PlusPlus),
+        ("--", MinusMinus),
+        ("(", OpenParen),
+        (")", CloseParen),
+        ("?", Question),
+        (":", Colon),
+        (",", Comma),
+        ("=", Equal),
+        ("|", Bar),
+        ("^", Caret),
+        ("&", And),
+        ("<", Less),
+        (">", Greater),
+        ("+", Plus),
+        ("-", Minus),
+        ("*", Asterisk),
+        ("/", Slash),
+        ("%", Percent),
+        ("~", Tilde),
+        ("!", Bang),
+    ]
+};
+
+/// Token of arithmetic expressions.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Token {
+    /// Integer constant.
+    Number(i64),
+    /// Variable name.
+    Variable(String),
+    /// Operator.
+    Operator(Operator),
+}
+
+/// Parses an integer constant.
+///
+/// The constant may be decimal, octal (with a leading `0`), or hexadecimal
+/// (with a leading `0x` or `0X`).
+pub fn parse_number(s: &str) -> Result<i64, ArithError> {
+    let (digits, radix) = if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        (hex, 16)
+    } else if s.len() > 1 && s.starts_with('0') {
+        (&s[1..], 8)
+    } else {
+        (s, 10)
+    };
+
+    if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+        return Err(ArithError::InvalidNumericConstant);
+    }
+    i64::from_str_radix(digits, radix).map_err(|_| ArithError::Overflow)
+}
+
+/// Tests if the character can be part of a number or variable name.
+fn is_word_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// Splits an arithmetic expression into tokens.
+pub fn tokenize(expression: &str) -> Result<Vec<Token>, ArithError> {
+    let mut tokens = Vec::new();
+    let mut rest = expression.trim_start();
+    while let Some(c) = rest.chars().next() {
+        if is_word_char(c) {
+            let len = rest.find(|c| !is_word_char(c)).unwrap_or(rest.len());
+            let word = &rest[..len];
+            rest = &rest[len..];
+            if c.is_ascii_digit() {
+                tokens.push(Token::Number(parse_number(word)?));
+            } else {
+                tokens.push(Token::Variable(word.to_owned()));
+            }
+        } else if let Some(&(op, operator)) = OPERATORS.iter().find(|(op, _)| rest.starts_with(op))
+        {
+            rest = &rest[op.len()..];
+            tokens.push(Token::Operator(operator));
+        } else {
+            return Err(ArithError::InvalidCharacter);
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decimal_octal_and_hexadecimal_numbers() {
+        assert_eq!(parse_number("0"), Ok(0));
+        assert_eq!(parse_number("42"), Ok(42));
+        assert_eq!(parse_number("017"), Ok(15));
+        assert_eq!(parse_number("0x1F"), Ok(31));
+        assert_eq!(parse_number("0Xff"), Ok(255));
+    }
+
+    #[test]
+    fn invalid_numbers() {
+        assert_eq!(parse_number("08"), Err(ArithError::InvalidNumericConstant));
+        assert_eq!(parse_number("0x"), Err(ArithError::InvalidNumericConstant));
+        assert_eq!(parse_number("1a"), Err(ArithError::InvalidNumericConstant));
+        assert_eq!(
+            parse_number("9223372036854775808"),
+            Err(ArithError::Overflow)
+        );
+    }
+
+    #[test]
+    fn tokens() {
+        use Operator::*;
+        assert_eq!(
+            tokenize(" a+++0x10 <<= (b_1) "),
+            Ok(vec![
+                Token::Variable("a".to_string()),
+                Token::Operator(PlusPlus),
+                Token::Operator(Plus),
+                Token::Number(16),
+                Token::Operator(LessLessEqual),
+                Token::Operator(OpenParen),
+                Token::Variable("b_1".to_string()),
+                Token::Operator(CloseParen),
+            ])
+        );
+        assert_eq!(tokenize(""), Ok(vec![]));
+    }
+
+    #[test]
+    fn invalid_character() {
+        assert_eq!(tokenize("1 @ 2"), Err(ArithError::InvalidCharacter));
+        assert_eq!(tokenize("1 $ 2"), Err(ArithError::InvalidCharacter));
+    }
+}
@@ -0,0 +1,211 @@
+// This file is part of yash, an extended POSIX shell.
+// Copyright (C) 2021 WATANABE Yuki
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Command substitution.
+//!
+//! The command substitution executes a command in a subshell and substitutes
+//! the standard output of the subshell. The output is read through a pipe.
+//! Trailing newlines are removed from the output.
+
+use super::Origin;
+use super::Phrase;
+use crate::read_eval_loop;
+use nix::errno::Errno;
+use std::os::unix::io::RawFd;
+use yash_env::exec::Divert;
+use yash_env::expansion::Error;
+use yash_env::expansion::Result;
+use yash_env::io::move_fd;
+use yash_env::io::STDOUT;
+use yash_env::option::ShellOption;
+use yash_env::Env;
+use yash_syntax::parser::lex::Lexer;
+use yash_syntax::source::Location;
+use yash_syntax::source::Source;
+use yash_syntax::syntax::BackquoteUnit;
+
+/// Converts the content of a backquoted command substitution to a string.
+///
+/// Backslash-escaped characters are unescaped.
+pub fn unescape_backquote(content: &[BackquoteUnit]) -> String {
+    content
+        .iter()
+        .map(|unit| match *unit {
+            BackquoteUnit::Literal(c) | BackquoteUnit::Backslashed(c) => c,
+        })
+        .collect()
+}
+
+/// Removes trailing newlines from the string.
+fn remove_trailing_newlines(s: &mut String) {
+    let len = s.trim_end_matches('\n').len();
+    s.truncate(len);
+}
+
+/// Reads the whole content of the file descriptor until the end of file.
+async fn read_all(env: &mut Env, fd: RawFd) -> nix::Result<Vec<u8>> {
+    let mut content = Vec::new();
+    let mut buffer = [0; 1024];
+    loop {
+        match env.system.read_async(fd, &mut buffer).await {
+            Ok(0) => return Ok(content),
+            Ok(count) => content.extend_from_slice(&buffer[..count]),
+            Err(nix::Error::Sys(Errno::EINTR)) => (),
+            Err(error) => return Err(error),
+        }
+    }
+}
+
+/// Performs command substitution.
+///
+/// The `content` is parsed and executed in a subshell. The source of the
+/// parsed code is [`Source::CommandSubst`] with the `location`.
+///
+/// The subshell is not interactive even if the current shell is, so an error
+/// in the subshell makes it exit.
+///
+/// The exit status of the subshell is saved in
+/// [`Env::last_command_subst_exit_status`].
+pub async fn expand(env: &mut Env, content: String, location: &Location) -> Result<Phrase> {
+    let to_error = |error: nix::Error| Error::CommandSubst {
+        errno: error.as_errno().unwrap_or(Errno::EINVAL),
+        location: location.clone(),
+    };
+
+    let (reader, writer) = env.system.pipe().map_err(to_error)?;
+
+    let original = location.clone();
+    let child = env
+        .start_subshell(move |env| {
+            Box::pin(async move {
+                // TODO Report errors in redirecting the standard output
+                let _ = env.system.close(reader);
+                let _ = move_fd(&mut *env.system, writer, STDOUT);
+                env.options.set(ShellOption::Interactive, false);
+
+                let mut lexer = Lexer::with_source(Source::CommandSubst { original }, &content);
+                if let Err(Divert::Exit(exit_status)) = read_eval_loop(env, &mut lexer).await {
+                    env.exit_status = exit_status;
+                }
+            })
+        })
+        .await;
+
+    let _ = env.system.close(writer);
+    let child = match child {
+        Ok(child) => child,
+        Err(error) => {
+            let _ = env.system.close(reader);
+            return Err(to_error(error));
+        }
+    };
+
+    let output = read_all(env, reader).await;
+    let _ = env.system.close(reader);
+    let exit_status = env.wait_for_subshell(child).await.map_err(to_error)?;
+    env.last_command_subst_exit_status = Some(exit_status);
+    let output = output.map_err(to_error)?;
+
+    // TODO Support non-UTF-8 output
+    let mut output = String::from_utf8_lossy(&output).into_owned();
+    remove_trailing_newlines(&mut output);
+    Ok(Phrase::with_str(&output, Origin::SoftExpansion))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::echo_builtin;
+    use futures::executor::block_on;
+    use futures::executor::LocalPool;
+    use std::rc::Rc;
+    use yash_env::exec::ExitStatus;
+    use yash_env::virtual_system::VirtualSystem;
+
+    #[test]
+    fn backquote_unescaping() {
+        let content = [
+            BackquoteUnit::Literal('a'),
+            BackquoteUnit::Backslashed('$'),
+            BackquoteUnit::Backslashed('\\'),
+            BackquoteUnit::Literal('"'),
+        ];
+        assert_eq!(unescape_backquote(&content), "a$\\\"");
+        assert_eq!(unescape_backquote(&[]), "");
+    }
+
+    #[test]
+    fn trailing_newlines() {
+        let mut s = "foo\n\nbar\n\n\n".to_string();
+        remove_trailing_newlines(&mut s);
+        assert_eq!(s, "foo\n\nbar");
+
+        let mut s = "\n\n".to_string();
+        remove_trailing_newlines(&mut s);
+        assert_eq!(s, "");
+    }
+
+    #[test]
+    fn system_error() {
+        // VirtualSystem cannot start a subshell without an executor.
+        let mut env = Env::new_virtual();
+        let location = Location::dummy("$(foo)");
+        let result = block_on(expand(&mut env, "foo".to_string(), &location));
+        assert_eq!(
+            result,
+            Err(Error::CommandSubst {
+                errno: Errno::ENOSYS,
+                location,
+            })
+        );
+        assert_eq!(env.last_command_subst_exit_status, None);
+    }
+
+    #[test]
+    fn output_of_subshell() {
+        let system = VirtualSystem::new();
+        let mut executor = LocalPool::new();
+        system.state.borrow_mut().executor = Some(Rc::new(executor.spawner()));
+        let mut env = Env::with_system(Box::new(system));
+        env.builtins.insert("echo", echo_builtin());
+        let location = Location::dummy("$(echo)");
+        let content = "echo foo bar; echo; echo".to_string();
+        let result = executor.run_until(expand(&mut env, content, &location));
+        let phrase = result.unwrap();
+        assert_eq!(phrase, Phrase::with_str("foo bar", Origin::SoftExpansion));
+        assert_eq!(
+            env.last_command_subst_exit_status,
+            Some(ExitStatus::SUCCESS)
+        );
+    }
+
+    #[test]
+    fn error_exits_subshell_of_interactive_shell() {
+        let system = VirtualSystem::new();
+        let mut executor = LocalPool::new();
+        system.state.borrow_mut().executor = Some(Rc::new(executor.spawner()));
+        let mut env = Env::with_system(Box::new(system));
+        env.options.set(ShellOption::Interactive, true);
+        env.builtins.insert("echo", echo_builtin());
+        let location = Location::dummy("$(echo)");
+        let content = "echo ${x?}\necho foo".to_string();
+        let result = executor.run_until(expand(&mut env, content, &location));
+        let phrase = result.unwrap();
+        assert_eq!(phrase, Phrase::with_str("", Origin::SoftExpansion));
+        assert_eq!(env.last_command_subst_exit_status, Some(ExitStatus::ERROR));
+        assert!(env.options.is_on(ShellOption::Interactive));
+    }
+}
@@ -0,0 +1,286 @@
+// This file is part of yash, an extended POSIX shell.
+// Copyright (C) 2021 WATANABE Yuki
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Pathname expansion.
+//!
+//! The pathname expansion divides a field into components delimited by `/`
+//! and treats each component as a [pattern](crate::pattern). Directories are
+//! searched for files whose names match the patterns, and the field is
+//! replaced with the pathnames of the matching files, sorted in the ascending
+//! order. If the field contains no pattern or no file matches, the field is
+//! left intact.
+//!
+//! A filename starting with a period is matched only by a pattern that starts
+//! with a literal period.
+//!
+//! The expansion is not performed at all while the
+//! [`NoGlob`](yash_env::option::ShellOption::NoGlob) option is on.
+//!
+//! Directories are read through [`System::read_dir`], so the expansion can
+//! be simulated with a virtual system.
+
+use super::AttrChar;
+use super::AttrField;
+use crate::pattern::Pattern;
+use nix::libc::{S_IFDIR, S_IFMT};
+use std::ffi::CString;
+use yash_env::expansion::Field;
+use yash_env::option::ShellOption;
+use yash_env::Env;
+use yash_env::System;
+
+/// Pathname search state.
+struct Search<'a> {
+    system: &'a dyn System,
+    results: Vec<String>,
+}
+
+impl Search<'_> {
+    /// Returns the names of the entries in the directory.
+    ///
+    /// An empty path denotes the current directory. Returns `None` if the
+    /// directory cannot be read.
+    fn read_dir(&self, path: &str) -> Option<Vec<String>> {
+        let dir = if path.is_empty() { "." } else { path };
+        let dir = CString::new(dir).ok()?;
+        let names = self.system.read_dir(&dir).ok()?;
+        // TODO Support filenames that are not valid UTF-8
+        Some(
+            names
+                .into_iter()
+                .filter_map(|name| name.into_string().ok())
+                .collect(),
+        )
+    }
+
+    /// Tests if a file exists at the path.
+    ///
+    /// A path ending with a `/` must name a directory.
+    fn exists(&self, path: &str) -> bool {
+        let stat = match CString::new(path) {
+            Ok(path) => self.system.stat(&path),
+            Err(_) => return false,
+        };
+        match stat {
+            Ok(stat) => !path.ends_with('/') || stat.st_mode & S_IFMT == S_IFDIR,
+            Err(_) => false,
+        }
+    }
+
+    /// Searches for pathnames matching the components.
+    ///
+    /// `path` is the directory pathname already matched, which is empty or
+    /// ends with a `/`.
+    fn search(&mut self, mut path: String, components: &[Pattern]) {
+        let (pattern, rest) = match components.split_first() {
+            Some(split) => split,
+            None => return,
+        };
+
+        if let Some(literal) = pattern.to_literal() {
+            // The literal is not looked up in the directory listing, which
+            // lacks `.` and `..`, but checked for existence at the end.
+            path.push_str(&literal);
+            if !rest.is_empty() {
+                path.push('/');
+                self.search(path, rest);
+            } else if self.exists(&path) {
+                self.results.push(path);
+            }
+            return;
+        }
+
+        let names = match self.read_dir(&path) {
+            Some(names) => names,
+            None => return,
+        };
+        for name in names {
+            if name.starts_with('.') && !pattern.starts_with_period() {
+                continue;
+            }
+            if !pattern.is_match(&name) {
+                continue;
+            }
+            let mut path = format!("{}{}", path, name);
+            if rest.is_empty() {
+                self.results.push(path);
+            } else {
+                path.push('/');
+                self.search(path, rest);
+            }
+        }
+    }
+}
+
+/// Performs pathname expansion on a field.
+///
+/// Returns the matching pathnames, or the field with quotes removed if the
+/// field is not a pattern or does not match any pathname.
+pub fn glob(env: &Env, field: AttrField) -> Vec<Field> {
+    if env.options.is_on(ShellOption::NoGlob) {
+        return vec![field.remove_quotes()];
+    }
+
+    let components: Vec<Pattern> = field
+        .chars
+        .split(|c| c.value == '/')
+        .map(|chars| Pattern::new(chars.iter().copied().filter_map(AttrChar::to_pattern_char)))
+        .collect();
+    if components
+        .iter()
+        .all(|pattern| pattern.to_literal().is_some())
+    {
+        return vec![field.remove_quotes()];
+    }
+
+    let mut search = Search {
+        system: &*env.system,
+        results: Vec::new(),
+    };
+    search.search(String::new(), &components);
+
+    let mut results = search.results;
+    if results.is_empty() {
+        return vec![field.remove_quotes()];
+    }
+    results.sort_unstable();
+    results
+        .into_iter()
+        .map(|value| Field {
+            value,
+            origin: field.origin.clone(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expansion::Origin;
+    use yash_env::virtual_system::INode;
+    use yash_env::VirtualSystem;
+    use yash_syntax::source::Location;
+
+    fn env_with_files(paths: &[&str]) -> Env {
+        let system = VirtualSystem::new();
+        let mut state = system.state.borrow_mut();
+        for path in paths {
+            state.file_system.save(path, INode::default()).unwrap();
+        }
+        drop(state);
+        Env::with_system(Box::new(system))
+    }
+
+    fn field(s: &str) -> AttrField {
+        let chars = s
+            .chars()
+            .map(|value| AttrChar {
+                value,
+                origin: Origin::Literal,
+                is_quoted: false,
+                is_quoting: false,
+            })
+            .collect();
+        AttrField {
+            chars,
+            origin: Location::dummy(s),
+        }
+    }
+
+    fn values(fields: Vec<Field>) -> Vec<String> {
+        fields.into_iter().map(|field| field.value).collect()
+    }
+
+    #[test]
+    fn field_without_pattern_is_intact() {
+        let env = env_with_files(&[]);
+        let result = glob(&env, field("/no/such/file"));
+        assert_eq!(values(result), ["/no/such/file"]);
+    }
+
+    #[test]
+    fn matching_pathnames_are_sorted() {
+        let env = env_with_files(&["/dir/foo", "/dir/bar", "/dir/baz", "/dir/qux"]);
+        let result = glob(&env, field("/dir/ba*"));
+        assert_eq!(values(result), ["/dir/bar", "/dir/baz"]);
+        let result = glob(&env, field("/dir/[fq]??"));
+        assert_eq!(values(result), ["/dir/foo", "/dir/qux"]);
+    }
+
+    #[test]
+    fn non_matching_pattern_is_intact() {
+        let env = env_with_files(&["/dir/foo"]);
+        let f = field("/dir/x*");
+        let origin = f.origin.clone();
+        let result = glob(&env, f);
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].value, "/dir/x*");
+        assert_eq!(result[0].origin, origin);
+    }
+
+    #[test]
+    fn patterns_in_directory_components() {
+        let env = env_with_files(&["/a1/x", "/a2/y", "/a3/x", "/b/x"]);
+        let result = glob(&env, field("/a*/x"));
+        assert_eq!(values(result), ["/a1/x", "/a3/x"]);
+        let result = glob(&env, field("/*/"));
+        assert_eq!(values(result), ["/a1/", "/a2/", "/a3/", "/b/"]);
+    }
+
+    #[test]
+    fn dot_and_dot_dot_after_pattern() {
+        let env = env_with_files(&["/a/x", "/b/y"]);
+        let result = glob(&env, field("/*/.."));
+        assert_eq!(values(result), ["/a/..", "/b/.."]);
+        let result = glob(&env, field("/*/./x"));
+        assert_eq!(values(result), ["/a/./x"]);
+    }
+
+    #[test]
+    fn trailing_slash_matches_directories_only() {
+        let env = env_with_files(&["/a/x", "/b"]);
+        let result = glob(&env, field("/*/"));
+        assert_eq!(values(result), ["/a/"]);
+    }
+
+    #[test]
+    fn dotfiles_need_explicit_period() {
+        let env = env_with_files(&["/d/.hidden", "/d/visible"]);
+        let result = glob(&env, field("/d/*"));
+        assert_eq!(values(result), ["/d/visible"]);
+        let result = glob(&env, field("/d/?hidden"));
+        assert_eq!(values(result), ["/d/?hidden"]);
+        let result = glob(&env, field("/d/.*"));
+        assert_eq!(values(result), ["/d/.hidden"]);
+    }
+
+    #[test]
+    fn quoted_pattern_characters_are_literal() {
+        let env = env_with_files(&["/d/*", "/d/a"]);
+        let mut f = field("/d/*");
+        f.chars[3].is_quoted = true;
+        let result = glob(&env, f);
+        assert_eq!(values(result), ["/d/*"]);
+    }
+
+    #[test]
+    fn noglob_option_disables_expansion() {
+        let mut env = env_with_files(&["/d/a", "/d/b"]);
+        env.options.set(ShellOption::NoGlob, true);
+        let result = glob(&env, field("/d/*"));
+        assert_eq!(values(result), ["/d/*"]);
+    }
+}
@@ -0,0 +1,513 @@
+// This file is part of yash, an extended POSIX shell.
+// Copyright (C) 2021 WATANABE Yuki
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Parameter expansion.
+//!
+//! The parameter expansion substitutes the value of a parameter. The value may
+//! be modified by a [modifier](Modifier) in the braced form of the expansion:
+//!
+//! - `${#name}` expands to the length of the value.
+//! - [Switches](yash_syntax::syntax::Switch) like `${name:-word}` substitute
+//!   or assign another value depending on whether the parameter is set.
+//! - [Trims](yash_syntax::syntax::Trim) like `${name%pattern}` remove a
+//!   prefix or suffix matching a pattern from the value.
+
+mod switch;
+mod trim;
+
+use super::Expand;
+use super::Origin;
+use super::Phrase;
+use async_trait::async_trait;
+use yash_env::expansion::Error;
+use yash_env::expansion::Result;
+use yash_env::option::ShellOption;
+use yash_env::variable::Value;
+use yash_env::Env;
+use yash_syntax::parser::lex::is_name_char;
+use yash_syntax::parser::lex::is_special_parameter_char;
+use yash_syntax::source::Location;
+use yash_syntax::syntax::Modifier;
+use yash_syntax::syntax::Param;
+
+/// Reference to a parameter expansion.
+///
+/// This is a common representation of [`TextUnit::RawParam`] and
+/// [`TextUnit::BracedParam`].
+///
+/// [`TextUnit::RawParam`]: yash_syntax::syntax::TextUnit::RawParam
+/// [`TextUnit::BracedParam`]: yash_syntax::syntax::TextUnit::BracedParam
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ParamRef<'a> {
+    /// Parameter name.
+    pub name: &'a str,
+    /// Modifier.
+    pub modifier: &'a Modifier,
+    /// Location of the initial `$` character of the parameter expansion.
+    pub location: &'a Location,
+    /// Whether the parameter expansion is enclosed in double quotes.
+    ///
+    /// This affects the expansion of the `$*` special parameter.
+    pub in_double_quotes: bool,
+}
+
+impl<'a> From<&'a Param> for ParamRef<'a> {
+    fn from(param: &'a Param) -> ParamRef<'a> {
+        ParamRef {
+            name: &param.name,
+            modifier: &param.modifier,
+            location: &param.location,
+            in_double_quotes: false,
+        }
+    }
+}
+
+/// Tests if a string is a valid variable name.
+///
+/// A valid name is non-empty, consists of name characters, and does not start
+/// with a digit.
+pub fn is_variable_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name.chars().all(is_name_char)
+}
+
+/// Tests if a string is a valid parameter name.
+///
+/// A valid parameter name is a [variable name](is_variable_name), a
+/// positional parameter index consisting of digits only, or a single special
+/// parameter character.
+fn is_param_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    let is_special = match (chars.next(), chars.next()) {
+        (Some(c), None) => is_special_parameter_char(c),
+        _ => false,
+    };
+    is_special
+        || is_variable_name(name)
+        || (!name.is_empty() && name.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// Joins the positional parameters for `"$*"`.
+///
+/// The parameters are separated by the first character of `$IFS`. If `$IFS` is
+/// unset, the separator is a space. If `$IFS` is empty, the parameters are
+/// joined without a separator.
+fn join_positional_params(env: &Env) -> String {
+    let separator = match env.variables.get("IFS").map(|v| &v.value) {
+        None => Some(' '),
+        Some(Value::Scalar(ifs)) => ifs.chars().next(),
+        Some(Value::Array(ifs)) => ifs.first().and_then(|ifs| ifs.chars().next()),
+    };
+    let separator = separator.map(String::from).unwrap_or_default();
+    env.variables.positional_params().join(&separator)
+}
+
+/// Returns the value of a parameter.
+///
+/// Special parameters are computed from the state of the environment. `$@` and
+/// `$*` expand to an array of the positional parameters, except that `$*` in
+/// double quotes expands to a scalar joining them. A positional parameter is
+/// unset if the index exceeds the number of positional parameters. `$!` is
+/// unset if no asynchronous command has been started.
+fn lookup(env: &Env, name: &str, in_double_quotes: bool) -> Option<Value> {
+    let params = env.variables.positional_params();
+    match name {
+        "@" => Some(Value::Array(params.to_vec())),
+        "*" if in_double_quotes => Some(Value::Scalar(join_positional_params(env))),
+        "*" => Some(Value::Array(params.to_vec())),
+        "#" => Some(Value::Scalar(params.len().to_string())),
+        "?" => Some(Value::Scalar(env.exit_status.0.to_string())),
+        "$" => Some(Value::Scalar(env.main_pid.to_string())),
+        "!" => env
+            .jobs
+            .last_async_pid
+            .map(|pid| Value::Scalar(pid.to_string())),
+        "-" => Some(Value::Scalar(env.options.short_names())),
+        "0" => Some(Value::Scalar(env.arg0.clone())),
+        _ if name.starts_with(|c: char| c.is_ascii_digit()) => {
+            // The index may overflow usize, in which case it is out of range.
+            let index = name.parse::<usize>().ok()?;
+            let value = params.get(index.checked_sub(1)?)?;
+            Some(Value::Scalar(value.clone()))
+        }
+        _ => env.variables.get(name).map(|v| v.value.clone()),
+    }
+}
+
+/// Converts a parameter value to a phrase.
+///
+/// A scalar value results in one field. Each item of an array value results in
+/// a separate field. An unset parameter results in one empty field.
+fn value_to_phrase(value: Option<&Value>) -> Phrase {
+    match value {
+        None => Phrase::one_empty_field(),
+        Some(Value::Scalar(value)) => Phrase::with_str(value, Origin::SoftExpansion),
+        Some(Value::Array(values)) => Phrase(
+            values
+                .iter()
+                .flat_map(|value| Phrase::with_str(value, Origin::SoftExpansion).0)
+                .collect(),
+        ),
+    }
+}
+
+/// Computes the length of a parameter value.
+///
+/// The result is the number of characters in the value. If the value is an
+/// array, each item results in a separate field containing the length of the
+/// item. The length of an unset parameter is zero.
+fn length(value: Option<&Value>) -> Phrase {
+    let to_phrase =
+        |s: &str| Phrase::with_str(&s.chars().count().to_string(), Origin::SoftExpansion);
+    match value {
+        None => Phrase::with_str("0", Origin::SoftExpansion),
+        Some(Value::Scalar(value)) => to_phrase(value),
+        Some(Value::Array(values)) => Phrase(values.iter().flat_map(|v| to_phrase(v).0).collect()),
+    }
+}
+
+#[async_trait(?Send)]
+impl Expand for ParamRef<'_> {
+    /// Performs parameter expansion.
+    ///
+    /// The value of an unset parameter expands to an empty field unless
+    /// modified by a switch. If the [`NoUnset`](ShellOption::NoUnset) option
+    /// is on, expanding an unset parameter without a switch is an error.
+    async fn expand(&self, env: &mut Env) -> Result<Phrase> {
+        if !is_param_name(self.name) {
+            return Err(Error::InvalidParamName {
+                name: self.name.to_owned(),
+                location: self.location.clone(),
+            });
+        }
+
+        let mut value = lookup(env, self.name, self.in_double_quotes);
+        if value.is_none()
+            && !matches!(self.modifier, Modifier::Switch(_))
+            && env.options.is_on(ShellOption::NoUnset)
+        {
+            return Err(Error::UnsetParam {
+                name: self.name.to_owned(),
+                location: self.location.clone(),
+            });
+        }
+        match self.modifier {
+            Modifier::None => (),
+            Modifier::Length => return Ok(length(value.as_ref())),
+            Modifier::Switch(switch) => {
+                if let Some(phrase) = switch::apply(env, switch, self, value.as_ref()).await? {
+                    return Ok(phrase);
+                }
+            }
+            Modifier::Trim(trim) => {
+                if let Some(value) = &mut value {
+                    trim::apply(env, trim, value).await?;
+                }
+            }
+        }
+        Ok(value_to_phrase(value.as_ref()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expansion::AttrChar;
+    use futures::executor::block_on;
+    use nix::unistd::Pid;
+    use yash_env::exec::ExitStatus;
+    use yash_env::variable::Scope;
+    use yash_env::variable::Variable;
+
+    fn soft(value: char) -> AttrChar {
+        AttrChar {
+            value,
+            origin: Origin::SoftExpansion,
+            is_quoted: false,
+            is_quoting: false,
+        }
+    }
+
+    #[test]
+    fn unset_parameter_expands_to_empty_field() {
+        let mut env = Env::new_virtual();
+        let location = Location::dummy("");
+        let param = ParamRef {
+            name: "foo",
+            modifier: &Modifier::None,
+            location: &location,
+            in_double_quotes: false,
+        };
+        let phrase = block_on(param.expand(&mut env)).unwrap();
+        assert_eq!(phrase, Phrase::one_empty_field());
+    }
+
+    #[test]
+    fn unset_parameter_with_nounset_is_error() {
+        let mut env = Env::new_virtual();
+        env.options.set(ShellOption::NoUnset, true);
+        let location = Location::dummy("");
+        let param = ParamRef {
+            name: "foo",
+            modifier: &Modifier::None,
+            location: &location,
+            in_double_quotes: false,
+        };
+        let result = block_on(param.expand(&mut env));
+        assert_eq!(
+            result,
+            Err(Error::UnsetParam {
+                name: "foo".to_string(),
+                location: location.clone(),
+            })
+        );
+
+        let param = ParamRef { name: "@", ..param };
+        let result = block_on(param.expand(&mut env));
+        assert_eq!(result, Ok(Phrase::zero_fields()));
+    }
+
+    #[test]
+    fn scalar_variable_expands_to_one_field() {
+        let mut env = Env::new_virtual();
+        env.variables.assign(
+            Scope::Global,
+            "foo".to_string(),
+            Variable {
+                value: Value::Scalar("ab".to_string()),
+                last_assigned_location: None,
+                is_exported: false,
+                read_only_location: None,
+            },
+        );
+        let location = Location::dummy("");
+        let param = ParamRef {
+            name: "foo",
+            modifier: &Modifier::None,
+            location: &location,
+            in_double_quotes: false,
+        };
+        let phrase = block_on(param.expand(&mut env)).unwrap();
+        assert_eq!(phrase, Phrase::one_field(vec![soft('a'), soft('b')]));
+    }
+
+    #[test]
+    fn variable_names() {
+        assert!(is_variable_name("a"));
+        assert!(is_variable_name("_foo1"));
+        assert!(!is_variable_name(""));
+        assert!(!is_variable_name("1"));
+        assert!(!is_variable_name("1a"));
+        assert!(!is_variable_name("@"));
+    }
+
+    #[test]
+    fn param_names() {
+        assert!(is_param_name("foo"));
+        assert!(is_param_name("1"));
+        assert!(is_param_name("10"));
+        assert!(is_param_name("@"));
+        assert!(is_param_name("#"));
+        assert!(!is_param_name(""));
+        assert!(!is_param_name("1a"));
+        assert!(!is_param_name("@@"));
+    }
+
+    #[test]
+    fn invalid_param_name() {
+        let mut env = Env::new_virtual();
+        let param: Param = "${1a}".parse().unwrap();
+        let result = block_on(ParamRef::from(&param).expand(&mut env));
+        assert_eq!(
+            result,
+            Err(Error::InvalidParamName {
+                name: "1a".to_string(),
+                location: param.location,
+            })
+        );
+    }
+
+    #[test]
+    fn length_of_values() {
+        let phrase = length(None);
+        assert_eq!(phrase, Phrase::one_field(vec![soft('0')]));
+
+        let value = Value::Scalar("日本語".to_string());
+        let phrase = length(Some(&value));
+        assert_eq!(phrase, Phrase::one_field(vec![soft('3')]));
+
+        let value = Value::Array(vec!["ab".to_string(), "".to_string()]);
+        let phrase = length(Some(&value));
+        assert_eq!(phrase, Phrase(vec![vec![soft('2')], vec![soft('0')]]));
+    }
+
+    #[test]
+    fn array_variable_expands_to_fields() {
+        let mut env = Env::new_virtual();
+        env.variables.assign(
+            Scope::Global,
+            "foo".to_string(),
+            Variable {
+                value: Value::Array(vec!["a".to_string(), "".to_string(), "b".to_string()]),
+                last_assigned_location: None,
+                is_exported: false,
+                read_only_location: None,
+            },
+        );
+        let location = Location::dummy("");
+        let param = ParamRef {
+            name: "foo",
+            modifier: &Modifier::None,
+            location: &location,
+            in_double_quotes: false,
+        };
+        let phrase = block_on(param.expand(&mut env)).unwrap();
+        assert_eq!(
+            phrase,
+            Phrase(vec![vec![soft('a')], vec![], vec![soft('b')]])
+        );
+    }
+
+    fn expand_param(env: &mut Env, name: &str) -> Phrase {
+        let location = Location::dummy("");
+        let param = ParamRef {
+            name,
+            modifier: &Modifier::None,
+            location: &location,
+            in_double_quotes: false,
+        };
+        block_on(param.expand(env)).unwrap()
+    }
+
+    fn set_positional_params(env: &mut Env, params: &[&str]) {
+        let params = params.iter().map(|&param| param.to_owned()).collect();
+        *env.variables.positional_params_mut() = params;
+    }
+
+    #[test]
+    fn positional_params_expand_to_values() {
+        let mut env = Env::new_virtual();
+        set_positional_params(
+            &mut env,
+            &["a", "b", "c", "d", "e", "f", "g", "h", "i", "j"],
+        );
+        assert_eq!(
+            expand_param(&mut env, "1"),
+            Phrase::one_field(vec![soft('a')])
+        );
+        assert_eq!(
+            expand_param(&mut env, "3"),
+            Phrase::one_field(vec![soft('c')])
+        );
+        assert_eq!(
+            expand_param(&mut env, "10"),
+            Phrase::one_field(vec![soft('j')])
+        );
+        assert_eq!(expand_param(&mut env, "11"), Phrase::one_empty_field());
+        let huge = "99999999999999999999999999";
+        assert_eq!(expand_param(&mut env, huge), Phrase::one_empty_field());
+    }
+
+    #[test]
+    fn number_of_positional_params() {
+        let mut env = Env::new_virtual();
+        assert_eq!(
+            expand_param(&mut env, "#"),
+            Phrase::one_field(vec![soft('0')])
+        );
+        set_positional_params(&mut env, &["a", "b"]);
+        assert_eq!(
+            expand_param(&mut env, "#"),
+            Phrase::one_field(vec![soft('2')])
+        );
+    }
+
+    #[test]
+    fn at_and_asterisk_expand_to_separate_fields() {
+        let mut env = Env::new_virtual();
+        set_positional_params(&mut env, &["a", "", "b"]);
+        let expected = Phrase(vec![vec![soft('a')], vec![], vec![soft('b')]]);
+        assert_eq!(expand_param(&mut env, "@"), expected);
+        assert_eq!(expand_param(&mut env, "*"), expected);
+
+        set_positional_params(&mut env, &[]);
+        assert_eq!(expand_param(&mut env, "@"), Phrase(vec![]));
+    }
+
+    #[test]
+    fn asterisk_in_double_quotes_expands_to_one_field() {
+        let mut env = Env::new_virtual();
+        set_positional_params(&mut env, &["a", "b"]);
+        let location = Location::dummy("");
+        let param = ParamRef {
+            name: "*",
+            modifier: &Modifier::None,
+            location: &location,
+            in_double_quotes: true,
+        };
+        let phrase = block_on(param.expand(&mut env)).unwrap();
+        assert_eq!(
+            phrase,
+            Phrase::one_field(vec![soft('a'), soft(' '), soft('b')])
+        );
+    }
+
+    #[test]
+    fn exit_status_parameter() {
+        let mut env = Env::new_virtual();
+        env.exit_status = ExitStatus(42);
+        let phrase = expand_param(&mut env, "?");
+        assert_eq!(phrase, Phrase::one_field(vec![soft('4'), soft('2')]));
+    }
+
+    #[test]
+    fn process_id_parameters() {
+        let mut env = Env::new_virtual();
+        env.main_pid = Pid::from_raw(123);
+        let phrase = expand_param(&mut env, "$");
+        assert_eq!(
+            phrase,
+            Phrase::one_field(vec![soft('1'), soft('2'), soft('3')])
+        );
+
+        assert_eq!(expand_param(&mut env, "!"), Phrase::one_empty_field());
+        env.jobs.last_async_pid = Some(Pid::from_raw(45));
+        let phrase = expand_param(&mut env, "!");
+        assert_eq!(phrase, Phrase::one_field(vec![soft('4'), soft('5')]));
+    }
+
+    #[test]
+    fn option_parameter() {
+        let mut env = Env::new_virtual();
+        assert_eq!(expand_param(&mut env, "-"), Phrase::one_field(vec![]));
+        env.options
+            .set(yash_env::option::ShellOption::Monitor, true);
+        assert_eq!(
+            expand_param(&mut env, "-"),
+            Phrase::one_field(vec![soft('m')])
+        );
+    }
+
+    #[test]
+    fn arg0_parameter() {
+        let mut env = Env::new_virtual();
+        env.arg0 = "yash".to_string();
+        let phrase = expand_param(&mut env, "0");
+        let expected = "yash".chars().map(soft).collect();
+        assert_eq!(phrase, Phrase::one_field(expected));
+    }
+}
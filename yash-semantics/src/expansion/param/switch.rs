@@ -0,0 +1,258 @@
+// This file is part of yash, an extended POSIX shell.
+// Copyright (C) 2021 WATANABE Yuki
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Applying switch modifiers.
+
+use super::is_variable_name;
+use super::ParamRef;
+use crate::assign::assign_variable;
+use crate::expansion::expand_word;
+use crate::expansion::Expand;
+use crate::expansion::Origin;
+use crate::expansion::Phrase;
+use yash_env::expansion::Error;
+use yash_env::expansion::Result;
+use yash_env::variable::Scope;
+use yash_env::variable::Value;
+use yash_env::Env;
+use yash_syntax::syntax::Switch;
+use yash_syntax::syntax::SwitchCondition;
+use yash_syntax::syntax::SwitchType;
+
+/// Tests if the parameter value satisfies the switch condition.
+///
+/// The value is _vacant_ if it is unset, or if it is empty and the condition
+/// is [`UnsetOrEmpty`](SwitchCondition::UnsetOrEmpty). An array value is
+/// empty if it has no items.
+fn is_vacant(value: Option<&Value>, condition: SwitchCondition) -> bool {
+    match (value, condition) {
+        (None, _) => true,
+        (Some(_), SwitchCondition::Unset) => false,
+        (Some(Value::Scalar(value)), SwitchCondition::UnsetOrEmpty) => value.is_empty(),
+        (Some(Value::Array(values)), SwitchCondition::UnsetOrEmpty) => values.is_empty(),
+    }
+}
+
+/// Assigns the expanded word of the switch to the variable.
+///
+/// Returns the assigned value as the result of the expansion.
+async fn assign(env: &mut Env, switch: &Switch, param: &ParamRef<'_>) -> Result<Phrase> {
+    if !is_variable_name(param.name) {
+        return Err(Error::NonassignableParam {
+            name: param.name.to_owned(),
+            location: param.location.clone(),
+        });
+    }
+
+    let field = expand_word(env, &switch.word).await?;
+
+    let phrase = Phrase::with_str(&field.value, Origin::SoftExpansion);
+    assign_variable(
+        env,
+        Scope::Global,
+        param.name.to_owned(),
+        Value::Scalar(field.value),
+        param.location.clone(),
+        false,
+    )?;
+    Ok(phrase)
+}
+
+/// Applies a switch.
+///
+/// Returns the result of the expansion if the switch replaces the parameter
+/// value. Returns `None` if the parameter value should be expanded as is.
+pub async fn apply(
+    env: &mut Env,
+    switch: &Switch,
+    param: &ParamRef<'_>,
+    value: Option<&Value>,
+) -> Result<Option<Phrase>> {
+    let is_vacant = is_vacant(value, switch.condition);
+    match switch.r#type {
+        SwitchType::Alter if is_vacant => Ok(None),
+        SwitchType::Alter => switch.word.expand(env).await.map(Some),
+        SwitchType::Default if is_vacant => switch.word.expand(env).await.map(Some),
+        SwitchType::Assign if is_vacant => assign(env, switch, param).await.map(Some),
+        SwitchType::Error if is_vacant => {
+            let field = expand_word(env, &switch.word).await?;
+            let message = Some(field.value).filter(|message| !message.is_empty());
+            Err(Error::VacantParam {
+                name: param.name.to_owned(),
+                message,
+                condition: switch.condition,
+                location: param.location.clone(),
+            })
+        }
+        SwitchType::Default | SwitchType::Assign | SwitchType::Error => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expansion::tests::assign_scalar;
+    use futures::executor::block_on;
+    use yash_env::variable::Variable;
+    use yash_syntax::source::Location;
+    use yash_syntax::syntax::Param;
+
+    fn expand(env: &mut Env, param: &str) -> Result<String> {
+        let param: Param = param.parse().unwrap();
+        let phrase = block_on(ParamRef::from(&param).expand(env))?;
+        Ok(phrase
+            .0
+            .iter()
+            .map(|field| field.iter().map(|c| c.value).collect::<String>())
+            .collect::<Vec<_>>()
+            .join("/"))
+    }
+
+    #[test]
+    fn vacancy() {
+        use SwitchCondition::*;
+        let empty = Value::Scalar("".to_string());
+        let non_empty = Value::Scalar("x".to_string());
+        let empty_array = Value::Array(vec![]);
+        assert!(is_vacant(None, Unset));
+        assert!(is_vacant(None, UnsetOrEmpty));
+        assert!(!is_vacant(Some(&empty), Unset));
+        assert!(is_vacant(Some(&empty), UnsetOrEmpty));
+        assert!(!is_vacant(Some(&non_empty), UnsetOrEmpty));
+        assert!(!is_vacant(Some(&empty_array), Unset));
+        assert!(is_vacant(Some(&empty_array), UnsetOrEmpty));
+    }
+
+    #[test]
+    fn alter() {
+        let mut env = Env::new_virtual();
+        assign_scalar(&mut env, "set", "value");
+        assign_scalar(&mut env, "empty", "");
+        assert_eq!(expand(&mut env, "${set+alt}").unwrap(), "alt");
+        assert_eq!(expand(&mut env, "${set:+alt}").unwrap(), "alt");
+        assert_eq!(expand(&mut env, "${empty+alt}").unwrap(), "alt");
+        assert_eq!(expand(&mut env, "${empty:+alt}").unwrap(), "");
+        assert_eq!(expand(&mut env, "${unset+alt}").unwrap(), "");
+        assert_eq!(expand(&mut env, "${unset:+alt}").unwrap(), "");
+    }
+
+    #[test]
+    fn default() {
+        let mut env = Env::new_virtual();
+        assign_scalar(&mut env, "set", "value");
+        assign_scalar(&mut env, "empty", "");
+        assert_eq!(expand(&mut env, "${set-def}").unwrap(), "value");
+        assert_eq!(expand(&mut env, "${set:-def}").unwrap(), "value");
+        assert_eq!(expand(&mut env, "${empty-def}").unwrap(), "");
+        assert_eq!(expand(&mut env, "${empty:-def}").unwrap(), "def");
+        assert_eq!(expand(&mut env, "${unset-def}").unwrap(), "def");
+        assert_eq!(expand(&mut env, "${unset:-def}").unwrap(), "def");
+    }
+
+    #[test]
+    fn assign_to_unset_and_empty_variables() {
+        let mut env = Env::new_virtual();
+        assign_scalar(&mut env, "empty", "");
+        assert_eq!(expand(&mut env, "${empty=new}").unwrap(), "");
+        assert_eq!(expand(&mut env, "${empty:=new}").unwrap(), "new");
+        assert_eq!(expand(&mut env, "${unset=\"a  b\"}").unwrap(), "a  b");
+
+        let empty = env.variables.get("empty").unwrap();
+        assert_eq!(empty.value, Value::Scalar("new".to_string()));
+        assert!(empty.last_assigned_location.is_some());
+        let unset = env.variables.get("unset").unwrap();
+        assert_eq!(unset.value, Value::Scalar("a  b".to_string()));
+        assert!(!unset.is_exported);
+    }
+
+    #[test]
+    fn assign_to_read_only_variable() {
+        let mut env = Env::new_virtual();
+        let read_only_location = Location::dummy("readonly x");
+        env.variables.assign(
+            Scope::Global,
+            "x".to_string(),
+            Variable {
+                value: Value::Scalar("".to_string()),
+                last_assigned_location: None,
+                is_exported: false,
+                read_only_location: Some(read_only_location.clone()),
+            },
+        );
+        let param: Param = "${x:=1}".parse().unwrap();
+        let result = block_on(ParamRef::from(&param).expand(&mut env));
+        assert_eq!(
+            result,
+            Err(Error::AssignReadOnly {
+                name: "x".to_string(),
+                read_only_location,
+                location: param.location,
+            })
+        );
+        let x = env.variables.get("x").unwrap();
+        assert_eq!(x.value, Value::Scalar("".to_string()));
+    }
+
+    #[test]
+    fn assign_to_non_variable() {
+        let mut env = Env::new_virtual();
+        let param: Param = "${1=x}".parse().unwrap();
+        let result = block_on(ParamRef::from(&param).expand(&mut env));
+        assert_eq!(
+            result,
+            Err(Error::NonassignableParam {
+                name: "1".to_string(),
+                location: param.location,
+            })
+        );
+    }
+
+    #[test]
+    fn error_with_set_variable() {
+        let mut env = Env::new_virtual();
+        assign_scalar(&mut env, "empty", "");
+        assert_eq!(expand(&mut env, "${empty?msg}").unwrap(), "");
+    }
+
+    #[test]
+    fn error_with_vacant_variable() {
+        let mut env = Env::new_virtual();
+        assign_scalar(&mut env, "empty", "");
+        let param: Param = "${empty:?no $empty value}".parse().unwrap();
+        let result = block_on(ParamRef::from(&param).expand(&mut env));
+        assert_eq!(
+            result,
+            Err(Error::VacantParam {
+                name: "empty".to_string(),
+                message: Some("no  value".to_string()),
+                condition: SwitchCondition::UnsetOrEmpty,
+                location: param.location,
+            })
+        );
+
+        let param: Param = "${unset?}".parse().unwrap();
+        let result = block_on(ParamRef::from(&param).expand(&mut env));
+        assert_eq!(
+            result,
+            Err(Error::VacantParam {
+                name: "unset".to_string(),
+                message: None,
+                condition: SwitchCondition::Unset,
+                location: param.location,
+            })
+        );
+    }
+}
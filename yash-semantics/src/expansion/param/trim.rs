@@ -0,0 +1,149 @@
+// This file is part of yash, an extended POSIX shell.
+// Copyright (C) 2021 WATANABE Yuki
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Applying trim modifiers.
+
+use crate::expansion::AttrChar;
+use crate::expansion::Expand;
+use crate::expansion::Origin;
+use crate::pattern::Pattern;
+use yash_env::expansion::Result;
+use yash_env::variable::Value;
+use yash_env::Env;
+use yash_syntax::syntax::Trim;
+use yash_syntax::syntax::TrimLength;
+use yash_syntax::syntax::TrimSide;
+
+/// Removes the part of the string matching the pattern.
+fn trim_str(pattern: &Pattern, side: TrimSide, length: TrimLength, value: &mut String) {
+    let longest = length == TrimLength::Longest;
+    match side {
+        TrimSide::Prefix => {
+            if let Some(len) = pattern.match_prefix(value, longest) {
+                value.drain(..len);
+            }
+        }
+        TrimSide::Suffix => {
+            if let Some(start) = pattern.match_suffix(value, longest) {
+                value.truncate(start);
+            }
+        }
+    }
+}
+
+/// Applies a trim.
+///
+/// The pattern is expanded and matched against the value. For an array value,
+/// each item is trimmed separately.
+pub async fn apply(env: &mut Env, trim: &Trim, value: &mut Value) -> Result<()> {
+    let phrase = trim.pattern.expand(env).await?;
+    let separator = AttrChar {
+        value: ' ',
+        origin: Origin::SoftExpansion,
+        is_quoted: false,
+        is_quoting: false,
+    };
+    let chars = phrase.join(Some(separator));
+    let pattern = Pattern::new(chars.iter().copied().filter_map(AttrChar::to_pattern_char));
+
+    match value {
+        Value::Scalar(value) => trim_str(&pattern, trim.side, trim.length, value),
+        Value::Array(values) => values
+            .iter_mut()
+            .for_each(|value| trim_str(&pattern, trim.side, trim.length, value)),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expansion::tests::assign_scalar;
+    use futures::executor::block_on;
+    use yash_syntax::syntax::Modifier;
+    use yash_syntax::syntax::Param;
+
+    fn parse_trim(param: &str) -> Trim {
+        let param: Param = param.parse().unwrap();
+        match param.modifier {
+            Modifier::Trim(trim) => trim,
+            modifier => panic!("unexpected modifier {:?}", modifier),
+        }
+    }
+
+    fn trim(env: &mut Env, param: &str, value: &str) -> String {
+        let trim = parse_trim(param);
+        let mut value = Value::Scalar(value.to_string());
+        block_on(apply(env, &trim, &mut value)).unwrap();
+        match value {
+            Value::Scalar(value) => value,
+            Value::Array(_) => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn shortest_prefix() {
+        let mut env = Env::new_virtual();
+        assert_eq!(trim(&mut env, "${x#*/}", "a/b/c"), "b/c");
+        assert_eq!(trim(&mut env, "${x#b}", "abc"), "abc");
+    }
+
+    #[test]
+    fn longest_prefix() {
+        let mut env = Env::new_virtual();
+        assert_eq!(trim(&mut env, "${x##*/}", "a/b/c"), "c");
+        assert_eq!(trim(&mut env, "${x##}", "abc"), "abc");
+    }
+
+    #[test]
+    fn shortest_suffix() {
+        let mut env = Env::new_virtual();
+        assert_eq!(trim(&mut env, "${x%.*}", "a.tar.gz"), "a.tar");
+        assert_eq!(trim(&mut env, "${x%?}", ""), "");
+    }
+
+    #[test]
+    fn longest_suffix() {
+        let mut env = Env::new_virtual();
+        assert_eq!(trim(&mut env, "${x%%.*}", "a.tar.gz"), "a");
+        assert_eq!(trim(&mut env, "${x%%*}", "abc"), "");
+    }
+
+    #[test]
+    fn quoted_pattern_chars_are_literal() {
+        let mut env = Env::new_virtual();
+        assert_eq!(trim(&mut env, "${x#'*'}", "*a"), "a");
+        assert_eq!(trim(&mut env, "${x#\\*}", "abc"), "abc");
+        assert_eq!(trim(&mut env, "${x#\"?\"}", "?a"), "a");
+    }
+
+    #[test]
+    fn expanded_pattern() {
+        let mut env = Env::new_virtual();
+        assign_scalar(&mut env, "p", "?");
+        assert_eq!(trim(&mut env, "${x#$p}", "abc"), "bc");
+        assert_eq!(trim(&mut env, "${x#\"$p\"}", "abc"), "abc");
+    }
+
+    #[test]
+    fn array_items_are_trimmed_separately() {
+        let mut env = Env::new_virtual();
+        let trim = parse_trim("${x%/*}");
+        let mut value = Value::Array(vec!["a/b".to_string(), "c".to_string()]);
+        block_on(apply(&mut env, &trim, &mut value)).unwrap();
+        assert_eq!(value, Value::Array(vec!["a".to_string(), "c".to_string()]));
+    }
+}
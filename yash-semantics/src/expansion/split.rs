@@ -0,0 +1,261 @@
+// This file is part of yash, an extended POSIX shell.
+// Copyright (C) 2021 WATANABE Yuki
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Field splitting.
+//!
+//! The field splitting divides a field into smaller fields delimited by
+//! characters contained in the value of the `$IFS` variable. Only unquoted
+//! characters that resulted from a [soft expansion](Origin::SoftExpansion)
+//! can be a delimiter.
+//!
+//! IFS characters are classified into _IFS whitespace_ (space, tab, and
+//! newline) and _IFS non-whitespace_ (any other characters). A sequence of IFS
+//! whitespace delimits fields. An IFS non-whitespace character, together with
+//! any adjacent IFS whitespace, delimits fields. IFS whitespace at the
+//! beginning and end of a field is ignored, and so is an IFS non-whitespace
+//! character at the end.
+//!
+//! If `$IFS` is unset, it is assumed to be a space, tab, and newline. If
+//! `$IFS` is empty, no splitting is performed.
+
+use super::AttrChar;
+use super::AttrField;
+use super::Origin;
+use yash_env::variable::Value;
+use yash_env::Env;
+
+/// Default value of `$IFS`.
+pub const DEFAULT_IFS: &str = " \t\n";
+
+/// Set of field separator characters.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Ifs {
+    chars: String,
+}
+
+impl Ifs {
+    /// Creates an `Ifs` from the characters.
+    pub fn new<S: Into<String>>(chars: S) -> Ifs {
+        Ifs {
+            chars: chars.into(),
+        }
+    }
+
+    /// Creates an `Ifs` from the value of the `$IFS` variable.
+    ///
+    /// If the variable is unset, the result is [`DEFAULT_IFS`].
+    pub fn from_env(env: &Env) -> Ifs {
+        match env.variables.get("IFS").map(|v| &v.value) {
+            Some(Value::Scalar(value)) => Ifs::new(value.as_str()),
+            Some(Value::Array(values)) => Ifs::new(values.concat()),
+            None => Ifs::new(DEFAULT_IFS),
+        }
+    }
+
+    /// Tests if this `Ifs` contains no characters.
+    pub fn is_empty(&self) -> bool {
+        self.chars.is_empty()
+    }
+
+    /// Classifies the character.
+    ///
+    /// Returns `None` if the character is not a delimiter. Otherwise, returns
+    /// whether the character is IFS whitespace.
+    fn classify(&self, c: &AttrChar) -> Option<bool> {
+        if c.origin != Origin::SoftExpansion || c.is_quoted || c.is_quoting {
+            return None;
+        }
+        if !self.chars.contains(c.value) {
+            return None;
+        }
+        Some(matches!(c.value, ' ' | '\t' | '\n'))
+    }
+}
+
+/// State of the splitter.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum State {
+    /// No character has been processed except IFS whitespace.
+    Start,
+    /// In the middle of a field.
+    InField,
+    /// After a delimiter consisting of IFS whitespace only.
+    AfterWhitespace,
+    /// After a delimiter containing IFS non-whitespace.
+    AfterNonWhitespace,
+}
+
+/// Splits a field.
+///
+/// The result may contain any number of fields. All of them have the same
+/// origin as the argument field.
+pub fn split(field: AttrField, ifs: &Ifs) -> Vec<AttrField> {
+    let origin = field.origin;
+    let new_field = |chars| AttrField {
+        chars,
+        origin: origin.clone(),
+    };
+
+    let mut fields = Vec::new();
+    let mut current = Vec::new();
+    let mut state = State::Start;
+    for c in field.chars {
+        match (ifs.classify(&c), state) {
+            (None, _) => {
+                current.push(c);
+                state = State::InField;
+            }
+            (Some(true), State::InField) => {
+                fields.push(new_field(std::mem::take(&mut current)));
+                state = State::AfterWhitespace;
+            }
+            (Some(true), _) => (),
+            (Some(false), State::InField) => {
+                fields.push(new_field(std::mem::take(&mut current)));
+                state = State::AfterNonWhitespace;
+            }
+            (Some(false), State::AfterWhitespace) => state = State::AfterNonWhitespace,
+            (Some(false), State::Start) | (Some(false), State::AfterNonWhitespace) => {
+                fields.push(new_field(Vec::new()));
+                state = State::AfterNonWhitespace;
+            }
+        }
+    }
+    if state == State::InField {
+        fields.push(new_field(current));
+    }
+    fields
+}
+
+/// Performs field splitting on the fields.
+///
+/// Fields that are empty after the initial expansion are removed even if the
+/// IFS is empty. Note that a field that contains only quoting characters
+/// (e.g., `""`) is not empty at this point.
+pub fn split_fields(fields: Vec<AttrField>, ifs: &Ifs) -> Vec<AttrField> {
+    if ifs.is_empty() {
+        let mut fields = fields;
+        fields.retain(|field| !field.chars.is_empty());
+        fields
+    } else {
+        fields
+            .into_iter()
+            .flat_map(|field| split(field, ifs))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expansion::tests::assign_scalar;
+    use yash_syntax::source::Location;
+
+    fn soft_field(s: &str) -> AttrField {
+        let chars = s
+            .chars()
+            .map(|value| AttrChar {
+                value,
+                origin: Origin::SoftExpansion,
+                is_quoted: false,
+                is_quoting: false,
+            })
+            .collect();
+        AttrField {
+            chars,
+            origin: Location::dummy(s),
+        }
+    }
+
+    fn values(fields: &[AttrField]) -> Vec<String> {
+        fields
+            .iter()
+            .map(|field| field.chars.iter().map(|c| c.value).collect())
+            .collect()
+    }
+
+    fn split_str(s: &str, ifs: &str) -> Vec<String> {
+        values(&split(soft_field(s), &Ifs::new(ifs)))
+    }
+
+    #[test]
+    fn whitespace_delimiters() {
+        assert_eq!(split_str("", " "), [""; 0]);
+        assert_eq!(split_str("   ", " \t\n"), [""; 0]);
+        assert_eq!(split_str("a", " \t\n"), ["a"]);
+        assert_eq!(split_str(" a\t b \n", " \t\n"), ["a", "b"]);
+        assert_eq!(split_str("a  b", " "), ["a", "b"]);
+    }
+
+    #[test]
+    fn non_whitespace_delimiters() {
+        assert_eq!(split_str("a:b", ":"), ["a", "b"]);
+        assert_eq!(split_str("a::b", ":"), ["a", "", "b"]);
+        assert_eq!(split_str(":a", ":"), ["", "a"]);
+        assert_eq!(split_str("a:", ":"), ["a"]);
+        assert_eq!(split_str(":", ":"), [""]);
+        assert_eq!(split_str("::", ":"), ["", ""]);
+    }
+
+    #[test]
+    fn mixed_delimiters() {
+        assert_eq!(split_str(" a : b ", " :"), ["a", "b"]);
+        assert_eq!(split_str("a :: b", " :"), ["a", "", "b"]);
+        assert_eq!(split_str(" : a", " :"), ["", "a"]);
+        assert_eq!(split_str("a : ", " :"), ["a"]);
+    }
+
+    #[test]
+    fn whitespace_not_in_ifs_is_not_delimiter() {
+        assert_eq!(split_str(" a b ", ":"), [" a b "]);
+    }
+
+    #[test]
+    fn quoted_and_literal_chars_are_not_delimiters() {
+        let mut field = soft_field("a b c d");
+        field.chars[1].is_quoted = true;
+        field.chars[3].origin = Origin::Literal;
+        field.chars[5].origin = Origin::HardExpansion;
+        let fields = split(field, &Ifs::new(" "));
+        assert_eq!(values(&fields), ["a b c d"]);
+    }
+
+    #[test]
+    fn split_fields_keep_origin() {
+        let field = soft_field("a b");
+        let origin = field.origin.clone();
+        let fields = split(field, &Ifs::new(" "));
+        assert_eq!(fields[0].origin, origin);
+        assert_eq!(fields[1].origin, origin);
+    }
+
+    #[test]
+    fn empty_ifs_only_removes_empty_fields() {
+        let fields = vec![soft_field("a b"), soft_field("")];
+        let fields = split_fields(fields, &Ifs::new(""));
+        assert_eq!(values(&fields), ["a b"]);
+    }
+
+    #[test]
+    fn ifs_from_env() {
+        let mut env = Env::new_virtual();
+        assert_eq!(Ifs::from_env(&env), Ifs::new(DEFAULT_IFS));
+        assign_scalar(&mut env, "IFS", ":");
+        assert_eq!(Ifs::from_env(&env), Ifs::new(":"));
+        assign_scalar(&mut env, "IFS", "");
+        assert!(Ifs::from_env(&env).is_empty());
+    }
+}
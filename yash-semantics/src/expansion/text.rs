@@ -0,0 +1,177 @@
+// This file is part of yash, an extended POSIX shell.
+// Copyright (C) 2021 WATANABE Yuki
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Initial expansion of texts and text units.
+
+use super::arith;
+use super::command_subst;
+use super::param::ParamRef;
+use super::AttrChar;
+use super::Expand;
+use super::Origin;
+use super::Phrase;
+use async_trait::async_trait;
+use yash_env::expansion::Result;
+use yash_env::Env;
+use yash_syntax::syntax::Modifier;
+use yash_syntax::syntax::Text;
+use yash_syntax::syntax::TextUnit::{self, *};
+
+/// Expands a text unit.
+///
+/// `in_double_quotes` is passed to [`ParamRef`].
+async fn expand_unit(unit: &TextUnit, env: &mut Env, in_double_quotes: bool) -> Result<Phrase> {
+    match unit {
+        Literal(value) => Ok(Phrase::one_field(vec![AttrChar {
+            value: *value,
+            origin: Origin::Literal,
+            is_quoted: false,
+            is_quoting: false,
+        }])),
+        Backslashed(value) => {
+            let bs = AttrChar {
+                value: '\\',
+                origin: Origin::Literal,
+                is_quoted: false,
+                is_quoting: true,
+            };
+            let c = AttrChar {
+                value: *value,
+                origin: Origin::Literal,
+                is_quoted: true,
+                is_quoting: false,
+            };
+            Ok(Phrase::one_field(vec![bs, c]))
+        }
+        RawParam { name, location } => {
+            let param = ParamRef {
+                name,
+                modifier: &Modifier::None,
+                location,
+                in_double_quotes,
+            };
+            param.expand(env).await
+        }
+        BracedParam(param) => {
+            let param = ParamRef {
+                in_double_quotes,
+                ..ParamRef::from(param)
+            };
+            param.expand(env).await
+        }
+        CommandSubst { content, location } => {
+            command_subst::expand(env, content.clone(), location).await
+        }
+        Backquote { content, location } => {
+            let content = command_subst::unescape_backquote(content);
+            command_subst::expand(env, content, location).await
+        }
+        Arith { content, location } => arith::expand(env, content, location).await,
+    }
+}
+
+#[async_trait(?Send)]
+impl Expand for TextUnit {
+    async fn expand(&self, env: &mut Env) -> Result<Phrase> {
+        expand_unit(self, env, false).await
+    }
+}
+
+#[async_trait(?Send)]
+impl Expand for Text {
+    async fn expand(&self, env: &mut Env) -> Result<Phrase> {
+        self.0.expand(env).await
+    }
+}
+
+/// Text unit in double quotes.
+struct QuotedUnit<'a>(&'a TextUnit);
+
+#[async_trait(?Send)]
+impl Expand for QuotedUnit<'_> {
+    async fn expand(&self, env: &mut Env) -> Result<Phrase> {
+        expand_unit(self.0, env, true).await
+    }
+}
+
+/// Expands a text in double quotes.
+///
+/// This function is the same as [`Text::expand`](Expand::expand) except that
+/// parameter expansions are performed as [in double
+/// quotes](ParamRef::in_double_quotes). The resulting characters are not marked
+/// as quoted.
+pub async fn expand_in_double_quotes(text: &Text, env: &mut Env) -> Result<Phrase> {
+    let units: Vec<QuotedUnit> = text.0.iter().map(QuotedUnit).collect();
+    units.expand(env).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expansion::tests::assign_scalar;
+    use futures::executor::block_on;
+
+    #[test]
+    fn literal_expands_to_itself() {
+        let mut env = Env::new_virtual();
+        let phrase = block_on(Literal('L').expand(&mut env)).unwrap();
+        let c = AttrChar {
+            value: 'L',
+            origin: Origin::Literal,
+            is_quoted: false,
+            is_quoting: false,
+        };
+        assert_eq!(phrase, Phrase::one_field(vec![c]));
+    }
+
+    #[test]
+    fn backslashed_expands_to_quoting_and_quoted_chars() {
+        let mut env = Env::new_virtual();
+        let phrase = block_on(Backslashed('$').expand(&mut env)).unwrap();
+        let bs = AttrChar {
+            value: '\\',
+            origin: Origin::Literal,
+            is_quoted: false,
+            is_quoting: true,
+        };
+        let c = AttrChar {
+            value: '$',
+            origin: Origin::Literal,
+            is_quoted: true,
+            is_quoting: false,
+        };
+        assert_eq!(phrase, Phrase::one_field(vec![bs, c]));
+    }
+
+    #[test]
+    fn empty_text_expands_to_one_empty_field() {
+        let mut env = Env::new_virtual();
+        let phrase = block_on(Text(vec![]).expand(&mut env)).unwrap();
+        assert_eq!(phrase, Phrase::one_empty_field());
+    }
+
+    #[test]
+    fn text_concatenates_units() {
+        let mut env = Env::new_virtual();
+        assign_scalar(&mut env, "x", "X");
+        let text: Text = "a${x}b".parse().unwrap();
+        let phrase = block_on(text.expand(&mut env)).unwrap();
+        let values: String = phrase.0[0].iter().map(|c| c.value).collect();
+        assert_eq!(phrase.0.len(), 1);
+        assert_eq!(values, "aXb");
+        assert_eq!(phrase.0[0][1].origin, Origin::SoftExpansion);
+    }
+}
@@ -0,0 +1,237 @@
+// This file is part of yash, an extended POSIX shell.
+// Copyright (C) 2021 WATANABE Yuki
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Initial expansion of words and word units.
+
+use super::text::expand_in_double_quotes;
+use super::AttrChar;
+use super::Expand;
+use super::Origin;
+use super::Phrase;
+use async_trait::async_trait;
+use yash_env::expansion::Result;
+use yash_env::variable::Value;
+use yash_env::Env;
+use yash_syntax::syntax::Word;
+use yash_syntax::syntax::WordUnit::{self, *};
+
+/// Returns a quotation mark character.
+fn quote(value: char) -> AttrChar {
+    AttrChar {
+        value,
+        origin: Origin::Literal,
+        is_quoted: false,
+        is_quoting: true,
+    }
+}
+
+/// Returns the home directory for a tilde expansion.
+///
+/// An empty name means the value of `$HOME`. Otherwise, the name is looked up
+/// in the user database through the system.
+fn home_dir(env: &Env, name: &str) -> Option<String> {
+    if name.is_empty() {
+        match env.variables.get("HOME").map(|v| &v.value) {
+            Some(Value::Scalar(home)) => Some(home.clone()),
+            _ => None,
+        }
+    } else {
+        let dir = env.system.getpwnam_dir(name).ok()??;
+        // TODO Support home directories that are not valid UTF-8
+        dir.into_os_string().into_string().ok()
+    }
+}
+
+/// Performs tilde expansion.
+///
+/// The tilde expansion with an empty name expands to the value of `$HOME`.
+/// Otherwise, it expands to the home directory of the user having the name.
+/// If `$HOME` is not a scalar variable or the user is not found, the tilde is
+/// left intact.
+fn expand_tilde(env: &Env, name: &str) -> Phrase {
+    match home_dir(env, name) {
+        Some(dir) => Phrase::with_str(&dir, Origin::HardExpansion),
+        None => Phrase::with_str(&format!("~{}", name), Origin::Literal),
+    }
+}
+
+#[async_trait(?Send)]
+impl Expand for WordUnit {
+    async fn expand(&self, env: &mut Env) -> Result<Phrase> {
+        match self {
+            Unquoted(unit) => unit.expand(env).await,
+            SingleQuote(value) => {
+                let mut chars = vec![quote('\'')];
+                chars.extend(value.chars().map(|value| AttrChar {
+                    value,
+                    origin: Origin::Literal,
+                    is_quoted: true,
+                    is_quoting: false,
+                }));
+                chars.push(quote('\''));
+                Ok(Phrase::one_field(chars))
+            }
+            DoubleQuote(text) => {
+                let mut phrase = expand_in_double_quotes(text, env).await?;
+                phrase.quote();
+                // Every field is enclosed in quotes so that an empty field
+                // (like one from "$@") is not removed. If the content results
+                // in no fields (like "$@" with no positional parameters), the
+                // quotes vanish as well.
+                for field in &mut phrase.0 {
+                    field.insert(0, quote('"'));
+                    field.push(quote('"'));
+                }
+                Ok(phrase)
+            }
+            Tilde(name) => Ok(expand_tilde(env, name)),
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl Expand for Word {
+    async fn expand(&self, env: &mut Env) -> Result<Phrase> {
+        self.units.expand(env).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expansion::tests::assign_scalar;
+    use futures::executor::block_on;
+    use std::path::PathBuf;
+    use yash_env::VirtualSystem;
+    use yash_syntax::syntax::Assign;
+    use yash_syntax::syntax::Text;
+    use yash_syntax::syntax::TextUnit::Literal;
+    use yash_syntax::syntax::Value::Scalar;
+
+    fn values(phrase: &Phrase) -> Vec<String> {
+        phrase
+            .0
+            .iter()
+            .map(|field| field.iter().map(|c| c.value).collect())
+            .collect()
+    }
+
+    #[test]
+    fn single_quote_expands_to_quoted_chars() {
+        let mut env = Env::new_virtual();
+        let unit = SingleQuote("a$".to_string());
+        let phrase = block_on(unit.expand(&mut env)).unwrap();
+        let a = AttrChar {
+            value: 'a',
+            origin: Origin::Literal,
+            is_quoted: true,
+            is_quoting: false,
+        };
+        let d = AttrChar { value: '$', ..a };
+        assert_eq!(
+            phrase,
+            Phrase::one_field(vec![quote('\''), a, d, quote('\'')])
+        );
+    }
+
+    #[test]
+    fn double_quote_quotes_expansion_results() {
+        let mut env = Env::new_virtual();
+        assign_scalar(&mut env, "x", "X");
+        let unit: WordUnit = "\"a$x\"".parse().unwrap();
+        let phrase = block_on(unit.expand(&mut env)).unwrap();
+        assert_eq!(values(&phrase), ["\"aX\""]);
+        let field = &phrase.0[0];
+        assert!(field[0].is_quoting);
+        assert!(!field[0].is_quoted);
+        assert!(field[1].is_quoted);
+        assert!(field[2].is_quoted);
+        assert_eq!(field[2].origin, Origin::SoftExpansion);
+        assert!(field[3].is_quoting);
+    }
+
+    #[test]
+    fn empty_double_quote_expands_to_quotes() {
+        let mut env = Env::new_virtual();
+        let unit = DoubleQuote(Text(vec![]));
+        let phrase = block_on(unit.expand(&mut env)).unwrap();
+        assert_eq!(phrase, Phrase::one_field(vec![quote('"'), quote('"')]));
+    }
+
+    #[test]
+    fn tilde_expands_to_home() {
+        let mut env = Env::new_virtual();
+        assign_scalar(&mut env, "HOME", "/home/foo");
+        let mut word: Word = "~/bar".parse().unwrap();
+        word.parse_tilde_front();
+        let phrase = block_on(word.expand(&mut env)).unwrap();
+        assert_eq!(values(&phrase), ["/home/foo/bar"]);
+        assert_eq!(phrase.0[0][0].origin, Origin::HardExpansion);
+        assert_eq!(phrase.0[0][9].origin, Origin::Literal);
+    }
+
+    #[test]
+    fn tilde_without_home_is_left_intact() {
+        let mut env = Env::new_virtual();
+        let phrase = block_on(Tilde("".to_string()).expand(&mut env)).unwrap();
+        assert_eq!(values(&phrase), ["~"]);
+    }
+
+    #[test]
+    fn tilde_expands_to_user_home() {
+        let system = VirtualSystem::new();
+        system
+            .state
+            .borrow_mut()
+            .home_dirs
+            .insert("foo".to_string(), PathBuf::from("/home/foo"));
+        let mut env = Env::with_system(Box::new(system));
+        let phrase = block_on(Tilde("foo".to_string()).expand(&mut env)).unwrap();
+        assert_eq!(values(&phrase), ["/home/foo"]);
+        assert_eq!(phrase.0[0][0].origin, Origin::HardExpansion);
+    }
+
+    #[test]
+    fn tilde_with_unknown_user_is_left_intact() {
+        let mut env = Env::new_virtual();
+        let phrase = block_on(Tilde("nobody".to_string()).expand(&mut env)).unwrap();
+        assert_eq!(values(&phrase), ["~nobody"]);
+    }
+
+    #[test]
+    fn tildes_in_assignment_value() {
+        let mut env = Env::new_virtual();
+        assign_scalar(&mut env, "HOME", "/home/foo");
+        let assign: Assign = "PATH=~/bin:~:/bin".parse().unwrap();
+        let word = match assign.value {
+            Scalar(word) => word,
+            value => panic!("unexpected value {:?}", value),
+        };
+        let phrase = block_on(word.expand(&mut env)).unwrap();
+        assert_eq!(values(&phrase), ["/home/foo/bin:/home/foo:/bin"]);
+    }
+
+    #[test]
+    fn word_concatenates_units() {
+        let mut env = Env::new_virtual();
+        let word = Word {
+            units: vec![Unquoted(Literal('a')), SingleQuote("b".to_string())],
+            location: yash_syntax::source::Location::dummy("ab"),
+        };
+        let phrase = block_on(word.expand(&mut env)).unwrap();
+        assert_eq!(values(&phrase), ["a'b'"]);
+    }
+}
@@ -0,0 +1,140 @@
+// This file is part of yash, an extended POSIX shell.
+// Copyright (C) 2021 WATANABE Yuki
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Implementation of function definition semantics.
+
+use super::Command;
+use crate::expansion::expand_word;
+use async_trait::async_trait;
+use std::rc::Rc;
+use yash_env::exec::ExitStatus;
+use yash_env::exec::Result;
+use yash_env::function::HashEntry;
+use yash_env::Env;
+use yash_syntax::syntax;
+
+/// Defines a function.
+///
+/// The function name is expanded and the function is added to
+/// [`Env::functions`], replacing the existing function of the same name, if
+/// any. It is an error to redefine a read-only function, in which case the
+/// exit status is [`ExitStatus::ERROR`] and the errexit option applies.
+/// Otherwise, the exit status is zero.
+#[async_trait(?Send)]
+impl Command for syntax::FunctionDefinition {
+    async fn execute(&self, env: &mut Env) -> Result {
+        let name = match expand_word(env, &self.name).await {
+            Ok(field) => field,
+            Err(error) => return crate::expansion::handle_error(env, error),
+        };
+
+        if let Some(existing) = env.functions.get(name.value.as_str()) {
+            if existing.0.is_read_only {
+                let message = format!(
+                    "cannot redefine read-only function `{}` (defined at line {})",
+                    name.value, existing.0.origin.line.number
+                );
+                crate::print_error(env, &name.origin, &message);
+                env.exit_status = ExitStatus::ERROR;
+                return env.apply_errexit();
+            }
+        }
+
+        let body = Rc::new(self.body.clone());
+        let origin = self.name.location.clone();
+        let entry = HashEntry::new(name.value, body, origin, false);
+        env.functions.replace(entry);
+        env.exit_status = ExitStatus::SUCCESS;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use yash_env::exec::Divert;
+    use yash_syntax::source::Location;
+
+    fn parse(source: &str) -> syntax::FunctionDefinition {
+        match source.parse().unwrap() {
+            syntax::Command::Function(definition) => definition,
+            command => panic!("not a function definition: {:?}", command),
+        }
+    }
+
+    #[test]
+    fn function_definition_new() {
+        let mut env = Env::new_virtual();
+        env.exit_status = ExitStatus(13);
+        let definition = parse("foo() { :; }");
+        let result = block_on(definition.execute(&mut env));
+        assert_eq!(result, Ok(()));
+        assert_eq!(env.exit_status, ExitStatus::SUCCESS);
+
+        let function = &env.functions.get("foo").unwrap().0;
+        assert_eq!(function.name, "foo");
+        assert_eq!(*function.body, definition.body);
+        assert_eq!(function.origin, definition.name.location);
+        assert!(!function.is_read_only);
+    }
+
+    #[test]
+    fn function_definition_overwrite() {
+        let mut env = Env::new_virtual();
+        let old = parse("foo() { old; }");
+        let new = parse("foo() { new; }");
+        block_on(old.execute(&mut env)).unwrap();
+        let result = block_on(new.execute(&mut env));
+        assert_eq!(result, Ok(()));
+        assert_eq!(env.functions.len(), 1);
+        assert_eq!(*env.functions.get("foo").unwrap().0.body, new.body);
+    }
+
+    #[test]
+    fn function_definition_read_only() {
+        let mut env = Env::new_virtual();
+        let old = parse("foo() { old; }");
+        env.functions.insert(HashEntry::new(
+            "foo".to_string(),
+            Rc::new(old.body.clone()),
+            Location::dummy("foo"),
+            true,
+        ));
+        let new = parse("foo() { new; }");
+        let result = block_on(new.execute(&mut env));
+        assert_eq!(result, Ok(()));
+        assert_eq!(env.exit_status, ExitStatus::ERROR);
+        assert_eq!(*env.functions.get("foo").unwrap().0.body, old.body);
+    }
+
+    #[test]
+    fn function_definition_read_only_with_errexit() {
+        let mut env = Env::new_virtual();
+        env.options
+            .set(yash_env::option::ShellOption::ErrExit, true);
+        let old = parse("foo() { old; }");
+        env.functions.insert(HashEntry::new(
+            "foo".to_string(),
+            Rc::new(old.body.clone()),
+            Location::dummy("foo"),
+            true,
+        ));
+        let new = parse("foo() { new; }");
+        let result = block_on(new.execute(&mut env));
+        assert_eq!(result, Err(Divert::Exit(ExitStatus::ERROR)));
+    }
+}
@@ -22,13 +22,24 @@
 //!
 //! TODO Elaborate
 
+pub mod assign;
 mod command_impl;
 pub mod command_search;
+mod compound_command;
+pub mod expansion;
+mod function_definition;
+pub mod pattern;
 mod pipeline;
+mod read_eval_loop;
+pub mod redir;
 mod simple_command;
+pub mod xtrace;
 
 use async_trait::async_trait;
+use yash_env::option::ShellOption;
 use yash_env::Env;
+use yash_syntax::source::Location;
+use yash_syntax::source::Source;
 
 pub use yash_env::exec::*;
 
@@ -41,35 +52,81 @@ pub trait Command {
     async fn execute(&self, env: &mut Env) -> Result;
 }
 
-/// Result of expansion.
+pub use expansion::expand_word;
+pub use expansion::expand_words;
+pub use expansion::Expand;
+pub use read_eval_loop::read_eval_loop;
+
+/// Executes a command with the errexit option suppressed.
 ///
-/// TODO elaborate
-#[derive(Clone, Debug, Eq, PartialEq)]
-pub struct Expansion {
-    // TODO define value
+/// This function sets [`Env::errexit_is_suppressed`] while executing the
+/// command, and restores the original value afterwards. Use this function to
+/// execute the condition of an `if`, `while`, or `until` command, a pipeline
+/// other than the last in an and-or list, and a negated pipeline.
+async fn execute_suppressing_errexit<C: Command + ?Sized>(command: &C, env: &mut Env) -> Result {
+    let suppressed = std::mem::replace(&mut env.errexit_is_suppressed, true);
+    let result = command.execute(env).await;
+    env.errexit_is_suppressed = suppressed;
+    result
 }
 
-// TODO Reconsider the name
-/// TODO describe
-#[async_trait(?Send)]
-pub trait Word {
-    /// TODO describe
-    async fn expand(&self, env: &mut Env) -> Result<Expansion>;
+/// Prints an error message with its location.
+///
+/// The location is printed with the [label](Source::label) of its source. If
+/// the location is inside a command substitution, the location of the command
+/// substitution is printed as well.
+fn print_error(env: &mut Env, location: &Location, message: &dyn std::fmt::Display) {
+    let mut text = format!(
+        "{}:{}:{}: {}",
+        location.line.source.label(),
+        location.line.number,
+        location.column,
+        message
+    );
+    let mut source = &location.line.source;
+    while let Source::CommandSubst { original } = source {
+        text.push_str(&format!(
+            "\n{}:{}:{}: (in this command substitution)",
+            original.line.source.label(),
+            original.line.number,
+            original.column
+        ));
+        source = &original.line.source;
+    }
+    env.print_error(&text);
 }
 
-// TODO Probably we should implement a read-execute loop in here
+/// Returns the divert that results from an error that aborts the shell.
+///
+/// A non-interactive shell exits with [`ExitStatus::ERROR`]. An interactive
+/// shell does not exit; the result is [`Divert::Interrupt`], which abandons the
+/// rest of the current command line so that the shell can go on with the next.
+fn error_divert(env: &Env) -> Divert {
+    if env.options.is_on(ShellOption::Interactive) {
+        Divert::Interrupt
+    } else {
+        Divert::Exit(ExitStatus::ERROR)
+    }
+}
 
 #[cfg(test)]
 pub(crate) mod tests {
+    use crate::xtrace::tests::env_with_stderr_pipe;
+    use crate::xtrace::tests::read_all;
     use std::future::ready;
     use std::future::Future;
+    use std::num::NonZeroU64;
     use std::pin::Pin;
+    use std::rc::Rc;
     use yash_env::builtin::Builtin;
+    use yash_env::builtin::Type::Intrinsic;
     use yash_env::builtin::Type::Special;
     use yash_env::exec::Divert;
     use yash_env::exec::ExitStatus;
     use yash_env::expansion::Field;
     use yash_env::Env;
+    use yash_syntax::source::Location;
+    use yash_syntax::source::Source;
 
     fn return_builtin_main(
         _env: &mut Env,
@@ -96,4 +153,88 @@ pub(crate) mod tests {
             execute: return_builtin_main,
         }
     }
+
+    fn break_builtin_main(
+        _env: &mut Env,
+        args: Vec<Field>,
+    ) -> Pin<Box<dyn Future<Output = yash_env::builtin::Result>>> {
+        let count = match args.get(1) {
+            Some(field) => field.value.parse().unwrap_or(1),
+            None => 1,
+        };
+        let divert = Divert::Break { count: count - 1 };
+        Box::pin(ready((ExitStatus::SUCCESS, Some(divert))))
+    }
+
+    /// Returns a minimal implementation of the `break` built-in.
+    pub fn break_builtin() -> Builtin {
+        Builtin {
+            r#type: Special,
+            execute: break_builtin_main,
+        }
+    }
+
+    fn continue_builtin_main(
+        _env: &mut Env,
+        _args: Vec<Field>,
+    ) -> Pin<Box<dyn Future<Output = yash_env::builtin::Result>>> {
+        Box::pin(ready((ExitStatus::SUCCESS, Some(Divert::Continue))))
+    }
+
+    /// Returns a minimal implementation of the `continue` built-in.
+    pub fn continue_builtin() -> Builtin {
+        Builtin {
+            r#type: Special,
+            execute: continue_builtin_main,
+        }
+    }
+
+    fn echo_builtin_main(
+        env: &mut Env,
+        args: Vec<Field>,
+    ) -> Pin<Box<dyn Future<Output = yash_env::builtin::Result>>> {
+        let fields = args[1..].iter().map(|field| field.value.as_str());
+        let mut content = fields.collect::<Vec<_>>().join(" ");
+        content.push('\n');
+        let exit_status = match env.system.write(1, content.as_bytes()) {
+            Ok(_) => ExitStatus::SUCCESS,
+            Err(_) => ExitStatus::FAILURE,
+        };
+        Box::pin(ready((exit_status, None)))
+    }
+
+    /// Returns a minimal implementation of the `echo` built-in.
+    ///
+    /// The built-in writes its operands to the standard output in one call to
+    /// [`System::write`](yash_env::System::write).
+    pub fn echo_builtin() -> Builtin {
+        Builtin {
+            r#type: Intrinsic,
+            execute: echo_builtin_main,
+        }
+    }
+
+    #[test]
+    fn print_error_shows_source_and_position() {
+        let (mut env, reader) = env_with_stderr_pipe();
+        let mut location = Location::dummy("foo");
+        Rc::make_mut(&mut location.line).source = Source::Stdin;
+        location.column = NonZeroU64::new(3).unwrap();
+        super::print_error(&mut env, &location, &"oops");
+        assert_eq!(read_all(&mut env, reader), "<stdin>:1:3: oops\n");
+    }
+
+    #[test]
+    fn print_error_shows_command_substitution() {
+        let (mut env, reader) = env_with_stderr_pipe();
+        let mut original = Location::dummy("$(foo)");
+        Rc::make_mut(&mut original.line).source = Source::Stdin;
+        let mut location = Location::dummy("foo");
+        Rc::make_mut(&mut location.line).source = Source::CommandSubst { original };
+        super::print_error(&mut env, &location, &"oops");
+        assert_eq!(
+            read_all(&mut env, reader),
+            "<command_substitution>:1:1: oops\n<stdin>:1:1: (in this command substitution)\n"
+        );
+    }
 }
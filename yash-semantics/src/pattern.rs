@@ -0,0 +1,584 @@
+// This file is part of yash, an extended POSIX shell.
+// Copyright (C) 2021 WATANABE Yuki
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Pattern matching.
+//!
+//! This module implements the pattern matching notation defined in POSIX.
+//! A [`Pattern`] is compiled from a sequence of [`PatternChar`]s, which
+//! distinguish characters that have special meaning from those that must be
+//! matched literally.
+//!
+//! The following special characters are supported:
+//!
+//! - `?` matches any single character.
+//! - `*` matches any string, including the empty string.
+//! - `[...]` is a bracket expression that matches a single character in the
+//!   brackets. A bracket expression may contain ranges like `a-z` and
+//!   character classes like `[:alpha:]`. A bracket expression starting with
+//!   `!` or `^` matches a character not in the brackets. A `\` in the
+//!   brackets makes the next character an ordinary member, so `[\]]` matches
+//!   `]`.
+//! - `\` makes the next character match literally.
+//!
+//! A `[` that does not start a valid bracket expression matches itself.
+
+/// Character constituting a pattern.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum PatternChar {
+    /// Character that may have special meaning in the pattern.
+    Normal(char),
+    /// Character that is always matched literally.
+    ///
+    /// This is used for characters that are quoted in the original word.
+    Literal(char),
+}
+
+impl PatternChar {
+    /// Returns the character value.
+    pub fn char_value(self) -> char {
+        match self {
+            PatternChar::Normal(c) | PatternChar::Literal(c) => c,
+        }
+    }
+}
+
+/// Character class in a bracket expression.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+enum Class {
+    Alnum,
+    Alpha,
+    Blank,
+    Cntrl,
+    Digit,
+    Graph,
+    Lower,
+    Print,
+    Punct,
+    Space,
+    Upper,
+    Xdigit,
+}
+
+impl Class {
+    /// Returns the class having the name.
+    fn from_name(name: &str) -> Option<Class> {
+        use Class::*;
+        let class = match name {
+            "alnum" => Alnum,
+            "alpha" => Alpha,
+            "blank" => Blank,
+            "cntrl" => Cntrl,
+            "digit" => Digit,
+            "graph" => Graph,
+            "lower" => Lower,
+            "print" => Print,
+            "punct" => Punct,
+            "space" => Space,
+            "upper" => Upper,
+            "xdigit" => Xdigit,
+            _ => return None,
+        };
+        Some(class)
+    }
+
+    /// Tests if the character belongs to this class.
+    fn contains(self, c: char) -> bool {
+        use Class::*;
+        match self {
+            Alnum => c.is_alphanumeric(),
+            Alpha => c.is_alphabetic(),
+            Blank => c == ' ' || c == '\t',
+            Cntrl => c.is_control(),
+            Digit => c.is_ascii_digit(),
+            Graph => !c.is_control() && !c.is_whitespace(),
+            Lower => c.is_lowercase(),
+            Print => !c.is_control(),
+            Punct => c.is_ascii_punctuation(),
+            Space => c.is_whitespace(),
+            Upper => c.is_uppercase(),
+            Xdigit => c.is_ascii_hexdigit(),
+        }
+    }
+}
+
+/// Element of a bracket expression.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+enum BracketItem {
+    /// Single character.
+    Char(char),
+    /// Inclusive range of characters.
+    Range(char, char),
+    /// Character class.
+    Class(Class),
+}
+
+impl BracketItem {
+    fn matches(self, c: char) -> bool {
+        match self {
+            BracketItem::Char(d) => c == d,
+            BracketItem::Range(start, end) => start <= c && c <= end,
+            BracketItem::Class(class) => class.contains(c),
+        }
+    }
+}
+
+/// Element of a [`Pattern`].
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+enum Atom {
+    /// Character that matches itself.
+    Char(char),
+    /// `?`
+    AnyChar,
+    /// `*`
+    AnyString,
+    /// Bracket expression.
+    Bracket {
+        /// Whether the bracket expression starts with `!` or `^`.
+        negated: bool,
+        items: Vec<BracketItem>,
+    },
+}
+
+impl Atom {
+    /// Tests if this single-character atom matches the character.
+    ///
+    /// This function returns false for `AnyString`.
+    fn matches_char(&self, c: char) -> bool {
+        match self {
+            Atom::Char(d) => c == *d,
+            Atom::AnyChar => true,
+            Atom::AnyString => false,
+            Atom::Bracket { negated, items } => {
+                items.iter().any(|item| item.matches(c)) != *negated
+            }
+        }
+    }
+}
+
+/// Parses the content of a bracket expression delimited by `[:` and `:]`
+/// or a similar pair.
+///
+/// `chars` must start just after the opening `[` and delimiter. Returns the
+/// content and the number of pattern characters consumed, including the
+/// closing delimiter and `]`.
+fn bracket_word(chars: &[PatternChar], delimiter: char) -> Option<(String, usize)> {
+    let mut word = String::new();
+    for (i, pair) in chars.windows(2).enumerate() {
+        if pair[0] == PatternChar::Normal(delimiter) && pair[1] == PatternChar::Normal(']') {
+            return Some((word, i + 2));
+        }
+        word.push(pair[0].char_value());
+    }
+    None
+}
+
+/// Parses a bracket expression.
+///
+/// `chars` must start just after the opening `[`. Returns the atom and the
+/// number of pattern characters consumed, including the closing `]`. Returns
+/// `None` if the bracket expression is not closed or otherwise invalid.
+fn bracket(chars: &[PatternChar]) -> Option<(Atom, usize)> {
+    let mut i = 0;
+    let negated = matches!(
+        chars.first(),
+        Some(PatternChar::Normal('!')) | Some(PatternChar::Normal('^'))
+    );
+    if negated {
+        i += 1;
+    }
+
+    // Single characters parsed so far, which may start a range
+    let mut items = Vec::new();
+    let first = i;
+    loop {
+        let c = *chars.get(i)?;
+        match c {
+            PatternChar::Normal(']') if i > first => {
+                return Some((Atom::Bracket { negated, items }, i + 1));
+            }
+            PatternChar::Normal('[') => match chars.get(i + 1) {
+                Some(PatternChar::Normal(':')) => {
+                    let (name, len) = bracket_word(&chars[i + 2..], ':')?;
+                    items.push(BracketItem::Class(Class::from_name(&name)?));
+                    i += 2 + len;
+                    continue;
+                }
+                Some(PatternChar::Normal(delimiter @ '='))
+                | Some(PatternChar::Normal(delimiter @ '.')) => {
+                    let (word, len) = bracket_word(&chars[i + 2..], *delimiter)?;
+                    let mut word_chars = word.chars();
+                    match (word_chars.next(), word_chars.next()) {
+                        (Some(c), None) => items.push(BracketItem::Char(c)),
+                        // TODO Support multi-character collating elements
+                        _ => return None,
+                    }
+                    i += 2 + len;
+                    continue;
+                }
+                _ => items.push(BracketItem::Char('[')),
+            },
+            PatternChar::Normal('\\') if i + 1 < chars.len() => {
+                items.push(BracketItem::Char(chars[i + 1].char_value()));
+                i += 2;
+                continue;
+            }
+            PatternChar::Normal('-')
+                if i > first
+                    && chars.get(i + 1) != Some(&PatternChar::Normal(']'))
+                    && chars.get(i + 1).is_some() =>
+            {
+                if let Some(BracketItem::Char(start)) = items.last().copied() {
+                    let (end, len) = match &chars[i + 1..] {
+                        [PatternChar::Normal('\\'), escaped, ..] => (escaped.char_value(), 3),
+                        [end, ..] => (end.char_value(), 2),
+                        [] => unreachable!(),
+                    };
+                    items.pop();
+                    items.push(BracketItem::Range(start, end));
+                    i += len;
+                    continue;
+                }
+                items.push(BracketItem::Char('-'));
+            }
+            c => items.push(BracketItem::Char(c.char_value())),
+        }
+        i += 1;
+    }
+}
+
+/// Compiled pattern.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Pattern {
+    atoms: Vec<Atom>,
+}
+
+impl Pattern {
+    /// Compiles a pattern.
+    pub fn new<I: IntoIterator<Item = PatternChar>>(chars: I) -> Pattern {
+        let chars: Vec<PatternChar> = chars.into_iter().collect();
+        let mut atoms = Vec::new();
+        let mut i = 0;
+        while let Some(&c) = chars.get(i) {
+            i += 1;
+            let atom = match c {
+                PatternChar::Normal('?') => Atom::AnyChar,
+                PatternChar::Normal('*') => Atom::AnyString,
+                PatternChar::Normal('\\') => match chars.get(i) {
+                    Some(next) => {
+                        i += 1;
+                        Atom::Char(next.char_value())
+                    }
+                    None => Atom::Char('\\'),
+                },
+                PatternChar::Normal('[') => match bracket(&chars[i..]) {
+                    Some((atom, len)) => {
+                        i += len;
+                        atom
+                    }
+                    None => Atom::Char('['),
+                },
+                PatternChar::Normal(c) | PatternChar::Literal(c) => Atom::Char(c),
+            };
+            atoms.push(atom);
+        }
+        Pattern { atoms }
+    }
+
+    /// Returns the string this pattern matches if it contains no special
+    /// characters.
+    #[must_use]
+    pub fn to_literal(&self) -> Option<String> {
+        self.atoms
+            .iter()
+            .map(|atom| match atom {
+                Atom::Char(c) => Some(*c),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Tests if this pattern starts with a literal period.
+    ///
+    /// In pathname expansion, a filename starting with a period must be
+    /// matched by a pattern that starts with a literal period.
+    #[must_use]
+    pub fn starts_with_period(&self) -> bool {
+        self.atoms.first() == Some(&Atom::Char('.'))
+    }
+
+    /// Tests if this pattern matches the whole string.
+    #[must_use]
+    pub fn is_match(&self, s: &str) -> bool {
+        let chars: Vec<char> = s.chars().collect();
+        let atoms = &self.atoms;
+        let (mut ai, mut ci) = (0, 0);
+        // Position of the last `*` and the index of the character it was
+        // tried to match up to
+        let mut backtrack = None;
+
+        while ci < chars.len() {
+            match atoms.get(ai) {
+                Some(Atom::AnyString) => {
+                    backtrack = Some((ai, ci));
+                    ai += 1;
+                    continue;
+                }
+                Some(atom) if atom.matches_char(chars[ci]) => {
+                    ai += 1;
+                    ci += 1;
+                    continue;
+                }
+                _ => (),
+            }
+
+            match backtrack {
+                Some((star, star_ci)) => {
+                    ai = star + 1;
+                    ci = star_ci + 1;
+                    backtrack = Some((star, ci));
+                }
+                None => return false,
+            }
+        }
+
+        atoms[ai..].iter().all(|atom| *atom == Atom::AnyString)
+    }
+
+    /// Returns the byte indices of the character boundaries in the string.
+    ///
+    /// The result includes both 0 and `s.len()`.
+    fn boundaries(s: &str) -> impl DoubleEndedIterator<Item = usize> + '_ {
+        s.char_indices()
+            .map(|(i, _)| i)
+            .chain(std::iter::once(s.len()))
+    }
+
+    /// Finds a prefix of the string that matches this pattern.
+    ///
+    /// If `longest` is true, the longest matching prefix is searched for.
+    /// Otherwise, the shortest. Returns the length of the prefix in bytes.
+    #[must_use]
+    pub fn match_prefix(&self, s: &str, longest: bool) -> Option<usize> {
+        let mut ends = Self::boundaries(s);
+        let is_match = |&end: &usize| self.is_match(&s[..end]);
+        if longest {
+            ends.rev().find(is_match)
+        } else {
+            ends.find(is_match)
+        }
+    }
+
+    /// Finds a suffix of the string that matches this pattern.
+    ///
+    /// If `longest` is true, the longest matching suffix is searched for.
+    /// Otherwise, the shortest. Returns the byte index of the start of the
+    /// suffix.
+    #[must_use]
+    pub fn match_suffix(&self, s: &str, longest: bool) -> Option<usize> {
+        let mut starts = Self::boundaries(s);
+        let is_match = |&start: &usize| self.is_match(&s[start..]);
+        if longest {
+            starts.find(is_match)
+        } else {
+            starts.rev().find(is_match)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pattern(s: &str) -> Pattern {
+        Pattern::new(s.chars().map(PatternChar::Normal))
+    }
+
+    #[test]
+    fn literal_pattern() {
+        let p = pattern("abc");
+        assert!(p.is_match("abc"));
+        assert!(!p.is_match("ab"));
+        assert!(!p.is_match("abcd"));
+        assert!(!p.is_match(""));
+    }
+
+    #[test]
+    fn any_char_pattern() {
+        let p = pattern("a?c");
+        assert!(p.is_match("abc"));
+        assert!(p.is_match("a-c"));
+        assert!(!p.is_match("ac"));
+        assert!(!p.is_match("abbc"));
+    }
+
+    #[test]
+    fn any_string_pattern() {
+        let p = pattern("a*c*");
+        assert!(p.is_match("ac"));
+        assert!(p.is_match("abc"));
+        assert!(p.is_match("abcabcx"));
+        assert!(!p.is_match("ab"));
+        assert!(pattern("*").is_match(""));
+        assert!(pattern("*x*y").is_match("xyxxy"));
+        assert!(!pattern("*x*y").is_match("xyxyx"));
+    }
+
+    #[test]
+    fn literal_chars_are_not_special() {
+        let p = Pattern::new(vec![PatternChar::Literal('*'), PatternChar::Normal('*')]);
+        assert!(p.is_match("*"));
+        assert!(p.is_match("*foo"));
+        assert!(!p.is_match("foo"));
+    }
+
+    #[test]
+    fn backslash_escapes_next_char() {
+        let p = pattern(r"\*a\?");
+        assert!(p.is_match("*a?"));
+        assert!(!p.is_match("xa?"));
+        assert!(!p.is_match("*ab"));
+        assert!(pattern("a\\").is_match("a\\"));
+    }
+
+    #[test]
+    fn bracket_expressions() {
+        let p = pattern("[abc]");
+        assert!(p.is_match("a"));
+        assert!(p.is_match("c"));
+        assert!(!p.is_match("d"));
+        assert!(!p.is_match("ab"));
+
+        let p = pattern("x[a-c0-9]");
+        assert!(p.is_match("xb"));
+        assert!(p.is_match("x5"));
+        assert!(!p.is_match("xd"));
+    }
+
+    #[test]
+    fn negated_bracket_expressions() {
+        let p = pattern("[!a-c]");
+        assert!(!p.is_match("a"));
+        assert!(p.is_match("d"));
+        let p = pattern("[^a]");
+        assert!(!p.is_match("a"));
+        assert!(p.is_match("b"));
+    }
+
+    #[test]
+    fn special_chars_in_brackets() {
+        let p = pattern("[]a]");
+        assert!(p.is_match("]"));
+        assert!(p.is_match("a"));
+        let p = pattern("[!]]");
+        assert!(!p.is_match("]"));
+        assert!(p.is_match("a"));
+        let p = pattern("[a-]");
+        assert!(p.is_match("-"));
+        let p = pattern("[*?]");
+        assert!(p.is_match("*"));
+        assert!(!p.is_match("a"));
+    }
+
+    #[test]
+    fn backslash_escapes_in_brackets() {
+        let p = pattern(r"[\]]");
+        assert!(p.is_match("]"));
+        assert!(!p.is_match("\\"));
+        let p = pattern(r"[a\-z]");
+        assert!(p.is_match("-"));
+        assert!(!p.is_match("b"));
+        let p = pattern(r"[!\!]");
+        assert!(!p.is_match("!"));
+        assert!(p.is_match("a"));
+        let p = pattern(r"[+-\]]");
+        assert!(p.is_match("]"));
+        assert!(p.is_match("A"));
+        assert!(!p.is_match("a"));
+    }
+
+    #[test]
+    fn character_classes() {
+        let p = pattern("[[:alpha:]][[:digit:]_]");
+        assert!(p.is_match("a1"));
+        assert!(p.is_match("Z_"));
+        assert!(!p.is_match("1a"));
+        let p = pattern("[![:space:][:upper:]]");
+        assert!(p.is_match("a"));
+        assert!(!p.is_match(" "));
+        assert!(!p.is_match("A"));
+        assert!(pattern("[[=a=]]").is_match("a"));
+        assert!(pattern("[[.-.]]").is_match("-"));
+    }
+
+    #[test]
+    fn unclosed_or_invalid_brackets_are_literal() {
+        assert!(pattern("[a").is_match("[a"));
+        assert!(pattern("[]").is_match("[]"));
+        assert!(!pattern("[[:foo:]]").is_match("a"));
+    }
+
+    #[test]
+    fn literal_brackets() {
+        let p = Pattern::new(vec![
+            PatternChar::Literal('['),
+            PatternChar::Normal('a'),
+            PatternChar::Normal(']'),
+        ]);
+        assert!(p.is_match("[a]"));
+        assert!(!p.is_match("a"));
+    }
+
+    #[test]
+    fn literal_conversion() {
+        assert_eq!(pattern("abc").to_literal(), Some("abc".to_string()));
+        assert_eq!(pattern(r"a\*").to_literal(), Some("a*".to_string()));
+        assert_eq!(pattern("a*").to_literal(), None);
+        assert_eq!(pattern("[a]").to_literal(), None);
+    }
+
+    #[test]
+    fn leading_period() {
+        assert!(pattern(".*").starts_with_period());
+        assert!(!pattern("*").starts_with_period());
+        assert!(!pattern("[.]a").starts_with_period());
+    }
+
+    #[test]
+    fn multibyte_chars() {
+        let p = pattern("?é*");
+        assert!(p.is_match("日é"));
+        assert_eq!(p.match_prefix("日éあ", false), Some(5));
+        assert_eq!(p.match_prefix("日éあ", true), Some(8));
+    }
+
+    #[test]
+    fn prefix_matching() {
+        let p = pattern("*/");
+        assert_eq!(p.match_prefix("a/b/c", false), Some(2));
+        assert_eq!(p.match_prefix("a/b/c", true), Some(4));
+        assert_eq!(p.match_prefix("abc", true), None);
+        assert_eq!(pattern("").match_prefix("abc", true), Some(0));
+    }
+
+    #[test]
+    fn suffix_matching() {
+        let p = pattern(".*");
+        assert_eq!(p.match_suffix("a.tar.gz", false), Some(5));
+        assert_eq!(p.match_suffix("a.tar.gz", true), Some(1));
+        assert_eq!(p.match_suffix("abc", false), None);
+        assert_eq!(pattern("").match_suffix("abc", false), Some(3));
+    }
+}
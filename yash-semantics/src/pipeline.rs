@@ -15,32 +15,212 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 //! Implementation of pipeline semantics.
+//!
+//! A pipeline containing only one command is executed in the current shell
+//! environment. Otherwise, each command of the pipeline is executed in its own
+//! subshell. The standard output of each command is connected to the standard
+//! input of the next command via a pipe. The exit status of the pipeline is
+//! that of the last command, or with the [`PipeFail`] option, that of the last
+//! command that exited with a non-zero exit status.
+//!
+//! If the pipeline begins with `!`, the exit status is negated: a zero exit
+//! status becomes one and a non-zero exit status becomes zero. The errexit
+//! option is suppressed while executing a negated pipeline.
+//!
+//! The errexit option is applied to the exit status of a pipeline of more than
+//! one command. The command of a one-command pipeline applies the option by
+//! itself.
 
 use super::Command;
 use async_trait::async_trait;
+use std::os::unix::io::RawFd;
+use yash_env::exec::Divert;
+use yash_env::exec::ExitStatus;
 use yash_env::exec::Result;
+use yash_env::io::move_fd;
+use yash_env::io::STDIN;
+use yash_env::io::STDOUT;
+use yash_env::option::ShellOption::PipeFail;
 use yash_env::Env;
 use yash_syntax::syntax;
 
 #[async_trait(?Send)]
 impl Command for syntax::Pipeline {
     async fn execute(&self, env: &mut Env) -> Result {
-        // TODO correctly execute pipeline
-        self.commands
-            .get(0)
-            .expect("empty pipeline not yet handled")
-            .execute(env)
-            .await
+        if !self.negation {
+            return execute_commands(env, &self.commands).await;
+        }
+
+        let suppressed = std::mem::replace(&mut env.errexit_is_suppressed, true);
+        let result = execute_commands(env, &self.commands).await;
+        env.errexit_is_suppressed = suppressed;
+        result?;
+
+        env.exit_status = if env.exit_status.is_successful() {
+            ExitStatus::FAILURE
+        } else {
+            ExitStatus::SUCCESS
+        };
+        Ok(())
     }
 }
 
+/// Executes the commands of a pipeline.
+async fn execute_commands(env: &mut Env, commands: &[syntax::Command]) -> Result {
+    match commands {
+        [] => {
+            env.exit_status = ExitStatus::SUCCESS;
+            Ok(())
+        }
+        [command] => command.execute(env).await,
+        commands => {
+            execute_multi_command_pipeline(env, commands).await;
+            env.apply_errexit()
+        }
+    }
+}
+
+/// Executes commands in subshells connected with pipes.
+///
+/// The exit status of the pipeline is set to `env.exit_status`.
+async fn execute_multi_command_pipeline(env: &mut Env, commands: &[syntax::Command]) {
+    let mut child_pids = Vec::with_capacity(commands.len());
+    let mut error = None;
+    let mut previous_reader = None;
+    let last_index = commands.len() - 1;
+
+    for (index, command) in commands.iter().enumerate() {
+        let pipe = if index < last_index {
+            match env.system.pipe() {
+                Ok(pipe) => Some(pipe),
+                Err(e) => {
+                    error = Some(e);
+                    break;
+                }
+            }
+        } else {
+            None
+        };
+
+        let command = command.clone();
+        let reader = previous_reader;
+        let result = env
+            .start_subshell(move |env| {
+                Box::pin(async move {
+                    if let Err(e) = connect_pipes(env, reader, pipe) {
+                        env.print_error(&format!("cannot connect pipes: {}", e));
+                        env.exit_status = ExitStatus::ERROR;
+                        return;
+                    }
+                    if let Err(Divert::Exit(exit_status)) = command.execute(env).await {
+                        env.exit_status = exit_status;
+                    }
+                })
+            })
+            .await;
+
+        if let Some(reader) = previous_reader {
+            let _ = env.system.close(reader);
+        }
+        previous_reader = pipe.map(|(reader, writer)| {
+            let _ = env.system.close(writer);
+            reader
+        });
+
+        match result {
+            Ok(pid) => child_pids.push(pid),
+            Err(e) => {
+                error = Some(e);
+                break;
+            }
+        }
+    }
+
+    if let Some(reader) = previous_reader {
+        let _ = env.system.close(reader);
+    }
+
+    let exit_statuses = match env.wait_for_subshells(&child_pids).await {
+        Ok(exit_statuses) => exit_statuses,
+        Err(e) => {
+            error.get_or_insert(e);
+            Vec::new()
+        }
+    };
+
+    env.exit_status = if let Some(error) = error {
+        env.print_error(&format!("cannot start pipeline: {}", error));
+        ExitStatus::NOEXEC
+    } else if env.options.is_on(PipeFail) {
+        exit_statuses
+            .into_iter()
+            .rev()
+            .find(|exit_status| !exit_status.is_successful())
+            .unwrap_or(ExitStatus::SUCCESS)
+    } else {
+        exit_statuses.last().copied().unwrap_or(ExitStatus::SUCCESS)
+    };
+}
+
+/// Connects the standard input and output of the current subshell to pipes.
+///
+/// `reader` is the reading end of the pipe from the previous command, if any.
+/// `pipe` is the pipe to the next command, if any.
+fn connect_pipes(
+    env: &mut Env,
+    reader: Option<RawFd>,
+    pipe: Option<(RawFd, RawFd)>,
+) -> nix::Result<()> {
+    if let Some((next_reader, _)) = pipe {
+        env.system.close(next_reader)?;
+    }
+    if let Some(reader) = reader {
+        move_fd(&mut *env.system, reader, STDIN)?;
+    }
+    if let Some((_, writer)) = pipe {
+        move_fd(&mut *env.system, writer, STDOUT)?;
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::tests::echo_builtin;
     use crate::tests::return_builtin;
     use futures::executor::block_on;
-    use yash_env::exec::Divert;
-    use yash_env::exec::ExitStatus;
+    use futures::executor::LocalPool;
+    use std::rc::Rc;
+    use yash_env::option::ShellOption::ErrExit;
+    use yash_env::virtual_system::VirtualSystem;
+    use yash_env::System;
+
+    /// Creates an environment whose standard output is connected to a pipe.
+    ///
+    /// Returns the environment, an executor for subshells, and the reading end
+    /// of the pipe.
+    fn env_with_pipe_output() -> (Env, LocalPool, RawFd) {
+        let mut system = VirtualSystem::new();
+        let executor = LocalPool::new();
+        system.state.borrow_mut().executor = Some(Rc::new(executor.spawner()));
+        let (reader, writer) = system.pipe().unwrap();
+        let reader = system.fcntl_dupfd(reader, 10).unwrap();
+        system.dup2(writer, STDOUT).unwrap();
+        system.close(0).unwrap();
+
+        let mut env = Env::with_system(Box::new(system));
+        env.builtins.insert("echo", echo_builtin());
+        env.builtins.insert("return", return_builtin());
+        (env, executor, reader)
+    }
+
+    /// Reads the content available in the pipe.
+    fn read_pipe(env: &mut Env, reader: RawFd) -> String {
+        let _ = env.system.close(STDOUT);
+        let mut buffer = [0; 100];
+        let count = block_on(env.system.read_async(reader, &mut buffer)).unwrap();
+        String::from_utf8(buffer[..count].to_vec()).unwrap()
+    }
 
     #[test]
     fn single_command_pipeline_returns_exit_status_intact_without_divert() {
@@ -61,4 +241,169 @@ mod tests {
         assert_eq!(result, Err(Divert::Return));
         assert_eq!(env.exit_status, ExitStatus(37));
     }
+
+    #[test]
+    fn multi_command_pipeline_returns_last_exit_status() {
+        let (mut env, mut executor, _) = env_with_pipe_output();
+        let pipeline: syntax::Pipeline = "return -n 10 | return -n 20 | return -n 30"
+            .parse()
+            .unwrap();
+        let result = executor.run_until(pipeline.execute(&mut env));
+        assert_eq!(result, Ok(()));
+        assert_eq!(env.exit_status, ExitStatus(30));
+    }
+
+    #[test]
+    fn multi_command_pipeline_ignores_divert_in_subshells() {
+        let (mut env, mut executor, _) = env_with_pipe_output();
+        let pipeline: syntax::Pipeline = "return 1 | return 2".parse().unwrap();
+        let result = executor.run_until(pipeline.execute(&mut env));
+        assert_eq!(result, Ok(()));
+        assert_eq!(env.exit_status, ExitStatus(2));
+    }
+
+    #[test]
+    fn multi_command_pipeline_connects_last_command_to_stdout() {
+        let (mut env, mut executor, reader) = env_with_pipe_output();
+        let pipeline: syntax::Pipeline = "echo foo | echo bar | echo baz".parse().unwrap();
+        let result = executor.run_until(pipeline.execute(&mut env));
+        assert_eq!(result, Ok(()));
+        assert_eq!(env.exit_status, ExitStatus::SUCCESS);
+        assert_eq!(read_pipe(&mut env, reader), "baz\n");
+    }
+
+    #[test]
+    fn multi_command_pipeline_closes_pipes_in_parent() {
+        let (mut env, mut executor, reader) = env_with_pipe_output();
+        let pipeline: syntax::Pipeline = "echo foo | echo bar".parse().unwrap();
+        let _ = executor.run_until(pipeline.execute(&mut env));
+        for fd in 0..10 {
+            if fd != STDOUT {
+                assert_eq!(
+                    env.system.fcntl_dupfd(fd, 20),
+                    Err(nix::Error::Sys(nix::errno::Errno::EBADF))
+                );
+            }
+        }
+        assert_eq!(read_pipe(&mut env, reader), "bar\n");
+    }
+
+    #[test]
+    fn negation_of_successful_pipeline() {
+        let (mut env, mut executor, _) = env_with_pipe_output();
+        let pipeline: syntax::Pipeline = "! return -n 1 | return -n 0".parse().unwrap();
+        let result = executor.run_until(pipeline.execute(&mut env));
+        assert_eq!(result, Ok(()));
+        assert_eq!(env.exit_status, ExitStatus(1));
+    }
+
+    #[test]
+    fn negation_of_failed_pipeline() {
+        let mut env = Env::new_virtual();
+        env.builtins.insert("return", return_builtin());
+        let pipeline: syntax::Pipeline = "! return -n 42".parse().unwrap();
+        let result = block_on(pipeline.execute(&mut env));
+        assert_eq!(result, Ok(()));
+        assert_eq!(env.exit_status, ExitStatus(0));
+    }
+
+    #[test]
+    fn negation_not_applied_on_divert() {
+        let mut env = Env::new_virtual();
+        env.builtins.insert("return", return_builtin());
+        let pipeline: syntax::Pipeline = "! return 42".parse().unwrap();
+        let result = block_on(pipeline.execute(&mut env));
+        assert_eq!(result, Err(Divert::Return));
+        assert_eq!(env.exit_status, ExitStatus(42));
+    }
+
+    #[test]
+    fn pipefail_returns_last_non_zero_exit_status() {
+        let (mut env, mut executor, _) = env_with_pipe_output();
+        env.options.set(PipeFail, true);
+        let pipeline: syntax::Pipeline = "return -n 3 | return -n 4 | return -n 0".parse().unwrap();
+        let result = executor.run_until(pipeline.execute(&mut env));
+        assert_eq!(result, Ok(()));
+        assert_eq!(env.exit_status, ExitStatus(4));
+    }
+
+    #[test]
+    fn pipefail_with_all_commands_successful() {
+        let (mut env, mut executor, _) = env_with_pipe_output();
+        env.options.set(PipeFail, true);
+        let pipeline: syntax::Pipeline = "return -n 0 | return -n 0".parse().unwrap();
+        let result = executor.run_until(pipeline.execute(&mut env));
+        assert_eq!(result, Ok(()));
+        assert_eq!(env.exit_status, ExitStatus(0));
+    }
+
+    #[test]
+    fn error_starting_subshell() {
+        let mut env = Env::new_virtual();
+        env.builtins.insert("return", return_builtin());
+        let pipeline: syntax::Pipeline = "return -n 0 | return -n 0".parse().unwrap();
+        let result = block_on(pipeline.execute(&mut env));
+        assert_eq!(result, Ok(()));
+        assert_eq!(env.exit_status, ExitStatus::NOEXEC);
+    }
+
+    #[test]
+    fn errexit_applies_to_multi_command_pipeline() {
+        let (mut env, mut executor, reader) = env_with_pipe_output();
+        env.options.set(ErrExit, true);
+        let pipeline: syntax::Pipeline = "echo a | { return -n 1; echo b; }".parse().unwrap();
+        let result = executor.run_until(pipeline.execute(&mut env));
+        assert_eq!(result, Err(Divert::Exit(ExitStatus(1))));
+        assert_eq!(env.exit_status, ExitStatus(1));
+        assert_eq!(read_pipe(&mut env, reader), "");
+    }
+
+    #[test]
+    fn errexit_ignores_non_last_command_of_pipeline() {
+        let (mut env, mut executor, _) = env_with_pipe_output();
+        env.options.set(ErrExit, true);
+        let pipeline: syntax::Pipeline = "return -n 1 | return -n 0".parse().unwrap();
+        let result = executor.run_until(pipeline.execute(&mut env));
+        assert_eq!(result, Ok(()));
+        assert_eq!(env.exit_status, ExitStatus::SUCCESS);
+    }
+
+    #[test]
+    fn errexit_applies_to_multi_command_pipeline_with_pipefail() {
+        let (mut env, mut executor, _) = env_with_pipe_output();
+        env.options.set(ErrExit, true);
+        env.options.set(PipeFail, true);
+        let pipeline: syntax::Pipeline = "return -n 3 | return -n 0".parse().unwrap();
+        let result = executor.run_until(pipeline.execute(&mut env));
+        assert_eq!(result, Err(Divert::Exit(ExitStatus(3))));
+    }
+
+    #[test]
+    fn errexit_is_suppressed_in_negated_pipeline() {
+        let (mut env, mut executor, reader) = env_with_pipe_output();
+        env.options.set(ErrExit, true);
+        let pipeline: syntax::Pipeline = "! echo a | { return -n 1; echo b; }".parse().unwrap();
+        let result = executor.run_until(pipeline.execute(&mut env));
+        assert_eq!(result, Ok(()));
+        assert_eq!(env.exit_status, ExitStatus(1));
+        assert!(!env.errexit_is_suppressed);
+        assert_eq!(read_pipe(&mut env, reader), "b\n");
+    }
+
+    #[test]
+    fn errexit_is_suppressed_in_negated_single_command_pipeline() {
+        let mut env = Env::new_virtual();
+        env.builtins.insert("return", return_builtin());
+        env.options.set(ErrExit, true);
+        let pipeline: syntax::Pipeline = "! return -n 1".parse().unwrap();
+        let result = block_on(pipeline.execute(&mut env));
+        assert_eq!(result, Ok(()));
+        assert_eq!(env.exit_status, ExitStatus::SUCCESS);
+
+        let pipeline: syntax::Pipeline = "! return -n 0".parse().unwrap();
+        let result = block_on(pipeline.execute(&mut env));
+        assert_eq!(result, Ok(()));
+        assert_eq!(env.exit_status, ExitStatus::FAILURE);
+        assert!(!env.errexit_is_suppressed);
+    }
 }
@@ -0,0 +1,184 @@
+// This file is part of yash, an extended POSIX shell.
+// Copyright (C) 2021 WATANABE Yuki
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Implementation of the read-eval loop.
+
+use crate::print_error;
+use crate::Command;
+use yash_env::exec::Divert;
+use yash_env::exec::ExitStatus;
+use yash_env::exec::Result;
+use yash_env::option::ShellOption;
+use yash_env::Env;
+use yash_syntax::parser::lex::Lexer;
+use yash_syntax::parser::Parser;
+
+/// Reads input, parses it, and executes commands in a loop.
+///
+/// This function repeatedly parses a command line from the lexer and executes
+/// it until the end of input. If the execution of a command results in a
+/// [`Divert`], the loop ends and the `Divert` is returned.
+///
+/// A syntax error is reported with its location and ends the loop with
+/// `Divert::Exit(ExitStatus::ERROR)`.
+///
+/// If the shell is interactive, errors do not end the loop: the rest of the
+/// line containing a syntax error is skipped, and a [`Divert::Interrupt`]
+/// resulting from an error in a command only abandons that command line.
+/// Also, finished asynchronous commands are removed from the job set before
+/// each command line is read, so that an interactive shell does not
+/// accumulate them.
+pub async fn read_eval_loop(env: &mut Env, lexer: &mut Lexer) -> Result {
+    loop {
+        let is_interactive = env.options.is_on(ShellOption::Interactive);
+        if is_interactive {
+            env.jobs.remove_finished();
+        }
+        let mut parser = Parser::with_aliases(lexer, env.aliases.clone());
+        match parser.command_line().await {
+            Ok(None) => return Ok(()),
+            Ok(Some(command)) => match command.execute(env).await {
+                Err(Divert::Interrupt) => (),
+                result => result?,
+            },
+            Err(error) => {
+                print_error(env, &error.location, &error);
+                env.exit_status = ExitStatus::ERROR;
+                if !is_interactive {
+                    return Err(Divert::Exit(ExitStatus::ERROR));
+                }
+                skip_line(lexer).await;
+            }
+        }
+    }
+}
+
+/// Consumes characters up to and including the next newline.
+async fn skip_line(lexer: &mut Lexer) {
+    while let Ok(Some(c)) = lexer.peek_char().await {
+        lexer.consume_char();
+        if c == '\n' {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::return_builtin;
+    use futures::executor::block_on;
+    use nix::unistd::Pid;
+    use yash_env::job::ChildProcess;
+    use yash_syntax::source::Source;
+
+    #[test]
+    fn empty_input() {
+        let mut env = Env::new_virtual();
+        env.exit_status = ExitStatus(7);
+        let mut lexer = Lexer::with_source(Source::Unknown, "");
+        let result = block_on(read_eval_loop(&mut env, &mut lexer));
+        assert_eq!(result, Ok(()));
+        assert_eq!(env.exit_status, ExitStatus(7));
+    }
+
+    #[test]
+    fn executes_all_command_lines() {
+        let mut env = Env::new_virtual();
+        env.builtins.insert("return", return_builtin());
+        let mut lexer = Lexer::with_source(Source::Unknown, "return -n 1\nreturn -n 2\n");
+        let result = block_on(read_eval_loop(&mut env, &mut lexer));
+        assert_eq!(result, Ok(()));
+        assert_eq!(env.exit_status, ExitStatus(2));
+    }
+
+    #[test]
+    fn divert_ends_loop() {
+        let mut env = Env::new_virtual();
+        env.builtins.insert("return", return_builtin());
+        let mut lexer = Lexer::with_source(Source::Unknown, "return 3\nreturn -n 4\n");
+        let result = block_on(read_eval_loop(&mut env, &mut lexer));
+        assert_eq!(result, Err(Divert::Return));
+        assert_eq!(env.exit_status, ExitStatus(3));
+    }
+
+    #[test]
+    fn syntax_error_aborts_loop() {
+        let mut env = Env::new_virtual();
+        env.builtins.insert("return", return_builtin());
+        let mut lexer = Lexer::with_source(Source::Unknown, "return -n 5\n)\nreturn -n 6\n");
+        let result = block_on(read_eval_loop(&mut env, &mut lexer));
+        assert_eq!(result, Err(Divert::Exit(ExitStatus::ERROR)));
+        assert_eq!(env.exit_status, ExitStatus::ERROR);
+    }
+
+    #[test]
+    fn syntax_error_skips_line_in_interactive_shell() {
+        let mut env = Env::new_virtual();
+        env.options.set(ShellOption::Interactive, true);
+        env.builtins.insert("return", return_builtin());
+        let mut lexer = Lexer::with_source(Source::Unknown, "return -n 5; )\nreturn -n 6\n");
+        let result = block_on(read_eval_loop(&mut env, &mut lexer));
+        assert_eq!(result, Ok(()));
+        assert_eq!(env.exit_status, ExitStatus(6));
+    }
+
+    #[test]
+    fn expansion_error_does_not_end_loop_in_interactive_shell() {
+        let mut env = Env::new_virtual();
+        env.options.set(ShellOption::Interactive, true);
+        env.builtins.insert("return", return_builtin());
+        let mut lexer = Lexer::with_source(Source::Unknown, "return -n ${x?}\nreturn -n 7\n");
+        let result = block_on(read_eval_loop(&mut env, &mut lexer));
+        assert_eq!(result, Ok(()));
+        assert_eq!(env.exit_status, ExitStatus(7));
+    }
+
+    #[test]
+    fn expansion_error_in_loop_abandons_command_line_in_interactive_shell() {
+        let mut env = Env::new_virtual();
+        env.options.set(ShellOption::Interactive, true);
+        env.builtins.insert("return", return_builtin());
+        let mut lexer = Lexer::with_source(
+            Source::Unknown,
+            "while return -n 0; do return -n ${x?}; done; return -n 3\nreturn -n 7\n",
+        );
+        let result = block_on(read_eval_loop(&mut env, &mut lexer));
+        assert_eq!(result, Ok(()));
+        assert_eq!(env.exit_status, ExitStatus(7));
+    }
+
+    #[test]
+    fn interactive_shell_removes_finished_jobs() {
+        let mut env = Env::new_virtual();
+        env.options.set(ShellOption::Interactive, true);
+        let running = Pid::from_raw(10);
+        let finished = Pid::from_raw(11);
+        let processes = &mut env.jobs.child_processes;
+        processes.insert(running, ChildProcess::default());
+        processes.insert(
+            finished,
+            ChildProcess {
+                exit_status: Some(ExitStatus(1)),
+            },
+        );
+        let mut lexer = Lexer::with_source(Source::Unknown, "");
+        let result = block_on(read_eval_loop(&mut env, &mut lexer));
+        assert_eq!(result, Ok(()));
+        assert!(env.jobs.child_processes.contains_key(&running));
+        assert!(!env.jobs.child_processes.contains_key(&finished));
+    }
+}
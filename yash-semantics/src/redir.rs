@@ -0,0 +1,836 @@
+// This file is part of yash, an extended POSIX shell.
+// Copyright (C) 2021 WATANABE Yuki
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Redirection.
+//!
+//! A redirection modifies a file descriptor of the shell. [`perform_redirs`]
+//! performs redirections and returns the original file descriptors saved
+//! before the modification so that [`undo_redirs`] can restore them after the
+//! command finishes.
+//!
+//! The original file descriptor is saved by duplicating it to a new file
+//! descriptor not less than [`MIN_SAVE_FD`] with the close-on-exec flag set, so
+//! that the saved file descriptor is not inherited by external utilities.
+//!
+//! The operand of a redirection is expanded by [`expand_word`], so field
+//! splitting and pathname expansion are not performed.
+//!
+//! The content of a here-document or here-string is passed to the file
+//! descriptor through a pipe if it fits in the pipe buffer. A larger content
+//! is written to an unnamed temporary file instead so that writing the
+//! content never blocks.
+
+use crate::expansion::expand_word;
+use crate::expansion::AttrChar;
+use crate::expansion::Expand;
+use crate::expansion::Origin;
+use crate::xtrace::XTrace;
+use nix::errno::Errno;
+use nix::fcntl::OFlag;
+use nix::libc::{S_IFMT, S_IFREG};
+use nix::sys::stat::Mode;
+use nix::unistd::Whence;
+use std::ffi::CStr;
+use std::ffi::CString;
+use std::fmt;
+use std::os::unix::io::RawFd;
+use std::path::Path;
+use yash_env::exec::ExitStatus;
+use yash_env::expansion;
+use yash_env::option::ShellOption;
+use yash_env::Env;
+use yash_syntax::source::Location;
+use yash_syntax::syntax::HereDoc;
+use yash_syntax::syntax::Redir;
+use yash_syntax::syntax::RedirBody;
+use yash_syntax::syntax::RedirOp;
+
+/// Minimum file descriptor to which an original file descriptor is saved.
+///
+/// File descriptors less than 10 are reserved for the user.
+pub const MIN_SAVE_FD: RawFd = 10;
+
+/// Maximum size of content that is passed through a pipe.
+///
+/// Writing content of this size to an empty pipe never blocks.
+const PIPE_SIZE: usize = nix::libc::PIPE_BUF;
+
+/// Directory in which temporary files are created for large content.
+const TMP_DIR: &str = "/tmp";
+
+/// Error in performing a redirection.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Error {
+    /// Error in expanding the redirection operand.
+    Expansion(expansion::Error),
+    /// The file could not be opened.
+    OpenFile {
+        /// Pathname of the file.
+        path: String,
+        /// Error value returned from the system.
+        errno: Errno,
+        /// Location of the redirection operand.
+        location: Location,
+    },
+    /// The operand is not a valid file descriptor.
+    MalformedFd {
+        /// Value of the operand.
+        value: String,
+        /// Location of the redirection operand.
+        location: Location,
+    },
+    /// The file descriptor could not be copied.
+    CopyFd {
+        /// File descriptor that was to be copied.
+        fd: RawFd,
+        /// Error value returned from the system.
+        errno: Errno,
+        /// Location of the redirection operand.
+        location: Location,
+    },
+    /// Any other error returned from the system.
+    System {
+        /// Error value returned from the system.
+        errno: Errno,
+        /// Location of the redirection operand.
+        location: Location,
+    },
+}
+
+impl Error {
+    /// Returns the location where this error occurred.
+    #[must_use]
+    pub fn location(&self) -> &Location {
+        use Error::*;
+        match self {
+            Expansion(error) => error.location(),
+            OpenFile { location, .. }
+            | MalformedFd { location, .. }
+            | CopyFd { location, .. }
+            | System { location, .. } => location,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Error::*;
+        match self {
+            Expansion(error) => error.fmt(f),
+            OpenFile { path, errno, .. } => {
+                write!(f, "cannot open file `{}`: {}", path, errno.desc())
+            }
+            MalformedFd { value, .. } => {
+                write!(f, "`{}` is not a valid file descriptor", value)
+            }
+            CopyFd { fd, errno, .. } => {
+                write!(f, "cannot copy file descriptor {}: {}", fd, errno.desc())
+            }
+            System { errno, .. } => write!(f, "cannot perform redirection: {}", errno.desc()),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<expansion::Error> for Error {
+    fn from(error: expansion::Error) -> Self {
+        Error::Expansion(error)
+    }
+}
+
+/// Result of redirection.
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Converts a `nix::Error` to an `Errno`.
+fn to_errno(error: nix::Error) -> Errno {
+    error.as_errno().unwrap_or(Errno::UnknownErrno)
+}
+
+/// Record of a file descriptor modified by a redirection.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SavedFd {
+    /// File descriptor modified by the redirection.
+    pub original: RawFd,
+    /// Copy of the original file descriptor, or `None` if the file descriptor
+    /// was not open.
+    pub save: Option<RawFd>,
+}
+
+/// Saves a file descriptor before it is modified.
+fn save_fd(env: &mut Env, fd: RawFd, location: &Location) -> Result<SavedFd> {
+    let save = match env.system.fcntl_dupfd(fd, MIN_SAVE_FD) {
+        Ok(save) => Some(save),
+        Err(nix::Error::Sys(Errno::EBADF)) => None,
+        Err(error) => {
+            return Err(Error::System {
+                errno: to_errno(error),
+                location: location.clone(),
+            })
+        }
+    };
+    Ok(SavedFd { original: fd, save })
+}
+
+/// Returns the flags to open a file for the redirection operator.
+///
+/// If `no_clobber` is true, the `>` operator fails to open an existing file.
+/// The `>|` operator always truncates the file.
+fn open_flags(operator: RedirOp, no_clobber: bool) -> OFlag {
+    use RedirOp::*;
+    match operator {
+        FileIn => OFlag::O_RDONLY,
+        FileInOut => OFlag::O_RDWR | OFlag::O_CREAT,
+        FileOut if no_clobber => OFlag::O_WRONLY | OFlag::O_CREAT | OFlag::O_EXCL,
+        FileOut | FileClobber => OFlag::O_WRONLY | OFlag::O_CREAT | OFlag::O_TRUNC,
+        FileAppend => OFlag::O_WRONLY | OFlag::O_CREAT | OFlag::O_APPEND,
+        FdIn | FdOut | Pipe | String => unreachable!("{:?} does not open a file", operator),
+    }
+}
+
+/// Opens a file and moves the file descriptor to `fd`.
+fn open_file(
+    env: &mut Env,
+    fd: RawFd,
+    operator: RedirOp,
+    path: String,
+    location: &Location,
+) -> Result<()> {
+    let c_path = CString::new(path.as_str()).map_err(|_| Error::OpenFile {
+        path: path.clone(),
+        errno: Errno::EINVAL,
+        location: location.clone(),
+    })?;
+    let mode = Mode::from_bits_truncate(0o666);
+    let no_clobber = env.options.is_on(ShellOption::NoClobber);
+    let flags = open_flags(operator, no_clobber);
+    let mut result = env.system.open(&c_path, flags, mode);
+    if no_clobber && operator == RedirOp::FileOut && result == Err(Errno::EEXIST.into()) {
+        result = open_existing_non_regular_file(env, &c_path);
+    }
+    let opened = result.map_err(|error| Error::OpenFile {
+        path,
+        errno: to_errno(error),
+        location: location.clone(),
+    })?;
+    move_fd(env, opened, fd, location)
+}
+
+/// Opens an existing file for writing unless it is a regular file.
+///
+/// With the noclobber option, the `>` operator may still open a non-regular
+/// file such as `/dev/null`. The file is opened without `O_CREAT` and then
+/// checked with `fstat` so that the check is not affected by a race condition.
+/// The result is `EEXIST` if the file is a regular file.
+fn open_existing_non_regular_file(env: &mut Env, path: &CStr) -> nix::Result<RawFd> {
+    let fd = env.system.open(path, OFlag::O_WRONLY, Mode::empty())?;
+    match env.system.fstat(fd) {
+        Ok(stat) if stat.st_mode & S_IFMT != S_IFREG => Ok(fd),
+        Ok(_) => {
+            let _ = env.system.close(fd);
+            Err(Errno::EEXIST.into())
+        }
+        Err(error) => {
+            let _ = env.system.close(fd);
+            Err(error)
+        }
+    }
+}
+
+/// Moves a file descriptor to another.
+///
+/// This function is a wrapper around [`yash_env::io::move_fd`] that converts
+/// the error.
+fn move_fd(env: &mut Env, from: RawFd, to: RawFd, location: &Location) -> Result<()> {
+    yash_env::io::move_fd(&mut *env.system, from, to).map_err(|error| Error::System {
+        errno: to_errno(error),
+        location: location.clone(),
+    })
+}
+
+/// Prepares a file descriptor from which the content can be read.
+fn open_content(env: &mut Env, content: &[u8]) -> nix::Result<RawFd> {
+    if content.len() <= PIPE_SIZE {
+        let (reader, writer) = env.system.pipe()?;
+        let result = env.system.write_all(writer, content);
+        let _ = env.system.close(writer);
+        match result {
+            Ok(()) => Ok(reader),
+            Err(error) => {
+                let _ = env.system.close(reader);
+                Err(error)
+            }
+        }
+    } else {
+        let fd = env.system.open_tmpfile(Path::new(TMP_DIR))?;
+        let result = env
+            .system
+            .write_all(fd, content)
+            .and_then(|()| env.system.lseek(fd, 0, Whence::SeekSet).map(drop));
+        match result {
+            Ok(()) => Ok(fd),
+            Err(error) => {
+                let _ = env.system.close(fd);
+                Err(error)
+            }
+        }
+    }
+}
+
+/// Makes the file descriptor `fd` read the content.
+fn redirect_content(env: &mut Env, fd: RawFd, content: &str, location: &Location) -> Result<()> {
+    let reader = open_content(env, content.as_bytes()).map_err(|error| Error::System {
+        errno: to_errno(error),
+        location: location.clone(),
+    })?;
+    move_fd(env, reader, fd, location)
+}
+
+/// Expands the content of a here-document.
+///
+/// The content is expanded without field splitting, pathname expansion, or
+/// quote removal, except that backslashes quoting other characters are
+/// removed. If the delimiter is quoted, the content is all literal.
+async fn expand_here_doc(env: &mut Env, here_doc: &HereDoc) -> Result<String> {
+    let phrase = here_doc.content.expand(env).await?;
+    let separator = AttrChar {
+        value: ' ',
+        origin: Origin::SoftExpansion,
+        is_quoted: false,
+        is_quoting: false,
+    };
+    let chars = phrase.join(Some(separator));
+    Ok(chars
+        .into_iter()
+        .filter(|c| !c.is_quoting)
+        .map(|c| c.value)
+        .collect())
+}
+
+/// Parses the operand of `<&`, `>&`, or `>>|` as a file descriptor.
+fn parse_fd(value: String, location: &Location) -> Result<RawFd> {
+    match value.parse() {
+        Ok(fd) if fd >= 0 && value.bytes().all(|b| b.is_ascii_digit()) => Ok(fd),
+        _ => Err(Error::MalformedFd {
+            value,
+            location: location.clone(),
+        }),
+    }
+}
+
+/// Performs a normal redirection.
+///
+/// The file descriptors modified are pushed to `saved`.
+async fn perform_normal(
+    env: &mut Env,
+    fd: RawFd,
+    operator: RedirOp,
+    operand: &yash_syntax::syntax::Word,
+    saved: &mut Vec<SavedFd>,
+    xtrace: Option<&mut XTrace>,
+) -> Result<()> {
+    use RedirOp::*;
+    let location = &operand.location;
+    let value = expand_word(env, operand).await?.value;
+    if let Some(xtrace) = xtrace {
+        xtrace.push_redir(fd, &operator, &value);
+    }
+    match operator {
+        FileIn | FileInOut | FileOut | FileAppend | FileClobber => {
+            saved.push(save_fd(env, fd, location)?);
+            open_file(env, fd, operator, value, location)
+        }
+        FdIn | FdOut => {
+            if value == "-" {
+                saved.push(save_fd(env, fd, location)?);
+                return env.system.close(fd).map_err(|error| Error::System {
+                    errno: to_errno(error),
+                    location: location.clone(),
+                });
+            }
+            let from = parse_fd(value, location)?;
+            // TODO Check if the file descriptor is readable or writable
+            saved.push(save_fd(env, fd, location)?);
+            if from == fd {
+                return Ok(());
+            }
+            match env.system.dup2(from, fd) {
+                Ok(_) => Ok(()),
+                Err(error) => Err(Error::CopyFd {
+                    fd: from,
+                    errno: to_errno(error),
+                    location: location.clone(),
+                }),
+            }
+        }
+        Pipe => {
+            let reader_fd = parse_fd(value, location)?;
+            saved.push(save_fd(env, fd, location)?);
+            saved.push(save_fd(env, reader_fd, location)?);
+            let to_error = |error| Error::System {
+                errno: to_errno(error),
+                location: location.clone(),
+            };
+            let (reader, writer) = env.system.pipe().map_err(to_error)?;
+            move_fd(env, writer, fd, location)?;
+            move_fd(env, reader, reader_fd, location)
+        }
+        String => {
+            saved.push(save_fd(env, fd, location)?);
+            let mut content = value;
+            content.push('\n');
+            redirect_content(env, fd, &content, location)
+        }
+    }
+}
+
+/// Performs a redirection.
+///
+/// If `xtrace` is given, the redirection is added to it after the operand is
+/// expanded.
+async fn perform_redir(
+    env: &mut Env,
+    redir: &Redir,
+    saved: &mut Vec<SavedFd>,
+    xtrace: Option<&mut XTrace>,
+) -> Result<()> {
+    let fd = redir.fd_or_default();
+    match &redir.body {
+        RedirBody::Normal { operator, operand } => {
+            perform_normal(env, fd, *operator, operand, saved, xtrace).await
+        }
+        RedirBody::HereDoc(here_doc) => {
+            let location = &here_doc.delimiter.location;
+            let content = expand_here_doc(env, here_doc).await?;
+            if let Some(xtrace) = xtrace {
+                xtrace.push_redir_raw(fd, here_doc);
+            }
+            saved.push(save_fd(env, fd, location)?);
+            redirect_content(env, fd, &content, location)
+        }
+    }
+}
+
+/// Reports a redirection error.
+///
+/// This function prints an error message with the location of the error and
+/// sets the exit status to [`ExitStatus::ERROR`]. If `abort` is true, the
+/// result is [`Divert::Exit`](yash_env::exec::Divert::Exit), or
+/// [`Divert::Interrupt`](yash_env::exec::Divert::Interrupt) in an interactive
+/// shell, which the caller should propagate. An expansion error always aborts
+/// as in [`expansion::handle_error`](crate::expansion::handle_error).
+pub fn handle_error(env: &mut Env, error: Error, abort: bool) -> yash_env::exec::Result {
+    if let Error::Expansion(error) = error {
+        return crate::expansion::handle_error(env, error);
+    }
+    crate::print_error(env, error.location(), &error);
+    env.exit_status = ExitStatus::ERROR;
+    if abort {
+        Err(crate::error_divert(env))
+    } else {
+        Ok(())
+    }
+}
+
+/// Performs redirections.
+///
+/// The redirections are performed in order. If successful, this function
+/// returns the file descriptors saved before modification, which should be
+/// passed to [`undo_redirs`] later. If a redirection fails, the redirections
+/// already performed are undone before the error is returned.
+///
+/// If `xtrace` is given, the performed redirections are added to it for
+/// tracing.
+pub async fn perform_redirs(
+    env: &mut Env,
+    redirs: &[Redir],
+    mut xtrace: Option<&mut XTrace>,
+) -> Result<Vec<SavedFd>> {
+    let mut saved = Vec::new();
+    for redir in redirs {
+        if let Err(error) = perform_redir(env, redir, &mut saved, xtrace.as_deref_mut()).await {
+            undo_redirs(env, saved);
+            return Err(error);
+        }
+    }
+    Ok(saved)
+}
+
+/// Restores file descriptors modified by redirections.
+///
+/// The file descriptors are restored in the reverse order of modification.
+pub fn undo_redirs(env: &mut Env, saved: Vec<SavedFd>) {
+    for SavedFd { original, save } in saved.into_iter().rev() {
+        match save {
+            Some(save) => {
+                let _ = env.system.dup2(save, original);
+                let _ = env.system.close(save);
+            }
+            None => {
+                let _ = env.system.close(original);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use yash_env::exec::Divert;
+    use yash_env::virtual_system::FileBody;
+    use yash_env::virtual_system::INode;
+    use yash_env::virtual_system::SystemState;
+    use yash_env::VirtualSystem;
+    use yash_syntax::syntax::SimpleCommand;
+
+    fn parse_redirs(s: &str) -> Vec<Redir> {
+        let command: SimpleCommand = s.parse().unwrap();
+        command.redirs
+    }
+
+    #[test]
+    fn parsing_fd() {
+        let location = Location::dummy("");
+        assert_eq!(parse_fd("0".to_string(), &location), Ok(0));
+        assert_eq!(parse_fd("12".to_string(), &location), Ok(12));
+        assert_eq!(
+            parse_fd("+1".to_string(), &location),
+            Err(Error::MalformedFd {
+                value: "+1".to_string(),
+                location: location.clone(),
+            })
+        );
+        assert_eq!(
+            parse_fd("x".to_string(), &location),
+            Err(Error::MalformedFd {
+                value: "x".to_string(),
+                location,
+            })
+        );
+    }
+
+    #[test]
+    fn open_flags_for_operators() {
+        assert_eq!(open_flags(RedirOp::FileIn, false), OFlag::O_RDONLY);
+        assert!(open_flags(RedirOp::FileOut, false).contains(OFlag::O_TRUNC));
+        assert!(open_flags(RedirOp::FileAppend, false).contains(OFlag::O_APPEND));
+        assert!(!open_flags(RedirOp::FileInOut, false).contains(OFlag::O_TRUNC));
+    }
+
+    #[test]
+    fn open_flags_with_noclobber() {
+        let flags = open_flags(RedirOp::FileOut, true);
+        assert_eq!(flags, OFlag::O_WRONLY | OFlag::O_CREAT | OFlag::O_EXCL);
+        let flags = open_flags(RedirOp::FileClobber, true);
+        assert_eq!(flags, OFlag::O_WRONLY | OFlag::O_CREAT | OFlag::O_TRUNC);
+        assert!(open_flags(RedirOp::FileAppend, true).contains(OFlag::O_APPEND));
+    }
+
+    #[test]
+    fn malformed_fd_error_has_operand_location() {
+        let mut env = Env::new_virtual();
+        let redirs = parse_redirs(">&foo");
+        let location = match &redirs[0].body {
+            RedirBody::Normal { operand, .. } => operand.location.clone(),
+            body => panic!("unexpected body {:?}", body),
+        };
+        let result = block_on(perform_redirs(&mut env, &redirs, None));
+        assert_eq!(
+            result,
+            Err(Error::MalformedFd {
+                value: "foo".to_string(),
+                location,
+            })
+        );
+    }
+
+    #[test]
+    fn expansion_error_in_operand() {
+        let mut env = Env::new_virtual();
+        let redirs = parse_redirs("<${x?}");
+        let error = block_on(perform_redirs(&mut env, &redirs, None)).unwrap_err();
+        assert!(matches!(error, Error::Expansion(_)), "{:?}", error);
+    }
+
+    #[test]
+    fn open_file_error_is_reported() {
+        let mut env = Env::new_virtual();
+        let redirs = parse_redirs("<file");
+        let error = block_on(perform_redirs(&mut env, &redirs, None)).unwrap_err();
+        assert!(
+            matches!(
+                error,
+                Error::OpenFile {
+                    errno: Errno::ENOENT,
+                    ..
+                }
+            ),
+            "{:?}",
+            error
+        );
+    }
+
+    #[test]
+    fn handle_error_aborts_non_interactive_shell() {
+        let mut env = Env::new_virtual();
+        let redirs = parse_redirs("<file");
+        let error = block_on(perform_redirs(&mut env, &redirs, None)).unwrap_err();
+        let result = handle_error(&mut env, error.clone(), false);
+        assert_eq!(result, Ok(()));
+        assert_eq!(env.exit_status, ExitStatus::ERROR);
+        let result = handle_error(&mut env, error, true);
+        assert_eq!(result, Err(Divert::Exit(ExitStatus::ERROR)));
+        assert_eq!(env.exit_status, ExitStatus::ERROR);
+    }
+
+    #[test]
+    fn handle_error_continues_interactive_shell() {
+        let mut env = Env::new_virtual();
+        env.options.set(ShellOption::Interactive, true);
+        let redirs = parse_redirs("<file");
+        let error = block_on(perform_redirs(&mut env, &redirs, None)).unwrap_err();
+        let result = handle_error(&mut env, error, true);
+        assert_eq!(result, Err(Divert::Interrupt));
+        assert_eq!(env.exit_status, ExitStatus::ERROR);
+    }
+
+    fn virtual_env() -> (Env, Rc<RefCell<SystemState>>) {
+        let system = VirtualSystem::new();
+        let state = Rc::clone(&system.state);
+        (Env::with_system(Box::new(system)), state)
+    }
+
+    fn file_content(state: &RefCell<SystemState>, path: &str) -> Vec<u8> {
+        let file = state.borrow().file_system.get(path).unwrap();
+        let file = file.borrow();
+        match &file.body {
+            FileBody::Regular { content, .. } => content.clone(),
+            body => panic!("not a regular file: {:?}", body),
+        }
+    }
+
+    fn save_file(state: &RefCell<SystemState>, path: &str, content: &str) {
+        let inode = INode::with_content(content);
+        state.borrow_mut().file_system.save(path, inode).unwrap();
+    }
+
+    #[test]
+    fn redirecting_output_to_new_file() {
+        let (mut env, state) = virtual_env();
+        let redirs = parse_redirs("3>/file");
+        let saved = block_on(perform_redirs(&mut env, &redirs, None)).unwrap();
+        assert_eq!(env.system.write(3, b"hello"), Ok(5));
+        undo_redirs(&mut env, saved);
+        assert_eq!(file_content(&state, "/file"), b"hello");
+        assert_eq!(env.system.write(3, b"x"), Err(Errno::EBADF.into()));
+    }
+
+    #[test]
+    fn redirecting_output_truncates_or_appends() {
+        let (mut env, state) = virtual_env();
+        save_file(&state, "/file", "old content");
+        let saved = block_on(perform_redirs(&mut env, &parse_redirs("3>/file"), None)).unwrap();
+        undo_redirs(&mut env, saved);
+        assert_eq!(file_content(&state, "/file"), b"");
+
+        save_file(&state, "/file", "old");
+        let redirs = parse_redirs("3>>/file");
+        let saved = block_on(perform_redirs(&mut env, &redirs, None)).unwrap();
+        env.system.write(3, b" new").unwrap();
+        undo_redirs(&mut env, saved);
+        assert_eq!(file_content(&state, "/file"), b"old new");
+    }
+
+    #[test]
+    fn redirecting_input_from_file() {
+        let (mut env, state) = virtual_env();
+        save_file(&state, "/file", "content");
+        let redirs = parse_redirs("3</file");
+        block_on(perform_redirs(&mut env, &redirs, None)).unwrap();
+        let mut buffer = [0; 10];
+        assert_eq!(env.system.read(3, &mut buffer), Ok(7));
+        assert_eq!(&buffer[..7], b"content");
+        assert_eq!(env.system.write(3, b"x"), Err(Errno::EBADF.into()));
+    }
+
+    #[test]
+    fn noclobber_prevents_overwriting_regular_file() {
+        let (mut env, state) = virtual_env();
+        env.options.set(ShellOption::NoClobber, true);
+        save_file(&state, "/file", "content");
+        let error = block_on(perform_redirs(&mut env, &parse_redirs("3>/file"), None));
+        assert!(
+            matches!(
+                error,
+                Err(Error::OpenFile {
+                    errno: Errno::EEXIST,
+                    ..
+                })
+            ),
+            "{:?}",
+            error
+        );
+        assert_eq!(file_content(&state, "/file"), b"content");
+
+        let redirs = parse_redirs("3>|/file");
+        block_on(perform_redirs(&mut env, &redirs, None)).unwrap();
+        assert_eq!(file_content(&state, "/file"), b"");
+    }
+
+    #[test]
+    fn noclobber_allows_new_or_non_regular_file() {
+        let (mut env, state) = virtual_env();
+        env.options.set(ShellOption::NoClobber, true);
+        let fifo = INode::new_fifo();
+        state.borrow_mut().file_system.save("/fifo", fifo).unwrap();
+        let redirs = parse_redirs("3>/fifo 4>/new");
+        block_on(perform_redirs(&mut env, &redirs, None)).unwrap();
+        assert_eq!(env.system.fcntl_getfl(3), Ok(OFlag::O_WRONLY));
+        assert_eq!(file_content(&state, "/new"), b"");
+    }
+
+    #[test]
+    fn large_content_is_redirected_via_temporary_file() {
+        let (mut env, state) = virtual_env();
+        let tmp = INode::new_directory();
+        state.borrow_mut().file_system.save(TMP_DIR, tmp).unwrap();
+        let content = "x".repeat(PIPE_SIZE * 2);
+        let location = Location::dummy("");
+        redirect_content(&mut env, 3, &content, &location).unwrap();
+        let mut buffer = vec![0; PIPE_SIZE * 3];
+        assert_eq!(env.system.read(3, &mut buffer), Ok(content.len()));
+        assert_eq!(&buffer[..content.len()], content.as_bytes());
+    }
+
+    #[test]
+    fn copying_fd_and_undoing() {
+        let mut env = Env::new_virtual();
+        let (reader, writer) = env.system.pipe().unwrap();
+        let redirs = parse_redirs("5>&1 1>&-");
+        let saved = block_on(perform_redirs(&mut env, &redirs, None)).unwrap();
+        assert_eq!(env.system.write(5, b"x"), Ok(1));
+        assert_eq!(env.system.write(writer, b"y"), Err(Errno::EBADF.into()));
+
+        undo_redirs(&mut env, saved);
+        assert_eq!(env.system.write(5, b"x"), Err(Errno::EBADF.into()));
+        assert_eq!(env.system.write(writer, b"z"), Ok(1));
+        let mut buffer = [0; 4];
+        let count = block_on(env.system.read_async(reader, &mut buffer)).unwrap();
+        assert_eq!(&buffer[..count], b"xz");
+    }
+
+    #[test]
+    fn undoing_restores_fd_table() {
+        let system = VirtualSystem::new();
+        let process_id = system.process_id;
+        let state = Rc::clone(&system.state);
+        let mut env = Env::with_system(Box::new(system));
+        env.system.pipe().unwrap();
+        save_file(&state, "/tmp/file", "");
+        let fds = |state: &RefCell<SystemState>| {
+            let state = state.borrow();
+            let fds = state.processes[&process_id].fds();
+            fds.iter()
+                .map(|(&fd, body)| (fd, Rc::clone(&body.open_file_description), body.flags))
+                .collect::<Vec<_>>()
+        };
+        let original = fds(&state);
+
+        let redirs = parse_redirs("3>&1 1>/tmp/file 10<&0 5</tmp/file 0<&-");
+        let saved = block_on(perform_redirs(&mut env, &redirs, None)).unwrap();
+        assert_ne!(fds(&state).len(), original.len());
+
+        undo_redirs(&mut env, saved);
+        let restored = fds(&state);
+        assert_eq!(restored.len(), original.len());
+        for ((fd1, ofd1, flags1), (fd2, ofd2, flags2)) in restored.iter().zip(&original) {
+            assert_eq!(fd1, fd2);
+            assert!(Rc::ptr_eq(ofd1, ofd2), "fd {}", fd1);
+            assert_eq!(flags1, flags2);
+        }
+    }
+
+    #[test]
+    fn copying_closed_fd_is_error() {
+        let mut env = Env::new_virtual();
+        let redirs = parse_redirs("3<&4");
+        let error = block_on(perform_redirs(&mut env, &redirs, None)).unwrap_err();
+        assert!(
+            matches!(
+                error,
+                Error::CopyFd {
+                    fd: 4,
+                    errno: Errno::EBADF,
+                    ..
+                }
+            ),
+            "{:?}",
+            error
+        );
+    }
+
+    fn here_doc(content: &str) -> HereDoc {
+        HereDoc {
+            delimiter: "END".parse().unwrap(),
+            remove_tabs: false,
+            content: content.parse().unwrap(),
+        }
+    }
+
+    #[test]
+    fn here_doc_content_is_expanded() {
+        let mut env = Env::new_virtual();
+        crate::expansion::tests::assign_scalar(&mut env, "x", "X");
+        let content = block_on(expand_here_doc(&mut env, &here_doc("a$x\\$x\n"))).unwrap();
+        assert_eq!(content, "aX$x\n");
+        let content = block_on(expand_here_doc(&mut env, &here_doc("$((1+2)) \"$x\"\n")));
+        assert_eq!(content.unwrap(), "3 \"X\"\n");
+    }
+
+    #[test]
+    fn literal_here_doc_content() {
+        let mut env = Env::new_virtual();
+        let here_doc = HereDoc {
+            delimiter: "'END'".parse().unwrap(),
+            remove_tabs: false,
+            content: yash_syntax::syntax::Text::from_literal_chars("$x\\\n".chars()),
+        };
+        let content = block_on(expand_here_doc(&mut env, &here_doc)).unwrap();
+        assert_eq!(content, "$x\\\n");
+    }
+
+    #[test]
+    fn error_messages() {
+        let location = Location::dummy("");
+        let error = Error::OpenFile {
+            path: "foo".to_string(),
+            errno: Errno::ENOENT,
+            location: location.clone(),
+        };
+        let message = format!("cannot open file `foo`: {}", Errno::ENOENT.desc());
+        assert_eq!(error.to_string(), message);
+        let error = Error::CopyFd {
+            fd: 5,
+            errno: Errno::EBADF,
+            location,
+        };
+        let message = format!("cannot copy file descriptor 5: {}", Errno::EBADF.desc());
+        assert_eq!(error.to_string(), message);
+    }
+}
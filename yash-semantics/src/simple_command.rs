@@ -17,14 +17,27 @@
 //! Implementation of simple command semantics.
 
 use super::Command;
+use crate::assign::perform_assignments;
 use crate::command_search::search;
-use crate::command_search::Target::{Builtin, External, Function};
+use crate::command_search::Target::{self, Builtin, External, Function};
+use crate::expansion::expand_words;
+use crate::expansion::handle_error;
+use crate::redir;
+use crate::redir::perform_redirs;
+use crate::redir::undo_redirs;
+use crate::redir::SavedFd;
+use crate::xtrace::XTrace;
 use async_trait::async_trait;
 use nix::errno::Errno;
 use std::ffi::CString;
+use yash_env::builtin::Type::Special;
+use yash_env::exec::Divert;
 use yash_env::exec::ExitStatus;
 use yash_env::exec::Result;
 use yash_env::expansion::Field;
+use yash_env::io::STDERR;
+use yash_env::variable::ContextType;
+use yash_env::variable::Scope;
 use yash_env::Env;
 use yash_syntax::syntax;
 
@@ -40,99 +53,230 @@ fn to_c_strings(s: Vec<Field>) -> Vec<CString> {
 impl Command for syntax::SimpleCommand {
     /// Executes the simple command.
     ///
+    /// The command words are expanded first, and then the redirections and
+    /// assignments are performed. The redirections are undone after the
+    /// command finishes. A redirection error aborts the shell if the command
+    /// is a special built-in.
+    ///
+    /// If there is no command name, the assignments persist in the
+    /// environment. If the command is a special built-in, the assignments
+    /// persist as well. Otherwise, the assigned variables are exported and
+    /// only visible to the command, that is, they are assigned in a volatile
+    /// context that is popped after the command finishes. An assignment to a
+    /// read-only variable is an error that aborts the shell.
+    ///
+    /// If the [`XTrace`](yash_env::option::ShellOption::XTrace) option is on,
+    /// the expanded assignments, command words, and redirections are printed
+    /// to the standard error before the command is executed. See the
+    /// [`xtrace`](crate::xtrace) module for details.
+    ///
+    /// A function is called in a new regular context with the fields after the
+    /// command name as the positional parameters. The context is popped when
+    /// the function returns.
+    /// `Divert::Return` from the function body is consumed here.
+    ///
+    /// If the exit status of the command is not zero and the errexit option is
+    /// [applicable](Env::errexit_is_applicable), the result is
+    /// `Divert::Exit`.
+    ///
     /// TODO Elaborate
     ///
     /// POSIX does not define the exit status when the `execve` system call
     /// fails for a reason other than `ENOEXEC`. In this implementation, the
     /// exit status is 127 for `ENOENT` and `ENOTDIR` and 126 for others.
     async fn execute(&self, env: &mut Env) -> Result {
-        // TODO expand words correctly
-        let fields: Vec<_> = self
-            .words
-            .iter()
-            .map(|w| Field {
-                value: w.to_string(),
-                origin: w.location.clone(),
-            })
-            .collect();
-
-        // TODO open redirections
-        // TODO expand and perform assignments
-
-        if let Some(name) = fields.get(0) {
-            match search(env, &name.value) {
-                Some(Builtin(builtin)) => {
-                    let (exit_status, abort) = (builtin.execute)(env, fields).await;
-                    env.exit_status = exit_status;
-                    if let Some(abort) = abort {
-                        return Err(abort);
+        env.last_command_subst_exit_status = None;
+        let fields = match expand_words(env, &self.words).await {
+            Ok(fields) => fields,
+            Err(error) => return handle_error(env, error),
+        };
+
+        let target = fields.first().map(|name| search(env, &name.value));
+        let is_special =
+            matches!(&target, Some(Some(Builtin(builtin))) if builtin.r#type == Special);
+
+        let mut xtrace = XTrace::from_options(&env.options);
+        if let Some(xtrace) = &mut xtrace {
+            fields
+                .iter()
+                .for_each(|field| xtrace.push_word(&field.value));
+        }
+
+        let saved_fds = match perform_redirs(env, &self.redirs, xtrace.as_mut()).await {
+            Ok(saved_fds) => saved_fds,
+            Err(error) => {
+                return redir::handle_error(env, error, is_special)
+                    .and_then(|()| env.apply_errexit())
+            }
+        };
+
+        let result = match target {
+            None => {
+                match perform_assignments(env, &self.assigns, Scope::Global, false, xtrace.as_mut())
+                    .await
+                {
+                    Ok(()) => {
+                        print_xtrace(env, xtrace, &saved_fds).await;
+                        env.exit_status = env
+                            .last_command_subst_exit_status
+                            .unwrap_or(ExitStatus::SUCCESS);
+                        Ok(())
                     }
+                    Err(error) => handle_error(env, error),
                 }
-                Some(Function(function)) => {
-                    println!("Function: {:?}", function);
-                    // TODO Call the function
+            }
+            Some(target) if is_special => {
+                match perform_assignments(env, &self.assigns, Scope::Global, false, xtrace.as_mut())
+                    .await
+                {
+                    Ok(()) => {
+                        print_xtrace(env, xtrace, &saved_fds).await;
+                        execute_target(env, target, fields).await
+                    }
+                    Err(error) => handle_error(env, error),
                 }
-                Some(External { path }) => {
-                    let args = to_c_strings(fields);
-                    let envs = env.variables.env_c_strings();
-                    let result = env
-                        .run_in_subshell(move |env| {
-                            // TODO Remove signal handlers not set by current traps
-
-                            let result = env.system.execve(path.as_c_str(), &args, &envs);
-                            // TODO Prefer into_err to unwrap_err
-                            let e = result.unwrap_err();
-                            // TODO Reopen as shell script on ENOEXEC
-                            match e {
-                                nix::Error::Sys(Errno::ENOENT)
-                                | nix::Error::Sys(Errno::ENOTDIR) => {
-                                    env.exit_status = ExitStatus::NOT_FOUND;
-                                }
-                                _ => {
-                                    env.exit_status = ExitStatus::NOEXEC;
-                                }
+            }
+            Some(target) => {
+                env.variables.push_context(ContextType::Volatile);
+                let result = match perform_assignments(
+                    env,
+                    &self.assigns,
+                    Scope::Volatile,
+                    true,
+                    xtrace.as_mut(),
+                )
+                .await
+                {
+                    Ok(()) => {
+                        print_xtrace(env, xtrace, &saved_fds).await;
+                        execute_target(env, target, fields).await
+                    }
+                    Err(error) => handle_error(env, error),
+                };
+                env.variables.pop_context();
+                result
+            }
+        };
+
+        undo_redirs(env, saved_fds);
+        result?;
+        env.apply_errexit()
+    }
+}
+
+/// Prints the trace of the simple command if the xtrace option is on.
+///
+/// The trace is printed to the standard error as it was before the
+/// redirections in `saved_fds` were performed, so redirecting the standard
+/// error of the command does not hide the trace. Nothing is printed if the
+/// standard error was not open.
+async fn print_xtrace(env: &mut Env, xtrace: Option<XTrace>, saved_fds: &[SavedFd]) {
+    if let Some(xtrace) = xtrace {
+        let fd = match saved_fds.iter().find(|saved| saved.original == STDERR) {
+            Some(saved) => saved.save,
+            None => Some(STDERR),
+        };
+        if let Some(fd) = fd {
+            xtrace.finish_to(env, fd).await;
+        }
+    }
+}
+
+/// Executes the target of command search.
+async fn execute_target(env: &mut Env, target: Option<Target>, fields: Vec<Field>) -> Result {
+    match target {
+        Some(Builtin(builtin)) => {
+            let (exit_status, abort) = (builtin.execute)(env, fields).await;
+            env.exit_status = exit_status;
+            if let Some(abort) = abort {
+                return Err(abort);
+            }
+        }
+        Some(Function(function)) => {
+            let params = fields
+                .into_iter()
+                .skip(1)
+                .map(|field| field.value)
+                .collect();
+            env.variables.push_context(ContextType::Regular);
+            *env.variables.positional_params_mut() = params;
+            let result = function.body.execute(env).await;
+            env.variables.pop_context();
+            match result {
+                Err(Divert::Return) => (),
+                result => return result,
+            }
+        }
+        Some(External { path }) => {
+            let args = to_c_strings(fields);
+            let envs = env.variables.env_c_strings();
+            let result = env
+                .run_in_subshell(move |env| {
+                    Box::pin(async move {
+                        // TODO Remove signal handlers not set by current traps
+
+                        let result = env.system.execve(path.as_c_str(), &args, &envs);
+                        // TODO Prefer into_err to unwrap_err
+                        let e = result.unwrap_err();
+                        // TODO Reopen as shell script on ENOEXEC
+                        match e {
+                            nix::Error::Sys(Errno::ENOENT) | nix::Error::Sys(Errno::ENOTDIR) => {
+                                env.exit_status = ExitStatus::NOT_FOUND;
+                            }
+                            _ => {
+                                env.exit_status = ExitStatus::NOEXEC;
                             }
-                            // TODO The error message should be printed via Env
-                            eprintln!("command execution failed: {:?}", e);
-                        })
-                        .await;
-
-                    match result {
-                        Ok(exit_status) => {
-                            env.exit_status = exit_status;
-                        }
-                        Err(e) => {
-                            // TODO The error message should be printed via Env
-                            eprintln!("command execution failed: {:?}", e);
-                            env.exit_status = ExitStatus::NOEXEC;
                         }
-                    }
+                        env.print_error(&format!("command execution failed: {:?}", e));
+                    })
+                })
+                .await;
+
+            match result {
+                Ok(exit_status) => {
+                    env.exit_status = exit_status;
                 }
-                None => {
-                    eprintln!("{}: command not found", name.value);
-                    // TODO The error message should be printed via Env
-                    env.exit_status = ExitStatus::NOT_FOUND;
+                Err(e) => {
+                    env.print_error(&format!("command execution failed: {:?}", e));
+                    env.exit_status = ExitStatus::NOEXEC;
                 }
             }
         }
-
-        Ok(())
+        None => {
+            let name = &fields[0];
+            env.print_error(&format!("{}: command not found", name.value));
+            env.exit_status = ExitStatus::NOT_FOUND;
+        }
     }
+
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::expansion::tests::assign_scalar;
+    use crate::tests::echo_builtin;
     use crate::tests::return_builtin;
+    use crate::xtrace::tests::env_with_stderr_pipe;
+    use crate::xtrace::tests::read_all;
     use futures::executor::block_on;
     use futures::executor::LocalPool;
+    use std::future::ready;
+    use std::future::Future;
     use std::path::PathBuf;
+    use std::pin::Pin;
     use std::rc::Rc;
-    use yash_env::exec::Divert;
+    use yash_env::builtin::Builtin;
+    use yash_env::builtin::Type::Intrinsic;
+    use yash_env::function::HashEntry;
+    use yash_env::option::ShellOption::ErrExit;
     use yash_env::variable::Value;
     use yash_env::variable::Variable;
+    use yash_env::virtual_system::FileBody;
     use yash_env::virtual_system::INode;
     use yash_env::VirtualSystem;
+    use yash_syntax::source::Location;
 
     #[test]
     fn simple_command_returns_exit_status_from_builtin_without_divert() {
@@ -154,6 +298,139 @@ mod tests {
         assert_eq!(env.exit_status, ExitStatus(37));
     }
 
+    #[test]
+    fn simple_command_expands_words() {
+        let mut env = Env::new_virtual();
+        env.builtins.insert("return", return_builtin());
+        env.variables.assign(
+            Scope::Global,
+            "x".to_string(),
+            Variable {
+                value: Value::Scalar("42".to_string()),
+                last_assigned_location: None,
+                is_exported: false,
+                read_only_location: None,
+            },
+        );
+        let command: syntax::SimpleCommand = "'return' -n $x".parse().unwrap();
+        let result = block_on(command.execute(&mut env));
+        assert_eq!(result, Ok(()));
+        assert_eq!(env.exit_status, ExitStatus(42));
+    }
+
+    #[test]
+    fn simple_command_without_command_name_returns_success() {
+        let mut env = Env::new_virtual();
+        env.exit_status = ExitStatus(5);
+        let command: syntax::SimpleCommand = "a=b".parse().unwrap();
+        let result = block_on(command.execute(&mut env));
+        assert_eq!(result, Ok(()));
+        assert_eq!(env.exit_status, ExitStatus::SUCCESS);
+    }
+
+    #[test]
+    fn simple_command_without_command_name_performs_assignments() {
+        let mut env = Env::new_virtual();
+        let command: syntax::SimpleCommand = "a=A b=$a".parse().unwrap();
+        let result = block_on(command.execute(&mut env));
+        assert_eq!(result, Ok(()));
+        let a = env.variables.get("a").unwrap();
+        assert_eq!(a.value, Value::Scalar("A".to_string()));
+        assert_eq!(
+            a.last_assigned_location,
+            Some(command.assigns[0].location.clone())
+        );
+        assert!(!a.is_exported);
+        let b = env.variables.get("b").unwrap();
+        assert_eq!(b.value, Value::Scalar("A".to_string()));
+    }
+
+    #[test]
+    fn assignments_persist_for_special_builtin() {
+        let mut env = Env::new_virtual();
+        env.builtins.insert("return", return_builtin());
+        let command: syntax::SimpleCommand = "a=A return -n 0".parse().unwrap();
+        let result = block_on(command.execute(&mut env));
+        assert_eq!(result, Ok(()));
+        let a = env.variables.get("a").unwrap();
+        assert_eq!(a.value, Value::Scalar("A".to_string()));
+        assert!(!a.is_exported);
+    }
+
+    #[test]
+    fn assignments_are_temporary_for_regular_builtin() {
+        let mut env = Env::new_virtual();
+        let builtin = Builtin {
+            r#type: Intrinsic,
+            ..return_builtin()
+        };
+        env.builtins.insert("return", builtin);
+        let variable = Variable {
+            value: Value::Scalar("old".to_string()),
+            last_assigned_location: None,
+            is_exported: false,
+            read_only_location: None,
+        };
+        env.variables
+            .assign(Scope::Global, "a".to_string(), variable.clone());
+        let command: syntax::SimpleCommand = "a=A b=B return -n 0".parse().unwrap();
+        let result = block_on(command.execute(&mut env));
+        assert_eq!(result, Ok(()));
+        assert_eq!(env.variables.get("a"), Some(&variable));
+        assert_eq!(env.variables.get("b"), None);
+    }
+
+    #[test]
+    fn assignment_to_read_only_variable_aborts() {
+        let mut env = Env::new_virtual();
+        env.variables.assign(
+            Scope::Global,
+            "a".to_string(),
+            Variable {
+                value: Value::Scalar("old".to_string()),
+                last_assigned_location: None,
+                is_exported: false,
+                read_only_location: Some(Location::dummy("readonly a")),
+            },
+        );
+        let command: syntax::SimpleCommand = "a=A".parse().unwrap();
+        let result = block_on(command.execute(&mut env));
+        assert_eq!(result, Err(Divert::Exit(ExitStatus::ERROR)));
+        assert_eq!(env.exit_status, ExitStatus::ERROR);
+        let a = env.variables.get("a").unwrap();
+        assert_eq!(a.value, Value::Scalar("old".to_string()));
+    }
+
+    #[test]
+    fn redirection_error_fails_regular_command() {
+        let mut env = Env::new_virtual();
+        let command: syntax::SimpleCommand = "no_such_command >&foo".parse().unwrap();
+        let result = block_on(command.execute(&mut env));
+        assert_eq!(result, Ok(()));
+        assert_eq!(env.exit_status, ExitStatus::ERROR);
+    }
+
+    #[test]
+    fn redirection_error_aborts_special_builtin() {
+        let mut env = Env::new_virtual();
+        env.builtins.insert("return", return_builtin());
+        let command: syntax::SimpleCommand = "a=A return -n 0 >&foo".parse().unwrap();
+        let result = block_on(command.execute(&mut env));
+        assert_eq!(result, Err(Divert::Exit(ExitStatus::ERROR)));
+        assert_eq!(env.exit_status, ExitStatus::ERROR);
+        assert_eq!(env.variables.get("a"), None);
+    }
+
+    #[test]
+    fn simple_command_aborts_on_expansion_error() {
+        let mut env = Env::new_virtual();
+        env.builtins.insert("return", return_builtin());
+        let command: syntax::SimpleCommand = "return -n ${x?}".parse().unwrap();
+        let result = block_on(command.execute(&mut env));
+        assert_eq!(result, Err(Divert::Exit(ExitStatus::ERROR)));
+        assert_eq!(env.exit_status, ExitStatus::ERROR);
+    }
+
     #[test]
     fn simple_command_calls_execve_with_correct_arguments() {
         let system = VirtualSystem::new();
@@ -163,12 +440,21 @@ mod tests {
         let mut content = INode::default();
         let mut executor = LocalPool::new();
         content.permissions.0 |= 0o100;
-        content.is_native_executable = true;
-        system.state.borrow_mut().file_system.save(path, content);
+        content.body = FileBody::Regular {
+            content: Vec::new(),
+            is_native_executable: true,
+        };
+        system
+            .state
+            .borrow_mut()
+            .file_system
+            .save(path, content)
+            .unwrap();
         system.state.borrow_mut().executor = Some(Rc::new(executor.spawner()));
 
         let mut env = Env::with_system(Box::new(system));
         env.variables.assign(
+            Scope::Global,
             "env".to_string(),
             Variable {
                 value: Value::Scalar("scalar".to_string()),
@@ -178,6 +464,7 @@ mod tests {
             },
         );
         env.variables.assign(
+            Scope::Global,
             "local".to_string(),
             Variable {
                 value: Value::Scalar("ignored".to_string()),
@@ -205,6 +492,38 @@ mod tests {
         assert_eq!(arguments.2, [CString::new("env=scalar").unwrap()]);
     }
 
+    #[test]
+    fn temporary_assignments_are_exported_to_external_utility() {
+        let system = VirtualSystem::new();
+        let state = Rc::clone(&system.state);
+        let path = PathBuf::from("/some/file");
+        let mut content = INode::default();
+        let mut executor = LocalPool::new();
+        content.permissions.0 |= 0o100;
+        content.body = FileBody::Regular {
+            content: Vec::new(),
+            is_native_executable: true,
+        };
+        system
+            .state
+            .borrow_mut()
+            .file_system
+            .save(path, content)
+            .unwrap();
+        system.state.borrow_mut().executor = Some(Rc::new(executor.spawner()));
+
+        let mut env = Env::with_system(Box::new(system));
+        let command: syntax::SimpleCommand = "FOO=bar /some/file".parse().unwrap();
+        let result = executor.run_until(command.execute(&mut env));
+        assert_eq!(result, Ok(()));
+        assert_eq!(env.variables.get("FOO"), None);
+
+        let state = state.borrow();
+        let process = state.processes.values().last().unwrap();
+        let arguments = process.last_exec().as_ref().unwrap();
+        assert_eq!(arguments.2, [CString::new("FOO=bar").unwrap()]);
+    }
+
     #[test]
     fn simple_command_returns_exit_status_from_external_utility() {
         let system = VirtualSystem::new();
@@ -212,8 +531,16 @@ mod tests {
         let mut content = INode::default();
         let mut executor = LocalPool::new();
         content.permissions.0 |= 0o100;
-        content.is_native_executable = true;
-        system.state.borrow_mut().file_system.save(path, content);
+        content.body = FileBody::Regular {
+            content: Vec::new(),
+            is_native_executable: true,
+        };
+        system
+            .state
+            .borrow_mut()
+            .file_system
+            .save(path, content)
+            .unwrap();
         system.state.borrow_mut().executor = Some(Rc::new(executor.spawner()));
 
         let mut env = Env::with_system(Box::new(system));
@@ -244,7 +571,12 @@ mod tests {
         let mut content = INode::default();
         let mut executor = LocalPool::new();
         content.permissions.0 |= 0o100;
-        system.state.borrow_mut().file_system.save(path, content);
+        system
+            .state
+            .borrow_mut()
+            .file_system
+            .save(path, content)
+            .unwrap();
         system.state.borrow_mut().executor = Some(Rc::new(executor.spawner()));
 
         let mut env = Env::with_system(Box::new(system));
@@ -263,6 +595,140 @@ mod tests {
         assert_eq!(env.exit_status, ExitStatus::NOEXEC);
     }
 
+    fn define_function(env: &mut Env, name: &str, body: &str) {
+        env.functions.insert(HashEntry::new(
+            name.to_string(),
+            Rc::new(body.parse().unwrap()),
+            Location::dummy(name),
+            false,
+        ));
+    }
+
+    fn params_builtin_main(
+        env: &mut Env,
+        _args: Vec<Field>,
+    ) -> Pin<Box<dyn Future<Output = yash_env::builtin::Result>>> {
+        let params = env.variables.positional_params().join(",");
+        let variable = Variable {
+            value: Value::Scalar(params),
+            last_assigned_location: None,
+            is_exported: false,
+            read_only_location: None,
+        };
+        env.variables
+            .assign(Scope::Global, "params".to_string(), variable);
+        Box::pin(ready((ExitStatus::SUCCESS, None)))
+    }
+
+    fn local_builtin_main(
+        env: &mut Env,
+        args: Vec<Field>,
+    ) -> Pin<Box<dyn Future<Output = yash_env::builtin::Result>>> {
+        for Field { value, .. } in args.into_iter().skip(1) {
+            let (name, value) = value.split_once('=').unwrap();
+            let variable = Variable {
+                value: Value::Scalar(value.to_string()),
+                last_assigned_location: None,
+                is_exported: false,
+                read_only_location: None,
+            };
+            env.variables
+                .assign(Scope::Local, name.to_string(), variable);
+        }
+        Box::pin(ready((ExitStatus::SUCCESS, None)))
+    }
+
+    #[test]
+    fn function_call_returns_exit_status_of_body() {
+        let mut env = Env::new_virtual();
+        env.builtins.insert("return", return_builtin());
+        define_function(&mut env, "foo", "{ return -n 42; }");
+        let command: syntax::SimpleCommand = "foo".parse().unwrap();
+        let result = block_on(command.execute(&mut env));
+        assert_eq!(result, Ok(()));
+        assert_eq!(env.exit_status, ExitStatus(42));
+    }
+
+    #[test]
+    fn function_call_consumes_return() {
+        let mut env = Env::new_virtual();
+        env.builtins.insert("return", return_builtin());
+        define_function(&mut env, "foo", "{ return 7; return -n 1; }");
+        let command: syntax::SimpleCommand = "foo".parse().unwrap();
+        let result = block_on(command.execute(&mut env));
+        assert_eq!(result, Ok(()));
+        assert_eq!(env.exit_status, ExitStatus(7));
+    }
+
+    #[test]
+    fn function_call_propagates_exit() {
+        let mut env = Env::new_virtual();
+        define_function(&mut env, "foo", "{ ${x?}; }");
+        let command: syntax::SimpleCommand = "foo".parse().unwrap();
+        let result = block_on(command.execute(&mut env));
+        assert_eq!(result, Err(Divert::Exit(ExitStatus::ERROR)));
+    }
+
+    #[test]
+    fn function_call_sets_positional_params() {
+        let mut env = Env::new_virtual();
+        let builtin = Builtin {
+            r#type: Intrinsic,
+            execute: params_builtin_main,
+        };
+        env.builtins.insert("params", builtin);
+        define_function(&mut env, "foo", "{ params; }");
+        *env.variables.positional_params_mut() = vec!["old".to_string()];
+        let command: syntax::SimpleCommand = "foo bar 'b a z'".parse().unwrap();
+        let result = block_on(command.execute(&mut env));
+        assert_eq!(result, Ok(()));
+        let params = env.variables.get("params").unwrap();
+        assert_eq!(params.value, Value::Scalar("bar,b a z".to_string()));
+        assert_eq!(env.variables.positional_params(), ["old"]);
+    }
+
+    #[test]
+    fn local_variables_are_removed_on_function_return() {
+        let mut env = Env::new_virtual();
+        let builtin = Builtin {
+            r#type: Intrinsic,
+            execute: local_builtin_main,
+        };
+        env.builtins.insert("local", builtin);
+        assign_scalar(&mut env, "x", "global");
+        define_function(&mut env, "foo", "{ local x=local; y=$x; }");
+        let command: syntax::SimpleCommand = "foo".parse().unwrap();
+        let result = block_on(command.execute(&mut env));
+        assert_eq!(result, Ok(()));
+        let x = env.variables.get("x").unwrap();
+        assert_eq!(x.value, Value::Scalar("global".to_string()));
+        let y = env.variables.get("y").unwrap();
+        assert_eq!(y.value, Value::Scalar("local".to_string()));
+    }
+
+    #[test]
+    fn temporary_assignments_are_visible_in_function() {
+        let mut env = Env::new_virtual();
+        define_function(&mut env, "foo", "{ b=$a; }");
+        let command: syntax::SimpleCommand = "a=A foo".parse().unwrap();
+        let result = block_on(command.execute(&mut env));
+        assert_eq!(result, Ok(()));
+        assert_eq!(env.variables.get("a"), None);
+        let b = env.variables.get("b").unwrap();
+        assert_eq!(b.value, Value::Scalar("A".to_string()));
+    }
+
+    #[test]
+    fn function_body_redirections_are_performed_on_each_call() {
+        let mut env = Env::new_virtual();
+        env.builtins.insert("return", return_builtin());
+        define_function(&mut env, "foo", "{ return -n 0; } <nonexistent");
+        let command: syntax::SimpleCommand = "foo".parse().unwrap();
+        let result = block_on(command.execute(&mut env));
+        assert_eq!(result, Err(Divert::Exit(ExitStatus::ERROR)));
+        assert_eq!(env.variables.positional_params(), [] as [String; 0]);
+    }
+
     #[test]
     fn exit_status_is_127_on_command_not_found() {
         let mut env = Env::new_virtual();
@@ -271,4 +737,119 @@ mod tests {
         assert_eq!(result, Ok(()));
         assert_eq!(env.exit_status, ExitStatus::NOT_FOUND);
     }
+
+    #[test]
+    fn errexit_exits_on_failed_command() {
+        let mut env = Env::new_virtual();
+        env.builtins.insert("return", return_builtin());
+        env.options.set(ErrExit, true);
+        let command: syntax::SimpleCommand = "return -n 0".parse().unwrap();
+        let result = block_on(command.execute(&mut env));
+        assert_eq!(result, Ok(()));
+
+        let command: syntax::SimpleCommand = "return -n 3".parse().unwrap();
+        let result = block_on(command.execute(&mut env));
+        assert_eq!(result, Err(Divert::Exit(ExitStatus(3))));
+        assert_eq!(env.exit_status, ExitStatus(3));
+    }
+
+    #[test]
+    fn errexit_is_not_applied_while_suppressed() {
+        let mut env = Env::new_virtual();
+        env.builtins.insert("return", return_builtin());
+        env.options.set(ErrExit, true);
+        env.errexit_is_suppressed = true;
+        let command: syntax::SimpleCommand = "return -n 3".parse().unwrap();
+        let result = block_on(command.execute(&mut env));
+        assert_eq!(result, Ok(()));
+        assert_eq!(env.exit_status, ExitStatus(3));
+    }
+
+    #[test]
+    fn errexit_exits_on_command_not_found() {
+        let mut env = Env::new_virtual();
+        env.options.set(ErrExit, true);
+        let command: syntax::SimpleCommand = "no_such_command".parse().unwrap();
+        let result = block_on(command.execute(&mut env));
+        assert_eq!(result, Err(Divert::Exit(ExitStatus::NOT_FOUND)));
+    }
+
+    #[test]
+    fn errexit_exits_on_redirection_error_of_regular_builtin() {
+        let mut env = Env::new_virtual();
+        env.builtins.insert("echo", echo_builtin());
+        env.options.set(ErrExit, true);
+        let command: syntax::SimpleCommand = "echo <nonexistent".parse().unwrap();
+        let result = block_on(command.execute(&mut env));
+        assert_eq!(result, Err(Divert::Exit(ExitStatus::ERROR)));
+    }
+
+    #[test]
+    fn errexit_exits_on_assignment_with_failed_command_substitution() {
+        let system = VirtualSystem::new();
+        let mut executor = LocalPool::new();
+        system.state.borrow_mut().executor = Some(Rc::new(executor.spawner()));
+        let mut env = Env::with_system(Box::new(system));
+        env.builtins.insert("return", return_builtin());
+        env.options.set(ErrExit, true);
+        let command: syntax::SimpleCommand = "a=$(return -n 5)".parse().unwrap();
+        let result = executor.run_until(command.execute(&mut env));
+        assert_eq!(result, Err(Divert::Exit(ExitStatus(5))));
+    }
+
+    #[test]
+    fn errexit_applies_to_function_result() {
+        let mut env = Env::new_virtual();
+        env.builtins.insert("return", return_builtin());
+        env.options.set(ErrExit, true);
+        define_function(&mut env, "foo", "{ return 4; }");
+        let command: syntax::SimpleCommand = "foo".parse().unwrap();
+        let result = block_on(command.execute(&mut env));
+        assert_eq!(result, Err(Divert::Exit(ExitStatus(4))));
+        assert_eq!(env.variables.positional_params(), [] as [String; 0]);
+    }
+
+    #[test]
+    fn errexit_in_function_body() {
+        let mut env = Env::new_virtual();
+        env.builtins.insert("return", return_builtin());
+        env.options.set(ErrExit, true);
+        define_function(&mut env, "foo", "{ return -n 2; x=1; }");
+        let command: syntax::SimpleCommand = "foo".parse().unwrap();
+        let result = block_on(command.execute(&mut env));
+        assert_eq!(result, Err(Divert::Exit(ExitStatus(2))));
+        assert_eq!(env.variables.get("x"), None);
+    }
+
+    #[test]
+    fn xtrace_prints_expanded_assignments_words_and_redirections() {
+        let (mut env, reader) = env_with_stderr_pipe();
+        env.builtins.insert("return", return_builtin());
+        assign_scalar(&mut env, "v", "a b");
+        let command: syntax::SimpleCommand = "x=$v y= return -n \"$v\" 3>&2".parse().unwrap();
+        let result = block_on(command.execute(&mut env));
+        assert_eq!(result, Ok(()));
+        assert_eq!(
+            read_all(&mut env, reader),
+            "+ x='a b' y='' return -n 'a b' 3>&2\n"
+        );
+    }
+
+    #[test]
+    fn xtrace_prints_assignments_without_command_name() {
+        let (mut env, reader) = env_with_stderr_pipe();
+        let command: syntax::SimpleCommand = "a=1 b=\"'\"".parse().unwrap();
+        block_on(command.execute(&mut env)).unwrap();
+        assert_eq!(read_all(&mut env, reader), "+ a=1 b=''\\'''\n");
+    }
+
+    #[test]
+    fn xtrace_prints_to_original_stderr() {
+        let (mut env, reader) = env_with_stderr_pipe();
+        env.builtins.insert("echo", echo_builtin());
+        let command: syntax::SimpleCommand = "echo hi 2>/file".parse().unwrap();
+        let result = block_on(command.execute(&mut env));
+        assert_eq!(result, Ok(()));
+        assert_eq!(read_all(&mut env, reader), "+ echo hi 2>/file\n");
+    }
 }
@@ -0,0 +1,317 @@
+// This file is part of yash, an extended POSIX shell.
+// Copyright (C) 2021 WATANABE Yuki
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Tracing commands with the xtrace option.
+//!
+//! When the [`XTrace`](ShellOption::XTrace) option is on, the shell prints
+//! each command to the standard error before executing it. A trace line
+//! starts with the value of the `$PS4` variable, which is subjected to
+//! parameter expansion, command substitution, and arithmetic expansion. The
+//! default prefix is `+ `.
+//!
+//! A simple command is traced by composing an [`XTrace`] buffer while
+//! expanding the command words, performing the redirections, and performing
+//! the assignments, and then calling [`XTrace::finish`]. Fields in the trace
+//! are quoted so that the line can be reused as a command. The headers of some
+//! compound commands are traced with [`print`].
+
+use crate::expansion::Expand;
+use std::borrow::Cow;
+use std::fmt::Write;
+use std::os::unix::io::RawFd;
+use yash_env::io::STDERR;
+use yash_env::option::OptionSet;
+use yash_env::option::ShellOption;
+use yash_env::variable::Value;
+use yash_env::Env;
+use yash_syntax::syntax::Text;
+
+/// Default value of `$PS4`.
+const DEFAULT_PS4: &str = "+ ";
+
+/// Tests if a character needs quoting.
+fn is_special_char(c: char) -> bool {
+    !(c.is_alphanumeric() || "%+,-./:@_".contains(c))
+}
+
+/// Quotes a string so that it can be read back as a single word.
+///
+/// The string is returned intact if it needs no quoting. Otherwise, it is
+/// enclosed in single quotes, and each single quote in the string is replaced
+/// with `'\''`.
+#[must_use]
+pub fn quote(value: &str) -> Cow<'_, str> {
+    if !value.is_empty() && !value.chars().any(is_special_char) {
+        return Cow::Borrowed(value);
+    }
+    let mut result = String::with_capacity(value.len() + 2);
+    result.push('\'');
+    for c in value.chars() {
+        if c == '\'' {
+            result.push_str("'\\''");
+        } else {
+            result.push(c);
+        }
+    }
+    result.push('\'');
+    Cow::Owned(result)
+}
+
+/// Buffer for composing the trace of a simple command.
+///
+/// The trace line consists of assignments, command words, and redirections in
+/// this order, regardless of the order in which they are added.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct XTrace {
+    assigns: String,
+    words: String,
+    redirs: String,
+}
+
+impl XTrace {
+    /// Creates an empty buffer.
+    #[must_use]
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Creates an empty buffer if the xtrace option is on.
+    #[must_use]
+    pub fn from_options(options: &OptionSet) -> Option<Self> {
+        if options.is_on(ShellOption::XTrace) {
+            Some(Self::new())
+        } else {
+            None
+        }
+    }
+
+    /// Adds a command word.
+    pub fn push_word(&mut self, word: &str) {
+        self.words.push_str(&quote(word));
+        self.words.push(' ');
+    }
+
+    /// Adds an assignment.
+    ///
+    /// An array value is traced in the `name=(values...)` form.
+    pub fn push_assign(&mut self, name: &str, value: &Value) {
+        self.assigns.push_str(name);
+        self.assigns.push('=');
+        match value {
+            Value::Scalar(value) => self.assigns.push_str(&quote(value)),
+            Value::Array(values) => {
+                self.assigns.push('(');
+                let mut first = true;
+                for value in values {
+                    if !first {
+                        self.assigns.push(' ');
+                    }
+                    first = false;
+                    self.assigns.push_str(&quote(value));
+                }
+                self.assigns.push(')');
+            }
+        }
+        self.assigns.push(' ');
+    }
+
+    /// Adds a redirection.
+    ///
+    /// The `operator` is the redirection operator and the `operand` is the
+    /// expanded operand, which is quoted in the trace. Use
+    /// [`push_redir_raw`](Self::push_redir_raw) to add an operand without
+    /// quoting.
+    pub fn push_redir(&mut self, fd: RawFd, operator: &dyn std::fmt::Display, operand: &str) {
+        let _ = write!(self.redirs, "{}{}{} ", fd, operator, quote(operand));
+    }
+
+    /// Adds a redirection whose operator and operand are already formatted.
+    ///
+    /// This function is used for here-documents, whose delimiter is traced as
+    /// written in the source code.
+    pub fn push_redir_raw(&mut self, fd: RawFd, redir: &dyn std::fmt::Display) {
+        let _ = write!(self.redirs, "{}{} ", fd, redir);
+    }
+
+    /// Tests if the buffer is empty.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.assigns.is_empty() && self.words.is_empty() && self.redirs.is_empty()
+    }
+
+    /// Prints the trace to the standard error.
+    ///
+    /// Nothing is printed if the buffer is empty.
+    pub async fn finish(self, env: &mut Env) {
+        self.finish_to(env, STDERR).await
+    }
+
+    /// Prints the trace to the given file descriptor.
+    ///
+    /// This function is used instead of [`finish`](Self::finish) when the
+    /// standard error has been redirected and the trace should go to a saved
+    /// copy of the original standard error.
+    pub async fn finish_to(self, env: &mut Env, fd: RawFd) {
+        if self.is_empty() {
+            return;
+        }
+        let mut line = self.assigns;
+        line.push_str(&self.words);
+        line.push_str(&self.redirs);
+        line.truncate(line.trim_end_matches(' ').len());
+        print_to(env, fd, &line).await;
+    }
+}
+
+/// Expands the value of `$PS4`.
+///
+/// The xtrace option is turned off during the expansion so that commands in
+/// command substitutions are not traced. The exit status is not affected. If
+/// the value cannot be parsed or expanded, it is used without expansion.
+async fn expand_ps4(env: &mut Env) -> String {
+    let value = match env.variables.get("PS4").map(|v| &v.value) {
+        None => return DEFAULT_PS4.to_string(),
+        Some(Value::Scalar(value)) => value.clone(),
+        Some(Value::Array(values)) => values.join(" "),
+    };
+    let text: Text = match value.parse() {
+        Ok(text) => text,
+        Err(_) => return value,
+    };
+
+    let exit_status = env.exit_status;
+    let last_command_subst_exit_status = env.last_command_subst_exit_status;
+    env.options.set(ShellOption::XTrace, false);
+    let result = text.expand(env).await;
+    env.options.set(ShellOption::XTrace, true);
+    env.exit_status = exit_status;
+    env.last_command_subst_exit_status = last_command_subst_exit_status;
+
+    match result {
+        Ok(phrase) => phrase.join(None).into_iter().map(|c| c.value).collect(),
+        Err(_) => value,
+    }
+}
+
+/// Prints a trace line to the standard error.
+///
+/// The line is prefixed with the expanded value of `$PS4` and followed by a
+/// newline. Errors writing to the standard error are ignored.
+pub async fn print(env: &mut Env, line: &str) {
+    print_to(env, STDERR, line).await
+}
+
+/// Prints a trace line to the given file descriptor.
+///
+/// This function is the same as [`print`] except for the destination.
+pub async fn print_to(env: &mut Env, fd: RawFd, line: &str) {
+    let mut trace = expand_ps4(env).await;
+    trace.push_str(line);
+    trace.push('\n');
+
+    let _ = env.system.write_all(fd, trace.as_bytes());
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::expansion::tests::assign_scalar;
+    use futures::executor::block_on;
+
+    #[test]
+    fn quoting() {
+        assert_eq!(quote("foo"), "foo");
+        assert_eq!(
+            quote("/usr/bin/a-b_c.d:e@f%g+h,i"),
+            "/usr/bin/a-b_c.d:e@f%g+h,i"
+        );
+        assert_eq!(quote(""), "''");
+        assert_eq!(quote("a b"), "'a b'");
+        assert_eq!(quote("$x"), "'$x'");
+        assert_eq!(quote("a=b"), "'a=b'");
+        assert_eq!(quote("it's"), "'it'\\''s'");
+    }
+
+    #[test]
+    fn trace_line_composition() {
+        let mut xtrace = XTrace::new();
+        assert!(xtrace.is_empty());
+        xtrace.push_word("echo");
+        xtrace.push_redir(1, &">", "out file");
+        xtrace.push_word("a b");
+        xtrace.push_assign("x", &Value::Scalar("1".to_string()));
+        xtrace.push_assign("y", &Value::Array(vec!["a".to_string(), "".to_string()]));
+        assert!(!xtrace.is_empty());
+
+        let mut line = xtrace.assigns;
+        line.push_str(&xtrace.words);
+        line.push_str(&xtrace.redirs);
+        assert_eq!(line, "x=1 y=(a '') echo 'a b' 1>'out file' ");
+    }
+
+    #[test]
+    fn xtrace_from_options() {
+        let mut options = OptionSet::default();
+        assert_eq!(XTrace::from_options(&options), None);
+        options.set(ShellOption::XTrace, true);
+        assert_eq!(XTrace::from_options(&options), Some(XTrace::new()));
+    }
+
+    /// Returns an environment with the xtrace option on and the standard error
+    /// redirected to a pipe, and the reading end of the pipe.
+    pub(crate) fn env_with_stderr_pipe() -> (Env, RawFd) {
+        let mut env = Env::new_virtual();
+        let (reader, writer) = env.system.pipe().unwrap();
+        env.system.dup2(writer, STDERR).unwrap();
+        env.system.close(writer).unwrap();
+        env.options.set(ShellOption::XTrace, true);
+        (env, reader)
+    }
+
+    /// Reads the trace available in the pipe.
+    pub(crate) fn read_all(env: &mut Env, reader: RawFd) -> String {
+        let mut buffer = [0; 200];
+        let count = block_on(env.system.read_async(reader, &mut buffer)).unwrap();
+        String::from_utf8(buffer[..count].to_vec()).unwrap()
+    }
+
+    #[test]
+    fn printing_with_default_ps4() {
+        let (mut env, reader) = env_with_stderr_pipe();
+        block_on(print(&mut env, "for i in 1 2"));
+        assert_eq!(read_all(&mut env, reader), "+ for i in 1 2\n");
+    }
+
+    #[test]
+    fn printing_with_expanded_ps4() {
+        let (mut env, reader) = env_with_stderr_pipe();
+        assign_scalar(&mut env, "PS4", "[$x$((1+1))] ");
+        assign_scalar(&mut env, "x", "X");
+        let mut xtrace = XTrace::new();
+        xtrace.push_word("foo");
+        block_on(xtrace.finish(&mut env));
+        assert_eq!(read_all(&mut env, reader), "[X2] foo\n");
+        assert!(env.options.is_on(ShellOption::XTrace));
+    }
+
+    #[test]
+    fn empty_trace_is_not_printed() {
+        let (mut env, reader) = env_with_stderr_pipe();
+        block_on(XTrace::new().finish(&mut env));
+        block_on(print(&mut env, "x"));
+        assert_eq!(read_all(&mut env, reader), "+ x\n");
+    }
+}
@@ -399,12 +399,12 @@ impl Parser<'_> {
     ///
     /// The parser created by this function does not perform alias substitution. To do it, pass an
     /// alias set to [`with_aliases`](Parser::with_aliases).
-    pub fn new(lexer: &mut Lexer) -> Parser {
+    pub fn new(lexer: &mut Lexer) -> Parser<'_> {
         Self::with_aliases(lexer, Rc::new(AliasSet::new()))
     }
 
     /// Creates a new parser based on the given lexer and alias set.
-    pub fn with_aliases(lexer: &mut Lexer, aliases: Rc<AliasSet>) -> Parser {
+    pub fn with_aliases(lexer: &mut Lexer, aliases: Rc<AliasSet>) -> Parser<'_> {
         Parser {
             lexer,
             aliases,
@@ -544,7 +544,7 @@ impl Parser<'_> {
     pub async fn has_blank(&mut self) -> Result<bool> {
         assert!(self.token.is_none(), "There should be no pending token");
         let c = self.lexer.peek_char().await?;
-        Ok(c.map_or(false, is_blank))
+        Ok(c.is_some_and(is_blank))
     }
 
     /// Remembers the given partial here-document for later parsing of its content.
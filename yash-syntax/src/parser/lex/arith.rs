@@ -64,7 +64,7 @@ impl Lexer {
 
         // Part 3: Parse `))`
         match self.peek_char().await? {
-            Some(sc) if sc == ')' => self.consume_char(),
+            Some(')') => self.consume_char(),
             Some(_) => unreachable!(),
             None => {
                 let opening_location = location;
@@ -74,7 +74,7 @@ impl Lexer {
             }
         }
         match self.peek_char().await? {
-            Some(sc) if sc == ')' => self.consume_char(),
+            Some(')') => self.consume_char(),
             Some(_) => {
                 self.rewind(index);
                 return Ok(Err(location));
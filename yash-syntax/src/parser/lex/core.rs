@@ -267,7 +267,7 @@ impl LexerCore {
     /// If `index` is larger than the currently read index.
     fn is_after_blank_ending_alias(&self, index: usize) -> bool {
         fn ends_with_blank(s: &str) -> bool {
-            s.chars().rev().next().map_or(false, is_blank)
+            s.chars().next_back().is_some_and(is_blank)
         }
         fn is_same_alias(alias: &Alias, sc: Option<&SourceChar>) -> bool {
             match sc {
@@ -602,13 +602,13 @@ pub struct WordLexer<'a> {
 impl Deref for WordLexer<'_> {
     type Target = Lexer;
     fn deref(&self) -> &Lexer {
-        &self.lexer
+        self.lexer
     }
 }
 
 impl DerefMut for WordLexer<'_> {
     fn deref_mut(&mut self) -> &mut Lexer {
-        &mut self.lexer
+        self.lexer
     }
 }
 
@@ -648,7 +648,7 @@ mod tests {
         impl Input for Failing {
             async fn next_line(&mut self, _: &Context) -> crate::input::Result {
                 let location = Location::dummy("line");
-                let error = std::io::Error::new(std::io::ErrorKind::Other, Failing);
+                let error = std::io::Error::other(Failing);
                 Err((location, error))
             }
         }
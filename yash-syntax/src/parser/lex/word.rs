@@ -123,7 +123,7 @@ impl WordLexer<'_> {
                 self.consume_char();
                 self.single_quote(location).await.map(Some)
             }
-            Some(c) if c == '"' => {
+            Some('"') => {
                 let location = self.location().await?.clone();
                 self.consume_char();
                 self.double_quote(location).await.map(Some)
@@ -29,6 +29,10 @@ pub enum Source {
     ///
     /// Normally you should not use this value, but it may be useful for quick debugging.
     Unknown,
+    /// Standard input.
+    ///
+    /// This applies to source code read from the standard input of the shell.
+    Stdin,
     /// Alias substitution.
     ///
     /// This applies to a code fragment that replaced another as a result of alias substitution.
@@ -38,10 +42,34 @@ pub enum Source {
         original: Location,
         alias: Rc<Alias>,
     },
+    /// Command substitution.
+    ///
+    /// This applies to the content of a command substitution that is parsed
+    /// when the substitution is executed.
+    ///
+    /// `original` is the location of the command substitution.
+    CommandSubst { original: Location },
     // TODO More Source types
 }
 
 impl Source {
+    /// Returns a label that names the source in messages.
+    ///
+    /// ```
+    /// # use yash_syntax::source::Source;
+    /// assert_eq!(Source::Unknown.label(), "<?>");
+    /// assert_eq!(Source::Stdin.label(), "<stdin>");
+    /// ```
+    #[must_use]
+    pub fn label(&self) -> &'static str {
+        match self {
+            Source::Unknown => "<?>",
+            Source::Stdin => "<stdin>",
+            Source::Alias { .. } => "<alias>",
+            Source::CommandSubst { .. } => "<command_substitution>",
+        }
+    }
+
     /// Tests if this source is alias substitution for the given name.
     ///
     /// Returns true if `self` is `Source::Alias` with the `name` or such an
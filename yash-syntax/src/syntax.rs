@@ -824,7 +824,7 @@ impl fmt::Display for HereDoc {
         f.write_str(if self.remove_tabs { "<<-" } else { "<<" })?;
 
         // This space is to disambiguate `<< --` and `<<- -`
-        if let Some(Unquoted(Literal('-'))) = self.delimiter.units.get(0) {
+        if let Some(Unquoted(Literal('-'))) = self.delimiter.units.first() {
             f.write_char(' ')?;
         }
 
@@ -877,7 +877,7 @@ impl<H> Redir<H> {
     /// the default file descriptor is selected depending on the type of `self.body`.
     pub fn fd_or_default(&self) -> RawFd {
         use RedirOp::*;
-        self.fd.unwrap_or_else(|| match self.body {
+        self.fd.unwrap_or(match self.body {
             RedirBody::Normal { operator, .. } => match operator {
                 FileIn | FileInOut | FdIn | String => STDIN_FD,
                 FileOut | FileAppend | FileClobber | FdOut | Pipe => STDOUT_FD,
@@ -25,12 +25,23 @@ pub use yash_syntax::*;
 async fn parse_and_print() -> i32 {
     use env::Env;
     use env::RealSystem;
+    use env::System;
     use semantics::Command;
     use std::num::NonZeroU64;
+    use yash_env::io::STDERR;
+    use yash_env::option::ShellOption;
+    use yash_env::variable::Scope;
     use yash_env::variable::Value::Scalar;
     use yash_env::variable::Variable;
 
-    struct Stdin;
+    /// Input that reads from the standard input.
+    ///
+    /// If `verbose` is true, the lines read are echoed to the standard error
+    /// through `system`.
+    struct Stdin {
+        system: RealSystem,
+        verbose: bool,
+    }
 
     #[async_trait::async_trait(?Send)]
     impl input::Input for Stdin {
@@ -38,22 +49,32 @@ async fn parse_and_print() -> i32 {
             let mut code = String::new();
             std::io::stdin()
                 .read_line(&mut code)
-                .map(|_| source::Line {
-                    value: code,
-                    // TODO correct line number
-                    number: NonZeroU64::new(1).unwrap(),
-                    source: source::Source::Unknown,
-                })
-                .map_err(|e| (source::Location::dummy(""), e))
+                .map_err(|e| (source::Location::dummy(""), e))?;
+            if self.verbose {
+                let _ = self.system.write_all(STDERR, code.as_bytes());
+            }
+            Ok(source::Line {
+                value: code,
+                // TODO correct line number
+                number: NonZeroU64::new(1).unwrap(),
+                source: source::Source::Stdin,
+            })
         }
     }
 
     let mut env = Env {
         aliases: Default::default(),
+        arg0: std::env::args()
+            .next()
+            .unwrap_or_else(|| "yash".to_string()),
         builtins: builtin::BUILTINS.iter().copied().collect(),
+        errexit_is_suppressed: false,
         exit_status: Default::default(),
+        last_command_subst_exit_status: None,
         functions: Default::default(),
         jobs: Default::default(),
+        main_pid: RealSystem.getpid(),
+        options: Default::default(),
         variables: Default::default(),
         system: Box::new(RealSystem),
     };
@@ -66,19 +87,28 @@ async fn parse_and_print() -> i32 {
             is_exported: true,
             read_only_location: None,
         };
-        env.variables.assign(name, value);
+        env.variables.assign(Scope::Global, name, value);
     }
 
     loop {
-        let mut lexer = parser::lex::Lexer::new(Box::new(Stdin));
+        let verbose = env.options.is_on(ShellOption::Verbose);
+        let mut lexer = parser::lex::Lexer::new(Box::new(Stdin {
+            system: RealSystem,
+            verbose,
+        }));
         let mut parser = parser::Parser::with_aliases(&mut lexer, env.aliases.clone());
         match parser.command_line().await {
             Ok(None) => break env.exit_status.0,
-            Ok(Some(command)) => command
-                .execute(&mut env)
-                .await
-                .unwrap_or_else(|a| eprintln!("{:?}", a)),
-            Err(e) => println!("{}", e),
+            Ok(Some(_))
+                if env.options.is_on(ShellOption::NoExec)
+                    && !env.options.is_on(ShellOption::Interactive) => {}
+            Ok(Some(command)) => match command.execute(&mut env).await {
+                Ok(()) => (),
+                Err(env::exec::Divert::Exit(exit_status)) => break exit_status.0,
+                Err(env::exec::Divert::Interrupt) => (),
+                Err(divert) => env.print_error(&format!("{:?}", divert)),
+            },
+            Err(error) => env.print_error(&error.to_string()),
         }
         // TODO If the lexer still has unconsumed input, it should be parsed
         // before the lexer is dropped.